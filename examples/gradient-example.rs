@@ -72,7 +72,7 @@ impl common::Renderer for GradientRender {
 
         let text = text.unwrap();
 
-        let mut surface = GPUSurface::new(&text.texture, 800.0, 800.0, true, device);
+        let mut surface = GPUSurface::new(&text.texture, 800.0, 800.0, AntiAlias::Msaa(4), device);
 
         surface.replay(self.picture.as_ref().unwrap());
 
@@ -598,13 +598,13 @@ impl NanovgRender {
                     a0 / (2.0 * std::f32::consts::PI),
                     1.0,
                     0.55,
-                    255,
+                    1.0,
                 ))
                 .add_color(Color::from_hsla(
                     a1 / (2.0 * std::f32::consts::PI),
                     1.0,
                     0.55,
-                    255,
+                    1.0,
                 ))
                 .into();
 
@@ -653,7 +653,7 @@ impl NanovgRender {
 
         paint.style = Style::Fill;
         paint.color = LinearGradient::new(Point::from(r, 0.0), Point::from(ax, ay))
-            .add_color(Color::from_hsla(hue, 1.0, 0.5, 255))
+            .add_color(Color::from_hsla(hue, 1.0, 0.5, 1.0))
             .add_color(Color::from_rgba_u8(255, 255, 255, 255))
             .into();
 
@@ -1213,7 +1213,13 @@ impl common::Renderer for NanovgRender {
             .get_current_texture()
             .expect("can not acquire texture");
 
-        let mut surface = GPUSurface::new(&text.texture, self.width, self.height, true, device);
+        let mut surface = GPUSurface::new(
+            &text.texture,
+            self.width,
+            self.height,
+            AntiAlias::Msaa(4),
+            device,
+        );
 
         let picture = self.render();
 
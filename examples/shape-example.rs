@@ -84,7 +84,7 @@ fn draw_clip_path(recorder: &mut PictureRecorder) {
 
     recorder.save();
 
-    recorder.clip_path(path.clone(), ClipOp::Intersect);
+    recorder.clip_path(path.clone(), ClipOp::Intersect, true);
 
     recorder.draw_circle(70.0, 85.0, 60.0, &paint);
 
@@ -96,7 +96,7 @@ fn draw_clip_path(recorder: &mut PictureRecorder) {
 
     path.fill_type = PathFillType::EvenOdd;
 
-    recorder.clip_path(path, ClipOp::Intersect);
+    recorder.clip_path(path, ClipOp::Intersect, true);
     recorder.draw_circle(70.0, 85.0, 60.0, &paint);
 
     recorder.restore();
@@ -191,7 +191,7 @@ impl common::Renderer for ShapeRender {
 
         let text = text.unwrap();
 
-        let mut surface = GPUSurface::new(&text.texture, 800.0, 800.0, true, device);
+        let mut surface = GPUSurface::new(&text.texture, 800.0, 800.0, AntiAlias::Msaa(4), device);
 
         surface.replay(self.picture.as_ref().unwrap());
 
@@ -0,0 +1,64 @@
+/// Describes how source colors are combined with the destination colors already on the target.
+///
+/// All modes operate on premultiplied colors. `Sa` and `Da` stand for source and destination alpha,
+/// `S` and `D` for the source and destination color.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum BlendMode {
+    /// r = 0
+    Clear,
+    /// r = S
+    Src,
+    /// r = D
+    Dst,
+    /// r = S + (1 - Sa) * D
+    #[default]
+    SrcOver,
+    /// r = D + (1 - Da) * S
+    DstOver,
+    /// r = S * Da
+    SrcIn,
+    /// r = D * Sa
+    DstIn,
+    /// r = S * (1 - Da)
+    SrcOut,
+    /// r = D * (1 - Sa)
+    DstOut,
+    /// r = S * Da + D * (1 - Sa)
+    SrcATop,
+    /// r = D * Sa + S * (1 - Da)
+    DstATop,
+    /// r = S * (1 - Da) + D * (1 - Sa)
+    Xor,
+    /// r = min(S + D, 1)
+    Plus,
+    /// r = S * D
+    Modulate,
+    /// r = S + D - S * D
+    Screen,
+}
+
+impl BlendMode {
+    /// Returns `[k0, k1, k2, k3, k4]` which describes the mode in shaders as
+    /// `r = S * (k0 + k1 * Da) + D * (k2 + k3 * Sa) + k4 * S * D`.
+    /// The result of `Plus` still needs to be clamped.
+    pub(crate) fn coefficients(&self) -> [f32; 5] {
+        match self {
+            BlendMode::Clear => [0.0, 0.0, 0.0, 0.0, 0.0],
+            BlendMode::Src => [1.0, 0.0, 0.0, 0.0, 0.0],
+            BlendMode::Dst => [0.0, 0.0, 1.0, 0.0, 0.0],
+            BlendMode::SrcOver => [1.0, 0.0, 1.0, -1.0, 0.0],
+            BlendMode::DstOver => [1.0, -1.0, 1.0, 0.0, 0.0],
+            BlendMode::SrcIn => [0.0, 1.0, 0.0, 0.0, 0.0],
+            BlendMode::DstIn => [0.0, 0.0, 0.0, 1.0, 0.0],
+            BlendMode::SrcOut => [1.0, -1.0, 0.0, 0.0, 0.0],
+            BlendMode::DstOut => [0.0, 0.0, 1.0, -1.0, 0.0],
+            BlendMode::SrcATop => [0.0, 1.0, 1.0, -1.0, 0.0],
+            BlendMode::DstATop => [1.0, -1.0, 0.0, 1.0, 0.0],
+            BlendMode::Xor => [1.0, -1.0, 1.0, -1.0, 0.0],
+            BlendMode::Plus => [1.0, 0.0, 1.0, 0.0, 0.0],
+            BlendMode::Modulate => [0.0, 0.0, 0.0, 0.0, 1.0],
+            BlendMode::Screen => [1.0, 0.0, 1.0, 0.0, -1.0],
+        }
+    }
+}
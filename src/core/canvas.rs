@@ -0,0 +1,303 @@
+use std::rc::Rc;
+
+use crate::text::TextBlob;
+
+use super::{ClipOp, Color, Image, Matrix, Paint, Path, Picture, Point, RRect, Rect};
+
+/// Drawing surface of the recording API. Code drawing through `&mut dyn Canvas` can draw into a
+/// `PictureRecorder`, or any other implementor such as a forwarding canvas, an exporter or a test double.
+/// `Picture::playback` replays a recorded picture into any canvas.
+pub trait Canvas {
+    /// Draws path with current clip and transform.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` the path to draw
+    /// * `paint` the paint controls the styling when drawing the path
+    fn draw_path(&mut self, path: Path, paint: &Paint);
+
+    /// Draws rect with current clip and transform.
+    ///
+    /// # Arguments
+    ///
+    /// * `rect` the rect to draw
+    /// * `paint` the paint controls the styling when drawing the rect
+    fn draw_rect(&mut self, rect: &Rect, paint: &Paint) {
+        self.draw_path(Path::new().add_rect(rect), paint);
+    }
+
+    /// Draws round rect with current clip and transform.
+    ///
+    /// # Arguments
+    ///
+    /// * `rect` the RoundRect to draw
+    /// * `paint` the paint controls the styling when drawing the round rect
+    fn draw_rrect(&mut self, rect: &RRect, paint: &Paint) {
+        self.draw_path(Path::new().add_rrect(rect), paint);
+    }
+
+    /// Draws oval with current clip and transform.
+    ///
+    /// # Arguments
+    ///
+    /// * `rect` the bounds of ellipse to draw
+    /// * `paint` the paint controls the styling when drawing the oval
+    fn draw_oval(&mut self, rect: &Rect, paint: &Paint) {
+        self.draw_path(Path::new().add_oval(rect), paint);
+    }
+
+    /// Draws circle with current clip and transform.
+    ///
+    /// # Arguments
+    ///
+    /// * `cx` the x coordinate of the center of the circle
+    /// * `cy` the y coordinate of the center of the circle
+    /// * `radius` the radius of the circle
+    /// * `paint` the paint controls the styling when drawing the circle
+    fn draw_circle(&mut self, cx: f32, cy: f32, radius: f32, paint: &Paint) {
+        if radius <= 0.0 {
+            return;
+        }
+
+        let oval = Rect::from_xywh(cx - radius, cy - radius, radius * 2.0, radius * 2.0);
+        self.draw_oval(&oval, paint);
+    }
+
+    /// Draws image with current clip and transform.
+    ///
+    /// # Arguments
+    ///
+    /// * `image` the image to draw
+    /// * `dst` the bounds of image to draw on canvas
+    /// * `src` part of image source to draw, pass `None` to draw the whole image
+    fn draw_image(&mut self, image: &Image, dst: &Rect, src: Option<&Rect>) {
+        self.draw_image_with_paint(image, dst, src, &Paint::new());
+    }
+
+    /// Draws image with current clip and transform, using the blend mode and color filter of paint.
+    ///
+    /// # Arguments
+    ///
+    /// * `image` the image to draw
+    /// * `dst` the bounds of image to draw on canvas
+    /// * `src` part of image source to draw, pass `None` to draw the whole image
+    /// * `paint` the paint controls blend mode and color filter, color and style are ignored
+    fn draw_image_with_paint(
+        &mut self,
+        image: &Image,
+        dst: &Rect,
+        src: Option<&Rect>,
+        paint: &Paint,
+    );
+
+    /// Draws text with current clip and transform at position pos with color color.
+    ///
+    /// # Arguments
+    ///
+    /// * `text` the text to draw
+    /// * `pos` the baseline position of text
+    /// * `color` the color of text
+    fn draw_text(&mut self, text: Rc<TextBlob>, pos: Point, color: Color) {
+        let mut paint = Paint::new();
+        paint.color = color.into();
+
+        self.draw_text_with_paint(text, pos, &paint);
+    }
+
+    /// Draws text with current clip and transform at position pos with paint.
+    ///
+    /// # Arguments
+    ///
+    /// * `text` the text to draw
+    /// * `pos` the baseline position of text
+    /// * `paint` the paint controls color, blend mode and color filter of text
+    fn draw_text_with_paint(&mut self, text: Rc<TextBlob>, pos: Point, paint: &Paint);
+
+    /// Draws picture with current clip and transform.
+    /// Implementors without a better way can replay the picture with `Picture::playback`.
+    ///
+    /// # Arguments
+    ///
+    /// * `picture` the picture to draw
+    /// * `matrix` transform applied to the picture before the current transform, pass `None` to draw it as is
+    /// * `paint` the alpha, blend mode, color filter and image filter used when compositing the picture
+    fn draw_picture(
+        &mut self,
+        picture: &Rc<Picture>,
+        matrix: Option<&Matrix>,
+        paint: Option<&Paint>,
+    );
+
+    /// Clips the current context with the specified path.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` the path to clip
+    /// * `op` the type of operation performed by the clip
+    /// * `anti_alias` whether the edge of clip is anti-aliased
+    fn clip_path(&mut self, path: Path, op: ClipOp, anti_alias: bool);
+
+    /// Clips the current context with the specified rect.
+    ///
+    /// # Arguments
+    ///
+    /// * `rect` the rect to clip
+    /// * `op` the type of operation performed by the clip
+    fn clip_rect(&mut self, rect: &Rect, op: ClipOp) {
+        self.clip_path(Path::new().add_rect(rect), op, true);
+    }
+
+    /// Clips the current context with the specified round rect.
+    ///
+    /// # Arguments
+    ///
+    /// * `rect` the round rect to clip
+    /// * `op` the type of operation performed by the clip
+    /// * `anti_alias` whether the edge of clip is anti-aliased
+    fn clip_rrect(&mut self, rect: &RRect, op: ClipOp, anti_alias: bool) {
+        self.clip_path(Path::new().add_rrect(rect), op, anti_alias);
+    }
+
+    /// Returns the conservative bounds of current clip in device space, `None` if the clip is not bounded.
+    fn get_device_clip_bounds(&self) -> Option<Rect>;
+
+    /// Returns the conservative bounds of current clip in local space, mapped back by current transform.
+    /// Returns `None` if the clip is not bounded or can not be mapped back under perspective.
+    fn get_local_clip_bounds(&self) -> Option<Rect> {
+        let bounds = self.get_device_clip_bounds()?;
+        let matrix = self.get_total_matrix();
+
+        if matrix.has_perspective() {
+            return None;
+        }
+
+        Some(match matrix.try_invert() {
+            Some(inverse) => inverse.map_rect(&bounds),
+            None => Rect::from_ltrb(0.0, 0.0, 0.0, 0.0),
+        })
+    }
+
+    /// Returns true if rect under current transform is surely outside of current clip, so drawing inside
+    /// it can be skipped. Returns false if it may be visible.
+    ///
+    /// # Arguments
+    ///
+    /// * `rect` the local bounds of the drawing, including stroke width and blur
+    fn quick_reject(&self, rect: &Rect) -> bool {
+        if !rect.is_finite() {
+            return true;
+        }
+
+        let matrix = self.get_total_matrix();
+
+        // bounds mapped by perspective are not reliable
+        if matrix.has_perspective() {
+            return false;
+        }
+
+        let bounds = matrix.map_rect(rect);
+
+        bounds.is_empty()
+            || self
+                .get_device_clip_bounds()
+                .is_some_and(|clip| bounds.intersect(&clip).is_none())
+    }
+
+    /// Saves current transform matrix and clip state.
+    fn save(&mut self);
+
+    /// Saves current transform matrix and clip state, and redirects following draws into an offscreen layer.
+    /// The layer is composited back when the matching `restore` is called.
+    ///
+    /// # Arguments
+    ///
+    /// * `bounds` the bounds of the layer in local space, pass `None` to use the current clip bounds
+    /// * `paint` the alpha, blend mode, color filter and image filter used when compositing the layer,
+    ///   pass `None` to composite with SrcOver
+    fn save_layer(&mut self, bounds: Option<Rect>, paint: Option<&Paint>);
+
+    /// Restores the transform matrix and clip to the last saved state.
+    /// Restore without a matching save is ignored.
+    fn restore(&mut self);
+
+    /// Returns the number of saved states which are not restored, including layers.
+    fn get_save_count(&self) -> usize;
+
+    /// Restores saved states until the save count is count.
+    ///
+    /// # Arguments
+    ///
+    /// * `count` the save count to restore to, usually returned by `get_save_count` before saving
+    fn restore_to_count(&mut self, count: usize) {
+        while self.get_save_count() > count {
+            self.restore();
+        }
+    }
+
+    /// Translates transform matrix by dx along the x-axis and dy along the y-axis.
+    ///
+    /// # Arguments
+    ///
+    /// * `dx` distance to translate on x-axis
+    /// * `dy` distance to translate on y-axis
+    fn translate(&mut self, dx: f32, dy: f32);
+
+    /// Rotates transform matrix by degree at point (px, py).
+    ///
+    /// # Arguments
+    ///
+    /// * `degree` degree to rotate on z-axis
+    /// * `px` x position of rotation center
+    /// * `py` y position of rotation center
+    fn rotate_at_xy(&mut self, degree: f32, px: f32, py: f32);
+
+    /// Rotates transform matrix by degree at point (0.0, 0.0).
+    ///
+    /// # Arguments
+    ///
+    /// * `degree` degree to rotate on z-axis
+    fn rotate(&mut self, degree: f32);
+
+    /// Scales transform matrix.
+    ///
+    /// # Arguments
+    ///
+    /// * `sx` scale at x-axis, negative value flips the x-axis
+    /// * `sy` scale at y-axis, negative value flips the y-axis
+    fn scale(&mut self, sx: f32, sy: f32);
+
+    /// Skews transform matrix.
+    ///
+    /// # Arguments
+    ///
+    /// * `sx` skew at x-axis, x is moved by `sx * y`
+    /// * `sy` skew at y-axis, y is moved by `sy * x`
+    fn skew(&mut self, sx: f32, sy: f32) {
+        let mut matrix = Matrix::new();
+        matrix.skew(sx, sy);
+
+        self.concat(&matrix);
+    }
+
+    /// Pre-multiplies transform matrix by matrix, so matrix is applied to the geometry first.
+    ///
+    /// # Arguments
+    ///
+    /// * `matrix` the matrix to concat
+    fn concat(&mut self, matrix: &Matrix);
+
+    /// Replaces transform matrix, affine and perspective matrices are both supported.
+    ///
+    /// # Arguments
+    ///
+    /// * `matrix` the new transform matrix
+    fn set_matrix(&mut self, matrix: &Matrix);
+
+    /// Replaces transform matrix with identity.
+    fn reset_matrix(&mut self) {
+        self.set_matrix(&Matrix::new());
+    }
+
+    /// Returns current transform matrix.
+    fn get_total_matrix(&self) -> Matrix;
+}
@@ -1,3 +1,7 @@
+use std::f32::consts::PI;
+use std::fmt;
+use std::str::FromStr;
+
 use bytemuck::{Pod, Zeroable};
 use nalgebra::clamp;
 
@@ -6,6 +10,7 @@ use super::paint::ColorType;
 /// Unpremultiplied color with RGBA channel
 #[repr(C)]
 #[derive(Debug, Clone, Copy, PartialEq, Pod, Zeroable)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Color {
     pub r: f32,
     pub g: f32,
@@ -13,6 +18,50 @@ pub struct Color {
     pub a: f32,
 }
 
+/// Error returned when a CSS color string can not be parsed.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ColorParseError {
+    /// the string is empty
+    Empty,
+    /// the hex notation has invalid digits or a length other than 3, 4, 6 or 8
+    InvalidHex(String),
+    /// the string is neither a named color nor a supported color function
+    UnknownColor(String),
+    /// the arguments of the color function can not be parsed, holds the function name
+    InvalidArguments(String),
+}
+
+impl fmt::Display for ColorParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ColorParseError::Empty => write!(f, "empty color string"),
+            ColorParseError::InvalidHex(hex) => write!(f, "invalid hex color `{}`", hex),
+            ColorParseError::UnknownColor(name) => write!(f, "unknown color `{}`", name),
+            ColorParseError::InvalidArguments(function) => {
+                write!(f, "invalid arguments of `{}()`", function)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ColorParseError {}
+
+pub(crate) fn srgb_to_linear(c: f32) -> f32 {
+    if c <= 0.04045 {
+        c / 12.92
+    } else {
+        ((c + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+pub(crate) fn linear_to_srgb(c: f32) -> f32 {
+    if c <= 0.0031308 {
+        c * 12.92
+    } else {
+        1.055 * c.powf(1.0 / 2.4) - 0.055
+    }
+}
+
 fn hsla_hue(h: f32, m1: f32, m2: f32) -> f32 {
     let mut h = h;
     if h < 0.0 {
@@ -64,7 +113,15 @@ impl Color {
         }
     }
 
-    pub fn from_hsla(h: f32, s: f32, l: f32, a: u8) -> Self {
+    /// Returns color value from hue, saturation, lightness and alpha.
+    ///
+    /// # Arguments
+    ///
+    /// * 'h' hue in turns, 0.0 and 1.0 are red. values out of [0.0, 1.0] wrap around
+    /// * 's' saturation, the value needs between [0.0, 1.0]
+    /// * 'l' lightness, the value needs between [0.0, 1.0]
+    /// * 'a' value of alpha channel. the value needs between [0.0, 1.0]
+    pub fn from_hsla(h: f32, s: f32, l: f32, a: f32) -> Self {
         let mut h = h % 1.0;
 
         if h < 0.0 {
@@ -86,12 +143,7 @@ impl Color {
         let g = clamp(hsla_hue(h, m1, m2), 0.0, 1.0);
         let b = clamp(hsla_hue(h - 1.0 / 3.0, m1, m2), 0.0, 1.0);
 
-        Self {
-            r,
-            g,
-            b,
-            a: a as f32 / 255.0,
-        }
+        Self { r, g, b, a }
     }
 
     pub fn transparent() -> Self {
@@ -198,10 +250,676 @@ impl Color {
 
         self
     }
+
+    /// Returns color value from hue, saturation, value and alpha.
+    ///
+    /// # Arguments
+    ///
+    /// * 'h' hue in turns, 0.0 and 1.0 are red. values out of [0.0, 1.0] wrap around
+    /// * 's' saturation, the value needs between [0.0, 1.0]
+    /// * 'v' value, the value needs between [0.0, 1.0]
+    /// * 'a' value of alpha channel. the value needs between [0.0, 1.0]
+    pub fn from_hsva(h: f32, s: f32, v: f32, a: f32) -> Self {
+        let h = h.rem_euclid(1.0) * 6.0;
+        let s = clamp(s, 0.0, 1.0);
+        let v = clamp(v, 0.0, 1.0);
+
+        let channel = |n: f32| {
+            let k = (n + h) % 6.0;
+            v - v * s * k.min(4.0 - k).clamp(0.0, 1.0)
+        };
+
+        Self {
+            r: channel(5.0),
+            g: channel(3.0),
+            b: channel(1.0),
+            a,
+        }
+    }
+
+    /// Returns color value from Oklab components, channels out of the sRGB gamut are clamped.
+    ///
+    /// # Arguments
+    ///
+    /// * 'l' perceived lightness, the value needs between [0.0, 1.0]
+    /// * 'a' green to red axis, typically between [-0.4, 0.4]
+    /// * 'b' blue to yellow axis, typically between [-0.4, 0.4]
+    /// * 'alpha' value of alpha channel. the value needs between [0.0, 1.0]
+    pub fn from_oklab(l: f32, a: f32, b: f32, alpha: f32) -> Self {
+        let l_ = (l + 0.39633778 * a + 0.21580376 * b).powi(3);
+        let m_ = (l - 0.105561346 * a - 0.06385417 * b).powi(3);
+        let s_ = (l - 0.08948418 * a - 1.2914855 * b).powi(3);
+
+        let r = 4.0767417 * l_ - 3.3077116 * m_ + 0.23096993 * s_;
+        let g = -1.268438 * l_ + 2.6097574 * m_ - 0.3413194 * s_;
+        let b = -0.0041960863 * l_ - 0.7034186 * m_ + 1.7076147 * s_;
+
+        Self {
+            r: linear_to_srgb(clamp(r, 0.0, 1.0)),
+            g: linear_to_srgb(clamp(g, 0.0, 1.0)),
+            b: linear_to_srgb(clamp(b, 0.0, 1.0)),
+            a: alpha,
+        }
+    }
+
+    /// Parses a CSS color string.
+    ///
+    /// Supports hex notation (`#rgb`, `#rgba`, `#rrggbb`, `#rrggbbaa`), named colors including
+    /// `transparent`, and the functions `rgb()`, `rgba()`, `hsl()`, `hsla()`, `oklab()` and `oklch()`
+    /// in both the comma separated and the space separated syntax.
+    ///
+    /// # Arguments
+    ///
+    /// * 'text' the CSS color, case insensitive
+    pub fn parse(text: &str) -> Result<Self, ColorParseError> {
+        let text = text.trim().to_ascii_lowercase();
+
+        if text.is_empty() {
+            return Err(ColorParseError::Empty);
+        }
+
+        if let Some(hex) = text.strip_prefix('#') {
+            return Self::parse_hex(hex).ok_or_else(|| ColorParseError::InvalidHex(text.clone()));
+        }
+
+        if let Some((function, args)) = text.strip_suffix(')').and_then(|t| t.split_once('(')) {
+            let function = function.trim();
+
+            return Self::parse_function(function, args)
+                .ok_or_else(|| ColorParseError::InvalidArguments(function.to_string()));
+        }
+
+        NAMED_COLORS
+            .iter()
+            .find(|(name, _)| *name == text)
+            .map(|(_, rgb)| {
+                Self::from_rgba_u8((rgb >> 16) as u8, (rgb >> 8) as u8, *rgb as u8, 255)
+            })
+            .or_else(|| (text == "transparent").then(Self::transparent))
+            .ok_or(ColorParseError::UnknownColor(text))
+    }
+
+    fn parse_hex(hex: &str) -> Option<Self> {
+        if !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+            return None;
+        }
+
+        let digit = |i: usize| u8::from_str_radix(&hex[i..i + 1], 16).unwrap();
+        let byte = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).unwrap();
+
+        match hex.len() {
+            3 => Some(Self::from_rgba_u8(
+                digit(0) * 17,
+                digit(1) * 17,
+                digit(2) * 17,
+                255,
+            )),
+            4 => Some(Self::from_rgba_u8(
+                digit(0) * 17,
+                digit(1) * 17,
+                digit(2) * 17,
+                digit(3) * 17,
+            )),
+            6 => Some(Self::from_rgba_u8(byte(0), byte(2), byte(4), 255)),
+            8 => Some(Self::from_rgba_u8(byte(0), byte(2), byte(4), byte(6))),
+            _ => None,
+        }
+    }
+
+    fn parse_function(function: &str, args: &str) -> Option<Self> {
+        let (channels, alpha) = match args.split_once('/') {
+            Some((channels, alpha)) => (channels, Some(alpha.trim())),
+            None => (args, None),
+        };
+
+        let mut values: Vec<&str> = channels
+            .split(|c: char| c == ',' || c.is_whitespace())
+            .filter(|v| !v.is_empty())
+            .collect();
+
+        // the legacy comma syntax passes alpha as the fourth value
+        let alpha = match alpha {
+            Some(alpha) if values.len() == 3 => Some(alpha),
+            None if values.len() == 4 => values.pop(),
+            None if values.len() == 3 => None,
+            _ => return None,
+        };
+
+        let alpha = match alpha {
+            Some(alpha) => clamp(parse_value(alpha, 1.0)?, 0.0, 1.0),
+            None => 1.0,
+        };
+
+        match function {
+            "rgb" | "rgba" => Some(Self::from_rgba(
+                clamp(parse_value(values[0], 255.0)? / 255.0, 0.0, 1.0),
+                clamp(parse_value(values[1], 255.0)? / 255.0, 0.0, 1.0),
+                clamp(parse_value(values[2], 255.0)? / 255.0, 0.0, 1.0),
+                alpha,
+            )),
+            "hsl" | "hsla" => Some(Self::from_hsla(
+                parse_angle(values[0])? / 360.0,
+                parse_value(values[1], 100.0)? / 100.0,
+                parse_value(values[2], 100.0)? / 100.0,
+                alpha,
+            )),
+            "oklab" => Some(Self::from_oklab(
+                clamp(parse_value(values[0], 1.0)?, 0.0, 1.0),
+                parse_value(values[1], 0.4)?,
+                parse_value(values[2], 0.4)?,
+                alpha,
+            )),
+            "oklch" => {
+                let chroma = parse_value(values[1], 0.4)?.max(0.0);
+                let hue = parse_angle(values[2])?.to_radians();
+
+                Some(Self::from_oklab(
+                    clamp(parse_value(values[0], 1.0)?, 0.0, 1.0),
+                    chroma * hue.cos(),
+                    chroma * hue.sin(),
+                    alpha,
+                ))
+            }
+            _ => None,
+        }
+    }
+
+    /// Returns the hue in turns, saturation, lightness and alpha of the color.
+    /// The result is the inverse of [`Color::from_hsla`], hue is 0.0 for gray colors.
+    pub fn to_hsla(&self) -> [f32; 4] {
+        let max = self.r.max(self.g).max(self.b);
+        let min = self.r.min(self.g).min(self.b);
+        let l = (max + min) * 0.5;
+
+        let d = max - min;
+        let s = if d == 0.0 {
+            0.0
+        } else {
+            d / (1.0 - (2.0 * l - 1.0).abs())
+        };
+
+        [self.hue(max, d), s, l, self.a]
+    }
+
+    /// Returns the hue in turns, saturation, value and alpha of the color.
+    /// The result is the inverse of [`Color::from_hsva`], hue is 0.0 for gray colors.
+    pub fn to_hsva(&self) -> [f32; 4] {
+        let max = self.r.max(self.g).max(self.b);
+        let min = self.r.min(self.g).min(self.b);
+
+        let d = max - min;
+        let s = if max == 0.0 { 0.0 } else { d / max };
+
+        [self.hue(max, d), s, max, self.a]
+    }
+
+    fn hue(&self, max: f32, d: f32) -> f32 {
+        if d == 0.0 {
+            return 0.0;
+        }
+
+        let h = if max == self.r {
+            (self.g - self.b) / d
+        } else if max == self.g {
+            (self.b - self.r) / d + 2.0
+        } else {
+            (self.r - self.g) / d + 4.0
+        };
+
+        (h / 6.0).rem_euclid(1.0)
+    }
+
+    /// Returns the Oklab lightness, a, b and alpha of the color.
+    pub fn to_oklab(&self) -> [f32; 4] {
+        let r = srgb_to_linear(self.r);
+        let g = srgb_to_linear(self.g);
+        let b = srgb_to_linear(self.b);
+
+        let l = (0.41222146 * r + 0.53633255 * g + 0.051445995 * b).cbrt();
+        let m = (0.2119035 * r + 0.6806995 * g + 0.10739696 * b).cbrt();
+        let s = (0.08830246 * r + 0.28171885 * g + 0.6299787 * b).cbrt();
+
+        [
+            0.21045426 * l + 0.7936178 * m - 0.004072047 * s,
+            1.9779985 * l - 2.4285922 * m + 0.4505937 * s,
+            0.025904037 * l + 0.78277177 * m - 0.80867577 * s,
+            self.a,
+        ]
+    }
+
+    /// Returns the color with the rgb channels multiplied by alpha.
+    pub fn premultiply(&self) -> Self {
+        Self {
+            r: self.r * self.a,
+            g: self.g * self.a,
+            b: self.b * self.a,
+            a: self.a,
+        }
+    }
+
+    /// Returns the color with the rgb channels divided by alpha, the inverse of [`Color::premultiply`].
+    /// Fully transparent colors become transparent black.
+    pub fn unpremultiply(&self) -> Self {
+        if self.a == 0.0 {
+            return Self::transparent();
+        }
+
+        Self {
+            r: self.r / self.a,
+            g: self.g / self.a,
+            b: self.b / self.a,
+            a: self.a,
+        }
+    }
+
+    /// Returns the color with the rgb channels converted from sRGB encoding to linear light.
+    pub fn to_linear(&self) -> Self {
+        Self {
+            r: srgb_to_linear(self.r),
+            g: srgb_to_linear(self.g),
+            b: srgb_to_linear(self.b),
+            a: self.a,
+        }
+    }
+
+    /// Returns the color with the rgb channels converted from linear light to sRGB encoding,
+    /// the inverse of [`Color::to_linear`].
+    pub fn to_srgb(&self) -> Self {
+        Self {
+            r: linear_to_srgb(self.r),
+            g: linear_to_srgb(self.g),
+            b: linear_to_srgb(self.b),
+            a: self.a,
+        }
+    }
+
+    /// Linearly interpolates every channel between this color and `other`.
+    ///
+    /// # Arguments
+    ///
+    /// * 'other' the color returned when `t` is 1.0
+    /// * 't' the interpolation factor, 0.0 returns this color
+    pub fn lerp(&self, other: &Color, t: f32) -> Self {
+        Self {
+            r: self.r + (other.r - self.r) * t,
+            g: self.g + (other.g - self.g) * t,
+            b: self.b + (other.b - self.b) * t,
+            a: self.a + (other.a - self.a) * t,
+        }
+    }
+
+    /// Returns the color as CSS hex notation, `#rrggbb` for opaque colors and `#rrggbbaa` otherwise.
+    pub fn to_hex(&self) -> String {
+        let byte = |v: f32| (clamp(v, 0.0, 1.0) * 255.0).round() as u8;
+
+        let a = byte(self.a);
+        if a == 255 {
+            format!(
+                "#{:02x}{:02x}{:02x}",
+                byte(self.r),
+                byte(self.g),
+                byte(self.b)
+            )
+        } else {
+            format!(
+                "#{:02x}{:02x}{:02x}{:02x}",
+                byte(self.r),
+                byte(self.g),
+                byte(self.b),
+                a
+            )
+        }
+    }
 }
 
+impl FromStr for Color {
+    type Err = ColorParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Color::parse(s)
+    }
+}
+
+/// Parses a number or a percentage, `percent` is the value of `100%`. `none` is zero.
+fn parse_value(value: &str, percent: f32) -> Option<f32> {
+    if value == "none" {
+        return Some(0.0);
+    }
+
+    let v = match value.strip_suffix('%') {
+        Some(v) => v.parse::<f32>().ok()? * percent / 100.0,
+        None => value.parse::<f32>().ok()?,
+    };
+
+    v.is_finite().then_some(v)
+}
+
+/// Parses a CSS angle into degrees, numbers without unit are degrees.
+fn parse_angle(value: &str) -> Option<f32> {
+    let units = [
+        ("deg", 1.0),
+        ("grad", 0.9),
+        ("rad", 180.0 / PI),
+        ("turn", 360.0),
+    ];
+
+    for (unit, scale) in units {
+        if let Some(v) = value.strip_suffix(unit) {
+            return parse_value(v, f32::NAN).map(|v| v * scale);
+        }
+    }
+
+    parse_value(value, f32::NAN)
+}
+
+/// Color space of the values stored in a render target.
+///
+/// Colors, gradients and images are described in sRGB, draws convert them into the color space of the
+/// target in the fragment shader. Wide-gamut and extended spaces keep values above 1.0, they need a
+/// float target such as `Rgba16Float` to survive.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum ColorSpace {
+    /// sRGB encoded values clamped to [0.0, 1.0].
+    #[default]
+    Srgb,
+    /// Linear light values with sRGB primaries.
+    LinearSrgb,
+    /// Display P3 primaries with the sRGB transfer function.
+    DisplayP3,
+    /// sRGB encoded values which are not clamped. The transfer function is mirrored for negative values.
+    ExtendedSrgb,
+}
+
+impl ColorSpace {
+    /// Converts an unpremultiplied sRGB color into this color space, same as the fragment shaders do.
+    /// Useful for the clear color of a target.
+    ///
+    /// # Arguments
+    ///
+    /// * `color` - The sRGB color to convert.
+    pub fn convert(&self, color: &Color) -> Color {
+        let to_linear = |c: f32| srgb_to_linear(c.abs()).copysign(c);
+        let to_srgb = |c: f32| linear_to_srgb(c.abs()).copysign(c);
+
+        match self {
+            ColorSpace::Srgb => Color::from_rgba(
+                clamp(color.r, 0.0, 1.0),
+                clamp(color.g, 0.0, 1.0),
+                clamp(color.b, 0.0, 1.0),
+                color.a,
+            ),
+            ColorSpace::LinearSrgb => Color::from_rgba(
+                to_linear(color.r),
+                to_linear(color.g),
+                to_linear(color.b),
+                color.a,
+            ),
+            ColorSpace::DisplayP3 => {
+                let r = to_linear(color.r);
+                let g = to_linear(color.g);
+                let b = to_linear(color.b);
+
+                Color::from_rgba(
+                    to_srgb(0.8224621 * r + 0.177538 * g),
+                    to_srgb(0.0331941 * r + 0.9668058 * g),
+                    to_srgb(0.0170827 * r + 0.0723974 * g + 0.9105199 * b),
+                    color.a,
+                )
+            }
+            ColorSpace::ExtendedSrgb => *color,
+        }
+    }
+}
+
+/// CSS named colors as `0xRRGGBB`, `transparent` is handled separately.
+const NAMED_COLORS: [(&str, u32); 148] = [
+    ("aliceblue", 0xF0F8FF),
+    ("antiquewhite", 0xFAEBD7),
+    ("aqua", 0x00FFFF),
+    ("aquamarine", 0x7FFFD4),
+    ("azure", 0xF0FFFF),
+    ("beige", 0xF5F5DC),
+    ("bisque", 0xFFE4C4),
+    ("black", 0x000000),
+    ("blanchedalmond", 0xFFEBCD),
+    ("blue", 0x0000FF),
+    ("blueviolet", 0x8A2BE2),
+    ("brown", 0xA52A2A),
+    ("burlywood", 0xDEB887),
+    ("cadetblue", 0x5F9EA0),
+    ("chartreuse", 0x7FFF00),
+    ("chocolate", 0xD2691E),
+    ("coral", 0xFF7F50),
+    ("cornflowerblue", 0x6495ED),
+    ("cornsilk", 0xFFF8DC),
+    ("crimson", 0xDC143C),
+    ("cyan", 0x00FFFF),
+    ("darkblue", 0x00008B),
+    ("darkcyan", 0x008B8B),
+    ("darkgoldenrod", 0xB8860B),
+    ("darkgray", 0xA9A9A9),
+    ("darkgreen", 0x006400),
+    ("darkgrey", 0xA9A9A9),
+    ("darkkhaki", 0xBDB76B),
+    ("darkmagenta", 0x8B008B),
+    ("darkolivegreen", 0x556B2F),
+    ("darkorange", 0xFF8C00),
+    ("darkorchid", 0x9932CC),
+    ("darkred", 0x8B0000),
+    ("darksalmon", 0xE9967A),
+    ("darkseagreen", 0x8FBC8F),
+    ("darkslateblue", 0x483D8B),
+    ("darkslategray", 0x2F4F4F),
+    ("darkslategrey", 0x2F4F4F),
+    ("darkturquoise", 0x00CED1),
+    ("darkviolet", 0x9400D3),
+    ("deeppink", 0xFF1493),
+    ("deepskyblue", 0x00BFFF),
+    ("dimgray", 0x696969),
+    ("dimgrey", 0x696969),
+    ("dodgerblue", 0x1E90FF),
+    ("firebrick", 0xB22222),
+    ("floralwhite", 0xFFFAF0),
+    ("forestgreen", 0x228B22),
+    ("fuchsia", 0xFF00FF),
+    ("gainsboro", 0xDCDCDC),
+    ("ghostwhite", 0xF8F8FF),
+    ("gold", 0xFFD700),
+    ("goldenrod", 0xDAA520),
+    ("gray", 0x808080),
+    ("green", 0x008000),
+    ("greenyellow", 0xADFF2F),
+    ("grey", 0x808080),
+    ("honeydew", 0xF0FFF0),
+    ("hotpink", 0xFF69B4),
+    ("indianred", 0xCD5C5C),
+    ("indigo", 0x4B0082),
+    ("ivory", 0xFFFFF0),
+    ("khaki", 0xF0E68C),
+    ("lavender", 0xE6E6FA),
+    ("lavenderblush", 0xFFF0F5),
+    ("lawngreen", 0x7CFC00),
+    ("lemonchiffon", 0xFFFACD),
+    ("lightblue", 0xADD8E6),
+    ("lightcoral", 0xF08080),
+    ("lightcyan", 0xE0FFFF),
+    ("lightgoldenrodyellow", 0xFAFAD2),
+    ("lightgray", 0xD3D3D3),
+    ("lightgreen", 0x90EE90),
+    ("lightgrey", 0xD3D3D3),
+    ("lightpink", 0xFFB6C1),
+    ("lightsalmon", 0xFFA07A),
+    ("lightseagreen", 0x20B2AA),
+    ("lightskyblue", 0x87CEFA),
+    ("lightslategray", 0x778899),
+    ("lightslategrey", 0x778899),
+    ("lightsteelblue", 0xB0C4DE),
+    ("lightyellow", 0xFFFFE0),
+    ("lime", 0x00FF00),
+    ("limegreen", 0x32CD32),
+    ("linen", 0xFAF0E6),
+    ("magenta", 0xFF00FF),
+    ("maroon", 0x800000),
+    ("mediumaquamarine", 0x66CDAA),
+    ("mediumblue", 0x0000CD),
+    ("mediumorchid", 0xBA55D3),
+    ("mediumpurple", 0x9370DB),
+    ("mediumseagreen", 0x3CB371),
+    ("mediumslateblue", 0x7B68EE),
+    ("mediumspringgreen", 0x00FA9A),
+    ("mediumturquoise", 0x48D1CC),
+    ("mediumvioletred", 0xC71585),
+    ("midnightblue", 0x191970),
+    ("mintcream", 0xF5FFFA),
+    ("mistyrose", 0xFFE4E1),
+    ("moccasin", 0xFFE4B5),
+    ("navajowhite", 0xFFDEAD),
+    ("navy", 0x000080),
+    ("oldlace", 0xFDF5E6),
+    ("olive", 0x808000),
+    ("olivedrab", 0x6B8E23),
+    ("orange", 0xFFA500),
+    ("orangered", 0xFF4500),
+    ("orchid", 0xDA70D6),
+    ("palegoldenrod", 0xEEE8AA),
+    ("palegreen", 0x98FB98),
+    ("paleturquoise", 0xAFEEEE),
+    ("palevioletred", 0xDB7093),
+    ("papayawhip", 0xFFEFD5),
+    ("peachpuff", 0xFFDAB9),
+    ("peru", 0xCD853F),
+    ("pink", 0xFFC0CB),
+    ("plum", 0xDDA0DD),
+    ("powderblue", 0xB0E0E6),
+    ("purple", 0x800080),
+    ("rebeccapurple", 0x663399),
+    ("red", 0xFF0000),
+    ("rosybrown", 0xBC8F8F),
+    ("royalblue", 0x4169E1),
+    ("saddlebrown", 0x8B4513),
+    ("salmon", 0xFA8072),
+    ("sandybrown", 0xF4A460),
+    ("seagreen", 0x2E8B57),
+    ("seashell", 0xFFF5EE),
+    ("sienna", 0xA0522D),
+    ("silver", 0xC0C0C0),
+    ("skyblue", 0x87CEEB),
+    ("slateblue", 0x6A5ACD),
+    ("slategray", 0x708090),
+    ("slategrey", 0x708090),
+    ("snow", 0xFFFAFA),
+    ("springgreen", 0x00FF7F),
+    ("steelblue", 0x4682B4),
+    ("tan", 0xD2B48C),
+    ("teal", 0x008080),
+    ("thistle", 0xD8BFD8),
+    ("tomato", 0xFF6347),
+    ("turquoise", 0x40E0D0),
+    ("violet", 0xEE82EE),
+    ("wheat", 0xF5DEB3),
+    ("white", 0xFFFFFF),
+    ("whitesmoke", 0xF5F5F5),
+    ("yellow", 0xFFFF00),
+    ("yellowgreen", 0x9ACD32),
+];
+
 impl Into<ColorType> for Color {
     fn into(self) -> ColorType {
         ColorType::SolidColor(self)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_css_colors() {
+        let orange = Color::from_rgba_u8(255, 165, 0, 255);
+
+        assert_eq!(Color::parse("#ffa500").unwrap(), orange);
+        assert_eq!(Color::parse("  Orange ").unwrap(), orange);
+        assert_eq!(Color::parse("rgb(255, 165, 0)").unwrap(), orange);
+        assert_eq!(
+            Color::parse("rgb(100% 64.705882% 0%)").unwrap().to_hex(),
+            "#ffa500"
+        );
+        assert_eq!(Color::parse("#f008").unwrap().to_hex(), "#ff000088");
+        assert_eq!(
+            "rgba(0, 0, 255, 0.5)".parse::<Color>().unwrap(),
+            Color::blue().with_alpha(0.5)
+        );
+        assert_eq!(
+            Color::parse("hsl(120deg 100% 50% / 25%)").unwrap().to_hex(),
+            "#00ff0040"
+        );
+        assert_eq!(
+            Color::parse("hsla(0.5turn, 100%, 50%, 1)")
+                .unwrap()
+                .to_hex(),
+            "#00ffff"
+        );
+        assert_eq!(
+            Color::parse("oklch(62.8% 0.2577 29.23)").unwrap().to_hex(),
+            "#ff0000"
+        );
+        assert_eq!(Color::parse("transparent").unwrap(), Color::transparent());
+
+        assert_eq!(Color::parse(""), Err(ColorParseError::Empty));
+        assert!(matches!(
+            Color::parse("#12345"),
+            Err(ColorParseError::InvalidHex(_))
+        ));
+        assert!(matches!(
+            Color::parse("reddish"),
+            Err(ColorParseError::UnknownColor(_))
+        ));
+        assert!(matches!(
+            Color::parse("rgb(1, 2)"),
+            Err(ColorParseError::InvalidArguments(_))
+        ));
+    }
+
+    #[test]
+    fn conversions_round_trip() {
+        let color = Color::from_rgba(0.2, 0.6, 0.4, 0.5);
+
+        let [h, s, l, a] = color.to_hsla();
+        assert_eq!(Color::from_hsla(h, s, l, a).to_hex(), color.to_hex());
+
+        let [h, s, v, a] = color.to_hsva();
+        assert_eq!(Color::from_hsva(h, s, v, a).to_hex(), color.to_hex());
+
+        let [l, ok_a, ok_b, a] = color.to_oklab();
+        assert_eq!(Color::from_oklab(l, ok_a, ok_b, a).to_hex(), color.to_hex());
+
+        assert_eq!(color.to_linear().to_srgb().to_hex(), color.to_hex());
+        assert_eq!(color.premultiply().unpremultiply().to_hex(), color.to_hex());
+        assert_eq!(
+            Color::black().lerp(&Color::white(), 0.5).to_hex(),
+            "#808080"
+        );
+    }
+
+    #[test]
+    fn convert_color_space() {
+        let hdr = Color::from_rgba(2.0, 0.5, -0.5, 1.0);
+
+        assert_eq!(
+            ColorSpace::Srgb.convert(&hdr),
+            Color::from_rgba(1.0, 0.5, 0.0, 1.0)
+        );
+        assert_eq!(ColorSpace::ExtendedSrgb.convert(&hdr), hdr);
+
+        let linear = ColorSpace::LinearSrgb.convert(&hdr);
+        assert!(linear.r > 1.0 && linear.b < 0.0);
+        assert!((linear.g - 0.21404).abs() < 1e-4);
+
+        let p3 = ColorSpace::DisplayP3.convert(&Color::red());
+        assert_eq!(p3.to_hex(), "#ea3323");
+        assert_eq!(
+            ColorSpace::DisplayP3.convert(&Color::white()).to_hex(),
+            "#ffffff"
+        );
+    }
+}
@@ -0,0 +1,229 @@
+use super::{BlendMode, Color};
+
+/// Transforms the color produced by the paint before it is blended into the destination.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ColorFilter {
+    /// 4x5 row-major color matrix applied to the unpremultiplied color.
+    /// The fifth column is the translation in normalized [0.0, 1.0] units.
+    ///
+    /// ```text
+    /// R' = m[0] * R + m[1] * G + m[2] * B + m[3] * A + m[4]
+    /// G' = m[5] * R + m[6] * G + m[7] * B + m[8] * A + m[9]
+    /// ...
+    /// ```
+    Matrix([f32; 20]),
+    /// Blends the given color as source over the paint color as destination with the blend mode.
+    Blend(Color, BlendMode),
+    /// Moves the luminance of the color into alpha channel and sets rgb to zero.
+    LumaToAlpha,
+    /// Converts the color from linear sRGB to sRGB encoding.
+    LinearToSrgb,
+    /// Converts the color from sRGB encoding to linear sRGB.
+    SrgbToLinear,
+    /// Applies the second filter first, then the first filter to the result. `Compose(outer, inner)`
+    Compose(Box<ColorFilter>, Box<ColorFilter>),
+}
+
+/// Max number of steps a single color filter stage can execute. Filters with more steps are split by
+/// `ColorFilter::split` and applied by chained stages.
+pub(crate) const MAX_COLOR_FILTER_STEPS: usize = 8;
+
+/// A single operation of a flattened color filter, executed in order by the shader.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum ColorFilterStep {
+    Matrix([f32; 20]),
+    Blend(Color, BlendMode),
+    LinearToSrgb,
+    SrgbToLinear,
+}
+
+const LUMA_TO_ALPHA: [f32; 20] = [
+    0.0, 0.0, 0.0, 0.0, 0.0, //
+    0.0, 0.0, 0.0, 0.0, 0.0, //
+    0.0, 0.0, 0.0, 0.0, 0.0, //
+    0.2126, 0.7152, 0.0722, 0.0, 0.0,
+];
+
+/// Returns the matrix which equals to apply `inner` first then `outer`.
+fn concat_color_matrix(outer: &[f32; 20], inner: &[f32; 20]) -> [f32; 20] {
+    let mut result = [0.0; 20];
+
+    for row in 0..4 {
+        for col in 0..5 {
+            let mut value = if col == 4 { outer[row * 5 + 4] } else { 0.0 };
+
+            for k in 0..4 {
+                value += outer[row * 5 + k] * inner[k * 5 + col];
+            }
+
+            result[row * 5 + col] = value;
+        }
+    }
+
+    result
+}
+
+impl ColorFilter {
+    /// Creates a color matrix filter which scales each channel and then adds the translation.
+    ///
+    /// # Arguments
+    ///
+    /// * `mul` the factor multiplied to each rgb channel
+    /// * `add` the value added to each rgb channel after multiply
+    pub fn lighting(mul: Color, add: Color) -> Self {
+        ColorFilter::Matrix([
+            mul.r, 0.0, 0.0, 0.0, add.r, //
+            0.0, mul.g, 0.0, 0.0, add.g, //
+            0.0, 0.0, mul.b, 0.0, add.b, //
+            0.0, 0.0, 0.0, 1.0, 0.0,
+        ])
+    }
+
+    /// Creates a color matrix filter which converts the color to grayscale.
+    pub fn grayscale() -> Self {
+        ColorFilter::Matrix([
+            0.2126, 0.7152, 0.0722, 0.0, 0.0, //
+            0.2126, 0.7152, 0.0722, 0.0, 0.0, //
+            0.2126, 0.7152, 0.0722, 0.0, 0.0, //
+            0.0, 0.0, 0.0, 1.0, 0.0,
+        ])
+    }
+
+    /// Creates a filter which applies `inner` first and then `outer`.
+    pub fn compose(outer: ColorFilter, inner: ColorFilter) -> Self {
+        ColorFilter::Compose(Box::new(outer), Box::new(inner))
+    }
+
+    /// Flatten the filter tree into steps in execution order.
+    /// Adjacent matrix steps are concatenated into one.
+    pub(crate) fn flatten(&self) -> Vec<ColorFilterStep> {
+        let mut steps = Vec::new();
+        self.flatten_into(&mut steps);
+
+        steps
+    }
+
+    /// Splits the filter into filters of at most `MAX_COLOR_FILTER_STEPS` steps in execution order, so
+    /// each of them fits into a single color filter stage. A filter which fits is returned as is.
+    pub(crate) fn split(&self) -> Vec<ColorFilter> {
+        let steps = self.flatten();
+
+        if steps.len() <= MAX_COLOR_FILTER_STEPS {
+            return vec![self.clone()];
+        }
+
+        steps
+            .chunks(MAX_COLOR_FILTER_STEPS)
+            .map(|chunk| {
+                chunk
+                    .iter()
+                    .map(|step| match step {
+                        ColorFilterStep::Matrix(m) => ColorFilter::Matrix(*m),
+                        ColorFilterStep::Blend(color, mode) => ColorFilter::Blend(*color, *mode),
+                        ColorFilterStep::LinearToSrgb => ColorFilter::LinearToSrgb,
+                        ColorFilterStep::SrgbToLinear => ColorFilter::SrgbToLinear,
+                    })
+                    .reduce(|inner, outer| ColorFilter::compose(outer, inner))
+                    .expect("chunk is not empty")
+            })
+            .collect()
+    }
+
+    fn flatten_into(&self, steps: &mut Vec<ColorFilterStep>) {
+        let step = match self {
+            ColorFilter::Compose(outer, inner) => {
+                inner.flatten_into(steps);
+                outer.flatten_into(steps);
+                return;
+            }
+            ColorFilter::Matrix(m) => ColorFilterStep::Matrix(*m),
+            ColorFilter::LumaToAlpha => ColorFilterStep::Matrix(LUMA_TO_ALPHA),
+            ColorFilter::Blend(color, mode) => ColorFilterStep::Blend(*color, *mode),
+            ColorFilter::LinearToSrgb => ColorFilterStep::LinearToSrgb,
+            ColorFilter::SrgbToLinear => ColorFilterStep::SrgbToLinear,
+        };
+
+        if let (Some(ColorFilterStep::Matrix(inner)), ColorFilterStep::Matrix(outer)) =
+            (steps.last_mut(), &step)
+        {
+            *inner = concat_color_matrix(outer, inner);
+            return;
+        }
+
+        steps.push(step);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn flatten_concat_matrix() {
+        let filter = ColorFilter::compose(
+            ColorFilter::LumaToAlpha,
+            ColorFilter::compose(
+                ColorFilter::SrgbToLinear,
+                ColorFilter::lighting(Color::from_rgba(0.5, 0.5, 0.5, 1.0), Color::transparent()),
+            ),
+        );
+
+        let steps = filter.flatten();
+
+        assert_eq!(steps.len(), 3);
+        assert_eq!(steps[1], ColorFilterStep::SrgbToLinear);
+
+        let filter = ColorFilter::compose(
+            ColorFilter::LumaToAlpha,
+            ColorFilter::lighting(Color::from_rgba(0.5, 0.5, 0.5, 1.0), Color::transparent()),
+        );
+
+        let steps = filter.flatten();
+
+        assert_eq!(steps.len(), 1);
+
+        match steps[0] {
+            ColorFilterStep::Matrix(m) => {
+                assert_eq!(m[15], 0.2126 * 0.5);
+                assert_eq!(m[16], 0.7152 * 0.5);
+                assert_eq!(m[18], 0.0);
+            }
+            _ => panic!("expect matrix"),
+        }
+    }
+
+    #[test]
+    fn split_deep_compose() {
+        let blend = |i: usize| {
+            ColorFilter::Blend(
+                Color::from_rgba(0.1 * i as f32, 0.0, 0.0, 1.0),
+                BlendMode::Plus,
+            )
+        };
+
+        // each level adds a grayscale matrix and a blend, so 20 blends and 19 matrices make 39 steps
+        let filter = (1..20).fold(blend(0), |inner, i| {
+            ColorFilter::compose(
+                blend(i),
+                ColorFilter::compose(ColorFilter::grayscale(), inner),
+            )
+        });
+        let steps = filter.flatten();
+        assert_eq!(steps.len(), 39);
+
+        let parts = filter.split();
+        assert_eq!(parts.len(), 5);
+        assert!(
+            parts
+                .iter()
+                .all(|part| part.flatten().len() <= MAX_COLOR_FILTER_STEPS)
+        );
+
+        let joined: Vec<ColorFilterStep> = parts.iter().flat_map(|part| part.flatten()).collect();
+        assert_eq!(joined, steps);
+
+        let filter = ColorFilter::compose(ColorFilter::grayscale(), blend(0));
+        assert_eq!(filter.split(), vec![filter]);
+    }
+}
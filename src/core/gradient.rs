@@ -1,7 +1,146 @@
+use std::f32::consts::PI;
+
 use crate::{Color, ColorType, Matrix, Point, TileMode};
 
+use super::color::srgb_to_linear;
+
+/// How the hue of two colors is interpolated in polar color spaces.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum HueInterpolation {
+    /// Takes the shorter arc between the two hues.
+    #[default]
+    Shorter,
+    /// Takes the longer arc between the two hues.
+    Longer,
+    /// Hue always increases from the first color to the second.
+    Increasing,
+    /// Hue always decreases from the first color to the second.
+    Decreasing,
+}
+
+/// The color space in which gradient colors are interpolated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum GradientInterpolation {
+    /// Interpolates unpremultiplied sRGB values and premultiplies afterwards.
+    /// Fading to a transparent color goes through the color channels of the transparent color.
+    #[default]
+    UnpremulSrgb,
+    /// Interpolates premultiplied sRGB values, same as CSS gradients.
+    PremulSrgb,
+    /// Interpolates premultiplied linear sRGB values.
+    LinearSrgb,
+    /// Interpolates premultiplied Oklab values.
+    Oklab,
+    /// Interpolates premultiplied Oklch values, hue follows the given direction.
+    Oklch(HueInterpolation),
+}
+
+/// Chroma below which a color is treated as achromatic and its hue is taken from the neighbour.
+const ACHROMATIC_CHROMA: f32 = 1e-4;
+
+impl GradientInterpolation {
+    /// Returns the mode index used by the `gradient_to_srgb` function in gradient.wgsl.
+    pub(crate) fn shader_mode(&self) -> u32 {
+        match self {
+            GradientInterpolation::UnpremulSrgb => 0,
+            GradientInterpolation::PremulSrgb => 1,
+            GradientInterpolation::LinearSrgb => 2,
+            GradientInterpolation::Oklab => 3,
+            GradientInterpolation::Oklch(_) => 4,
+        }
+    }
+
+    /// Converts colors into the interpolation space, so that the shader only interpolates linearly.
+    /// Channels are premultiplied by alpha except for `UnpremulSrgb` and the hue of `Oklch`.
+    /// Oklch hues are unwrapped so that each pair of neighbours follows the hue direction.
+    pub(crate) fn convert(&self, colors: &[Color]) -> Vec<[f32; 4]> {
+        match self {
+            GradientInterpolation::UnpremulSrgb => {
+                colors.iter().map(|c| [c.r, c.g, c.b, c.a]).collect()
+            }
+            GradientInterpolation::PremulSrgb => colors
+                .iter()
+                .map(|c| [c.r * c.a, c.g * c.a, c.b * c.a, c.a])
+                .collect(),
+            GradientInterpolation::LinearSrgb => colors
+                .iter()
+                .map(|c| {
+                    [
+                        srgb_to_linear(c.r) * c.a,
+                        srgb_to_linear(c.g) * c.a,
+                        srgb_to_linear(c.b) * c.a,
+                        c.a,
+                    ]
+                })
+                .collect(),
+            GradientInterpolation::Oklab => colors
+                .iter()
+                .map(|c| {
+                    let lab = c.to_oklab();
+                    [lab[0] * c.a, lab[1] * c.a, lab[2] * c.a, c.a]
+                })
+                .collect(),
+            GradientInterpolation::Oklch(hue) => Self::convert_oklch(colors, *hue),
+        }
+    }
+
+    fn convert_oklch(colors: &[Color], hue: HueInterpolation) -> Vec<[f32; 4]> {
+        let lch: Vec<[f32; 3]> = colors
+            .iter()
+            .map(|c| {
+                let lab = c.to_oklab();
+                let chroma = lab[1].hypot(lab[2]);
+
+                [lab[0], chroma, lab[2].atan2(lab[1])]
+            })
+            .collect();
+
+        // achromatic colors have no hue, use the hue of the closest chromatic neighbour
+        let chromatic: Vec<usize> = (0..lch.len())
+            .filter(|i| lch[*i][1] > ACHROMATIC_CHROMA)
+            .collect();
+
+        let mut hues: Vec<f32> = (0..lch.len())
+            .map(|i| {
+                if lch[i][1] > ACHROMATIC_CHROMA {
+                    return lch[i][2];
+                }
+
+                chromatic
+                    .iter()
+                    .min_by_key(|j| j.abs_diff(i))
+                    .map(|j| lch[*j][2])
+                    .unwrap_or(0.0)
+            })
+            .collect();
+
+        for i in 1..hues.len() {
+            let prev = hues[i - 1];
+            let mut delta = (hues[i] - prev).rem_euclid(2.0 * PI);
+
+            delta = match hue {
+                HueInterpolation::Shorter if delta > PI => delta - 2.0 * PI,
+                HueInterpolation::Longer if delta > 0.0 && delta < PI => delta - 2.0 * PI,
+                HueInterpolation::Decreasing if delta > 0.0 => delta - 2.0 * PI,
+                _ => delta,
+            };
+
+            hues[i] = prev + delta;
+        }
+
+        colors
+            .iter()
+            .zip(lch.iter().zip(hues))
+            .map(|(c, (lch, h))| [lch[0] * c.a, lch[1] * c.a, h, c.a])
+            .collect()
+    }
+}
+
 /// A gradient with linear direction between two points.
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct LinearGradient {
     pub matrix: Matrix,
     /// The colors to be distributed between the two points.
@@ -16,6 +155,8 @@ pub struct LinearGradient {
     pub p2: Point,
     /// Defines how to repeat, fold or imit colors outside of the typically defined range of the source of the colors (such as the bounds of an image or the defining geometry of a gradient).
     pub tile_mode: TileMode,
+    /// The color space in which colors are interpolated.
+    pub interpolation: GradientInterpolation,
 }
 
 impl LinearGradient {
@@ -33,6 +174,7 @@ impl LinearGradient {
             p1,
             p2,
             tile_mode: Default::default(),
+            interpolation: Default::default(),
         }
     }
 
@@ -77,6 +219,12 @@ impl LinearGradient {
         self.tile_mode = tile_mode;
         self
     }
+
+    /// Replace the color space in which colors of the gradient are interpolated.
+    pub fn with_interpolation(mut self, interpolation: GradientInterpolation) -> Self {
+        self.interpolation = interpolation;
+        self
+    }
 }
 
 impl Into<ColorType> for LinearGradient {
@@ -87,6 +235,7 @@ impl Into<ColorType> for LinearGradient {
 
 /// A gradient with a given center point and radius.
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct RadialGradient {
     pub matrix: Matrix,
     /// The colors to be distributed between the two points.
@@ -101,6 +250,8 @@ pub struct RadialGradient {
     pub radius: f32,
     /// Defines how to repeat, fold or imit colors outside of the typically defined range of the source of the colors (such as the bounds of an image or the defining geometry of a gradient).
     pub tile_mode: TileMode,
+    /// The color space in which colors are interpolated.
+    pub interpolation: GradientInterpolation,
 }
 
 impl RadialGradient {
@@ -118,6 +269,7 @@ impl RadialGradient {
             center,
             radius,
             tile_mode: Default::default(),
+            interpolation: Default::default(),
         }
     }
 
@@ -162,6 +314,12 @@ impl RadialGradient {
         self.tile_mode = tile_mode;
         self
     }
+
+    /// Replace the color space in which colors of the gradient are interpolated.
+    pub fn with_interpolation(mut self, interpolation: GradientInterpolation) -> Self {
+        self.interpolation = interpolation;
+        self
+    }
 }
 
 impl Into<ColorType> for RadialGradient {
@@ -169,3 +327,27 @@ impl Into<ColorType> for RadialGradient {
         ColorType::RadialGradient(self)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn oklch_hue_direction() {
+        let colors = vec![Color::red(), Color::blue()];
+
+        let hue = |h: HueInterpolation| {
+            let c = GradientInterpolation::Oklch(h).convert(&colors);
+            c[1][2] - c[0][2]
+        };
+
+        assert!(hue(HueInterpolation::Shorter).abs() <= PI);
+        assert!(hue(HueInterpolation::Longer).abs() >= PI);
+        assert!(hue(HueInterpolation::Increasing) >= 0.0);
+        assert!(hue(HueInterpolation::Decreasing) <= 0.0);
+
+        let white = GradientInterpolation::Oklab.convert(&[Color::white()]);
+        assert!((white[0][0] - 1.0).abs() < 1e-3);
+        assert!(white[0][1].abs() < 1e-3 && white[0][2].abs() < 1e-3);
+    }
+}
@@ -1,5 +1,7 @@
 use std::rc::Rc;
 
+use super::paint::ColorType;
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum ImageFormat {
     RGBA8888,
@@ -77,3 +79,9 @@ impl Image {
         }
     }
 }
+
+impl From<Image> for ColorType {
+    fn from(image: Image) -> Self {
+        ColorType::Image(image)
+    }
+}
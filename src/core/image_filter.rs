@@ -0,0 +1,390 @@
+use nalgebra::Matrix4;
+
+use super::{Color, ColorFilter, Rect};
+
+/// Filter applied to the content of a layer before it is composited onto the parent.
+///
+/// Filters form a graph. An input of `None` means the source of the filter, which is the layer content,
+/// or the result of the inner filter when used as the outer filter of `Compose`.
+/// Distances and sigmas are in the local space of the layer when `save_layer` is called.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ImageFilter {
+    /// Gaussian blur of the input.
+    Blur {
+        sigma_x: f32,
+        sigma_y: f32,
+        input: Option<Box<ImageFilter>>,
+    },
+    /// Draws the input over its shadow. The shadow is the alpha of the input filled with color,
+    /// blurred and moved by (dx, dy).
+    DropShadow {
+        dx: f32,
+        dy: f32,
+        sigma_x: f32,
+        sigma_y: f32,
+        color: Color,
+        input: Option<Box<ImageFilter>>,
+    },
+    /// Same as `DropShadow` but only the shadow is drawn.
+    DropShadowOnly {
+        dx: f32,
+        dy: f32,
+        sigma_x: f32,
+        sigma_y: f32,
+        color: Color,
+        input: Option<Box<ImageFilter>>,
+    },
+    /// Moves the input by (dx, dy).
+    Offset {
+        dx: f32,
+        dy: f32,
+        input: Option<Box<ImageFilter>>,
+    },
+    /// Transforms the color of the input.
+    ColorFilter {
+        filter: ColorFilter,
+        input: Option<Box<ImageFilter>>,
+    },
+    /// Draws each input in order with SrcOver blending.
+    Merge(Vec<Option<ImageFilter>>),
+    /// Applies the second filter first, then the first filter to the result. `Compose(outer, inner)`
+    Compose(Box<ImageFilter>, Box<ImageFilter>),
+}
+
+/// Extent of a Gaussian with sigma, beyond which the weight is negligible.
+pub(crate) fn blur_extent(sigma: f32) -> f32 {
+    sigma.max(0.0) * 3.0
+}
+
+fn shadow_bounds(src: &Rect, dx: f32, dy: f32, sigma_x: f32, sigma_y: f32) -> Rect {
+    let mut shadow = *src;
+    shadow.offset(dx, dy);
+    shadow.outset(blur_extent(sigma_x), blur_extent(sigma_y));
+    shadow
+}
+
+impl ImageFilter {
+    /// Creates a blur filter.
+    ///
+    /// # Arguments
+    ///
+    /// * `sigma_x` standard deviation of the Gaussian along x-axis
+    /// * `sigma_y` standard deviation of the Gaussian along y-axis
+    /// * `input` the filter to blur, pass `None` to blur the source
+    pub fn blur(sigma_x: f32, sigma_y: f32, input: Option<ImageFilter>) -> Self {
+        ImageFilter::Blur {
+            sigma_x,
+            sigma_y,
+            input: input.map(Box::new),
+        }
+    }
+
+    /// Creates a filter which draws the input over its shadow.
+    ///
+    /// # Arguments
+    ///
+    /// * `dx` distance the shadow moved along x-axis
+    /// * `dy` distance the shadow moved along y-axis
+    /// * `sigma_x` blur standard deviation of the shadow along x-axis
+    /// * `sigma_y` blur standard deviation of the shadow along y-axis
+    /// * `color` color of the shadow
+    /// * `input` the filter casts the shadow, pass `None` to use the source
+    pub fn drop_shadow(
+        dx: f32,
+        dy: f32,
+        sigma_x: f32,
+        sigma_y: f32,
+        color: Color,
+        input: Option<ImageFilter>,
+    ) -> Self {
+        ImageFilter::DropShadow {
+            dx,
+            dy,
+            sigma_x,
+            sigma_y,
+            color,
+            input: input.map(Box::new),
+        }
+    }
+
+    /// Creates a filter which draws only the shadow of the input.
+    /// Arguments are the same as `drop_shadow`.
+    pub fn drop_shadow_only(
+        dx: f32,
+        dy: f32,
+        sigma_x: f32,
+        sigma_y: f32,
+        color: Color,
+        input: Option<ImageFilter>,
+    ) -> Self {
+        ImageFilter::DropShadowOnly {
+            dx,
+            dy,
+            sigma_x,
+            sigma_y,
+            color,
+            input: input.map(Box::new),
+        }
+    }
+
+    /// Creates a filter which moves the input by (dx, dy).
+    pub fn offset(dx: f32, dy: f32, input: Option<ImageFilter>) -> Self {
+        ImageFilter::Offset {
+            dx,
+            dy,
+            input: input.map(Box::new),
+        }
+    }
+
+    /// Creates a filter which transforms the color of the input with `filter`.
+    pub fn color_filter(filter: ColorFilter, input: Option<ImageFilter>) -> Self {
+        ImageFilter::ColorFilter {
+            filter,
+            input: input.map(Box::new),
+        }
+    }
+
+    /// Creates a filter which draws the inputs in order. `None` stands for the source.
+    pub fn merge(inputs: Vec<Option<ImageFilter>>) -> Self {
+        ImageFilter::Merge(inputs)
+    }
+
+    /// Creates a filter which applies `inner` first and then `outer`.
+    pub fn compose(outer: ImageFilter, inner: ImageFilter) -> Self {
+        ImageFilter::Compose(Box::new(outer), Box::new(inner))
+    }
+
+    /// Returns the bounds the output of this filter can cover, if the source covers `src`.
+    pub(crate) fn map_bounds(&self, src: &Rect) -> Rect {
+        let input_bounds = |input: &Option<Box<ImageFilter>>| match input {
+            Some(input) => input.map_bounds(src),
+            None => *src,
+        };
+
+        match self {
+            ImageFilter::Blur {
+                sigma_x,
+                sigma_y,
+                input,
+            } => {
+                let mut bounds = input_bounds(input);
+                bounds.outset(blur_extent(*sigma_x), blur_extent(*sigma_y));
+                bounds
+            }
+            ImageFilter::DropShadow {
+                dx,
+                dy,
+                sigma_x,
+                sigma_y,
+                input,
+                ..
+            } => {
+                let bounds = input_bounds(input);
+                bounds.union(&shadow_bounds(&bounds, *dx, *dy, *sigma_x, *sigma_y))
+            }
+            ImageFilter::DropShadowOnly {
+                dx,
+                dy,
+                sigma_x,
+                sigma_y,
+                input,
+                ..
+            } => shadow_bounds(&input_bounds(input), *dx, *dy, *sigma_x, *sigma_y),
+            ImageFilter::Offset { dx, dy, input } => {
+                let mut bounds = input_bounds(input);
+                bounds.offset(*dx, *dy);
+                bounds
+            }
+            ImageFilter::ColorFilter { input, .. } => input_bounds(input),
+            ImageFilter::Merge(inputs) => inputs
+                .iter()
+                .map(|input| match input {
+                    Some(input) => input.map_bounds(src),
+                    None => *src,
+                })
+                .fold(Rect::from_ltrb(0.0, 0.0, 0.0, 0.0), |acc, b| acc.union(&b)),
+            ImageFilter::Compose(outer, inner) => outer.map_bounds(&inner.map_bounds(src)),
+        }
+    }
+
+    /// Returns the bounds of the source which affects the output of this filter inside `dst`.
+    pub(crate) fn reverse_bounds(&self, dst: &Rect) -> Rect {
+        let input_bounds = |input: &Option<Box<ImageFilter>>, bounds: Rect| match input {
+            Some(input) => input.reverse_bounds(&bounds),
+            None => bounds,
+        };
+
+        match self {
+            ImageFilter::Blur {
+                sigma_x,
+                sigma_y,
+                input,
+            } => {
+                let mut bounds = *dst;
+                bounds.outset(blur_extent(*sigma_x), blur_extent(*sigma_y));
+                input_bounds(input, bounds)
+            }
+            ImageFilter::DropShadow {
+                dx,
+                dy,
+                sigma_x,
+                sigma_y,
+                input,
+                ..
+            } => input_bounds(
+                input,
+                dst.union(&shadow_bounds(dst, -dx, -dy, *sigma_x, *sigma_y)),
+            ),
+            ImageFilter::DropShadowOnly {
+                dx,
+                dy,
+                sigma_x,
+                sigma_y,
+                input,
+                ..
+            } => input_bounds(input, shadow_bounds(dst, -dx, -dy, *sigma_x, *sigma_y)),
+            ImageFilter::Offset { dx, dy, input } => {
+                let mut bounds = *dst;
+                bounds.offset(-dx, -dy);
+                input_bounds(input, bounds)
+            }
+            ImageFilter::ColorFilter { input, .. } => input_bounds(input, *dst),
+            ImageFilter::Merge(inputs) => inputs
+                .iter()
+                .map(|input| match input {
+                    Some(input) => input.reverse_bounds(dst),
+                    None => *dst,
+                })
+                .fold(Rect::from_ltrb(0.0, 0.0, 0.0, 0.0), |acc, b| acc.union(&b)),
+            ImageFilter::Compose(outer, inner) => inner.reverse_bounds(&outer.reverse_bounds(dst)),
+        }
+    }
+
+    /// Returns the filter with distances and sigmas mapped from local space into device space by `matrix`.
+    /// Sigmas are scaled by the length of the mapped axes, so rotation is approximated.
+    pub(crate) fn transformed(&self, matrix: &Matrix4<f32>) -> ImageFilter {
+        let map_vector = |dx: f32, dy: f32| {
+            (
+                matrix.m11 * dx + matrix.m12 * dy,
+                matrix.m21 * dx + matrix.m22 * dy,
+            )
+        };
+        let scale_x = matrix.m11.hypot(matrix.m21);
+        let scale_y = matrix.m12.hypot(matrix.m22);
+
+        let map_input = |input: &Option<Box<ImageFilter>>| {
+            input
+                .as_ref()
+                .map(|input| Box::new(input.transformed(matrix)))
+        };
+
+        match self {
+            ImageFilter::Blur {
+                sigma_x,
+                sigma_y,
+                input,
+            } => ImageFilter::Blur {
+                sigma_x: sigma_x * scale_x,
+                sigma_y: sigma_y * scale_y,
+                input: map_input(input),
+            },
+            ImageFilter::DropShadow {
+                dx,
+                dy,
+                sigma_x,
+                sigma_y,
+                color,
+                input,
+            } => {
+                let (dx, dy) = map_vector(*dx, *dy);
+
+                ImageFilter::DropShadow {
+                    dx,
+                    dy,
+                    sigma_x: sigma_x * scale_x,
+                    sigma_y: sigma_y * scale_y,
+                    color: *color,
+                    input: map_input(input),
+                }
+            }
+            ImageFilter::DropShadowOnly {
+                dx,
+                dy,
+                sigma_x,
+                sigma_y,
+                color,
+                input,
+            } => {
+                let (dx, dy) = map_vector(*dx, *dy);
+
+                ImageFilter::DropShadowOnly {
+                    dx,
+                    dy,
+                    sigma_x: sigma_x * scale_x,
+                    sigma_y: sigma_y * scale_y,
+                    color: *color,
+                    input: map_input(input),
+                }
+            }
+            ImageFilter::Offset { dx, dy, input } => {
+                let (dx, dy) = map_vector(*dx, *dy);
+
+                ImageFilter::Offset {
+                    dx,
+                    dy,
+                    input: map_input(input),
+                }
+            }
+            ImageFilter::ColorFilter { filter, input } => ImageFilter::ColorFilter {
+                filter: filter.clone(),
+                input: map_input(input),
+            },
+            ImageFilter::Merge(inputs) => ImageFilter::Merge(
+                inputs
+                    .iter()
+                    .map(|input| input.as_ref().map(|input| input.transformed(matrix)))
+                    .collect(),
+            ),
+            ImageFilter::Compose(outer, inner) => ImageFilter::Compose(
+                Box::new(outer.transformed(matrix)),
+                Box::new(inner.transformed(matrix)),
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn filter_bounds() {
+        let src = Rect::from_ltrb(10.0, 10.0, 20.0, 20.0);
+
+        let shadow = ImageFilter::drop_shadow(5.0, 5.0, 1.0, 1.0, Color::black(), None);
+        assert_eq!(
+            shadow.map_bounds(&src),
+            Rect::from_ltrb(10.0, 10.0, 28.0, 28.0)
+        );
+
+        let filter = ImageFilter::compose(
+            ImageFilter::offset(10.0, 0.0, None),
+            ImageFilter::blur(2.0, 0.0, None),
+        );
+        assert_eq!(
+            filter.map_bounds(&src),
+            Rect::from_ltrb(14.0, 10.0, 36.0, 20.0)
+        );
+        assert_eq!(
+            filter.reverse_bounds(&src),
+            Rect::from_ltrb(-6.0, 10.0, 16.0, 20.0)
+        );
+
+        let scaled = filter.transformed(&Matrix4::new_scaling(2.0));
+        assert_eq!(
+            scaled.map_bounds(&src),
+            Rect::from_ltrb(18.0, 10.0, 52.0, 20.0)
+        );
+    }
+}
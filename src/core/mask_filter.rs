@@ -0,0 +1,35 @@
+/// Describes how the blurred coverage is combined with the original coverage of the geometry.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum BlurStyle {
+    /// blurred inside and outside
+    #[default]
+    Normal,
+    /// solid inside, blurred outside
+    Solid,
+    /// nothing inside, blurred outside
+    Outer,
+    /// blurred inside, nothing outside
+    Inner,
+}
+
+/// Filter applied to the coverage of the geometry before it is shaded with the paint color.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum MaskFilter {
+    /// Gaussian blur of the coverage.
+    /// `sigma` is the standard deviation of the Gaussian in local space, it is scaled by the current transform.
+    Blur { style: BlurStyle, sigma: f32 },
+}
+
+impl MaskFilter {
+    /// Creates a blur mask filter.
+    ///
+    /// # Arguments
+    ///
+    /// * `style` how the blurred coverage is combined with the original coverage
+    /// * `sigma` standard deviation of the Gaussian, must be positive
+    pub fn blur(style: BlurStyle, sigma: f32) -> Self {
+        MaskFilter::Blur { style, sigma }
+    }
+}
@@ -5,8 +5,12 @@ use nalgebra::{Matrix4, Vector3, Vector4};
 use crate::{geometry::degree_to_radian, Point, Rect};
 
 /// Holds the matrix information which can be used to transform the Point, Rect or other geometries.
+/// With the `serde` feature the matrix is represented by its four rows.
 #[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(transparent))]
 pub struct Matrix {
+    #[cfg_attr(feature = "serde", serde(with = "rows"))]
     pub(crate) matrix: Matrix4<f32>,
 }
 
@@ -18,6 +22,32 @@ impl Matrix {
         }
     }
 
+    /// Creates a matrix from the rows of a 3x3 matrix transforming 2D homogeneous points.
+    /// The first two rows are the affine part, the last row is the perspective part which is
+    /// `[0.0, 0.0, 1.0]` for affine matrices.
+    ///
+    /// # Arguments
+    ///
+    /// * `rows` - `[[scale_x, skew_x, trans_x], [skew_y, scale_y, trans_y], [persp_0, persp_1, persp_2]]`
+    pub fn from_rows(rows: [[f32; 3]; 3]) -> Self {
+        let [[a, b, c], [d, e, f], [g, h, i]] = rows;
+
+        Self {
+            matrix: Matrix4::new(a, b, 0.0, c, d, e, 0.0, f, 0.0, 0.0, 1.0, 0.0, g, h, 0.0, i),
+        }
+    }
+
+    /// Returns the rows of the 3x3 matrix transforming 2D homogeneous points, see `Matrix::from_rows`.
+    pub fn rows(&self) -> [[f32; 3]; 3] {
+        let m = &self.matrix;
+
+        [
+            [m.m11, m.m12, m.m14],
+            [m.m21, m.m22, m.m24],
+            [m.m41, m.m42, m.m44],
+        ]
+    }
+
     /// Check if the matrix is identity.
     pub fn is_identity(&self) -> bool {
         self.matrix.is_identity(f32::EPSILON)
@@ -34,6 +64,11 @@ impl Matrix {
         self.matrix.m12 != 0.0 || self.matrix.m21 != 0.0
     }
 
+    /// Check if the matrix has perspective, then mapped points are divided by their projected w.
+    pub fn has_perspective(&self) -> bool {
+        self.matrix.m41 != 0.0 || self.matrix.m42 != 0.0 || self.matrix.m44 != 1.0
+    }
+
     /// Append translate to this matrix.
     ///
     /// # Arguments
@@ -58,6 +93,20 @@ impl Matrix {
         self.matrix = s * self.matrix;
     }
 
+    /// Append skew to this matrix.
+    ///
+    /// # Arguments
+    ///
+    /// * `sx` - Skew factor at x-axis, x is moved by `sx * y`.
+    /// * `sy` - Skew factor at y-axis, y is moved by `sy * x`.
+    pub fn skew(&mut self, sx: f32, sy: f32) {
+        let s: Matrix4<f32> = Matrix4::new(
+            1.0, sx, 0.0, 0.0, sy, 1.0, 0.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 0.0, 1.0,
+        );
+
+        self.matrix = s * self.matrix;
+    }
+
     /// Append rotate to this matrix.
     ///
     /// # Arguments
@@ -95,6 +144,8 @@ impl Matrix {
     }
 
     /// Apply this matrix to the point.
+    /// With perspective the point is divided by its projected w, points with w not above zero are
+    /// behind the viewer and are not divided.
     ///
     /// # Arguments
     ///
@@ -106,6 +157,10 @@ impl Matrix {
 
         let vector = self.matrix * Vector4::new(point.x, point.y, 0.0, 1.0);
 
+        if vector.w > 0.0 && vector.w != 1.0 {
+            return Point::from(vector.x / vector.w, vector.y / vector.w);
+        }
+
         return Point::from(vector.x, vector.y);
     }
 
@@ -176,6 +231,30 @@ impl Mul<Rect> for Matrix {
     }
 }
 
+/// Represents a `Matrix4` as an array of rows, the order people write matrices in.
+#[cfg(feature = "serde")]
+mod rows {
+    use nalgebra::Matrix4;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    pub(super) fn serialize<S: Serializer>(
+        matrix: &Matrix4<f32>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        let rows: [[f32; 4]; 4] = std::array::from_fn(|r| std::array::from_fn(|c| matrix[(r, c)]));
+
+        rows.serialize(serializer)
+    }
+
+    pub(super) fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Matrix4<f32>, D::Error> {
+        let rows = <[[f32; 4]; 4]>::deserialize(deserializer)?;
+
+        Ok(Matrix4::from_fn(|r, c| rows[r][c]))
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -210,4 +289,30 @@ mod tests {
         assert_eq!(transformed_point.x, 40.0);
         assert_eq!(transformed_point.y, 120.0);
     }
+
+    #[test]
+    fn test_matrix_skew_and_perspective() {
+        let mut matrix = Matrix::new();
+        matrix.skew(0.5, 0.0);
+
+        assert_eq!(
+            matrix.rows(),
+            [[1.0, 0.5, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]]
+        );
+        assert_eq!(
+            matrix.map_point(&Point::from(10.0, 20.0)),
+            Point::from(20.0, 20.0)
+        );
+        assert!(!matrix.has_perspective());
+
+        let rows = [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.01, 0.0, 1.0]];
+        let matrix = Matrix::from_rows(rows);
+
+        assert_eq!(matrix.rows(), rows);
+        assert!(matrix.has_perspective());
+        assert_eq!(
+            matrix.map_point(&Point::from(100.0, 50.0)),
+            Point::from(50.0, 25.0)
+        );
+    }
 }
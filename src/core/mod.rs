@@ -1,26 +1,42 @@
+pub(crate) mod blend;
+pub(crate) mod canvas;
 pub(crate) mod color;
+pub(crate) mod color_filter;
 pub(crate) mod geometry;
 pub(crate) mod gradient;
 pub(crate) mod image;
+pub(crate) mod image_filter;
+pub(crate) mod mask_filter;
 pub(crate) mod matrix;
 pub(crate) mod paint;
 pub(crate) mod path;
 pub(crate) mod picture;
+pub(crate) mod rtree;
+pub(crate) mod runtime_shader;
+pub(crate) mod serialize;
 pub(crate) mod state;
 
+pub use blend::BlendMode;
+pub use canvas::Canvas;
 use bytemuck::{Pod, Zeroable};
-pub use color::Color;
-pub use gradient::{LinearGradient, RadialGradient};
+pub use color::{Color, ColorParseError, ColorSpace};
+pub use color_filter::ColorFilter;
+pub use gradient::{GradientInterpolation, HueInterpolation, LinearGradient, RadialGradient};
 pub use image::*;
+pub use image_filter::ImageFilter;
+pub use mask_filter::{BlurStyle, MaskFilter};
 pub use matrix::*;
 use nalgebra::{Matrix4, Vector4};
 pub use paint::{ColorType, Paint, Stroke, StrokeCap, StrokeJoin, Style};
 pub use path::{Path, PathDirection, PathFillType};
-pub use picture::{ClipOp, Picture, PictureRecorder};
+pub use picture::{ClipOp, Diagnostic, Picture, PictureRecorder};
+pub use runtime_shader::{RuntimeShader, RuntimeShaderError, RuntimeUniforms, UniformType};
+pub use serialize::{DeserializeError, FontResolver};
 
 /// Defines how to repeat, fold or imit colors outside of the typically defined range of the source of the
 /// colors (such as the bounds of an image or the defining geometry of a gradient).
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum TileMode {
     /// Replicate the edge color if the vertex draws outside of its orignal bounds.
     #[default]
@@ -36,6 +52,7 @@ pub enum TileMode {
 
 #[repr(C)]
 #[derive(Debug, Clone, Copy, PartialEq, Pod, Zeroable)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Point {
     pub x: f32,
     pub y: f32,
@@ -61,6 +78,7 @@ impl Point {
 }
 
 #[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Rect {
     /// The left coordinate of the rectangle. If sorted.
     pub left: f32,
@@ -137,6 +155,71 @@ impl Rect {
         self.bottom += dy;
     }
 
+    /// Returns the intersection of this rectangle and `other`.
+    /// Returns `None` if the two rectangles do not overlap.
+    pub fn intersect(&self, other: &Rect) -> Option<Rect> {
+        let r = Rect::from_ltrb(
+            self.left.max(other.left),
+            self.top.max(other.top),
+            self.right.min(other.right),
+            self.bottom.min(other.bottom),
+        );
+
+        if r.is_empty() {
+            None
+        } else {
+            Some(r)
+        }
+    }
+
+    /// Returns the smallest rectangle that contains both this rectangle and `other`.
+    /// Empty rectangles are ignored.
+    pub fn union(&self, other: &Rect) -> Rect {
+        if other.is_empty() {
+            return *self;
+        }
+
+        if self.is_empty() {
+            return *other;
+        }
+
+        Rect::from_ltrb(
+            self.left.min(other.left),
+            self.top.min(other.top),
+            self.right.max(other.right),
+            self.bottom.max(other.bottom),
+        )
+    }
+
+    /// Returns true if `other` is inside or equal to this rectangle.
+    pub fn contains(&self, other: &Rect) -> bool {
+        !self.is_empty()
+            && !other.is_empty()
+            && self.left <= other.left
+            && self.top <= other.top
+            && self.right >= other.right
+            && self.bottom >= other.bottom
+    }
+
+    /// Outsets the rectangle by dx on the left and right, and by dy on the top and bottom.
+    /// Negative values inset the rectangle.
+    pub fn outset(&mut self, dx: f32, dy: f32) {
+        self.left -= dx;
+        self.top -= dy;
+        self.right += dx;
+        self.bottom += dy;
+    }
+
+    /// Returns the rectangle with each edge moved outward to the nearest integer.
+    pub fn round_out(&self) -> Rect {
+        Rect::from_ltrb(
+            self.left.floor(),
+            self.top.floor(),
+            self.right.ceil(),
+            self.bottom.ceil(),
+        )
+    }
+
     pub fn is_finite(&self) -> bool {
         let mut accum = 0.0;
         accum *= self.left;
@@ -149,6 +232,7 @@ impl Rect {
 }
 
 #[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct RRect {
     pub(crate) rect: Rect,
     pub(crate) radii: [Point; 4],
@@ -1,7 +1,11 @@
-use super::{Color, LinearGradient, RadialGradient};
+use super::{
+    BlendMode, Color, ColorFilter, Image, ImageFilter, LinearGradient, MaskFilter, Matrix,
+    RadialGradient, RuntimeUniforms,
+};
 
 /// Cap draws at the beginning and end of an open path contour.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum StrokeCap {
     /// no stroke extension
     #[default]
@@ -14,6 +18,7 @@ pub enum StrokeCap {
 
 /// Specifies how corners are drawn when a shape is stroked.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum StrokeJoin {
     /// draw sharp corners. extends to the miter limit
     #[default]
@@ -26,6 +31,8 @@ pub enum StrokeJoin {
 
 /// Specifies the style of the stroke.
 #[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
 pub struct Stroke {
     /// width of the stroke.
     /// default value is 1.0
@@ -80,12 +87,38 @@ impl Stroke {
 
 /// Controls the Style when rendering geometry
 #[derive(Debug, Clone, Copy, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Style {
     /// Fill the geometry
     #[default]
     Fill,
     /// Stroke the geometry
     Stroke(Stroke),
+    /// Fill the geometry and stroke its outline in one draw.
+    /// The overlap of fill and stroke is blended once, which keeps translucent paints uniform.
+    FillAndStroke(Stroke),
+}
+
+impl Style {
+    /// Returns the conservative distance the geometry may extend beyond the path bounds.
+    pub(crate) fn outset(&self) -> f32 {
+        match self {
+            Style::Fill => 0.0,
+            Style::Stroke(stroke) | Style::FillAndStroke(stroke) => {
+                let mut radius = stroke.width * 0.5;
+
+                if stroke.join == StrokeJoin::Miter {
+                    radius *= stroke.miter_limit.max(1.0);
+                }
+
+                if stroke.cap == StrokeCap::Square {
+                    radius *= std::f32::consts::SQRT_2;
+                }
+
+                radius
+            }
+        }
+    }
 }
 
 impl Into<Style> for Stroke {
@@ -94,15 +127,57 @@ impl Into<Style> for Stroke {
     }
 }
 
+/// Describes how the geometry is shaded. Color types compose into a shader tree,
+/// which is evaluated in the local space of the draw.
+/// With the `serde` feature, images and runtime shaders fail to serialize since they hold GPU resources
+/// or compiled programs.
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum ColorType {
     SolidColor(Color),
     LinearGradient(LinearGradient),
     RadialGradient(RadialGradient),
+    /// image placed at the local origin with its pixel size, pixels outside are clamped to the edge
+    #[cfg_attr(feature = "serde", serde(skip))]
+    Image(Image),
+    /// blends the first color as source with the second color as destination
+    Blend(BlendMode, Box<ColorType>, Box<ColorType>),
+    /// shades the color in the space mapped by the matrix
+    WithLocalMatrix(Matrix, Box<ColorType>),
+    /// runs the color filter on the color
+    WithColorFilter(ColorFilter, Box<ColorType>),
+    /// user supplied WGSL shader with its uniform values
+    #[cfg_attr(feature = "serde", serde(skip))]
+    RuntimeShader(RuntimeUniforms),
+}
+
+impl ColorType {
+    /// Creates a color which blends two colors.
+    ///
+    /// # Arguments
+    ///
+    /// * `mode` how the colors are combined
+    /// * `src` color used as source
+    /// * `dst` color used as destination
+    pub fn blend(mode: BlendMode, src: impl Into<ColorType>, dst: impl Into<ColorType>) -> Self {
+        ColorType::Blend(mode, Box::new(src.into()), Box::new(dst.into()))
+    }
+
+    /// Shades this color in the space mapped by `matrix`.
+    pub fn with_local_matrix(self, matrix: Matrix) -> Self {
+        ColorType::WithLocalMatrix(matrix, Box::new(self))
+    }
+
+    /// Runs `filter` on this color.
+    pub fn with_color_filter(self, filter: ColorFilter) -> Self {
+        ColorType::WithColorFilter(filter, Box::new(self))
+    }
 }
 
 /// Paint controls options applied when drawing.
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
 pub struct Paint {
     /// unpremultiplied color used when stroking or filling.
     /// default value is black
@@ -110,6 +185,28 @@ pub struct Paint {
     /// style when rendering geometry
     /// default value is Style::Fill
     pub style: Style,
+    /// how the result is combined with the destination
+    /// default value is BlendMode::SrcOver
+    pub blend_mode: BlendMode,
+    /// filter applied to the color before blending
+    /// default value is None
+    pub color_filter: Option<ColorFilter>,
+    /// filter applied to the coverage of the geometry
+    /// default value is None
+    pub mask_filter: Option<MaskFilter>,
+    /// filter applied to the content of a layer, only used by the paint passed to `save_layer`
+    /// default value is None
+    pub image_filter: Option<ImageFilter>,
+    /// adds ordered dither noise to gradients and images to hide banding on low bit depth targets
+    /// default value is false
+    pub dither: bool,
+    /// smooths edges of the geometry. Without it, edges cover whole pixels even on multisampled surfaces
+    /// default value is true
+    pub anti_alias: bool,
+    /// moves the edges of rects and polygons to the pixel grid, so they are drawn crisp.
+    /// Only used when the draw has no rotation, skew or perspective
+    /// default value is false
+    pub pixel_snap: bool,
 }
 
 impl Paint {
@@ -117,6 +214,77 @@ impl Paint {
         Self {
             color: Color::black().into(),
             style: Style::Fill,
+            blend_mode: BlendMode::SrcOver,
+            color_filter: None,
+            mask_filter: None,
+            image_filter: None,
+            dither: false,
+            anti_alias: true,
+            pixel_snap: false,
+        }
+    }
+
+    /// Returns the alpha of the paint color.
+    /// Only solid color has a single alpha value, other color types are treated as opaque.
+    pub(crate) fn alpha(&self) -> f32 {
+        match &self.color {
+            ColorType::SolidColor(color) => color.a,
+            _ => 1.0,
         }
     }
 }
+
+impl Default for Paint {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(all(test, feature = "serde"))]
+mod tests {
+    use crate::{LinearGradient, Path, PathFillType, Point, TileMode};
+
+    use super::*;
+
+    #[test]
+    fn serde_round_trip() {
+        let mut matrix = Matrix::new();
+        matrix.translate(5.0, 6.0);
+
+        let mut paint = Paint::new();
+        paint.color = LinearGradient::new(Point::from(0.0, 0.0), Point::from(10.0, 0.0))
+            .with_colors(vec![Color::red(), Color::blue()])
+            .with_tile_mode(TileMode::Mirror)
+            .with_matrix(matrix)
+            .into();
+        paint.style = Stroke::new()
+            .with_width(2.0)
+            .with_cap(StrokeCap::Round)
+            .into();
+
+        let json = serde_json::to_string(&paint).unwrap();
+        assert!(json.contains(r#""tile_mode":"Mirror""#));
+        assert!(json.contains(r#""matrix":[[1.0,0.0,0.0,5.0],[0.0,1.0,0.0,6.0]"#));
+
+        let decoded: Paint = serde_json::from_str(&json).unwrap();
+        assert_eq!(serde_json::to_string(&decoded).unwrap(), json);
+
+        // missing fields take their default value
+        let paint: Paint = serde_json::from_str(r#"{"style":{"Stroke":{"width":3.0}}}"#).unwrap();
+        assert_eq!(paint.style, Style::Stroke(Stroke::new().with_width(3.0)));
+        assert!(paint.anti_alias);
+
+        let path = Path::with_fill_type(PathFillType::EvenOdd)
+            .move_to(1.0, 2.0)
+            .quad_to(3.0, 4.0, 5.0, 6.0)
+            .close();
+        let json = serde_json::to_string(&path).unwrap();
+        assert_eq!(
+            json,
+            r#"{"fill_type":"EvenOdd","verbs":[{"MoveTo":{"x":1.0,"y":2.0}},{"QuadTo":[{"x":3.0,"y":4.0},{"x":5.0,"y":6.0}]},"Close"]}"#
+        );
+
+        let decoded: Path = serde_json::from_str(&json).unwrap();
+        assert_eq!(decoded.verts, path.verts);
+    }
+}
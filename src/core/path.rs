@@ -6,6 +6,7 @@ use super::{
 };
 
 #[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum PathVerb {
     MoveTo(Point),
     LineTo(Point),
@@ -17,6 +18,7 @@ pub enum PathVerb {
 
 /// The fill type of a path.
 #[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum PathFillType {
     /// Specifies that "inside" is computed by a non-zero sum of signed edge crossings
     #[default]
@@ -140,7 +142,10 @@ impl PointIterator {
 /// Path always starts with a move verb to a Cartesian coordinate, and may be followed by additional verbs that add lines or curves.
 /// Adding a close verb makes the geometry into a continuous loop, a closed contour.
 /// A path instance may contain any number of contours, each beginning with a move verb.
+/// With the `serde` feature the path is represented by its fill type and verbs.
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(from = "PathData", into = "PathData"))]
 pub struct Path {
     pub verts: Vec<PathVerb>,
 
@@ -461,6 +466,161 @@ impl Path {
         self.add_circle_dir(cx, cy, radius, Default::default())
     }
 
+    /// Appends a verb the same way the builder method of the verb does.
+    pub(crate) fn add_verb(self, verb: PathVerb) -> Self {
+        match verb {
+            PathVerb::MoveTo(p) => self.move_to_point(p),
+            PathVerb::LineTo(p) => self.line_to_point(p),
+            PathVerb::QuadTo(p1, p2) => self.quad_to_point(p1, p2),
+            PathVerb::ConicTo(p1, p2, w) => self.conic_to_point(p1, p2, w),
+            PathVerb::CubicTo(p1, p2, p3) => self.cubic_to_point(p1, p2, p3),
+            PathVerb::Close => self.close(),
+        }
+    }
+
+    /// Returns the bounds of all points in the path, including control points of curves.
+    /// The result is a conservative bounds of the geometry. Returns an empty rect if the path has no points.
+    pub fn bounds(&self) -> Rect {
+        let mut left = f32::MAX;
+        let mut top = f32::MAX;
+        let mut right = f32::MIN;
+        let mut bottom = f32::MIN;
+
+        let mut add_point = |p: &Point| {
+            left = left.min(p.x);
+            top = top.min(p.y);
+            right = right.max(p.x);
+            bottom = bottom.max(p.y);
+        };
+
+        for verb in &self.verts {
+            match verb {
+                PathVerb::MoveTo(p) | PathVerb::LineTo(p) => add_point(p),
+                PathVerb::QuadTo(p1, p2) | PathVerb::ConicTo(p1, p2, _) => {
+                    add_point(p1);
+                    add_point(p2);
+                }
+                PathVerb::CubicTo(p1, p2, p3) => {
+                    add_point(p1);
+                    add_point(p2);
+                    add_point(p3);
+                }
+                PathVerb::Close => {}
+            }
+        }
+
+        if left > right || top > bottom {
+            return Rect::from_ltrb(0.0, 0.0, 0.0, 0.0);
+        }
+
+        Rect::from_ltrb(left, top, right, bottom)
+    }
+
+    /// Returns the rectangle if the path is a single axis-aligned rectangle, made of a move and three or four lines.
+    pub(crate) fn as_rect(&self) -> Option<Rect> {
+        let mut points = Vec::with_capacity(5);
+
+        for (i, verb) in self.verts.iter().enumerate() {
+            match verb {
+                PathVerb::MoveTo(p) if i == 0 => points.push(*p),
+                PathVerb::LineTo(p) if i > 0 => points.push(*p),
+                PathVerb::Close if i == self.verts.len() - 1 => {}
+                _ => return None,
+            }
+        }
+
+        // the contour may return to its start explicitly
+        if points.len() == 5 && points[4] == points[0] {
+            points.pop();
+        }
+
+        if points.len() != 4 {
+            return None;
+        }
+
+        // edges alternate between horizontal and vertical
+        let horizontal = points[0].y == points[1].y;
+        for i in 0..4 {
+            let (a, b) = (points[i], points[(i + 1) % 4]);
+
+            if (i % 2 == 0) == horizontal {
+                if a.y != b.y {
+                    return None;
+                }
+            } else if a.x != b.x {
+                return None;
+            }
+        }
+
+        Some(self.bounds())
+    }
+
+    /// Returns true if all points and conic weights of the path are finite, neither NaN nor infinite.
+    pub fn is_finite(&self) -> bool {
+        let finite = |p: &Point| p.x.is_finite() && p.y.is_finite();
+
+        self.verts.iter().all(|verb| match verb {
+            PathVerb::MoveTo(p) | PathVerb::LineTo(p) => finite(p),
+            PathVerb::QuadTo(p1, p2) => finite(p1) && finite(p2),
+            PathVerb::ConicTo(p1, p2, w) => finite(p1) && finite(p2) && w.is_finite(),
+            PathVerb::CubicTo(p1, p2, p3) => finite(p1) && finite(p2) && finite(p3),
+            PathVerb::Close => true,
+        })
+    }
+
+    /// Returns the path with its points moved to the pixel grid of the target, so edges do not
+    /// straddle pixels. Only paths made of lines under a scale and translate transform can be
+    /// snapped, `None` is returned for curves, rotation, skew and perspective.
+    ///
+    /// # Arguments
+    ///
+    /// * `transform` transform from local space to the logical space of the target
+    /// * `scale` pixels per logical unit of the target on each axis
+    /// * `offset` position inside the pixel points are moved to on each axis, 0.5 is the pixel center
+    pub(crate) fn snap_to_pixels(
+        &self,
+        transform: &Matrix4<f32>,
+        scale: [f32; 2],
+        offset: [f32; 2],
+    ) -> Option<Path> {
+        if transform.m12 != 0.0
+            || transform.m21 != 0.0
+            || transform.m41 != 0.0
+            || transform.m42 != 0.0
+            || transform.m11 == 0.0
+            || transform.m22 == 0.0
+        {
+            return None;
+        }
+
+        let sx = transform.m11 * scale[0];
+        let sy = transform.m22 * scale[1];
+        let tx = transform.m14 * scale[0];
+        let ty = transform.m24 * scale[1];
+
+        let snap = |p: &Point| Point {
+            x: ((p.x * sx + tx - offset[0]).round() + offset[0] - tx) / sx,
+            y: ((p.y * sy + ty - offset[1]).round() + offset[1] - ty) / sy,
+        };
+
+        let verts = self
+            .verts
+            .iter()
+            .map(|verb| match verb {
+                PathVerb::MoveTo(p) => Some(PathVerb::MoveTo(snap(p))),
+                PathVerb::LineTo(p) => Some(PathVerb::LineTo(snap(p))),
+                PathVerb::Close => Some(PathVerb::Close),
+                _ => None,
+            })
+            .collect::<Option<Vec<_>>>()?;
+
+        Some(Path {
+            verts,
+            last_move_to_index: self.last_move_to_index,
+            fill_type: self.fill_type,
+        })
+    }
+
     /// Appends PathVerb::Close to Path.
     /// A closed contour connects the first and last Point with line, forming a continuous loop.
     pub fn close(mut self) -> Self {
@@ -471,6 +631,34 @@ impl Path {
     }
 }
 
+/// Representation of a path with the `serde` feature.
+#[cfg(feature = "serde")]
+#[derive(serde::Serialize, serde::Deserialize)]
+struct PathData {
+    #[serde(default)]
+    fill_type: PathFillType,
+    verbs: Vec<PathVerb>,
+}
+
+#[cfg(feature = "serde")]
+impl From<PathData> for Path {
+    fn from(data: PathData) -> Self {
+        data.verbs
+            .into_iter()
+            .fold(Path::with_fill_type(data.fill_type), Path::add_verb)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl From<Path> for PathData {
+    fn from(path: Path) -> Self {
+        Self {
+            fill_type: path.fill_type,
+            verbs: path.verts,
+        }
+    }
+}
+
 pub(crate) struct Contour {
     pub(crate) points: Vec<Point>,
     pub(crate) closed: bool,
@@ -642,3 +830,62 @@ impl<'a> PolylineBuilder<'a> {
         return Polyline { contours };
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn snap_to_pixels() {
+        let path = Path::new()
+            .move_to(1.2, 1.7)
+            .line_to(10.6, 1.7)
+            .line_to(10.6, 8.4)
+            .close();
+        let points = |path: &Path| -> Vec<Point> {
+            path.verts
+                .iter()
+                .filter_map(|verb| match verb {
+                    PathVerb::MoveTo(p) | PathVerb::LineTo(p) => Some(*p),
+                    _ => None,
+                })
+                .collect()
+        };
+
+        // fills snap to pixel edges
+        let snapped = path
+            .snap_to_pixels(&Matrix4::identity(), [1.0, 1.0], [0.0, 0.0])
+            .unwrap();
+        assert_eq!(
+            points(&snapped),
+            vec![
+                Point::from(1.0, 2.0),
+                Point::from(11.0, 2.0),
+                Point::from(11.0, 8.0)
+            ]
+        );
+
+        // odd width strokes snap to pixel centers, in pixels of a 2x target
+        let translate = Matrix4::new_translation(&nalgebra::Vector3::new(0.25, 0.0, 0.0));
+        let snapped = path
+            .snap_to_pixels(&translate, [2.0, 2.0], [0.5, 0.5])
+            .unwrap();
+        assert_eq!(
+            points(&snapped),
+            vec![
+                Point::from(1.0, 1.75),
+                Point::from(10.5, 1.75),
+                Point::from(10.5, 8.25)
+            ]
+        );
+
+        // curves and rotations can not be snapped
+        let curve = Path::new().move_to(0.0, 0.0).quad_to(5.0, 5.0, 10.0, 0.0);
+        assert!(curve
+            .snap_to_pixels(&Matrix4::identity(), [1.0, 1.0], [0.0, 0.0])
+            .is_none());
+
+        let rotate = Matrix4::new_rotation(nalgebra::Vector3::new(0.0, 0.0, 0.5));
+        assert!(path.snap_to_pixels(&rotate, [1.0, 1.0], [0.0, 0.0]).is_none());
+    }
+}
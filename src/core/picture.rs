@@ -3,21 +3,29 @@ use std::rc::Rc;
 use nalgebra::{Matrix4, Vector3};
 
 use crate::{
+    gpu::AntiAlias,
     render::{
-        fragment::{
-            ClipMaskFragment, LinearGradientFragment, RadialGradientFragment, SolidColorFragment,
-            TextureFragment,
-        },
+        clip_coverage::{ClipCoverage, ClipCoverageRenderer},
+        fragment::{ClipMaskFragment, ShaderFragment, SolidColorFragment, TextureFragment},
         glyph_render::TextBlobRender,
+        layer::LayerRenderer,
+        mask_blur::{BlurMask, MaskBlurRenderer},
         raster::{PathFill, PathStroke},
-        Fragment, PathCliper, PathRenderer, Raster, Renderer,
+        Fragment, PathCliper, PathRenderer, Raster, RenderTarget, Renderer, ScissorRenderer,
     },
     text::TextBlob,
 };
 
-use super::{image, state::State, Color, ColorType, Image, Paint, Path, Point, RRect, Rect, Style};
+use super::{
+    image, image_filter::blur_extent, path::PathVerb, rtree::RTree, state::State, BlendMode,
+    BlurStyle, Canvas, Color, ColorType, Image, ImageFilter, LinearGradient, MaskFilter, Matrix,
+    Paint, Path, Point, RRect, RadialGradient, Rect, Stroke, Style,
+};
 
 /// Defines the type of operation performed by a clip operation.
+/// `Union`, `Xor`, `ReverseDifference` and `Replace` can grow the clip area outside of the clips of
+/// parent saves, but not outside of the bounds of the current layer. The clip area before them comes back
+/// when their save is restored.
 #[derive(Debug, Clone, Copy, Eq, PartialEq, Default)]
 pub enum ClipOp {
     /// The clip area is the intersection of the current clip area and the specified path.
@@ -25,13 +33,76 @@ pub enum ClipOp {
     Intersect,
     /// The clip area is the difference of the current clip area and the specified path.
     Difference,
+    /// The clip area is the union of the current clip area and the specified path.
+    Union,
+    /// The clip area is the exclusive or of the current clip area and the specified path.
+    Xor,
+    /// The clip area is the difference of the specified path and the current clip area.
+    ReverseDifference,
+    /// The clip area is replaced by the specified path.
+    Replace,
+}
+
+impl ClipOp {
+    /// Returns true if the op can grow the clip area outside of the current clip area.
+    pub(crate) fn is_expanding(&self) -> bool {
+        matches!(
+            self,
+            ClipOp::Union | ClipOp::Xor | ClipOp::ReverseDifference | ClipOp::Replace
+        )
+    }
+}
+
+/// Problem found by a recorder in strict mode, see `PictureRecorder::with_strict_mode`.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Diagnostic {
+    /// `restore` is called without a matching `save`, it is ignored.
+    UnbalancedRestore,
+    /// Saves are not restored when the recording finishes, holding the number of them.
+    /// They are restored by `finish_record`.
+    UnrestoredSaves(usize),
+    /// A command has coordinates or transform values which are NaN or infinite, it is dropped.
+    /// Holding the name of the command.
+    NonFiniteCoordinates(&'static str),
+    /// A draw is entirely outside of the current clip, it is dropped. Holding the name of the command.
+    EmptyAfterClip(&'static str),
+}
+
+impl std::fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Diagnostic::UnbalancedRestore => write!(f, "restore without matching save"),
+            Diagnostic::UnrestoredSaves(count) => {
+                write!(f, "{} saves are not restored when finishing record", count)
+            }
+            Diagnostic::NonFiniteCoordinates(command) => {
+                write!(f, "non-finite coordinates in `{}`", command)
+            }
+            Diagnostic::EmptyAfterClip(command) => {
+                write!(f, "`{}` is empty after clip", command)
+            }
+        }
+    }
 }
 
 pub(crate) enum DrawCommand {
     DrawPath(Path, Paint),
-    ClipPath(Path, ClipOp),
-    DrawImage(Image, Rect, Matrix4<f32>),
-    DrawText(Rc<TextBlob>, Point, Color),
+    /// the clip path, the operation and whether the edge of clip is anti-aliased
+    ClipPath(Path, ClipOp, bool),
+    DrawImage(Image, Rect, Matrix4<f32>, Paint),
+    DrawText(Rc<TextBlob>, Point, Paint),
+    DrawLayer(Box<Layer>),
+}
+
+/// Draws recorded between `save_layer` and the matching `restore`.
+pub(crate) struct Layer {
+    /// device space bounds of the layer, `None` means the layer covers the whole target
+    pub(crate) bounds: Option<Rect>,
+    /// the image filter of paint is already mapped to device space
+    pub(crate) paint: Paint,
+    pub(crate) draws: Vec<Draw>,
+    /// max depth used by draws inside the layer
+    pub(crate) depth_count: u32,
 }
 
 pub(crate) struct Draw {
@@ -40,159 +111,1068 @@ pub(crate) struct Draw {
     pub(crate) transform: Matrix4<f32>,
 }
 
+/// Clip whose scope is not finished while draws are replayed.
+struct ClipScope {
+    depth: u32,
+    /// feathered coverage draws inside the scope are shaded through, nested in the coverage of parent clips
+    coverage: Option<ClipCoverage>,
+    /// whether `coverage` holds the whole clip, or everything is inside of the clip if it is `None`
+    complete: bool,
+    /// pixel rect of the target draws inside the scope are restricted to, intersected with parent clips
+    scissor: Option<[u32; 4]>,
+}
+
+/// Generates the renderers of draws replayed into target.
+/// Draws inside the scope of a clip with feathered coverage are shaded through it, and draws inside the
+/// scope of a rect clip replaced with scissor are restricted to it.
+/// Aliased draws on a multisampled target are shaded through coverage masks they share.
+///
+/// # Arguments
+///
+/// * `draws` the draws in recording order, draws which are not visible may be skipped
+/// * `target` the target the draws are replayed into
+/// * `depth_offset` offset added to the depth of each draw
+pub(crate) fn gen_renders<'a>(
+    draws: impl IntoIterator<Item = &'a Draw>,
+    target: &RenderTarget,
+    depth_offset: u32,
+) -> Vec<Box<dyn Renderer>> {
+    let draws: Vec<&Draw> = draws.into_iter().collect();
+    let expanded = expanded_clips(draws.iter().copied());
+    let (mut masks, aliased) = aliased_masks(&draws, target);
+    // the innermost clip is the last
+    let mut clips: Vec<ClipScope> = Vec::new();
+
+    let renders: Vec<Box<dyn Renderer>> = draws
+        .into_iter()
+        .zip(expanded)
+        .zip(aliased)
+        .filter_map(|((draw, expanded), aliased)| {
+            // a clip gets its depth when restored, so draws inside it have lower depth
+            while clips.last().is_some_and(|clip| clip.depth <= draw.depth) {
+                clips.pop();
+            }
+
+            let coverage = clips.last().and_then(|clip| clip.coverage.clone());
+            let complete = clips.last().is_none_or(|clip| clip.complete);
+            let scissor = clips.last().and_then(|clip| clip.scissor);
+
+            let render = match &draw.command {
+                DrawCommand::ClipPath(path, op, anti_alias) => {
+                    // clips expanded inside their scope must stay in depth to be expanded
+                    let rect = if expanded {
+                        None
+                    } else {
+                        draw.scissor_rect(target, path, *op, *anti_alias)
+                    };
+
+                    // a scissor clip leaves its depth unused, draws after its scope still pass the test
+                    if let Some(rect) = rect {
+                        clips.push(ClipScope {
+                            depth: draw.depth,
+                            coverage,
+                            complete: false,
+                            scissor: Some(scissor.map_or(rect, |s| intersect_scissor(&s, &rect))),
+                        });
+
+                        return None;
+                    }
+
+                    let (render, own) = draw.gen_clip_render(
+                        target,
+                        depth_offset,
+                        path,
+                        *op,
+                        *anti_alias,
+                        clips.last(),
+                    );
+
+                    let (coverage, complete) = match own {
+                        Some(own) => (Some(own), op.is_expanding() || complete),
+                        // hard edges of expanding clips are not shaded through the parent coverage
+                        None if op.is_expanding() => {
+                            let all_inside = *op == ClipOp::Union && complete && coverage.is_none();
+                            (None, all_inside)
+                        }
+                        None => (coverage, false),
+                    };
+
+                    clips.push(ClipScope {
+                        depth: draw.depth,
+                        coverage,
+                        complete,
+                        scissor,
+                    });
+
+                    render
+                }
+                _ => match aliased {
+                    Some((index, mask)) => {
+                        draw.gen_aliased_render(target, depth_offset, &mask, &mut masks[index])
+                    }
+                    None => draw.gen_render(target, depth_offset, coverage.as_ref()),
+                },
+            };
+
+            Some(match scissor {
+                Some(scissor) => {
+                    Box::new(ScissorRenderer::new(render, scissor)) as Box<dyn Renderer>
+                }
+                None => render,
+            })
+        })
+        .collect();
+
+    // the shared masks are drawn before the draws shaded through them are prepared
+    masks
+        .into_iter()
+        .map(|mask| {
+            Box::new(ClipCoverageRenderer::new(
+                mask.coverage,
+                wgpu::Color::TRANSPARENT,
+                mask.renders,
+            )) as Box<dyn Renderer>
+        })
+        .chain(renders)
+        .collect()
+}
+
+/// Coverage mask shared by the aliased draws replayed into a multisampled target. The coverage of all of
+/// them is drawn without multisample in one pass, each draw is shaded through the mask inside its own bounds.
+struct AliasedMask {
+    coverage: ClipCoverage,
+    /// draws the coverage of each draw sharing the mask
+    renders: Vec<Box<dyn Renderer>>,
+}
+
+/// Groups the aliased draws replayed into a multisampled target into shared coverage masks. Draws share
+/// a mask if their bounds do not overlap, so the coverage of one draw is not shaded by another.
+/// Returns the masks, and the index of the shared mask and the bounds of the coverage of each draw.
+///
+/// # Arguments
+///
+/// * `draws` the draws in recording order
+/// * `target` the target the draws are replayed into
+fn aliased_masks(
+    draws: &[&Draw],
+    target: &RenderTarget,
+) -> (Vec<AliasedMask>, Vec<Option<(usize, BlurMask)>>) {
+    // pixel bounds of the draws sharing each mask
+    let mut groups: Vec<Vec<Rect>> = Vec::new();
+
+    let aliased = draws
+        .iter()
+        .map(|draw| {
+            let mask = draw.aliased_mask(target)?;
+            let pixels = mask.pixel_bounds(target);
+
+            // a draw outside of the target has no coverage
+            if pixels.is_empty() {
+                return None;
+            }
+
+            let index = match groups
+                .iter()
+                .position(|group| group.iter().all(|b| b.intersect(&pixels).is_none()))
+            {
+                Some(index) => index,
+                None => {
+                    groups.push(Vec::new());
+                    groups.len() - 1
+                }
+            };
+
+            groups[index].push(pixels);
+
+            Some((index, mask))
+        })
+        .collect();
+
+    let masks = groups
+        .iter()
+        .map(|group| {
+            let pixels = group.iter().fold(group[0], |u, b| u.union(b));
+            let bounds = Rect::from_ltrb(
+                pixels.left / target.scale_x,
+                pixels.top / target.scale_y,
+                pixels.right / target.scale_x,
+                pixels.bottom / target.scale_y,
+            );
+
+            AliasedMask {
+                coverage: ClipCoverage::new(target, Some(&bounds), None),
+                renders: Vec::new(),
+            }
+        })
+        .collect();
+
+    (masks, aliased)
+}
+
+/// Returns whether each draw is a clip with clips expanding the clip inside its scope, such as
+/// `ClipOp::Union`. The draws outside of such clips may be visible again.
+///
+/// # Arguments
+///
+/// * `draws` the draws in recording order
+fn expanded_clips<'a>(draws: impl IntoIterator<Item = &'a Draw>) -> Vec<bool> {
+    // (depth, index) of clips whose scope is not finished, the innermost is the last
+    let mut clips: Vec<(u32, usize)> = Vec::new();
+    let mut expanded = Vec::new();
+
+    for (index, draw) in draws.into_iter().enumerate() {
+        expanded.push(false);
+
+        while clips.last().is_some_and(|(depth, _)| *depth <= draw.depth) {
+            clips.pop();
+        }
+
+        if let DrawCommand::ClipPath(_, op, _) = &draw.command {
+            if op.is_expanding() {
+                for (_, parent) in &clips {
+                    expanded[*parent] = true;
+                }
+            }
+
+            clips.push((draw.depth, index));
+        }
+    }
+
+    expanded
+}
+
+/// Returns the intersection of two [x, y, width, height] pixel rects, which is empty if they do not overlap.
+fn intersect_scissor(a: &[u32; 4], b: &[u32; 4]) -> [u32; 4] {
+    let left = a[0].max(b[0]);
+    let top = a[1].max(b[1]);
+    let right = (a[0] + a[2]).min(b[0] + b[2]).max(left);
+    let bottom = (a[1] + a[3]).min(b[1] + b[3]).max(top);
+
+    [left, top, right - left, bottom - top]
+}
+
 impl Draw {
+    /// Returns a copy of the draw replayed by another picture.
+    ///
+    /// # Arguments
+    ///
+    /// * `matrix` the transform of the replaying picture, applied after the transform of the draw
+    /// * `depth_offset` offset added to the depth of the draw, draws inside layers keep their depth
+    fn replayed(&self, matrix: &Matrix4<f32>, depth_offset: u32) -> Draw {
+        let command = match &self.command {
+            DrawCommand::DrawPath(path, paint) => {
+                DrawCommand::DrawPath(path.clone(), paint.clone())
+            }
+            DrawCommand::ClipPath(path, op, anti_alias) => {
+                DrawCommand::ClipPath(path.clone(), *op, *anti_alias)
+            }
+            DrawCommand::DrawImage(image, dst, image_matrix, paint) => {
+                DrawCommand::DrawImage(image.clone(), *dst, *image_matrix, paint.clone())
+            }
+            DrawCommand::DrawText(text, pos, paint) => {
+                DrawCommand::DrawText(text.clone(), *pos, paint.clone())
+            }
+            DrawCommand::DrawLayer(layer) => {
+                // bounds and image filter of layer are in the device space of the replayed picture
+                let mut paint = layer.paint.clone();
+                paint.image_filter = paint.image_filter.map(|filter| filter.transformed(matrix));
+
+                DrawCommand::DrawLayer(Box::new(Layer {
+                    bounds: layer
+                        .bounds
+                        .map(|b| Matrix { matrix: *matrix }.map_rect(&b)),
+                    paint,
+                    draws: layer
+                        .draws
+                        .iter()
+                        .map(|draw| draw.replayed(matrix, 0))
+                        .collect(),
+                    depth_count: layer.depth_count,
+                }))
+            }
+        };
+
+        Draw {
+            depth: self.depth + depth_offset,
+            command,
+            transform: matrix * self.transform,
+        }
+    }
+
+    /// Returns the device space bounds of a clip path, `None` if it is not known under perspective.
+    fn clip_bounds(&self) -> Option<Rect> {
+        let transform = Matrix {
+            matrix: self.transform,
+        };
+
+        match &self.command {
+            DrawCommand::ClipPath(path, _, _) if !transform.has_perspective() => {
+                Some(transform.map_rect(&path.bounds()))
+            }
+            _ => None,
+        }
+    }
+
+    /// Returns the draw wrapped in layers if the color filter of its paint is longer than one color filter
+    /// stage. The draw keeps the first part of the filter, and each layer composites its content with the
+    /// next part. The outermost layer blends with the blend mode of the paint.
+    fn split_color_filter(&self) -> Option<Draw> {
+        let paint = match &self.command {
+            DrawCommand::DrawPath(_, paint)
+            | DrawCommand::DrawImage(_, _, _, paint)
+            | DrawCommand::DrawText(_, _, paint) => paint,
+            DrawCommand::DrawLayer(layer) => &layer.paint,
+            DrawCommand::ClipPath(..) => return None,
+        };
+
+        let mut parts = paint.color_filter.as_ref()?.split();
+        if parts.len() == 1 {
+            return None;
+        }
+
+        let blend_mode = paint.blend_mode;
+        let bounds = draws_bounds(std::slice::from_ref(self), None)[0];
+
+        let mut inner = self.replayed(&Matrix4::identity(), 0);
+        inner.depth = 1;
+
+        if let DrawCommand::DrawPath(_, paint)
+        | DrawCommand::DrawImage(_, _, _, paint)
+        | DrawCommand::DrawText(_, _, paint) = &mut inner.command
+        {
+            paint.color_filter = Some(parts.remove(0));
+            paint.blend_mode = BlendMode::SrcOver;
+        } else if let DrawCommand::DrawLayer(layer) = &mut inner.command {
+            layer.paint.color_filter = Some(parts.remove(0));
+            layer.paint.blend_mode = BlendMode::SrcOver;
+        }
+
+        let last = parts.len() - 1;
+        let outer = parts
+            .into_iter()
+            .enumerate()
+            .fold(inner, |draw, (index, filter)| Draw {
+                depth: 1,
+                command: DrawCommand::DrawLayer(Box::new(Layer {
+                    bounds,
+                    paint: Paint {
+                        color_filter: Some(filter),
+                        blend_mode: if index == last {
+                            blend_mode
+                        } else {
+                            BlendMode::SrcOver
+                        },
+                        ..Paint::new()
+                    },
+                    draws: vec![draw],
+                    depth_count: 1,
+                })),
+                transform: Matrix4::identity(),
+            });
+
+        Some(Draw {
+            depth: self.depth,
+            ..outer
+        })
+    }
+
+    /// Generates the renderer of the draw. Aliased draws on a multisampled target are anti-aliased unless
+    /// they are generated by `gen_renders`, which shades them through a shared coverage mask.
+    ///
+    /// # Arguments
+    ///
+    /// * `target` the target the draw is replayed into
+    /// * `depth_offset` offset added to the depth of the draw
+    /// * `clip` feathered coverage of the clip the draw is inside. Text and draws with mask filter are not
+    ///   shaded through it, they are clipped by the hard clip which includes the feathered edge.
     pub(crate) fn gen_render(
         &self,
-        vw: f32,
-        vh: f32,
-        target_format: wgpu::TextureFormat,
-        anti_alias: bool,
+        target: &RenderTarget,
         depth_offset: u32,
+        clip: Option<&ClipCoverage>,
     ) -> Box<dyn Renderer> {
+        if let Some(draw) = self.split_color_filter() {
+            return draw.gen_render(target, depth_offset, clip);
+        }
+
+        let vw = target.width;
+        let vh = target.height;
+        let depth = (self.depth + depth_offset) as f32;
+        let transform = target.base_transform * self.transform;
+
         match &self.command {
             DrawCommand::DrawPath(path, paint) => {
-                let raster: Box<dyn Raster> = match paint.style {
-                    Style::Fill => Box::new(PathFill::new(path.clone(), self.transform.clone())),
-                    Style::Stroke(stroke) => Box::new(PathStroke::new(
-                        path.clone(),
-                        self.transform.clone(),
-                        stroke.width,
-                        stroke.miter_limit,
-                        stroke.cap,
-                        stroke.join,
-                    )),
+                let fragment = gen_fragment(&paint.color, vw, vh, transform);
+
+                let snapped = self.snap_path(path, paint, &paint.style, target);
+                let path = snapped.as_ref().unwrap_or(path);
+
+                if paint.mask_filter.is_some() {
+                    let mut bounds = path.bounds();
+                    let outset = paint.style.outset();
+                    bounds.outset(outset, outset);
+
+                    return self.gen_mask_render(target, depth, paint, &bounds, fragment);
+                }
+
+                let fringe = target.fringe(paint.anti_alias);
+
+                let stroke_raster = |stroke: &Stroke| -> Box<dyn Raster> {
+                    Box::new(
+                        PathStroke::new(
+                            path.clone(),
+                            transform,
+                            stroke.width,
+                            stroke.miter_limit,
+                            stroke.cap,
+                            stroke.join,
+                        )
+                        .with_fringe(fringe),
+                    )
+                };
+
+                let fill_raster =
+                    || Box::new(PathFill::new(path.clone(), transform).with_fringe(fringe));
+
+                let (raster, outline): (Box<dyn Raster>, _) = match &paint.style {
+                    Style::Fill => (fill_raster(), None),
+                    Style::Stroke(stroke) => (stroke_raster(stroke), None),
+                    Style::FillAndStroke(stroke) => (fill_raster(), Some(stroke_raster(stroke))),
+                };
+
+                Box::new(
+                    PathRenderer::new(
+                        target
+                            .pipeline_key()
+                            .with_blend_mode(paint.blend_mode)
+                            .with_dither(self.dither(paint)),
+                        raster,
+                        fragment,
+                        depth,
+                    )
+                    .with_color_filter(paint.color_filter.as_ref())
+                    .with_outline(outline)
+                    .with_clip(clip),
+                )
+            }
+            DrawCommand::ClipPath(path, op, anti_alias) => {
+                // the coverage is not known to hold the whole clip before
+                let parent = clip.map(|clip| ClipScope {
+                    depth: 0,
+                    coverage: Some(clip.clone()),
+                    complete: false,
+                    scissor: None,
+                });
+                let (render, _) = self.gen_clip_render(
+                    target,
+                    depth_offset,
+                    path,
+                    *op,
+                    *anti_alias,
+                    parent.as_ref(),
+                );
+
+                render
+            }
+            DrawCommand::DrawImage(image, rect, matrix, paint) => {
+                let fragment = gen_image_fragment(image, matrix, vw, vh, transform);
+
+                let path = Path::new().add_rect(rect);
+                let path = self
+                    .snap_path(&path, paint, &Style::Fill, target)
+                    .unwrap_or(path);
+
+                if paint.mask_filter.is_some() {
+                    return self.gen_mask_render(target, depth, paint, &path.bounds(), fragment);
+                }
+
+                Box::new(
+                    PathRenderer::new(
+                        target
+                            .pipeline_key()
+                            .with_blend_mode(paint.blend_mode)
+                            .with_dither(self.dither(paint)),
+                        Box::new(
+                            PathFill::new(path, transform)
+                                .with_fringe(target.fringe(paint.anti_alias)),
+                        ),
+                        fragment,
+                        depth,
+                    )
+                    .with_color_filter(paint.color_filter.as_ref())
+                    .with_clip(clip),
+                )
+            }
+
+            DrawCommand::DrawText(blob, pos, paint) => {
+                let color = match &paint.color {
+                    ColorType::SolidColor(color) => *color,
+                    _ => Color::black(),
                 };
 
-                let fragment: Box<dyn Fragment> = match &paint.color {
-                    ColorType::SolidColor(color) => Box::new(SolidColorFragment::new(
-                        *color,
+                if paint.mask_filter.is_some() {
+                    let fragment = Box::new(SolidColorFragment::new(color, vw, vh, transform));
+
+                    return self.gen_mask_render(target, depth, paint, &blob.bounds(pos), fragment);
+                }
+
+                Box::new(
+                    TextBlobRender::new(
+                        target.pipeline_key().with_blend_mode(paint.blend_mode),
+                        blob.clone(),
+                        color,
+                        *pos,
+                        depth,
                         vw,
                         vh,
-                        self.transform.clone(),
+                        transform,
+                    )
+                    .with_color_filter(paint.color_filter.as_ref())
+                    .with_text_gamma(target.text_gamma()),
+                )
+            }
+            DrawCommand::DrawLayer(layer) => {
+                let bounds = LayerRenderer::layer_bounds(
+                    target,
+                    layer.bounds.as_ref(),
+                    layer.paint.image_filter.as_ref(),
+                );
+                let layer_target = LayerRenderer::child_target(target, &bounds);
+
+                let renders = gen_renders(&layer.draws, &layer_target, 0);
+
+                Box::new(
+                    LayerRenderer::new(
+                        *target,
+                        bounds,
+                        &layer.paint,
+                        depth,
+                        layer.depth_count,
+                        renders,
+                    )
+                    .with_clip(clip),
+                )
+            }
+        }
+    }
+
+    /// Generates the renderer of a clip and the feathered coverage of it.
+    /// The coverage is `None` unless the target is anti-aliased with `AntiAlias::Analytic` and the clip
+    /// is anti-aliased. Clips which can grow the clip, such as `ClipOp::Union`, keep hard edges if their
+    /// coverage can not be built from the parent coverage.
+    ///
+    /// # Arguments
+    ///
+    /// * `target` the target the clip is replayed into
+    /// * `depth_offset` offset added to the depth of the clip
+    /// * `path` the clip path
+    /// * `op` the type of operation performed by the clip
+    /// * `anti_alias` whether the edge of clip is anti-aliased. Multisample can not be turned off per
+    ///   draw, so aliased clips made of lines are snapped to pixels on multisampled targets.
+    /// * `parent` the innermost clip this clip is nested in, `None` if there is no clip before
+    fn gen_clip_render(
+        &self,
+        target: &RenderTarget,
+        depth_offset: u32,
+        path: &Path,
+        op: ClipOp,
+        anti_alias: bool,
+        parent: Option<&ClipScope>,
+    ) -> (Box<dyn Renderer>, Option<ClipCoverage>) {
+        let complete = parent.is_none_or(|parent| parent.complete);
+        let parent = parent.and_then(|parent| parent.coverage.as_ref());
+        let depth = (self.depth + depth_offset) as f32;
+        let transform = target.base_transform * self.transform;
+
+        let snapped = if !anti_alias && target.anti_alias.sample_count() > 1 {
+            path.snap_to_pixels(&transform, [target.scale_x, target.scale_y], [0.0, 0.0])
+        } else {
+            None
+        };
+
+        // clips which can grow the clip build their coverage from the clip before, which must be known
+        let feathered = anti_alias
+            && target.anti_alias == AntiAlias::Analytic
+            && match op {
+                ClipOp::Intersect | ClipOp::Difference | ClipOp::Replace => true,
+                // without parent coverage everything is already inside of the clip
+                ClipOp::Union => complete && parent.is_some(),
+                ClipOp::ReverseDifference => complete,
+                // xor of two coverages can not be blended into a single channel
+                ClipOp::Xor => complete && parent.is_none(),
+            };
+
+        let cliper = PathCliper::new(
+            target.format,
+            target.anti_alias.sample_count(),
+            PathFill::new(snapped.unwrap_or(path.clone()), transform)
+                .with_fringe(target.fringe(feathered)),
+            ClipMaskFragment::new(target.width, target.height, transform),
+            op,
+            depth,
+        );
+
+        if !feathered {
+            return (Box::new(cliper), None);
+        }
+
+        let bounds = Matrix { matrix: transform }.map_rect(&path.bounds());
+
+        let coverage = match op {
+            ClipOp::Intersect => ClipCoverage::new(target, Some(&bounds), parent),
+            ClipOp::Difference => ClipCoverage::new(target, None, parent),
+            ClipOp::Replace => ClipCoverage::new(target, Some(&bounds), None),
+            // the coverage covers the whole target, so the parent coverage is sampled in place
+            _ => ClipCoverage::new(target, None, None),
+        };
+        let coverage_target = coverage.coverage_target(target);
+        let nested_parent = match op {
+            ClipOp::Intersect | ClipOp::Difference => parent.map(|parent| parent.nested()),
+            _ => parent.cloned(),
+        };
+
+        let path_draw = |color: Color, depth: u32, clip: Option<&ClipCoverage>| {
+            let paint = Paint {
+                color: ColorType::SolidColor(color),
+                ..Paint::new()
+            };
+
+            Draw {
+                depth,
+                command: DrawCommand::DrawPath(path.clone(), paint),
+                transform: self.transform,
+            }
+            .gen_render(&coverage_target, 0, clip)
+        };
+
+        // copies the parent coverage with color
+        let parent_draw = |color: Color, depth: u32, parent: &ClipCoverage| {
+            let quad = Draw {
+                depth,
+                command: DrawCommand::DrawPath(
+                    Path::new().add_rect(&Rect::from_xywh(
+                        0.0,
+                        0.0,
+                        coverage_target.width,
+                        coverage_target.height,
                     )),
-                    ColorType::LinearGradient(gradient) => {
-                        if gradient.colors.len() < 2 {
-                            Box::new(SolidColorFragment::new(
-                                Color::black(),
-                                vw,
-                                vh,
-                                self.transform.clone(),
-                            ))
-                        } else if !gradient.stops.is_empty()
-                            && gradient.stops.len() != gradient.colors.len()
-                        {
-                            Box::new(SolidColorFragment::new(
-                                Color::black(),
-                                vw,
-                                vh,
-                                self.transform.clone(),
-                            ))
-                        } else {
-                            Box::new(LinearGradientFragment::new(
-                                &gradient,
-                                vw,
-                                vh,
-                                self.transform.clone(),
-                            ))
-                        }
-                    }
-                    ColorType::RadialGradient(gradient) => {
-                        if gradient.colors.len() < 2 {
-                            Box::new(SolidColorFragment::new(
-                                Color::black(),
-                                vw,
-                                vh,
-                                self.transform.clone(),
-                            ))
-                        } else if !gradient.stops.is_empty()
-                            && gradient.stops.len() != gradient.colors.len()
-                        {
-                            Box::new(SolidColorFragment::new(
-                                Color::black(),
-                                vw,
-                                vh,
-                                self.transform.clone(),
-                            ))
-                        } else {
-                            Box::new(RadialGradientFragment::new(
-                                &gradient,
-                                vw,
-                                vh,
-                                self.transform.clone(),
-                            ))
-                        }
-                    }
-                };
+                    Paint {
+                        color: ColorType::SolidColor(color),
+                        anti_alias: false,
+                        ..Paint::new()
+                    },
+                ),
+                // the quad is in the space of the coverage target
+                transform: coverage_target
+                    .base_transform
+                    .try_inverse()
+                    .unwrap_or(Matrix4::identity()),
+            };
 
-                Box::new(PathRenderer::new(
-                    target_format,
-                    anti_alias,
-                    raster,
-                    fragment,
-                    (self.depth + depth_offset) as f32,
-                ))
-            }
-            DrawCommand::ClipPath(path, op) => {
-                let raster = PathFill::new(path.clone(), self.transform.clone());
-                let fragment = ClipMaskFragment::new(vw, vh, self.transform.clone());
-
-                Box::new(PathCliper::new(
-                    target_format,
-                    anti_alias,
-                    raster,
-                    fragment,
-                    *op,
-                    (self.depth + depth_offset) as f32,
-                ))
-            }
-            DrawCommand::DrawImage(image, rect, matrix) => {
-                let raster = Box::new(PathFill::new(
-                    Path::new().add_rect(rect),
-                    self.transform.clone(),
-                ));
-                let fragment = match &image.source {
-                    image::ImageSource::Bitmap(bitmap) => {
-                        Box::new(TextureFragment::new_with_bitmap(
-                            vw,
-                            vh,
-                            self.transform.clone(),
-                            bitmap.clone(),
-                            matrix.clone(),
-                        ))
-                    }
-                    image::ImageSource::Texture(texture, info) => {
-                        Box::new(TextureFragment::new_with_texture(
-                            vw,
-                            vh,
-                            self.transform.clone(),
-                            texture.clone(),
-                            info.clone(),
-                            matrix.clone(),
-                        ))
-                    }
+            quad.gen_render(&coverage_target, 0, Some(parent))
+        };
+
+        let (clear_color, renders) = match (op, &nested_parent) {
+            // the path is drawn inside of the parent coverage
+            (ClipOp::Intersect, parent) => (
+                wgpu::Color::BLACK,
+                vec![path_draw(Color::white(), 1, parent.as_ref())],
+            ),
+            // the parent coverage is copied and the path is erased from it
+            (ClipOp::Difference, Some(parent)) => (
+                wgpu::Color::BLACK,
+                vec![
+                    parent_draw(Color::white(), 1, parent),
+                    path_draw(Color::black(), 2, None),
+                ],
+            ),
+            // xor is only feathered without parent coverage, which is the same as difference
+            (ClipOp::Difference, None) | (ClipOp::Xor, _) => {
+                (wgpu::Color::WHITE, vec![path_draw(Color::black(), 1, None)])
+            }
+            // the parent coverage is copied and the path is added to it
+            (ClipOp::Union, Some(parent)) => (
+                wgpu::Color::BLACK,
+                vec![
+                    parent_draw(Color::white(), 1, parent),
+                    path_draw(Color::white(), 2, None),
+                ],
+            ),
+            (ClipOp::Union, None) => (wgpu::Color::WHITE, vec![]),
+            // the path is drawn and the parent coverage is erased from it
+            (ClipOp::ReverseDifference, Some(parent)) => (
+                wgpu::Color::BLACK,
+                vec![
+                    path_draw(Color::white(), 1, None),
+                    parent_draw(Color::black(), 2, parent),
+                ],
+            ),
+            // everything was inside of the clip, so nothing is left
+            (ClipOp::ReverseDifference, None) => (wgpu::Color::BLACK, vec![]),
+            (ClipOp::Replace, _) => (wgpu::Color::BLACK, vec![path_draw(Color::white(), 1, None)]),
+        };
+
+        let cliper = cliper.with_coverage(Some(ClipCoverageRenderer::new(
+            coverage.clone(),
+            clear_color,
+            renders,
+        )));
+
+        (Box::new(cliper), Some(coverage))
+    }
+
+    /// Returns the [x, y, width, height] pixel rect of target a clip can be replaced with.
+    /// Only intersect clips of rects whose edges are axis-aligned in the target can be replaced.
+    /// Anti-aliased edges must be on the pixel grid, otherwise they are feathered with stencil.
+    ///
+    /// # Arguments
+    ///
+    /// * `target` the target the clip is replayed into
+    /// * `path` the clip path
+    /// * `op` the type of operation performed by the clip
+    /// * `anti_alias` whether the edge of clip is anti-aliased
+    fn scissor_rect(
+        &self,
+        target: &RenderTarget,
+        path: &Path,
+        op: ClipOp,
+        anti_alias: bool,
+    ) -> Option<[u32; 4]> {
+        if op != ClipOp::Intersect {
+            return None;
+        }
+
+        let rect = path.as_rect()?;
+        let transform = Matrix {
+            matrix: target.base_transform * self.transform,
+        };
+
+        if transform.has_rotation() || transform.has_perspective() {
+            return None;
+        }
+
+        let bounds = transform.map_rect(&rect);
+        if !bounds.is_finite() {
+            return None;
+        }
+        let edges = [
+            bounds.left * target.scale_x,
+            bounds.top * target.scale_y,
+            bounds.right * target.scale_x,
+            bounds.bottom * target.scale_y,
+        ];
+
+        let edges = if anti_alias {
+            if edges.iter().any(|e| (e - e.round()).abs() > 1e-3) {
+                return None;
+            }
+
+            edges.map(f32::round)
+        } else {
+            // aliased edges cover the pixels whose centers are inside, same as the stencil
+            edges.map(|e| (e - 0.5).ceil())
+        };
+
+        let width = (target.width * target.scale_x).round();
+        let height = (target.height * target.scale_y).round();
+
+        let left = edges[0].clamp(0.0, width) as u32;
+        let top = edges[1].clamp(0.0, height) as u32;
+        let right = (edges[2].clamp(0.0, width) as u32).max(left);
+        let bottom = (edges[3].clamp(0.0, height) as u32).max(top);
+
+        Some([left, top, right - left, bottom - top])
+    }
+
+    /// Returns true if the draw is shaded with dither noise.
+    /// Only gradients and images are dithered, solid colors do not band.
+    fn dither(&self, paint: &Paint) -> bool {
+        paint.dither
+            && match &self.command {
+                DrawCommand::DrawPath(..) => !matches!(paint.color, ColorType::SolidColor(_)),
+                DrawCommand::DrawImage(..) => true,
+                _ => false,
+            }
+    }
+
+    /// Returns true if the draw turns off anti-alias on a multisampled target.
+    /// Multisample can not be turned off per draw, so the coverage is drawn into a mask without it.
+    fn aliased(paint: &Paint, target: &RenderTarget) -> bool {
+        !paint.anti_alias && target.anti_alias.sample_count() > 1
+    }
+
+    /// Returns the bounds of the coverage mask of a path or image draw which turns off anti-alias on a
+    /// multisampled target. Draws with mask filter draw their own mask, and draws whose color filter is
+    /// split into layers are aliased inside the innermost layer.
+    ///
+    /// # Arguments
+    ///
+    /// * `target` the target the draw is replayed into
+    fn aliased_mask(&self, target: &RenderTarget) -> Option<BlurMask> {
+        let paint = match &self.command {
+            DrawCommand::DrawPath(_, paint) | DrawCommand::DrawImage(_, _, _, paint) => paint,
+            _ => return None,
+        };
+
+        if !Self::aliased(paint, target)
+            || paint.mask_filter.is_some()
+            || paint
+                .color_filter
+                .as_ref()
+                .is_some_and(|filter| filter.split().len() > 1)
+        {
+            return None;
+        }
+
+        let bounds = match &self.command {
+            DrawCommand::DrawPath(path, paint) => {
+                let snapped = self.snap_path(path, paint, &paint.style, target);
+                let mut bounds = snapped.as_ref().unwrap_or(path).bounds();
+                let outset = paint.style.outset();
+                bounds.outset(outset, outset);
+                bounds
+            }
+            DrawCommand::DrawImage(_, rect, _, paint) => {
+                let path = Path::new().add_rect(rect);
+                self.snap_path(&path, paint, &Style::Fill, target)
+                    .unwrap_or(path)
+                    .bounds()
+            }
+            _ => return None,
+        };
+
+        let transform = target.base_transform * self.transform;
+
+        Some(BlurMask::new(
+            target,
+            &bounds,
+            &transform,
+            BlurStyle::Normal,
+            0.0,
+        ))
+    }
+
+    /// Generates the renderer of an aliased draw on a multisampled target, which shades the paint inside the
+    /// bounds of its coverage mask through the mask shared with other aliased draws. The renderer drawing
+    /// the coverage without multisample is added to the shared mask.
+    ///
+    /// # Arguments
+    ///
+    /// * `target` the target the draw is replayed into
+    /// * `depth_offset` offset added to the depth of the draw
+    /// * `mask` bounds of the coverage of the draw, returned by `Draw::aliased_mask`
+    /// * `shared` the mask the coverage is drawn into
+    fn gen_aliased_render(
+        &self,
+        target: &RenderTarget,
+        depth_offset: u32,
+        mask: &BlurMask,
+        shared: &mut AliasedMask,
+    ) -> Box<dyn Renderer> {
+        let vw = target.width;
+        let vh = target.height;
+        let depth = (self.depth + depth_offset) as f32;
+        let transform = target.base_transform * self.transform;
+
+        let (paint, fragment, geometry) = match &self.command {
+            DrawCommand::DrawPath(path, paint) => (
+                paint,
+                gen_fragment(&paint.color, vw, vh, transform),
+                path.clone(),
+            ),
+            DrawCommand::DrawImage(image, rect, matrix, paint) => (
+                paint,
+                gen_image_fragment(image, matrix, vw, vh, transform),
+                Path::new().add_rect(rect),
+            ),
+            _ => return self.gen_render(target, depth_offset, None),
+        };
+
+        // the geometry is drawn with opaque white, the coverage ends up in the red channel
+        let coverage_paint = Paint {
+            color: ColorType::SolidColor(Color::white()),
+            style: match &self.command {
+                DrawCommand::DrawPath(..) => paint.style,
+                _ => Style::Fill,
+            },
+            anti_alias: false,
+            pixel_snap: paint.pixel_snap,
+            ..Paint::new()
+        };
+
+        let coverage_target = RenderTarget {
+            anti_alias: AntiAlias::None,
+            ..shared.coverage.coverage_target(target)
+        };
+
+        shared.renders.push(
+            Draw {
+                depth: shared.renders.len() as u32 + 1,
+                command: DrawCommand::DrawPath(geometry, coverage_paint),
+                transform: self.transform,
+            }
+            .gen_render(&coverage_target, 0, None),
+        );
+
+        Box::new(
+            PathRenderer::new(
+                target
+                    .pipeline_key()
+                    .with_blend_mode(paint.blend_mode)
+                    .with_dither(self.dither(paint)),
+                Box::new(PathFill::new(mask.local_quad(&transform), transform)),
+                fragment,
+                depth,
+            )
+            .with_color_filter(paint.color_filter.as_ref())
+            .with_clip(Some(&shared.coverage)),
+        )
+    }
+
+    /// Returns the path moved to the pixel grid of target, if paint asks for pixel snapping.
+    /// Strokes with odd pixel width are centered on pixels, so their edges are on the grid.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` the geometry of the draw in local space
+    /// * `paint` the paint of the draw
+    /// * `style` how the geometry is drawn
+    /// * `target` the target the draw is replayed into
+    fn snap_path(
+        &self,
+        path: &Path,
+        paint: &Paint,
+        style: &Style,
+        target: &RenderTarget,
+    ) -> Option<Path> {
+        if !paint.pixel_snap {
+            return None;
+        }
+
+        let transform = target.base_transform * self.transform;
+        let scale = [target.scale_x, target.scale_y];
+
+        let offset = match style {
+            Style::Fill => [0.0, 0.0],
+            Style::Stroke(stroke) | Style::FillAndStroke(stroke) => {
+                let center = |s: f32| {
+                    let width = (stroke.width * s).abs().round();
+                    if width % 2.0 == 1.0 { 0.5 } else { 0.0 }
                 };
 
-                Box::new(PathRenderer::new(
-                    target_format,
-                    anti_alias,
-                    raster,
-                    fragment,
-                    (self.depth + depth_offset) as f32,
-                ))
+                [
+                    center(transform.m11 * scale[0]),
+                    center(transform.m22 * scale[1]),
+                ]
+            }
+        };
+
+        path.snap_to_pixels(&transform, scale, offset)
+    }
+
+    /// Generates the renderer of a draw whose coverage is drawn into a mask first, then shaded through it.
+    /// The mask is blurred by the mask filter of paint.
+    ///
+    /// # Arguments
+    ///
+    /// * `target` the target the draw is replayed into
+    /// * `depth` depth of the draw inside target
+    /// * `paint` the paint of the draw
+    /// * `bounds` local bounds of the geometry
+    /// * `fragment` shades the coverage in the mask
+    fn gen_mask_render(
+        &self,
+        target: &RenderTarget,
+        depth: f32,
+        paint: &Paint,
+        bounds: &Rect,
+        fragment: Box<dyn Fragment>,
+    ) -> Box<dyn Renderer> {
+        let transform = target.base_transform * self.transform;
+
+        let mask = match paint.mask_filter {
+            Some(MaskFilter::Blur { style, sigma }) => {
+                BlurMask::new(target, bounds, &transform, style, sigma)
             }
+            None => BlurMask::new(target, bounds, &transform, BlurStyle::Normal, 0.0),
+        }
+        .with_anti_alias(paint.anti_alias);
 
-            DrawCommand::DrawText(blob, pos, color) => Box::new(TextBlobRender::new(
-                target_format,
-                anti_alias,
-                blob.clone(),
-                color.clone(),
-                pos.clone(),
-                (self.depth + depth_offset) as f32,
-                vw,
-                vh,
-                self.transform.clone(),
-            )),
+        // the geometry is drawn with opaque white, the coverage ends up in the red channel
+        let coverage_paint = Paint {
+            color: ColorType::SolidColor(Color::white()),
+            style: paint.style,
+            anti_alias: paint.anti_alias,
+            pixel_snap: paint.pixel_snap,
+            ..Paint::new()
+        };
+
+        let command = match &self.command {
+            DrawCommand::DrawPath(path, _) => DrawCommand::DrawPath(path.clone(), coverage_paint),
+            DrawCommand::DrawText(blob, pos, _) => {
+                DrawCommand::DrawText(blob.clone(), *pos, coverage_paint)
+            }
+            _ => DrawCommand::DrawPath(Path::new().add_rect(bounds), coverage_paint),
+        };
+
+        let coverage = Draw {
+            depth: 1,
+            command,
+            transform: self.transform,
         }
+        .gen_render(&mask.coverage_target(target), 0, None);
+
+        let composite = PathRenderer::new(
+            target
+                .pipeline_key()
+                .with_blend_mode(paint.blend_mode)
+                .with_dither(self.dither(paint)),
+            Box::new(PathFill::new(mask.local_quad(&transform), transform)),
+            fragment,
+            depth,
+        )
+        .with_color_filter(paint.color_filter.as_ref());
+
+        Box::new(MaskBlurRenderer::new(*target, mask, coverage, composite))
+    }
+}
+
+/// Generates the fragment which shades the geometry with the color of paint.
+/// Solid colors use the built-in pipeline, other colors generate the shader from the tree.
+/// Invalid gradients are shaded with black color.
+fn gen_fragment(color: &ColorType, vw: f32, vh: f32, transform: Matrix4<f32>) -> Box<dyn Fragment> {
+    match color {
+        ColorType::SolidColor(color) => {
+            Box::new(SolidColorFragment::new(*color, vw, vh, transform))
+        }
+        _ => Box::new(ShaderFragment::new(color, vw, vh, transform)),
+    }
+}
+
+/// Generates the fragment which samples the image with the image matrix.
+fn gen_image_fragment(
+    image: &Image,
+    matrix: &Matrix4<f32>,
+    vw: f32,
+    vh: f32,
+    transform: Matrix4<f32>,
+) -> Box<dyn Fragment> {
+    match &image.source {
+        image::ImageSource::Bitmap(bitmap) => Box::new(TextureFragment::new_with_bitmap(
+            vw,
+            vh,
+            transform,
+            bitmap.clone(),
+            *matrix,
+        )),
+        image::ImageSource::Texture(texture, info) => Box::new(TextureFragment::new_with_texture(
+            vw,
+            vh,
+            transform,
+            texture.clone(),
+            info.clone(),
+            *matrix,
+        )),
     }
 }
 
@@ -200,6 +1180,336 @@ impl Draw {
 /// A picture can be played back multiple times.
 pub struct Picture {
     pub(crate) draws: Vec<Draw>,
+    /// bounds declared when recording, or the bounds of the recorded draws
+    pub(crate) cull_rect: Rect,
+    /// device space bounds of draws, used to replay only the draws inside a rect
+    pub(crate) rtree: RTree,
+}
+
+impl Picture {
+    /// Creates a picture of draws and builds its bounding box hierarchy.
+    ///
+    /// # Arguments
+    ///
+    /// * `draws` the recorded draws, with the depth of clips assigned
+    /// * `cull_rect` the declared bounds, pass `None` to use the bounds of draws
+    pub(crate) fn new(draws: Vec<Draw>, cull_rect: Option<Rect>) -> Self {
+        let bounds = draws_bounds(&draws, None);
+        let cull_rect = cull_rect
+            .or(union_bounds(&draws, &bounds))
+            .unwrap_or(Rect::from_ltrb(0.0, 0.0, 0.0, 0.0));
+
+        Picture {
+            rtree: RTree::new(&bounds),
+            draws,
+            cull_rect,
+        }
+    }
+
+    /// Returns the number of depth values used by the draws.
+    pub(crate) fn depth_count(&self) -> u32 {
+        // clips are already closed, so the max depth is used by the last draw or clip
+        self.draws.iter().map(|draw| draw.depth).max().unwrap_or(0)
+    }
+
+    /// Returns the bounds of the picture in the space it is recorded in.
+    /// If the recorder is created with `PictureRecorder::new_with_bounds`, the declared bounds are returned,
+    /// otherwise the conservative bounds of all draws, including stroke width, blur and clip.
+    /// An empty picture has empty bounds.
+    pub fn bounds(&self) -> Rect {
+        self.cull_rect
+    }
+
+    /// Returns the number of recorded operations, including clips and the draws inside layers.
+    pub fn approximate_op_count(&self) -> usize {
+        fn count(draws: &[Draw]) -> usize {
+            draws
+                .iter()
+                .map(|draw| match &draw.command {
+                    DrawCommand::DrawLayer(layer) => 1 + count(&layer.draws),
+                    _ => 1,
+                })
+                .sum()
+        }
+
+        count(&self.draws)
+    }
+
+    /// Replays the recorded commands into canvas, under the current transform and clip of canvas.
+    /// The canvas is left with the same save count and transform as before.
+    ///
+    /// # Arguments
+    ///
+    /// * `canvas` the canvas receiving the commands
+    pub fn playback(&self, canvas: &mut dyn Canvas) {
+        let base = canvas.get_total_matrix();
+
+        playback_draws(&self.draws, &base, canvas);
+    }
+
+    /// Returns the approximate number of bytes used by the recorded operations.
+    /// Images, text blobs and runtime shaders are shared with the caller and not counted.
+    pub fn approximate_bytes_used(&self) -> usize {
+        std::mem::size_of::<Picture>()
+            + draws_bytes(&self.draws)
+            + self.rtree.approximate_bytes_used()
+    }
+}
+
+/// Computes the device space bounds of draws including stroke width and blur, limited by the clips they
+/// are drawn in. Returns `None` for the draws which are not bounded, clips which do not bound their scope
+/// such as `ClipOp::Difference`, and clips expanded inside their scope.
+/// The bounds of a clip with `ClipOp::Intersect` is the area its scope can touch.
+///
+/// # Arguments
+///
+/// * `draws` the draws in recording order
+/// * `clip` device space bounds of the clip the draws are inside, `None` means unbounded
+fn draws_bounds(draws: &[Draw], clip: Option<Rect>) -> Vec<Option<Rect>> {
+    // (depth, bounds) of clips whose scope is not finished, the innermost is the last
+    let mut clips: Vec<(u32, Option<Rect>)> = Vec::new();
+    let base = clip;
+
+    let limit = |bounds: Rect, clip: Option<Rect>| match clip {
+        Some(clip) => bounds
+            .intersect(&clip)
+            .unwrap_or(Rect::from_ltrb(0.0, 0.0, 0.0, 0.0)),
+        None => bounds,
+    };
+
+    draws
+        .iter()
+        .zip(expanded_clips(draws))
+        .map(|(draw, expanded)| {
+            while clips.last().is_some_and(|(depth, _)| *depth <= draw.depth) {
+                clips.pop();
+            }
+
+            let clip = clips.last().map_or(clip, |(_, bounds)| *bounds);
+            let transform = Matrix {
+                matrix: draw.transform,
+            };
+
+            let bounds = match &draw.command {
+                DrawCommand::DrawPath(path, paint) => {
+                    device_bounds(&transform, path.bounds(), &paint.style, paint.mask_filter)
+                }
+                DrawCommand::DrawImage(_, dst, _, paint) => {
+                    device_bounds(&transform, *dst, &Style::Fill, paint.mask_filter)
+                }
+                DrawCommand::DrawText(blob, pos, paint) => device_bounds(
+                    &transform,
+                    blob.bounds(pos),
+                    &Style::Fill,
+                    paint.mask_filter,
+                ),
+                DrawCommand::ClipPath(path, op, _) => {
+                    let path_bounds = transform.map_rect(&path.bounds());
+                    // expanding clips are only limited by the layer bounds
+                    let bounds = match op {
+                        ClipOp::Intersect => Some(limit(path_bounds, clip)),
+                        ClipOp::Difference => clip,
+                        ClipOp::Union | ClipOp::Xor => {
+                            clip.map(|clip| limit(clip.union(&path_bounds), base))
+                        }
+                        ClipOp::ReverseDifference | ClipOp::Replace => {
+                            Some(limit(path_bounds, base))
+                        }
+                    };
+                    clips.push((draw.depth, bounds));
+
+                    let bounded = matches!(
+                        op,
+                        ClipOp::Intersect | ClipOp::ReverseDifference | ClipOp::Replace
+                    );
+
+                    return bounds.filter(|_| bounded && !expanded);
+                }
+                DrawCommand::DrawLayer(layer) => {
+                    // the content is cut by the layer bounds, then the image filter may move or grow it
+                    let inner = draws_bounds(&layer.draws, layer.bounds);
+                    let content = union_bounds(&layer.draws, &inner)
+                        .unwrap_or(Rect::from_ltrb(0.0, 0.0, 0.0, 0.0));
+
+                    match &layer.paint.image_filter {
+                        Some(filter) => filter.map_bounds(&content),
+                        None => content,
+                    }
+                }
+            };
+
+            Some(limit(bounds, clip))
+        })
+        .collect()
+}
+
+/// Returns the union of bounds of draws, clips are not counted.
+///
+/// # Arguments
+///
+/// * `draws` the draws
+/// * `bounds` the bounds of each draw returned by `draws_bounds`
+fn union_bounds(draws: &[Draw], bounds: &[Option<Rect>]) -> Option<Rect> {
+    draws
+        .iter()
+        .zip(bounds)
+        .filter(|(draw, _)| !matches!(draw.command, DrawCommand::ClipPath(..)))
+        .filter_map(|(_, bounds)| bounds.filter(|b| !b.is_empty()))
+        .reduce(|a, b| a.union(&b))
+}
+
+/// Returns the device space bounds of geometry, including stroke width and blur.
+///
+/// # Arguments
+///
+/// * `transform` the transform of the draw
+/// * `bounds` local bounds of the geometry
+/// * `style` the style of the draw, stroke width extends the bounds
+/// * `mask_filter` the mask filter of the draw, blur extends the bounds
+fn device_bounds(
+    transform: &Matrix,
+    mut bounds: Rect,
+    style: &Style,
+    mask_filter: Option<MaskFilter>,
+) -> Rect {
+    let outset = style.outset()
+        + match mask_filter {
+            Some(MaskFilter::Blur { sigma, .. }) => blur_extent(sigma),
+            None => 0.0,
+        };
+    bounds.outset(outset, outset);
+
+    transform.map_rect(&bounds)
+}
+
+/// Replays draws into canvas. Each clip is replayed inside its own save, which is restored when the
+/// first draw outside the scope of the clip is reached.
+///
+/// # Arguments
+///
+/// * `draws` the draws in recording order
+/// * `base` the transform of canvas when the playback starts, the transform of draws is applied after it
+/// * `canvas` the canvas receiving the commands
+fn playback_draws(draws: &[Draw], base: &Matrix, canvas: &mut dyn Canvas) {
+    // depth of clips whose scope is not finished, the innermost is the last
+    let mut clips: Vec<u32> = Vec::new();
+
+    for draw in draws {
+        while clips.last().is_some_and(|depth| *depth <= draw.depth) {
+            clips.pop();
+            canvas.restore();
+        }
+
+        let transform = *base
+            * Matrix {
+                matrix: draw.transform,
+            };
+
+        match &draw.command {
+            DrawCommand::DrawPath(path, paint) => {
+                canvas.set_matrix(&transform);
+                canvas.draw_path(path.clone(), paint);
+            }
+            DrawCommand::ClipPath(path, op, anti_alias) => {
+                canvas.save();
+                clips.push(draw.depth);
+
+                canvas.set_matrix(&transform);
+                canvas.clip_path(path.clone(), *op, *anti_alias);
+            }
+            DrawCommand::DrawImage(image, dst, matrix, paint) => {
+                // the image matrix maps dst back to the source rect
+                let src = Matrix { matrix: *matrix }.map_rect(dst);
+
+                canvas.set_matrix(&transform);
+                canvas.draw_image_with_paint(image, dst, Some(&src), paint);
+            }
+            DrawCommand::DrawText(text, pos, paint) => {
+                canvas.set_matrix(&transform);
+                canvas.draw_text_with_paint(text.clone(), *pos, paint);
+            }
+            DrawCommand::DrawLayer(layer) => {
+                // layer bounds and image filter are already in the space of the picture
+                canvas.set_matrix(base);
+                canvas.save_layer(layer.bounds, Some(&layer.paint));
+                playback_draws(&layer.draws, base, canvas);
+                canvas.restore();
+            }
+        }
+    }
+
+    for _ in clips {
+        canvas.restore();
+    }
+
+    canvas.set_matrix(base);
+}
+
+/// Returns the bytes used by draws and what they own on the heap.
+fn draws_bytes(draws: &[Draw]) -> usize {
+    let path_bytes = |path: &Path| path.verts.capacity() * std::mem::size_of::<PathVerb>();
+
+    std::mem::size_of_val(draws)
+        + draws
+            .iter()
+            .map(|draw| match &draw.command {
+                DrawCommand::DrawPath(path, paint) => path_bytes(path) + paint_bytes(paint),
+                DrawCommand::ClipPath(path, ..) => path_bytes(path),
+                DrawCommand::DrawImage(.., paint) | DrawCommand::DrawText(.., paint) => {
+                    paint_bytes(paint)
+                }
+                DrawCommand::DrawLayer(layer) => {
+                    std::mem::size_of::<Layer>()
+                        + paint_bytes(&layer.paint)
+                        + draws_bytes(&layer.draws)
+                }
+            })
+            .sum::<usize>()
+}
+
+/// Returns the bytes a paint owns on the heap.
+fn paint_bytes(paint: &Paint) -> usize {
+    fn color_bytes(color: &ColorType) -> usize {
+        match color {
+            ColorType::LinearGradient(LinearGradient { colors, stops, .. })
+            | ColorType::RadialGradient(RadialGradient { colors, stops, .. }) => {
+                colors.capacity() * std::mem::size_of::<Color>()
+                    + stops.capacity() * std::mem::size_of::<f32>()
+            }
+            ColorType::Blend(_, src, dst) => {
+                2 * std::mem::size_of::<ColorType>() + color_bytes(src) + color_bytes(dst)
+            }
+            ColorType::WithLocalMatrix(_, color) | ColorType::WithColorFilter(_, color) => {
+                std::mem::size_of::<ColorType>() + color_bytes(color)
+            }
+            _ => 0,
+        }
+    }
+
+    fn filter_bytes(filter: &ImageFilter) -> usize {
+        let input_bytes = |input: &Option<Box<ImageFilter>>| {
+            input
+                .as_ref()
+                .map_or(0, |f| std::mem::size_of::<ImageFilter>() + filter_bytes(f))
+        };
+
+        match filter {
+            ImageFilter::Blur { input, .. }
+            | ImageFilter::DropShadow { input, .. }
+            | ImageFilter::DropShadowOnly { input, .. }
+            | ImageFilter::Offset { input, .. }
+            | ImageFilter::ColorFilter { input, .. } => input_bytes(input),
+            ImageFilter::Merge(inputs) => {
+                inputs.capacity() * std::mem::size_of::<Option<ImageFilter>>()
+                    + inputs.iter().flatten().map(filter_bytes).sum::<usize>()
+            }
+            ImageFilter::Compose(outer, inner) => {
+                2 * std::mem::size_of::<ImageFilter>() + filter_bytes(outer) + filter_bytes(inner)
+            }
+        }
+    }
+
+    color_bytes(&paint.color) + paint.image_filter.as_ref().map_or(0, filter_bytes)
 }
 
 /// Recorder drawing commands and can generate a Picture.
@@ -207,6 +1517,21 @@ pub struct PictureRecorder {
     pub(crate) state: State,
     pub(crate) draws: Vec<Draw>,
     pub(crate) current_depth: u32,
+    layers: Vec<LayerRecord>,
+    /// bounds declared by `new_with_bounds`
+    cull_rect: Option<Rect>,
+    strict: bool,
+    diagnostics: Vec<Diagnostic>,
+}
+
+/// Parent recording state saved when a layer begins.
+struct LayerRecord {
+    /// save count right after the layer is saved, the layer ends when restored below it
+    save_count: usize,
+    bounds: Option<Rect>,
+    paint: Paint,
+    parent_draws: Vec<Draw>,
+    parent_depth: u32,
 }
 
 impl PictureRecorder {
@@ -215,7 +1540,84 @@ impl PictureRecorder {
             state: State::new(),
             draws: Vec::new(),
             current_depth: 0,
+            layers: Vec::new(),
+            cull_rect: None,
+            strict: false,
+            diagnostics: Vec::new(),
+        }
+    }
+
+    /// Creates a recorder whose picture declares its bounds up front.
+    /// The bounds are returned by `Picture::bounds` as is, draws outside of them are still recorded.
+    ///
+    /// # Arguments
+    ///
+    /// * `bounds` the bounds of the picture
+    pub fn new_with_bounds(bounds: &Rect) -> Self {
+        Self {
+            cull_rect: Some(*bounds),
+            ..Self::new()
+        }
+    }
+
+    /// Enables or disables strict mode. In strict mode unbalanced restores, commands with non-finite
+    /// coordinates and draws which are empty after clip are recorded as diagnostics, which are returned by
+    /// `finish_record_with_diagnostics`. Commands with non-finite coordinates and empty draws are dropped.
+    ///
+    /// # Arguments
+    ///
+    /// * `strict` whether to record diagnostics
+    pub fn with_strict_mode(mut self, strict: bool) -> Self {
+        self.strict = strict;
+        self
+    }
+
+    /// Checks a command in strict mode, returns false and records a diagnostic if it has non-finite
+    /// coordinates. Always returns true out of strict mode.
+    ///
+    /// # Arguments
+    ///
+    /// * `command` the name of the command
+    /// * `finite` whether the coordinates of the command are finite
+    fn check_finite(&mut self, command: &'static str, finite: bool) -> bool {
+        if self.strict && !finite {
+            self.diagnostics
+                .push(Diagnostic::NonFiniteCoordinates(command));
+        }
+
+        !self.strict || finite
+    }
+
+    /// Checks a draw, returns false if it can not be visible inside current clip, or it has non-finite
+    /// coordinates in strict mode. Diagnostics are recorded in strict mode.
+    ///
+    /// # Arguments
+    ///
+    /// * `command` the name of the command
+    /// * `finite` whether the coordinates of the draw are finite
+    /// * `bounds` returns the device space bounds of the draw under current transform
+    fn check_draw(
+        &mut self,
+        command: &'static str,
+        finite: bool,
+        bounds: impl FnOnce(&Matrix) -> Rect,
+    ) -> bool {
+        if !self.check_finite(command, finite) {
+            return false;
+        }
+
+        let matrix = self.get_total_matrix();
+
+        // bounds mapped by perspective are not reliable
+        if matrix.has_perspective() || !self.state.quick_reject(&bounds(&matrix)) {
+            return true;
+        }
+
+        if self.strict {
+            self.diagnostics.push(Diagnostic::EmptyAfterClip(command));
         }
+
+        false
     }
 
     /// Draws path with current clip and transform.
@@ -225,6 +1627,13 @@ impl PictureRecorder {
     /// * `path` the path to draw
     /// * `paint` the paint controls the styling when drawing the path
     pub fn draw_path(&mut self, path: Path, paint: &Paint) {
+        let finite = path.is_finite();
+        if !self.check_draw("draw_path", finite, |m| {
+            device_bounds(m, path.bounds(), &paint.style, paint.mask_filter)
+        }) {
+            return;
+        }
+
         self.current_depth += 1;
         self.draws.push(Draw {
             depth: self.current_depth,
@@ -287,6 +1696,31 @@ impl PictureRecorder {
     /// * `dst` the bounds of image to draw on canvas
     /// * `src` part of image source to draw, pass `None` to draw the whole image
     pub fn draw_image(&mut self, image: &Image, dst: &Rect, src: Option<&Rect>) {
+        self.draw_image_with_paint(image, dst, src, &Paint::new());
+    }
+
+    /// Draws image with current clip and transform, using the blend mode and color filter of paint.
+    ///
+    /// # Arguments
+    ///
+    /// * `image` the image to draw
+    /// * `dst` the bounds of image to draw on canvas
+    /// * `src` part of image source to draw, pass `None` to draw the whole image
+    /// * `paint` the paint controls blend mode and color filter, color and style are ignored
+    pub fn draw_image_with_paint(
+        &mut self,
+        image: &Image,
+        dst: &Rect,
+        src: Option<&Rect>,
+        paint: &Paint,
+    ) {
+        let finite = dst.is_finite() && src.is_none_or(|src| src.is_finite());
+        if !self.check_draw("draw_image", finite, |m| {
+            device_bounds(m, *dst, &Style::Fill, paint.mask_filter)
+        }) {
+            return;
+        }
+
         let src = src
             .unwrap_or(&Rect::from_xywh(
                 0.0,
@@ -330,7 +1764,7 @@ impl PictureRecorder {
 
         self.draws.push(Draw {
             depth: self.current_depth,
-            command: DrawCommand::DrawImage(image.clone(), dst.clone(), matrix),
+            command: DrawCommand::DrawImage(image.clone(), dst.clone(), matrix, paint.clone()),
             transform: self.state.current_transform(),
         });
     }
@@ -344,40 +1778,196 @@ impl PictureRecorder {
     /// * `pos` the baseline position of text
     /// * `color` the color of text
     pub fn draw_text(&mut self, text: Rc<TextBlob>, pos: Point, color: Color) {
+        let mut paint = Paint::new();
+        paint.color = color.into();
+
+        self.draw_text_with_paint(text, pos, &paint);
+    }
+
+    /// Draws text with current clip and transform at position pos with paint.
+    /// Currently only support solid color and fill style
+    ///
+    /// # Arguments
+    ///
+    /// * `text` the text to draw
+    /// * `pos` the baseline position of text
+    /// * `paint` the paint controls color, blend mode and color filter of text
+    pub fn draw_text_with_paint(&mut self, text: Rc<TextBlob>, pos: Point, paint: &Paint) {
+        let finite = pos.x.is_finite() && pos.y.is_finite();
+        if !self.check_draw("draw_text", finite, |m| {
+            device_bounds(m, text.bounds(&pos), &Style::Fill, paint.mask_filter)
+        }) {
+            return;
+        }
+
         self.current_depth += 1;
         self.draws.push(Draw {
             depth: self.current_depth,
-            command: DrawCommand::DrawText(text, pos, color),
+            command: DrawCommand::DrawText(text, pos, paint.clone()),
             transform: self.state.current_transform(),
         });
     }
 
+    /// Draws picture with current clip and transform. The draws of picture are replayed into this recorder,
+    /// so the picture can be drawn many times without recording it again.
+    ///
+    /// # Arguments
+    ///
+    /// * `picture` the picture to draw
+    /// * `matrix` transform applied to the picture before the current transform, pass `None` to draw it as is
+    /// * `paint` the alpha, blend mode, color filter and image filter used when compositing the picture.
+    ///   The picture is drawn into a layer only when the paint is not an opaque SrcOver.
+    pub fn draw_picture(
+        &mut self,
+        picture: &Rc<Picture>,
+        matrix: Option<&Matrix>,
+        paint: Option<&Paint>,
+    ) {
+        if picture.draws.is_empty() {
+            return;
+        }
+
+        let finite = matrix.is_none_or(|m| m.matrix.iter().all(|v| v.is_finite()));
+        if !self.check_draw("draw_picture", finite, |m| {
+            let matrix = matrix.map_or(m.matrix, |matrix| m.matrix * matrix.matrix);
+            Matrix { matrix }.map_rect(&picture.bounds())
+        }) {
+            return;
+        }
+
+        // draws of picture can be blended one by one, unless the paint changes their composition
+        let paint = paint.filter(|paint| {
+            paint.alpha() < 1.0
+                || paint.blend_mode != BlendMode::SrcOver
+                || paint.color_filter.is_some()
+                || paint.image_filter.is_some()
+        });
+
+        match paint {
+            Some(paint) => {
+                let bounds = matrix.map_or(picture.bounds(), |m| m.map_rect(&picture.bounds()));
+                self.save_layer(Some(bounds), Some(paint));
+            }
+            None => self.save(),
+        }
+
+        if let Some(matrix) = matrix {
+            self.state.concat(&matrix.matrix);
+        }
+
+        let transform = self.state.current_transform();
+        let depth_offset = self.current_depth;
+
+        self.draws.extend(
+            picture
+                .draws
+                .iter()
+                .map(|draw| draw.replayed(&transform, depth_offset)),
+        );
+        self.current_depth += picture.depth_count();
+
+        // clips of picture expanding the clip are restored with the draws of picture
+        let first = self.draws.len() - picture.draws.len();
+        for (index, draw) in self.draws.iter().enumerate().skip(first) {
+            if matches!(&draw.command, DrawCommand::ClipPath(_, op, _) if op.is_expanding()) {
+                self.state.save_expanding_clip(index);
+            }
+        }
+
+        self.restore();
+    }
+
     /// Clips the current context with the specified path.
     ///
     /// # Arguments
     ///
     /// * `path` the path to clip
     /// * `op` the type of operation performed by the clip
-    pub fn clip_path(&mut self, path: Path, op: ClipOp) {
-        self.draws.push(Draw {
-            depth: 0,
-            command: DrawCommand::ClipPath(path, op),
-            transform: self.state.current_transform(),
-        });
+    /// * `anti_alias` whether the edge of clip is anti-aliased
+    pub fn clip_path(&mut self, path: Path, op: ClipOp, anti_alias: bool) {
+        if !self.check_finite("clip_path", path.is_finite()) {
+            return;
+        }
+
+        let matrix = self.get_total_matrix();
+        let bounds = Some(matrix.map_rect(&path.bounds())).filter(|_| !matrix.has_perspective());
+
+        match (op, bounds) {
+            (ClipOp::Intersect, Some(bounds)) => self.state.intersect_clip_bounds(&bounds),
+            (ClipOp::Intersect | ClipOp::Difference, _) => {}
+            // expanding clips can grow up to the layer bounds
+            (_, bounds) => {
+                let bounds = match op {
+                    ClipOp::Union | ClipOp::Xor => bounds.and_then(|bounds| {
+                        let current = self.state.device_clip_bounds()?;
+                        Some(current.union(&bounds))
+                    }),
+                    _ => bounds,
+                };
+                let layer = self.layers.last().and_then(|layer| layer.bounds);
+
+                self.state.set_device_clip_bounds(bounds.or(layer));
+                if let Some(layer) = layer {
+                    self.state.intersect_clip_bounds(&layer);
+                }
+            }
+        }
+
+        self.draws.push(Draw {
+            depth: 0,
+            command: DrawCommand::ClipPath(path, op, anti_alias),
+            transform: self.state.current_transform(),
+        });
+
+        let last_index = self.draws.len() - 1;
+
+        self.state.save_clip(last_index);
+        if op.is_expanding() {
+            self.state.save_expanding_clip(last_index);
+        }
+    }
+
+    /// Clips the current context with the specified rect.
+    ///
+    /// # Arguments
+    ///
+    /// * `rect` the rect to clip
+    /// * `op` the type of operation performed by the clip
+    pub fn clip_rect(&mut self, rect: &Rect, op: ClipOp) {
+        self.clip_path(Path::new().add_rect(rect), op, true);
+    }
+
+    /// Clips the current context with the specified round rect.
+    ///
+    /// # Arguments
+    ///
+    /// * `rect` the round rect to clip
+    /// * `op` the type of operation performed by the clip
+    /// * `anti_alias` whether the edge of clip is anti-aliased
+    pub fn clip_rrect(&mut self, rect: &RRect, op: ClipOp, anti_alias: bool) {
+        self.clip_path(Path::new().add_rrect(rect), op, anti_alias);
+    }
 
-        let last_index = self.draws.len() - 1;
+    /// Returns the conservative bounds of current clip in device space, which is the space of the picture.
+    /// Returns `None` if the clip is not bounded.
+    pub fn get_device_clip_bounds(&self) -> Option<Rect> {
+        self.state.device_clip_bounds()
+    }
 
-        self.state.save_clip(last_index);
+    /// Returns the conservative bounds of current clip in local space, mapped back by current transform.
+    /// Returns `None` if the clip is not bounded or can not be mapped back under perspective.
+    pub fn get_local_clip_bounds(&self) -> Option<Rect> {
+        Canvas::get_local_clip_bounds(self)
     }
 
-    /// Clips the current context with the specified rect.
+    /// Returns true if rect under current transform is surely outside of current clip, so drawing inside
+    /// it can be skipped. Returns false if it may be visible.
     ///
     /// # Arguments
     ///
-    /// * `rect` the rect to clip
-    /// * `op` the type of operation performed by the clip
-    pub fn clip_rect(&mut self, rect: &Rect, op: ClipOp) {
-        self.clip_path(Path::new().add_rect(rect), op);
+    /// * `rect` the local bounds of the drawing, including stroke width and blur
+    pub fn quick_reject(&self, rect: &Rect) -> bool {
+        Canvas::quick_reject(self, rect)
     }
 
     /// Save current transform matrix and clip state
@@ -385,22 +1975,189 @@ impl PictureRecorder {
         self.state.save();
     }
 
-    /// Restore the transform matrix and clip to the last saved state
+    /// Save current transform matrix and clip state, and redirect following draws into an offscreen layer.
+    /// The layer is composited back when the matching `restore` is called.
+    ///
+    /// # Arguments
+    ///
+    /// * `bounds` the bounds of the layer in local space, pass `None` to use the current clip bounds
+    /// * `paint` the alpha, blend mode, color filter and image filter used when compositing the layer,
+    ///   pass `None` to composite with SrcOver
+    pub fn save_layer(&mut self, bounds: Option<Rect>, paint: Option<&Paint>) {
+        // the layer is still saved with non-finite bounds, so the matching restore is balanced
+        let bounds = bounds.filter(|b| self.check_finite("save_layer", b.is_finite()));
+
+        let mut paint = paint.cloned().unwrap_or(Paint::new());
+
+        // image filter is replayed in device space, same as the layer bounds
+        paint.image_filter = paint
+            .image_filter
+            .map(|filter| filter.transformed(&self.state.current_transform()));
+
+        // content outside the clip may be moved or blurred into it by the image filter
+        let clip = match (&paint.image_filter, self.state.device_clip_bounds()) {
+            (Some(filter), Some(clip)) => Some(filter.reverse_bounds(&clip)),
+            (_, clip) => clip,
+        };
+
+        let bounds = bounds.map(|b| {
+            Matrix {
+                matrix: self.state.current_transform(),
+            }
+            .map_rect(&b)
+        });
+
+        let bounds = match (bounds, clip) {
+            (Some(bounds), Some(clip)) => {
+                Some(bounds.intersect(&clip).unwrap_or(Rect::from_ltrb(0.0, 0.0, 0.0, 0.0)))
+            }
+            (Some(bounds), None) => Some(bounds),
+            (None, clip) => clip,
+        };
+
+        self.state.save();
+        // content of the layer is cut by its bounds
+        self.state.set_device_clip_bounds(bounds);
+
+        self.layers.push(LayerRecord {
+            save_count: self.state.save_count(),
+            bounds,
+            paint,
+            parent_draws: std::mem::take(&mut self.draws),
+            parent_depth: std::mem::replace(&mut self.current_depth, 0),
+        });
+    }
+
+    /// Restore the transform matrix and clip to the last saved state.
+    /// Restore without a matching save is ignored.
     pub fn restore(&mut self) {
+        if self.state.save_count() == 0 {
+            if self.strict {
+                self.diagnostics.push(Diagnostic::UnbalancedRestore);
+            }
+            return;
+        }
+
         let clip_state = self.state.restore();
 
-        if clip_state.is_none() {
+        if let Some(clip_state) = &clip_state {
+            for i in clip_state.clip_op.iter().rev() {
+                self.current_depth += 1;
+                self.draws[*i].depth = self.current_depth;
+            }
+        }
+
+        let close_layer = self
+            .layers
+            .last()
+            .is_some_and(|layer| self.state.save_count() < layer.save_count);
+
+        if close_layer {
+            self.close_layer();
+        } else if let Some(clip_state) = clip_state.filter(|c| !c.expanding.is_empty()) {
+            self.record_clips_again(&clip_state.expanding);
+        }
+    }
+
+    /// Records the clips of the remaining states again after clips expanding the clip are restored, since
+    /// the area they expanded is not clipped by the clips recorded before them any more.
+    /// Clips which flip the clip, such as `ClipOp::Xor`, are recorded after the area they and the restored
+    /// clips can touch is reset, so they are not applied twice. The reset is skipped if the area is not
+    /// known under perspective.
+    ///
+    /// # Arguments
+    ///
+    /// * `expanding` indices of the restored clips expanding the clip
+    fn record_clips_again(&mut self, expanding: &[usize]) {
+        let first = self.layers.last().map_or(0, |layer| layer.save_count);
+        let clips = self.state.clips_since(first);
+
+        let Some(&(first_level, _)) = clips.first() else {
             return;
+        };
+
+        let flips = |index: &usize| {
+            matches!(
+                self.draws[*index].command,
+                DrawCommand::ClipPath(_, ClipOp::Xor | ClipOp::ReverseDifference, _)
+            )
+        };
+
+        let flipping: Vec<usize> = clips
+            .iter()
+            .map(|(_, index)| *index)
+            .filter(|index| flips(index))
+            .collect();
+
+        let reset = if !flipping.is_empty() {
+            expanding
+                .iter()
+                .chain(&flipping)
+                .map(|index| self.draws[*index].clip_bounds())
+                .reduce(|a, b| Some(a?.union(&b?)))
+                .flatten()
+        } else {
+            None
+        };
+
+        if let Some(reset) = reset {
+            let mut reset = reset.round_out();
+            reset.outset(1.0, 1.0);
+
+            self.draws.push(Draw {
+                depth: 0,
+                command: DrawCommand::ClipPath(Path::new().add_rect(&reset), ClipOp::Union, false),
+                transform: Matrix4::identity(),
+            });
+            self.state.save_clip_at(first_level, self.draws.len() - 1);
+        }
+
+        for (level, index) in clips {
+            let mut draw = self.draws[index].replayed(&Matrix4::identity(), 0);
+            draw.depth = 0;
+
+            self.draws.push(draw);
+            self.state.save_clip_at(level, self.draws.len() - 1);
         }
+    }
 
-        let clip_state = clip_state.unwrap();
+    /// Returns the number of saved states which are not restored, including layers.
+    /// A new recorder has save count 0.
+    pub fn get_save_count(&self) -> usize {
+        self.state.save_count()
+    }
 
-        for i in clip_state.clip_op.iter().rev() {
-            self.current_depth += 1;
-            self.draws[*i].depth = self.current_depth;
+    /// Restores saved states until the save count is count. Nothing happens if the save count is
+    /// already less than or equal to count.
+    ///
+    /// # Arguments
+    ///
+    /// * `count` the save count to restore to, usually returned by `get_save_count` before saving
+    pub fn restore_to_count(&mut self, count: usize) {
+        while self.state.save_count() > count {
+            self.restore();
         }
     }
 
+    fn close_layer(&mut self) {
+        let record = self.layers.pop().expect("No layer to close");
+
+        let draws = std::mem::replace(&mut self.draws, record.parent_draws);
+        let depth_count = std::mem::replace(&mut self.current_depth, record.parent_depth);
+
+        self.current_depth += 1;
+        self.draws.push(Draw {
+            depth: self.current_depth,
+            command: DrawCommand::DrawLayer(Box::new(Layer {
+                bounds: record.bounds,
+                paint: record.paint,
+                draws,
+                depth_count,
+            })),
+            transform: Matrix4::identity(),
+        });
+    }
+
     /// Translates transform matrix by dx alone the x-axis and dy along the y-axis
     ///
     /// # Arguments
@@ -408,9 +2165,26 @@ impl PictureRecorder {
     /// * `dx` distance to translate on x-axis
     /// * `dy` distance to translate on y-axis
     pub fn translate(&mut self, dx: f32, dy: f32) {
+        if !self.check_finite("translate", dx.is_finite() && dy.is_finite()) {
+            return;
+        }
+
         self.state.translate(dx, dy);
     }
 
+    /// Pre-multiplies transform matrix by matrix, so matrix is applied to the geometry first.
+    ///
+    /// # Arguments
+    ///
+    /// * `matrix` the matrix to concat
+    pub fn concat(&mut self, matrix: &Matrix) {
+        if !self.check_finite("concat", matrix.matrix.iter().all(|v| v.is_finite())) {
+            return;
+        }
+
+        self.state.concat(&matrix.matrix);
+    }
+
     /// Rotate transform matrix by degree at point (px, py)
     ///
     /// # Arguments
@@ -419,6 +2193,11 @@ impl PictureRecorder {
     /// * `px` x position of rotation center
     /// * `py` y position of rotation center
     pub fn rotate_at_xy(&mut self, degree: f32, px: f32, py: f32) {
+        let finite = degree.is_finite() && px.is_finite() && py.is_finite();
+        if !self.check_finite("rotate_at_xy", finite) {
+            return;
+        }
+
         self.state.rotate_at(degree, px, py);
     }
 
@@ -428,6 +2207,10 @@ impl PictureRecorder {
     ///
     /// * `degree` degree to rotate on z-axis
     pub fn rotate(&mut self, degree: f32) {
+        if !self.check_finite("rotate", degree.is_finite()) {
+            return;
+        }
+
         self.state.rotate(degree);
     }
 
@@ -435,19 +2218,73 @@ impl PictureRecorder {
     ///
     /// # Arguments
     ///
-    /// * `sx` scale at x-axis must be positive
-    /// * `sy` scale at y-axis must be positive
+    /// * `sx` scale at x-axis, negative value flips the x-axis
+    /// * `sy` scale at y-axis, negative value flips the y-axis
     pub fn scale(&mut self, sx: f32, sy: f32) {
-        if sx <= 0.0 || sy <= 0.0 {
-            // scale must be positive value
+        if !self.check_finite("scale", sx.is_finite() && sy.is_finite()) {
             return;
         }
 
         self.state.scale(sx, sy);
     }
 
+    /// Skew transform matrix
+    ///
+    /// # Arguments
+    ///
+    /// * `sx` skew at x-axis, x is moved by `sx * y`
+    /// * `sy` skew at y-axis, y is moved by `sy * x`
+    pub fn skew(&mut self, sx: f32, sy: f32) {
+        if !self.check_finite("skew", sx.is_finite() && sy.is_finite()) {
+            return;
+        }
+
+        self.state.skew(sx, sy);
+    }
+
+    /// Replaces transform matrix, affine and perspective matrices are both supported.
+    /// The matrix is restored by the matching `restore` like other transforms.
+    ///
+    /// # Arguments
+    ///
+    /// * `matrix` the new transform matrix
+    pub fn set_matrix(&mut self, matrix: &Matrix) {
+        if !self.check_finite("set_matrix", matrix.matrix.iter().all(|v| v.is_finite())) {
+            return;
+        }
+
+        self.state.set_matrix(&matrix.matrix);
+    }
+
+    /// Replaces transform matrix with identity.
+    pub fn reset_matrix(&mut self) {
+        self.set_matrix(&Matrix::new());
+    }
+
+    /// Returns current transform matrix, which maps local coordinates to the picture.
+    pub fn get_total_matrix(&self) -> Matrix {
+        Matrix {
+            matrix: self.state.current_transform(),
+        }
+    }
+
     /// Finish record and generate a Picture instance with recorded drawing commands
-    pub fn finish_record(mut self) -> Picture {
+    pub fn finish_record(self) -> Picture {
+        self.finish_record_with_diagnostics().0
+    }
+
+    /// Finish record and generate a Picture instance with recorded drawing commands, together with
+    /// the diagnostics recorded in strict mode. The diagnostics are always empty out of strict mode.
+    pub fn finish_record_with_diagnostics(mut self) -> (Picture, Vec<Diagnostic>) {
+        if self.strict && self.state.save_count() > 0 {
+            let count = self.state.save_count();
+            self.diagnostics.push(Diagnostic::UnrestoredSaves(count));
+        }
+
+        while !self.layers.is_empty() {
+            self.restore();
+        }
+
         loop {
             let clip_state = self.state.pop_clip_stack();
 
@@ -463,6 +2300,490 @@ impl PictureRecorder {
             }
         }
 
-        Picture { draws: self.draws }
+        (Picture::new(self.draws, self.cull_rect), self.diagnostics)
+    }
+}
+
+impl Canvas for PictureRecorder {
+    fn draw_path(&mut self, path: Path, paint: &Paint) {
+        PictureRecorder::draw_path(self, path, paint);
+    }
+
+    fn draw_image_with_paint(
+        &mut self,
+        image: &Image,
+        dst: &Rect,
+        src: Option<&Rect>,
+        paint: &Paint,
+    ) {
+        PictureRecorder::draw_image_with_paint(self, image, dst, src, paint);
+    }
+
+    fn draw_text_with_paint(&mut self, text: Rc<TextBlob>, pos: Point, paint: &Paint) {
+        PictureRecorder::draw_text_with_paint(self, text, pos, paint);
+    }
+
+    fn draw_picture(
+        &mut self,
+        picture: &Rc<Picture>,
+        matrix: Option<&Matrix>,
+        paint: Option<&Paint>,
+    ) {
+        PictureRecorder::draw_picture(self, picture, matrix, paint);
+    }
+
+    fn clip_path(&mut self, path: Path, op: ClipOp, anti_alias: bool) {
+        PictureRecorder::clip_path(self, path, op, anti_alias);
+    }
+
+    fn get_device_clip_bounds(&self) -> Option<Rect> {
+        PictureRecorder::get_device_clip_bounds(self)
+    }
+
+    fn save(&mut self) {
+        PictureRecorder::save(self);
+    }
+
+    fn save_layer(&mut self, bounds: Option<Rect>, paint: Option<&Paint>) {
+        PictureRecorder::save_layer(self, bounds, paint);
+    }
+
+    fn restore(&mut self) {
+        PictureRecorder::restore(self);
+    }
+
+    fn get_save_count(&self) -> usize {
+        PictureRecorder::get_save_count(self)
+    }
+
+    fn restore_to_count(&mut self, count: usize) {
+        PictureRecorder::restore_to_count(self, count);
+    }
+
+    fn translate(&mut self, dx: f32, dy: f32) {
+        PictureRecorder::translate(self, dx, dy);
+    }
+
+    fn rotate_at_xy(&mut self, degree: f32, px: f32, py: f32) {
+        PictureRecorder::rotate_at_xy(self, degree, px, py);
+    }
+
+    fn rotate(&mut self, degree: f32) {
+        PictureRecorder::rotate(self, degree);
+    }
+
+    fn scale(&mut self, sx: f32, sy: f32) {
+        PictureRecorder::scale(self, sx, sy);
+    }
+
+    fn skew(&mut self, sx: f32, sy: f32) {
+        PictureRecorder::skew(self, sx, sy);
+    }
+
+    fn concat(&mut self, matrix: &Matrix) {
+        PictureRecorder::concat(self, matrix);
+    }
+
+    fn set_matrix(&mut self, matrix: &Matrix) {
+        PictureRecorder::set_matrix(self, matrix);
+    }
+
+    fn get_total_matrix(&self) -> Matrix {
+        PictureRecorder::get_total_matrix(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::StrokeJoin;
+
+    #[test]
+    fn save_layer_nests_draws() {
+        let mut recorder = PictureRecorder::new();
+
+        recorder.draw_rect(&Rect::from_xywh(0.0, 0.0, 10.0, 10.0), &Paint::new());
+        recorder.clip_rect(&Rect::from_xywh(0.0, 0.0, 50.0, 50.0), ClipOp::Intersect);
+        recorder.save_layer(Some(Rect::from_xywh(20.0, 20.0, 100.0, 100.0)), None);
+        recorder.draw_rect(&Rect::from_xywh(25.0, 25.0, 10.0, 10.0), &Paint::new());
+        recorder.save_layer(None, None);
+        recorder.draw_rect(&Rect::from_xywh(25.0, 25.0, 10.0, 10.0), &Paint::new());
+        // outside of the layer bounds
+        recorder.draw_rect(&Rect::from_xywh(0.0, 0.0, 10.0, 10.0), &Paint::new());
+
+        let picture = recorder.finish_record();
+
+        assert_eq!(picture.draws.len(), 3);
+
+        let layer = match &picture.draws[2].command {
+            DrawCommand::DrawLayer(layer) => layer,
+            _ => panic!("expect a layer"),
+        };
+
+        assert_eq!(picture.draws[2].depth, 2);
+        assert_eq!(layer.bounds, Some(Rect::from_ltrb(20.0, 20.0, 50.0, 50.0)));
+        assert_eq!(layer.draws.len(), 2);
+        assert_eq!(layer.depth_count, 2);
+        assert!(matches!(layer.draws[1].command, DrawCommand::DrawLayer(_)));
+    }
+
+    #[test]
+    fn picture_bounds() {
+        let recorder = PictureRecorder::new();
+        assert!(recorder.finish_record().bounds().is_empty());
+
+        let mut recorder = PictureRecorder::new();
+        let stroke = Stroke::new().with_width(4.0).with_join(StrokeJoin::Round);
+
+        recorder.translate(10.0, 10.0);
+        recorder.draw_rect(
+            &Rect::from_xywh(0.0, 0.0, 10.0, 10.0),
+            &Paint {
+                style: Style::Stroke(stroke),
+                ..Paint::new()
+            },
+        );
+
+        recorder.clip_rect(&Rect::from_xywh(0.0, 0.0, 30.0, 30.0), ClipOp::Intersect);
+        recorder.save_layer(
+            None,
+            Some(&Paint {
+                image_filter: Some(ImageFilter::offset(20.0, 0.0, None)),
+                ..Paint::new()
+            }),
+        );
+        recorder.draw_rect(&Rect::from_xywh(0.0, 0.0, 100.0, 100.0), &Paint::new());
+        recorder.restore();
+
+        let picture = recorder.finish_record();
+
+        // the stroke grows the rect by half the width, the layer content is cut by the clip and
+        // moved by the filter, then cut again by the clip of the layer
+        assert_eq!(picture.bounds(), Rect::from_ltrb(8.0, 8.0, 40.0, 40.0));
+        assert_eq!(picture.approximate_op_count(), 4);
+        assert!(picture.approximate_bytes_used() > std::mem::size_of::<Picture>());
+
+        let bounds = Rect::from_xywh(0.0, 0.0, 5.0, 5.0);
+        let mut recorder = PictureRecorder::new_with_bounds(&bounds);
+        recorder.draw_rect(&Rect::from_xywh(0.0, 0.0, 10.0, 10.0), &Paint::new());
+
+        assert_eq!(recorder.finish_record().bounds(), bounds);
+    }
+
+    #[test]
+    fn aliased_draws_share_mask() {
+        let mut target = RenderTarget {
+            width: 100.0,
+            height: 100.0,
+            scale_x: 1.0,
+            scale_y: 1.0,
+            format: wgpu::TextureFormat::Rgba8Unorm,
+            anti_alias: AntiAlias::Msaa(4),
+            color_space: crate::core::ColorSpace::Srgb,
+            text_gamma: [2.2, 0.0],
+            base_transform: Matrix4::identity(),
+        };
+        let aliased = Paint {
+            anti_alias: false,
+            ..Paint::new()
+        };
+
+        let mut recorder = PictureRecorder::new();
+        recorder.draw_rect(&Rect::from_xywh(10.2, 10.2, 20.0, 20.0), &aliased);
+        recorder.draw_circle(60.5, 20.5, 10.0, &aliased);
+        // overlaps the first rect
+        recorder.draw_rect(&Rect::from_xywh(25.0, 25.0, 10.0, 10.0), &aliased);
+        recorder.draw_rect(&Rect::from_xywh(0.0, 0.0, 5.0, 5.0), &Paint::new());
+        // outside of the target
+        recorder.draw_rect(&Rect::from_xywh(200.0, 0.0, 5.0, 5.0), &aliased);
+
+        let picture = recorder.finish_record();
+        let draws: Vec<&Draw> = picture.draws.iter().collect();
+
+        let (masks, aliased) = aliased_masks(&draws, &target);
+        let indices: Vec<Option<usize>> = aliased
+            .iter()
+            .map(|mask| mask.map(|(index, _)| index))
+            .collect();
+
+        assert_eq!(masks.len(), 2);
+        assert_eq!(indices, vec![Some(0), Some(0), Some(1), None, None]);
+        assert_eq!(
+            aliased[0].unwrap().1.pixel_bounds(&target),
+            Rect::from_ltrb(10.0, 10.0, 31.0, 31.0)
+        );
+
+        // the whole picture is generated with one coverage pass for each mask
+        assert_eq!(gen_renders(&picture.draws, &target, 0).len(), 7);
+
+        target.anti_alias = AntiAlias::None;
+        let (masks, _) = aliased_masks(&draws, &target);
+        assert!(masks.is_empty());
+    }
+
+    #[test]
+    fn fill_and_stroke_single_draw() {
+        let mut recorder = PictureRecorder::new();
+        let stroke = Stroke::new().with_width(6.0).with_join(StrokeJoin::Round);
+
+        recorder.draw_rect(
+            &Rect::from_xywh(10.0, 10.0, 20.0, 20.0),
+            &Paint {
+                style: Style::FillAndStroke(stroke),
+                ..Paint::new()
+            },
+        );
+
+        let picture = recorder.finish_record();
+
+        // the fill and the stroke are one draw, which grows by half the stroke width
+        assert_eq!(picture.draws.len(), 1);
+        assert!(matches!(
+            &picture.draws[0].command,
+            DrawCommand::DrawPath(_, paint) if paint.style == Style::FillAndStroke(stroke)
+        ));
+        assert_eq!(picture.draws[0].depth, 1);
+        assert_eq!(picture.bounds(), Rect::from_ltrb(7.0, 7.0, 33.0, 33.0));
+    }
+
+    #[test]
+    fn transform_stack() {
+        let mut recorder = PictureRecorder::new();
+        let point = Point::from(5.0, 5.0);
+
+        recorder.translate(10.0, 0.0);
+        recorder.save();
+        recorder.scale(-1.0, 2.0);
+        assert_eq!(
+            recorder.get_total_matrix().map_point(&point),
+            Point::from(5.0, 10.0)
+        );
+
+        let mut matrix = Matrix::new();
+        matrix.skew(1.0, 0.0);
+        recorder.set_matrix(&matrix);
+        assert_eq!(recorder.get_total_matrix(), matrix);
+
+        recorder.reset_matrix();
+        assert!(recorder.get_total_matrix().is_identity());
+
+        recorder.restore();
+        assert_eq!(
+            recorder.get_total_matrix().map_point(&point),
+            Point::from(15.0, 5.0)
+        );
+    }
+
+    #[test]
+    fn clip_bounds_queries() {
+        let mut recorder = PictureRecorder::new();
+        let rect = Rect::from_xywh(0.0, 0.0, 10.0, 10.0);
+
+        assert_eq!(recorder.get_device_clip_bounds(), None);
+        assert!(!recorder.quick_reject(&Rect::from_xywh(-100.0, 0.0, 1.0, 1.0)));
+
+        recorder.translate(10.0, 10.0);
+        let rrect = RRect::from_rect_xy(rect, 2.0, 2.0);
+        recorder.clip_rrect(&rrect, ClipOp::Intersect, false);
+        recorder.scale(2.0, 2.0);
+
+        assert_eq!(
+            recorder.get_device_clip_bounds(),
+            Some(Rect::from_xywh(10.0, 10.0, 10.0, 10.0))
+        );
+        assert_eq!(
+            recorder.get_local_clip_bounds(),
+            Some(Rect::from_xywh(0.0, 0.0, 5.0, 5.0))
+        );
+        assert!(recorder.quick_reject(&Rect::from_xywh(6.0, 0.0, 4.0, 4.0)));
+        assert!(!recorder.quick_reject(&Rect::from_xywh(4.0, 4.0, 4.0, 4.0)));
+
+        recorder.draw_rect(&Rect::from_xywh(6.0, 0.0, 4.0, 4.0), &Paint::new());
+        recorder.draw_rect(&Rect::from_xywh(4.0, 4.0, 4.0, 4.0), &Paint::new());
+
+        let picture = recorder.finish_record();
+
+        assert_eq!(picture.draws.len(), 2);
+        assert!(matches!(
+            picture.draws[0].command,
+            DrawCommand::ClipPath(_, ClipOp::Intersect, false)
+        ));
+    }
+
+    #[test]
+    fn expanding_clip_restored() {
+        let mut recorder = PictureRecorder::new();
+        let clip = Rect::from_xywh(0.0, 0.0, 10.0, 10.0);
+        let union = Rect::from_xywh(20.0, 0.0, 10.0, 10.0);
+
+        recorder.clip_rect(&clip, ClipOp::Intersect);
+        recorder.save();
+        recorder.clip_rect(&union, ClipOp::Union);
+
+        assert_eq!(
+            recorder.get_device_clip_bounds(),
+            Some(Rect::from_xywh(0.0, 0.0, 30.0, 10.0))
+        );
+        recorder.draw_rect(&Rect::from_xywh(20.0, 0.0, 5.0, 5.0), &Paint::new());
+
+        recorder.restore();
+        assert_eq!(recorder.get_device_clip_bounds(), Some(clip));
+        recorder.draw_rect(&Rect::from_xywh(0.0, 0.0, 5.0, 5.0), &Paint::new());
+
+        let picture = recorder.finish_record();
+        let depths: Vec<u32> = picture.draws.iter().map(|draw| draw.depth).collect();
+
+        // the intersect clip is recorded again after the union clip, the first one encloses it
+        assert!(matches!(
+            picture.draws[3].command,
+            DrawCommand::ClipPath(_, ClipOp::Intersect, true)
+        ));
+        assert_eq!(depths, vec![5, 2, 1, 4, 3]);
+        assert_eq!(
+            expanded_clips(&picture.draws),
+            vec![true, false, false, false, false]
+        );
+    }
+
+    #[test]
+    fn rect_clip_as_scissor() {
+        let rect = Rect::from_xywh(10.0, 20.0, 30.0, 40.0);
+
+        assert_eq!(Path::new().add_rect(&rect).as_rect(), Some(rect));
+        let rrect = RRect::from_rect_xy(rect, 2.0, 2.0);
+        assert_eq!(Path::new().add_rrect(&rrect).as_rect(), None);
+
+        let path = Path::new()
+            .move_to(10.0, 20.0)
+            .line_to(40.0, 20.0)
+            .line_to(40.0, 60.0)
+            .line_to(12.0, 60.0)
+            .close();
+        assert_eq!(path.as_rect(), None);
+
+        assert_eq!(
+            intersect_scissor(&[0, 0, 100, 100], &[50, 80, 100, 100]),
+            [50, 80, 50, 20]
+        );
+        assert_eq!(
+            intersect_scissor(&[0, 0, 10, 10], &[20, 20, 5, 5]),
+            [20, 20, 0, 0]
+        );
+    }
+
+    #[test]
+    fn strict_mode_diagnostics() {
+        let mut recorder = PictureRecorder::new().with_strict_mode(true);
+        let paint = Paint::new();
+
+        recorder.save();
+        recorder.translate(10.0, 0.0);
+        recorder.clip_rect(&Rect::from_xywh(0.0, 0.0, 8.0, 8.0), ClipOp::Intersect);
+        recorder.save_layer(None, None);
+        assert_eq!(recorder.get_save_count(), 2);
+
+        recorder.draw_rect(&Rect::from_xywh(1.0, 1.0, 4.0, 4.0), &paint);
+        recorder.draw_rect(&Rect::from_xywh(20.0, 1.0, 4.0, 4.0), &paint);
+        recorder.draw_rect(&Rect::from_xywh(f32::NAN, 1.0, 4.0, 4.0), &paint);
+        recorder.scale(f32::INFINITY, 1.0);
+
+        recorder.restore_to_count(0);
+        assert_eq!(recorder.get_save_count(), 0);
+        assert!(recorder.get_total_matrix().is_identity());
+
+        recorder.restore();
+        recorder.save();
+
+        let (picture, diagnostics) = recorder.finish_record_with_diagnostics();
+
+        assert_eq!(picture.approximate_op_count(), 3);
+        assert_eq!(
+            diagnostics,
+            vec![
+                Diagnostic::EmptyAfterClip("draw_path"),
+                Diagnostic::NonFiniteCoordinates("draw_path"),
+                Diagnostic::NonFiniteCoordinates("scale"),
+                Diagnostic::UnbalancedRestore,
+                Diagnostic::UnrestoredSaves(1),
+            ]
+        );
+    }
+
+    #[test]
+    fn draw_picture_replays_draws() {
+        let mut recorder = PictureRecorder::new();
+        recorder.clip_rect(&Rect::from_xywh(0.0, 0.0, 8.0, 8.0), ClipOp::Intersect);
+        recorder.draw_rect(&Rect::from_xywh(0.0, 0.0, 10.0, 10.0), &Paint::new());
+        let child = Rc::new(recorder.finish_record());
+
+        let mut recorder = PictureRecorder::new();
+        recorder.draw_rect(&Rect::from_xywh(0.0, 0.0, 10.0, 10.0), &Paint::new());
+        recorder.translate(100.0, 0.0);
+
+        let mut matrix = Matrix::new();
+        matrix.translate(0.0, 50.0);
+        recorder.draw_picture(&child, Some(&matrix), Some(&Paint::new()));
+
+        let mut paint = Paint::new();
+        paint.color = Color::from_rgba(0.0, 0.0, 0.0, 0.5).into();
+        recorder.draw_picture(&child, None, Some(&paint));
+        recorder.draw_rect(&Rect::from_xywh(0.0, 0.0, 10.0, 10.0), &Paint::new());
+
+        let picture = recorder.finish_record();
+
+        // an opaque paint replays the draws in place, the clip of child closes after its draw
+        assert_eq!(picture.draws.len(), 5);
+        assert_eq!(
+            picture.draws.iter().map(|d| d.depth).collect::<Vec<_>>(),
+            vec![1, 3, 2, 4, 5]
+        );
+        assert_eq!(
+            Matrix {
+                matrix: picture.draws[2].transform
+            }
+            .map_rect(&Rect::from_xywh(0.0, 0.0, 10.0, 10.0)),
+            Rect::from_xywh(100.0, 50.0, 10.0, 10.0)
+        );
+
+        // a translucent paint composites the picture through a layer
+        let layer = match &picture.draws[3].command {
+            DrawCommand::DrawLayer(layer) => layer,
+            _ => panic!("expect a layer"),
+        };
+        assert_eq!(layer.draws.len(), 2);
+        assert_eq!(layer.depth_count, 2);
+        assert_eq!(layer.bounds, Some(Rect::from_xywh(100.0, 0.0, 8.0, 8.0)));
+        assert_eq!(picture.bounds(), Rect::from_ltrb(0.0, 0.0, 110.0, 58.0));
+    }
+
+    #[test]
+    fn playback_records_same_picture() {
+        let mut recorder = PictureRecorder::new();
+        recorder.translate(10.0, 20.0);
+        recorder.save();
+        recorder.scale(2.0, 2.0);
+        recorder.clip_rect(&Rect::from_xywh(0.0, 0.0, 50.0, 50.0), ClipOp::Intersect);
+        recorder.draw_rect(&Rect::from_xywh(0.0, 0.0, 10.0, 10.0), &Paint::new());
+        recorder.clip_rect(&Rect::from_xywh(5.0, 5.0, 5.0, 5.0), ClipOp::Difference);
+        recorder.draw_oval(&Rect::from_xywh(0.0, 0.0, 10.0, 10.0), &Paint::new());
+        recorder.restore();
+        recorder.save_layer(
+            None,
+            Some(&Paint {
+                image_filter: Some(ImageFilter::offset(4.0, 0.0, None)),
+                ..Paint::new()
+            }),
+        );
+        recorder.clip_rect(&Rect::from_xywh(0.0, 0.0, 8.0, 8.0), ClipOp::Intersect);
+        recorder.draw_rect(&Rect::from_xywh(0.0, 0.0, 10.0, 10.0), &Paint::new());
+        recorder.restore();
+        recorder.draw_rect(&Rect::from_xywh(0.0, 0.0, 10.0, 10.0), &Paint::new());
+
+        let picture = recorder.finish_record();
+
+        let mut recorder = PictureRecorder::new();
+        picture.playback(&mut recorder);
+
+        assert_eq!(recorder.finish_record().serialize(), picture.serialize());
     }
 }
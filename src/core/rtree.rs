@@ -0,0 +1,179 @@
+use super::Rect;
+
+/// Max number of children of a node.
+const NODE_CAPACITY: usize = 8;
+
+enum Node {
+    Leaf { bounds: Rect, index: usize },
+    Branch { bounds: Rect, children: Vec<Node> },
+}
+
+impl Node {
+    fn bounds(&self) -> &Rect {
+        match self {
+            Node::Leaf { bounds, .. } | Node::Branch { bounds, .. } => bounds,
+        }
+    }
+
+    fn center(&self) -> (f32, f32) {
+        let bounds = self.bounds();
+
+        (
+            (bounds.left + bounds.right) * 0.5,
+            (bounds.top + bounds.bottom) * 0.5,
+        )
+    }
+
+    fn search(&self, query: &Rect, result: &mut Vec<usize>) {
+        if self.bounds().intersect(query).is_none() {
+            return;
+        }
+
+        match self {
+            Node::Leaf { index, .. } => result.push(*index),
+            Node::Branch { children, .. } => {
+                for child in children {
+                    child.search(query, result);
+                }
+            }
+        }
+    }
+}
+
+/// Bounding box hierarchy of the draws of a picture, bulk loaded with sort-tile-recursive packing.
+pub(crate) struct RTree {
+    root: Option<Node>,
+    /// indices of draws which are replayed whatever the query is
+    unbounded: Vec<usize>,
+    node_count: usize,
+}
+
+impl RTree {
+    /// Builds the tree of draws.
+    ///
+    /// # Arguments
+    ///
+    /// * `bounds` the device space bounds of each draw, `None` means the draw is not bounded.
+    ///   Draws with empty bounds are not visible and never returned by `search`.
+    pub(crate) fn new(bounds: &[Option<Rect>]) -> Self {
+        let mut unbounded = Vec::new();
+        let mut nodes = Vec::new();
+
+        for (index, bounds) in bounds.iter().enumerate() {
+            match bounds {
+                Some(bounds) if bounds.is_empty() => {}
+                Some(bounds) => nodes.push(Node::Leaf {
+                    bounds: *bounds,
+                    index,
+                }),
+                None => unbounded.push(index),
+            }
+        }
+
+        let mut node_count = nodes.len();
+
+        while nodes.len() > 1 {
+            nodes = pack(nodes);
+            node_count += nodes.len();
+        }
+
+        Self {
+            root: nodes.pop(),
+            unbounded,
+            node_count,
+        }
+    }
+
+    /// Returns the indices of draws which may be visible inside query, in drawing order.
+    ///
+    /// # Arguments
+    ///
+    /// * `query` device space rect to search
+    pub(crate) fn search(&self, query: &Rect) -> Vec<usize> {
+        let mut result = self.unbounded.clone();
+
+        if let Some(root) = &self.root {
+            root.search(query, &mut result);
+        }
+
+        result.sort_unstable();
+        result
+    }
+
+    /// Returns the approximate number of bytes used by the tree.
+    pub(crate) fn approximate_bytes_used(&self) -> usize {
+        self.node_count * std::mem::size_of::<Node>()
+            + self.unbounded.capacity() * std::mem::size_of::<usize>()
+    }
+}
+
+/// Packs nodes into parents of one level up. Nodes are sorted into vertical slices by their center,
+/// then each slice is sorted vertically and cut into parents, so siblings are close to each other.
+fn pack(mut nodes: Vec<Node>) -> Vec<Node> {
+    let parent_count = nodes.len().div_ceil(NODE_CAPACITY);
+    let slice_count = (parent_count as f32).sqrt().ceil() as usize;
+    let slice_size = slice_count * NODE_CAPACITY;
+
+    nodes.sort_by(|a, b| a.center().0.total_cmp(&b.center().0));
+
+    let mut parents = Vec::with_capacity(parent_count);
+    let mut nodes = nodes.into_iter().peekable();
+
+    while nodes.peek().is_some() {
+        let mut slice: Vec<Node> = nodes.by_ref().take(slice_size).collect();
+        slice.sort_by(|a, b| a.center().1.total_cmp(&b.center().1));
+
+        let mut slice = slice.into_iter().peekable();
+        while slice.peek().is_some() {
+            let children: Vec<Node> = slice.by_ref().take(NODE_CAPACITY).collect();
+            let bounds = children
+                .iter()
+                .skip(1)
+                .fold(*children[0].bounds(), |bounds, child| {
+                    bounds.union(child.bounds())
+                });
+
+            parents.push(Node::Branch { bounds, children });
+        }
+    }
+
+    parents
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn search_matches_brute_force() {
+        let bounds: Vec<Option<Rect>> = (0..500)
+            .map(|i| match i % 50 {
+                0 => None,
+                1 => Some(Rect::from_ltrb(0.0, 0.0, 0.0, 0.0)),
+                _ => {
+                    let x = (i * 37 % 101) as f32 * 10.0;
+                    let y = (i * 53 % 97) as f32 * 10.0;
+                    Some(Rect::from_xywh(x, y, (i % 7) as f32 * 5.0 + 1.0, 8.0))
+                }
+            })
+            .collect();
+
+        let tree = RTree::new(&bounds);
+
+        for query in [
+            Rect::from_xywh(0.0, 0.0, 100.0, 100.0),
+            Rect::from_xywh(250.0, 400.0, 300.0, 50.0),
+            Rect::from_xywh(-100.0, -100.0, 50.0, 50.0),
+            Rect::from_xywh(0.0, 0.0, 2000.0, 2000.0),
+        ] {
+            let expected: Vec<usize> = bounds
+                .iter()
+                .enumerate()
+                .filter(|(_, b)| b.map_or(true, |b| b.intersect(&query).is_some()))
+                .map(|(index, _)| index)
+                .collect();
+
+            assert_eq!(tree.search(&query), expected);
+        }
+    }
+}
@@ -0,0 +1,517 @@
+use std::{
+    fmt,
+    rc::Rc,
+    sync::atomic::{AtomicU32, Ordering},
+};
+
+use super::paint::ColorType;
+
+/// Name of the uniform block variable visible to the shader source.
+const UNIFORMS_NAME: &str = "uniforms";
+
+/// Name of the struct type of the uniform block.
+const UNIFORMS_TYPE_NAME: &str = "RuntimeUniforms";
+
+static NEXT_PROGRAM_ID: AtomicU32 = AtomicU32::new(0);
+
+/// Type of a field inside the uniform block of a `RuntimeShader`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UniformType {
+    /// `f32`
+    Float,
+    /// `vec2<f32>`
+    Vec2,
+    /// `vec3<f32>`
+    Vec3,
+    /// `vec4<f32>`
+    Vec4,
+    /// `mat4x4<f32>`, values are column major
+    Mat4,
+}
+
+impl UniformType {
+    fn wgsl(&self) -> &'static str {
+        match self {
+            UniformType::Float => "f32",
+            UniformType::Vec2 => "vec2<f32>",
+            UniformType::Vec3 => "vec3<f32>",
+            UniformType::Vec4 => "vec4<f32>",
+            UniformType::Mat4 => "mat4x4<f32>",
+        }
+    }
+
+    /// Returns the number of floats of the type.
+    pub fn components(&self) -> usize {
+        match self {
+            UniformType::Float => 1,
+            UniformType::Vec2 => 2,
+            UniformType::Vec3 => 3,
+            UniformType::Vec4 => 4,
+            UniformType::Mat4 => 16,
+        }
+    }
+}
+
+/// Errors reported when a `RuntimeShader` is created or its uniforms are set.
+#[derive(Debug, Clone, PartialEq)]
+pub enum RuntimeShaderError {
+    /// the source is not valid WGSL, holds the diagnostic of the parser
+    Parse(String),
+    /// the source fails validation, holds the diagnostic of the validator
+    Validation(String),
+    /// the source does not declare `fn main(coord: vec2<f32>) -> vec4<f32>`
+    MissingMain,
+    /// the source declares something a runtime shader can not use, such as entry points or bindings
+    Unsupported(String),
+    /// the uniform block has no field with the name
+    UnknownUniform(String),
+    /// the value does not have the component count of the uniform field
+    UniformMismatch {
+        name: String,
+        expected: usize,
+        actual: usize,
+    },
+}
+
+impl fmt::Display for RuntimeShaderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RuntimeShaderError::Parse(message) => write!(f, "parse error: {}", message),
+            RuntimeShaderError::Validation(message) => write!(f, "validation error: {}", message),
+            RuntimeShaderError::MissingMain => {
+                write!(f, "missing `fn main(coord: vec2<f32>) -> vec4<f32>`")
+            }
+            RuntimeShaderError::Unsupported(what) => write!(f, "unsupported declaration: {}", what),
+            RuntimeShaderError::UnknownUniform(name) => write!(f, "unknown uniform `{}`", name),
+            RuntimeShaderError::UniformMismatch {
+                name,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "uniform `{}` expects {} values, got {}",
+                name, expected, actual
+            ),
+        }
+    }
+}
+
+impl std::error::Error for RuntimeShaderError {}
+
+/// Field of the uniform block.
+#[derive(Debug)]
+struct UniformField {
+    name: String,
+    ty: UniformType,
+    /// offset in floats inside the uniform block
+    offset: usize,
+}
+
+/// Validated module of a runtime shader, shared by all clones.
+struct RuntimeProgram {
+    /// identifies the program in pipeline names
+    id: u32,
+    /// the user source, kept to write the shader into a serialized picture
+    source: String,
+    module: naga::Module,
+    info: naga::valid::ModuleInfo,
+    uniforms: Vec<UniformField>,
+    /// size in floats of the uniform block, aligned to 16 bytes
+    uniform_size: usize,
+}
+
+/// A user supplied WGSL shader used as a paint color.
+///
+/// The source implements `fn main(coord: vec2<f32>) -> vec4<f32>` which returns the unpremultiplied
+/// color at `coord` in the local space of the draw. Fields declared at creation are read from the
+/// `uniforms` variable, for example `uniforms.time`. The source is validated when the shader is created,
+/// it can declare helper functions, structs and constants but no entry points or bindings.
+///
+/// Example:
+///
+/// ```rust
+/// use mickey::*;
+///
+/// let shader = RuntimeShader::new(
+///     "fn main(coord: vec2<f32>) -> vec4<f32> {
+///         return vec4<f32>(fract(coord.x / uniforms.size), 0.0, 0.0, 1.0);
+///     }",
+///     &[("size", UniformType::Float)],
+/// )
+/// .unwrap();
+///
+/// let mut uniforms = shader.make_uniforms();
+/// uniforms.set_float("size", 16.0).unwrap();
+///
+/// let mut paint = Paint::new();
+/// paint.color = uniforms.into();
+/// ```
+#[derive(Clone)]
+pub struct RuntimeShader {
+    program: Rc<RuntimeProgram>,
+}
+
+impl fmt::Debug for RuntimeShader {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RuntimeShader")
+            .field("id", &self.program.id)
+            .field("uniforms", &self.program.uniforms)
+            .finish()
+    }
+}
+
+impl RuntimeShader {
+    /// Creates a runtime shader and validates its source.
+    ///
+    /// # Arguments
+    ///
+    /// * `source` WGSL implementing `fn main(coord: vec2<f32>) -> vec4<f32>`
+    /// * `uniforms` name and type of each field of the uniform block, in declaration order
+    pub fn new(source: &str, uniforms: &[(&str, UniformType)]) -> Result<Self, RuntimeShaderError> {
+        // declarations are order independent in WGSL, appending the uniform block keeps
+        // the line numbers of diagnostics matching the user source
+        let mut wgsl = source.to_string();
+
+        if !uniforms.is_empty() {
+            wgsl.push_str(&format!("\nstruct {} {{\n", UNIFORMS_TYPE_NAME));
+            for (name, ty) in uniforms {
+                wgsl.push_str(&format!("    {}: {},\n", name, ty.wgsl()));
+            }
+            wgsl.push_str(&format!(
+                "}};\n@group(0) @binding(0)\nvar<uniform> {}: {};\n",
+                UNIFORMS_NAME, UNIFORMS_TYPE_NAME
+            ));
+        }
+
+        let module = naga::front::wgsl::parse_str(&wgsl)
+            .map_err(|e| RuntimeShaderError::Parse(e.emit_to_string(&wgsl)))?;
+
+        let info = naga::valid::Validator::new(
+            naga::valid::ValidationFlags::all(),
+            naga::valid::Capabilities::empty(),
+        )
+        .validate(&module)
+        .map_err(|e| RuntimeShaderError::Validation(e.emit_to_string(&wgsl)))?;
+
+        Self::check_declarations(&module)?;
+
+        // the module is written back into the generated shader, make sure the backend accepts it
+        naga::back::wgsl::write_string(&module, &info, naga::back::wgsl::WriterFlags::empty())
+            .map_err(|e| RuntimeShaderError::Unsupported(e.to_string()))?;
+
+        let mut fields = Vec::new();
+        let mut uniform_size = 0;
+
+        let block = module
+            .types
+            .iter()
+            .find(|(_, ty)| ty.name.as_deref() == Some(UNIFORMS_TYPE_NAME));
+
+        if let Some((_, ty)) = block {
+            if let naga::TypeInner::Struct { members, span } = &ty.inner {
+                for ((name, ty), member) in uniforms.iter().zip(members.iter()) {
+                    fields.push(UniformField {
+                        name: name.to_string(),
+                        ty: *ty,
+                        offset: member.offset as usize / 4,
+                    });
+                }
+
+                uniform_size = (*span as usize).div_ceil(16) * 4;
+            }
+        }
+
+        Ok(Self {
+            program: Rc::new(RuntimeProgram {
+                id: NEXT_PROGRAM_ID.fetch_add(1, Ordering::Relaxed),
+                source: source.to_string(),
+                module,
+                info,
+                uniforms: fields,
+                uniform_size,
+            }),
+        })
+    }
+
+    /// Checks the module declares a valid `main` and nothing bound outside of the uniform block.
+    fn check_declarations(module: &naga::Module) -> Result<(), RuntimeShaderError> {
+        if let Some(entry) = module.entry_points.first() {
+            return Err(RuntimeShaderError::Unsupported(format!(
+                "entry point `{}`",
+                entry.name
+            )));
+        }
+
+        if let Some((_, o)) = module.overrides.iter().next() {
+            return Err(RuntimeShaderError::Unsupported(format!(
+                "override `{}`",
+                o.name.as_deref().unwrap_or_default()
+            )));
+        }
+
+        for (_, var) in module.global_variables.iter() {
+            if var.binding.is_some() && var.name.as_deref() != Some(UNIFORMS_NAME) {
+                return Err(RuntimeShaderError::Unsupported(format!(
+                    "binding `{}`",
+                    var.name.as_deref().unwrap_or_default()
+                )));
+            }
+        }
+
+        let is_vector = |ty: naga::Handle<naga::Type>, size: naga::VectorSize| {
+            module.types[ty].inner
+                == naga::TypeInner::Vector {
+                    size,
+                    scalar: naga::Scalar::F32,
+                }
+        };
+
+        let main = module
+            .functions
+            .iter()
+            .find(|(_, function)| function.name.as_deref() == Some("main"));
+
+        match main {
+            Some((_, main))
+                if main.arguments.len() == 1
+                    && is_vector(main.arguments[0].ty, naga::VectorSize::Bi)
+                    && main
+                        .result
+                        .as_ref()
+                        .is_some_and(|result| is_vector(result.ty, naga::VectorSize::Quad)) =>
+            {
+                Ok(())
+            }
+            _ => Err(RuntimeShaderError::MissingMain),
+        }
+    }
+
+    /// Returns uniforms of this shader with all fields set to zero.
+    pub fn make_uniforms(&self) -> RuntimeUniforms {
+        RuntimeUniforms {
+            shader: self.clone(),
+            data: vec![0.0; self.program.uniform_size],
+        }
+    }
+
+    /// Returns the id of the program, clones of a shader share the same id.
+    pub(crate) fn id(&self) -> u32 {
+        self.program.id
+    }
+
+    /// Returns the source the shader is created from.
+    pub(crate) fn source(&self) -> &str {
+        &self.program.source
+    }
+
+    /// Returns name and type of each field of the uniform block, in declaration order.
+    pub(crate) fn uniform_declarations(&self) -> Vec<(&str, UniformType)> {
+        self.program
+            .uniforms
+            .iter()
+            .map(|field| (field.name.as_str(), field.ty))
+            .collect()
+    }
+
+    /// Returns uniforms of this shader holding the uniform block `data`.
+    /// Returns `None` if `data` does not have the size of the uniform block.
+    pub(crate) fn make_uniforms_with_data(&self, data: Vec<f32>) -> Option<RuntimeUniforms> {
+        if data.len() != self.program.uniform_size {
+            return None;
+        }
+
+        Some(RuntimeUniforms {
+            shader: self.clone(),
+            data,
+        })
+    }
+
+    /// Returns the size in bytes of the uniform block, zero if the shader has no uniforms.
+    pub(crate) fn uniform_size(&self) -> u64 {
+        self.program.uniform_size as u64 * 4
+    }
+
+    /// Generates the WGSL of the shader to be embedded into another shader.
+    ///
+    /// # Arguments
+    ///
+    /// * `prefix` prepended to every declaration, `main` becomes `{prefix}main`
+    /// * `group` bind group of the uniform block
+    /// * `binding` binding of the uniform block
+    pub(crate) fn gen_source(&self, prefix: &str, group: u32, binding: u32) -> String {
+        let mut module = self.program.module.clone();
+
+        let rename = |name: &mut Option<String>| {
+            if let Some(name) = name {
+                *name = format!("{}{}", prefix, name);
+            }
+        };
+
+        for (_, function) in module.functions.iter_mut() {
+            rename(&mut function.name);
+        }
+
+        for (_, constant) in module.constants.iter_mut() {
+            rename(&mut constant.name);
+        }
+
+        for (_, var) in module.global_variables.iter_mut() {
+            rename(&mut var.name);
+
+            if var.binding.is_some() {
+                var.binding = Some(naga::ResourceBinding { group, binding });
+            }
+        }
+
+        // predeclared types are written by the backend itself and keep their names
+        let named: Vec<_> = module
+            .types
+            .iter()
+            .filter(|(handle, ty)| {
+                ty.name.is_some()
+                    && !module
+                        .special_types
+                        .predeclared_types
+                        .values()
+                        .any(|h| h == handle)
+            })
+            .map(|(handle, ty)| (handle, ty.clone()))
+            .collect();
+
+        for (handle, mut ty) in named {
+            rename(&mut ty.name);
+            module.types.replace(handle, ty);
+        }
+
+        naga::back::wgsl::write_string(
+            &module,
+            &self.program.info,
+            naga::back::wgsl::WriterFlags::empty(),
+        )
+        .expect("runtime shader is validated at creation")
+    }
+}
+
+/// Uniform values of a `RuntimeShader`, set per draw.
+#[derive(Debug, Clone)]
+pub struct RuntimeUniforms {
+    shader: RuntimeShader,
+    data: Vec<f32>,
+}
+
+impl RuntimeUniforms {
+    /// Sets the value of a uniform field.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` name of the field
+    /// * `value` floats of the value, the count must match the field type
+    pub fn set(&mut self, name: &str, value: &[f32]) -> Result<(), RuntimeShaderError> {
+        let field = self
+            .shader
+            .program
+            .uniforms
+            .iter()
+            .find(|field| field.name == name)
+            .ok_or_else(|| RuntimeShaderError::UnknownUniform(name.to_string()))?;
+
+        if field.ty.components() != value.len() {
+            return Err(RuntimeShaderError::UniformMismatch {
+                name: name.to_string(),
+                expected: field.ty.components(),
+                actual: value.len(),
+            });
+        }
+
+        self.data[field.offset..field.offset + value.len()].copy_from_slice(value);
+
+        Ok(())
+    }
+
+    /// Sets the value of a `UniformType::Float` field.
+    pub fn set_float(&mut self, name: &str, value: f32) -> Result<(), RuntimeShaderError> {
+        self.set(name, &[value])
+    }
+
+    pub fn shader(&self) -> &RuntimeShader {
+        &self.shader
+    }
+
+    /// Returns the uniform block laid out for the uniform address space.
+    pub(crate) fn data(&self) -> &[f32] {
+        &self.data
+    }
+}
+
+impl From<RuntimeUniforms> for ColorType {
+    fn from(uniforms: RuntimeUniforms) -> Self {
+        ColorType::RuntimeShader(uniforms)
+    }
+}
+
+impl From<RuntimeShader> for ColorType {
+    fn from(shader: RuntimeShader) -> Self {
+        ColorType::RuntimeShader(shader.make_uniforms())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn create_and_set_uniforms() {
+        let shader = RuntimeShader::new(
+            "fn main(coord: vec2<f32>) -> vec4<f32> {
+                return uniforms.color * uniforms.scale;
+            }",
+            &[("scale", UniformType::Float), ("color", UniformType::Vec4)],
+        )
+        .unwrap();
+
+        // vec4 is aligned to 16 bytes
+        assert_eq!(shader.uniform_size(), 32);
+
+        let mut uniforms = shader.make_uniforms();
+        uniforms.set_float("scale", 0.5).unwrap();
+        uniforms.set("color", &[1.0, 0.0, 0.0, 1.0]).unwrap();
+
+        assert_eq!(uniforms.data(), &[0.5, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 1.0]);
+        assert_eq!(
+            uniforms.set("color", &[1.0]),
+            Err(RuntimeShaderError::UniformMismatch {
+                name: "color".to_string(),
+                expected: 4,
+                actual: 1,
+            })
+        );
+        assert_eq!(
+            uniforms.set_float("time", 1.0),
+            Err(RuntimeShaderError::UnknownUniform("time".to_string()))
+        );
+
+        let source = shader.gen_source("runtime_2_", 1, 5);
+        assert!(source.contains("fn runtime_2_main("));
+        assert!(source.contains("@group(1) @binding(5)"));
+    }
+
+    #[test]
+    fn invalid_source() {
+        assert!(matches!(
+            RuntimeShader::new("fn main(coord: vec2<f32>) -> vec4<f32> {", &[]),
+            Err(RuntimeShaderError::Parse(_))
+        ));
+        assert_eq!(
+            RuntimeShader::new("fn main(coord: vec2<f32>) -> f32 { return 1.0; }", &[]).err(),
+            Some(RuntimeShaderError::MissingMain)
+        );
+        assert!(matches!(
+            RuntimeShader::new(
+                "@group(0) @binding(1) var<uniform> other: vec4<f32>;
+                fn main(coord: vec2<f32>) -> vec4<f32> { return other; }",
+                &[]
+            ),
+            Err(RuntimeShaderError::Unsupported(_))
+        ));
+    }
+}
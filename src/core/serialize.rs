@@ -0,0 +1,1496 @@
+use std::{fmt, rc::Rc};
+
+use nalgebra::Matrix4;
+
+use crate::text::{Font, FontDescription, FontStyle, TextBlob, TextRun};
+
+use super::{
+    image::ImageSource,
+    path::PathVerb,
+    picture::{Draw, DrawCommand, Layer},
+    Bitmap, BlendMode, BlurStyle, ClipOp, Color, ColorFilter, ColorType, GradientInterpolation,
+    HueInterpolation, Image, ImageFilter, ImageFormat, ImageInfo, LinearGradient, MaskFilter,
+    Matrix, Paint, Path, PathFillType, Picture, Point, RadialGradient, Rect, RuntimeShader,
+    RuntimeShaderError, Stroke, StrokeCap, StrokeJoin, Style, TileMode, UniformType,
+};
+
+/// Identifies the data of a serialized picture.
+const MAGIC: [u8; 4] = *b"MKPC";
+
+/// Version of the format written by `Picture::serialize`.
+/// Data written by a newer version is rejected.
+const VERSION: u32 = 1;
+
+/// Max nesting of color trees, filters and layers accepted when reading.
+/// Protects the stack from corrupt data.
+const MAX_NESTING: u32 = 64;
+
+// Enums are written as their index in these tables, new values must be appended.
+
+const BLEND_MODES: [BlendMode; 15] = [
+    BlendMode::Clear,
+    BlendMode::Src,
+    BlendMode::Dst,
+    BlendMode::SrcOver,
+    BlendMode::DstOver,
+    BlendMode::SrcIn,
+    BlendMode::DstIn,
+    BlendMode::SrcOut,
+    BlendMode::DstOut,
+    BlendMode::SrcATop,
+    BlendMode::DstATop,
+    BlendMode::Xor,
+    BlendMode::Plus,
+    BlendMode::Modulate,
+    BlendMode::Screen,
+];
+
+const TILE_MODES: [TileMode; 4] = [
+    TileMode::Clamp,
+    TileMode::Repeat,
+    TileMode::Mirror,
+    TileMode::Decal,
+];
+
+const INTERPOLATIONS: [GradientInterpolation; 8] = [
+    GradientInterpolation::UnpremulSrgb,
+    GradientInterpolation::PremulSrgb,
+    GradientInterpolation::LinearSrgb,
+    GradientInterpolation::Oklab,
+    GradientInterpolation::Oklch(HueInterpolation::Shorter),
+    GradientInterpolation::Oklch(HueInterpolation::Longer),
+    GradientInterpolation::Oklch(HueInterpolation::Increasing),
+    GradientInterpolation::Oklch(HueInterpolation::Decreasing),
+];
+
+const STROKE_CAPS: [StrokeCap; 3] = [StrokeCap::Butt, StrokeCap::Round, StrokeCap::Square];
+
+const STROKE_JOINS: [StrokeJoin; 3] = [StrokeJoin::Miter, StrokeJoin::Round, StrokeJoin::Bevel];
+
+const FILL_TYPES: [PathFillType; 2] = [PathFillType::Winding, PathFillType::EvenOdd];
+
+const IMAGE_FORMATS: [ImageFormat; 3] = [
+    ImageFormat::RGBA8888,
+    ImageFormat::BGRA8888,
+    ImageFormat::RGBX8888,
+];
+
+const BLUR_STYLES: [BlurStyle; 4] = [
+    BlurStyle::Normal,
+    BlurStyle::Solid,
+    BlurStyle::Outer,
+    BlurStyle::Inner,
+];
+
+const UNIFORM_TYPES: [UniformType; 5] = [
+    UniformType::Float,
+    UniformType::Vec2,
+    UniformType::Vec3,
+    UniformType::Vec4,
+    UniformType::Mat4,
+];
+
+const CLIP_OPS: [ClipOp; 6] = [
+    ClipOp::Intersect,
+    ClipOp::Difference,
+    ClipOp::Union,
+    ClipOp::Xor,
+    ClipOp::ReverseDifference,
+    ClipOp::Replace,
+];
+
+/// Errors reported when a serialized picture can not be read.
+#[derive(Debug, Clone, PartialEq)]
+pub enum DeserializeError {
+    /// the data does not start with the magic of a serialized picture
+    InvalidMagic,
+    /// the data is written by a newer version of the format, holds the version
+    UnsupportedVersion(u32),
+    /// the data ends in the middle of a value
+    Truncated,
+    /// the data holds an invalid value, holds a description of it
+    Corrupt(String),
+    /// the resolver has no font matching the description
+    MissingFont(FontDescription),
+    /// the resolver has no image for the external reference with the index
+    MissingImage(u32),
+    /// the source of a runtime shader fails to compile
+    RuntimeShader(RuntimeShaderError),
+}
+
+impl fmt::Display for DeserializeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DeserializeError::InvalidMagic => write!(f, "data is not a serialized picture"),
+            DeserializeError::UnsupportedVersion(version) => {
+                write!(f, "unsupported picture version {}", version)
+            }
+            DeserializeError::Truncated => write!(f, "data is truncated"),
+            DeserializeError::Corrupt(what) => write!(f, "data is corrupt: {}", what),
+            DeserializeError::MissingFont(description) => {
+                write!(f, "no font matches `{}`", description.name)
+            }
+            DeserializeError::MissingImage(index) => write!(f, "no image for reference {}", index),
+            DeserializeError::RuntimeShader(e) => write!(f, "runtime shader error: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for DeserializeError {}
+
+/// Provides the resources a serialized picture refers to instead of embedding them.
+///
+/// Text blobs only hold the description of their fonts, the font is looked up when the picture is read.
+/// Images backed by a `wgpu::Texture` have no pixels on the CPU, they are written as external references
+/// numbered in the order they first appear in the picture.
+///
+/// Closures taking a `&FontDescription` are resolvers without external images.
+pub trait FontResolver {
+    /// Returns the font matching `description`.
+    fn resolve_font(&self, description: &FontDescription) -> Option<Rc<Font>>;
+
+    /// Returns the image of an external reference, images are not resolved by default.
+    ///
+    /// # Arguments
+    ///
+    /// * `index` the index of the reference
+    /// * `info` the info of the image when it was written
+    fn resolve_image(&self, _index: u32, _info: &ImageInfo) -> Option<Image> {
+        None
+    }
+}
+
+impl<F> FontResolver for F
+where
+    F: Fn(&FontDescription) -> Option<Rc<Font>>,
+{
+    fn resolve_font(&self, description: &FontDescription) -> Option<Rc<Font>> {
+        self(description)
+    }
+}
+
+impl Picture {
+    /// Writes the picture into a versioned binary format, which can be read back by `Picture::deserialize`.
+    /// Bitmaps are embedded, texture images and fonts are written as references. Resources shared by
+    /// several draws are written once.
+    pub fn serialize(&self) -> Vec<u8> {
+        let mut writer = Writer::default();
+
+        writer.bytes.extend_from_slice(&MAGIC);
+        writer.u32(VERSION);
+        writer.rect(&self.cull_rect);
+        writer.draws(&self.draws);
+
+        writer.bytes
+    }
+
+    /// Reads a picture written by `Picture::serialize`.
+    ///
+    /// # Arguments
+    ///
+    /// * `bytes` the serialized picture
+    /// * `resolver` provides the fonts of text blobs and the images of external references
+    pub fn deserialize(
+        bytes: &[u8],
+        resolver: &dyn FontResolver,
+    ) -> Result<Picture, DeserializeError> {
+        let mut reader = Reader::new(bytes, resolver);
+
+        if reader.take(MAGIC.len())? != MAGIC {
+            return Err(DeserializeError::InvalidMagic);
+        }
+
+        let version = reader.u32()?;
+        if version == 0 || version > VERSION {
+            return Err(DeserializeError::UnsupportedVersion(version));
+        }
+
+        let cull_rect = reader.rect()?;
+        let draws = reader.draws()?;
+
+        let depth_count = draws.iter().map(|draw| draw.depth).max().unwrap_or(0);
+        check_depths(&draws, depth_count)?;
+
+        if reader.pos != bytes.len() {
+            return Err(corrupt("trailing bytes after the picture"));
+        }
+
+        Ok(Picture::new(draws, Some(cull_rect)))
+    }
+}
+
+fn corrupt(what: &str) -> DeserializeError {
+    DeserializeError::Corrupt(what.to_string())
+}
+
+/// Checks the depths of draws are the ones a recorder assigns, so they can not overflow when replayed.
+/// Every draw uses one depth of `1..=depth_count`, the max depth is `depth_count`, and draws other than
+/// clips, which get their depth when restored, are in increasing depth.
+///
+/// # Arguments
+///
+/// * `draws` the draws of a picture or layer
+/// * `depth_count` the number of depth values used by the draws
+fn check_depths(draws: &[Draw], depth_count: u32) -> Result<(), DeserializeError> {
+    if depth_count as usize > draws.len() {
+        return Err(corrupt("depth count exceeds the number of draws"));
+    }
+
+    let mut last = 0;
+
+    for draw in draws {
+        if draw.depth == 0 || draw.depth > depth_count {
+            return Err(corrupt("draw depth is out of range"));
+        }
+
+        if !matches!(draw.command, DrawCommand::ClipPath(..)) {
+            if draw.depth <= last {
+                return Err(corrupt("draw depth goes backwards"));
+            }
+
+            last = draw.depth;
+        }
+    }
+
+    if draws.iter().map(|draw| draw.depth).max().unwrap_or(0) != depth_count {
+        return Err(corrupt("depth count does not match the draws"));
+    }
+
+    Ok(())
+}
+
+/// Returns the index of `item` in `pool` and whether it is seen for the first time.
+/// New items are appended, so readers assign the same index when they meet the item.
+fn intern<T: Clone>(pool: &mut Vec<T>, item: &T, same: impl Fn(&T, &T) -> bool) -> (u32, bool) {
+    match pool.iter().position(|other| same(other, item)) {
+        Some(index) => (index as u32, false),
+        None => {
+            pool.push(item.clone());
+            (pool.len() as u32 - 1, true)
+        }
+    }
+}
+
+/// Little endian writer, shared resources are written after the index where they first appear.
+#[derive(Default)]
+struct Writer {
+    bytes: Vec<u8>,
+    bitmaps: Vec<Rc<Bitmap>>,
+    textures: Vec<Rc<wgpu::Texture>>,
+    fonts: Vec<Rc<Font>>,
+    blobs: Vec<Rc<TextBlob>>,
+    shaders: Vec<RuntimeShader>,
+}
+
+impl Writer {
+    fn u8(&mut self, value: u8) {
+        self.bytes.push(value);
+    }
+
+    fn bool(&mut self, value: bool) {
+        self.u8(value as u8);
+    }
+
+    fn u16(&mut self, value: u16) {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn u32(&mut self, value: u32) {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn i32(&mut self, value: i32) {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn f32(&mut self, value: f32) {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn len(&mut self, len: usize) {
+        self.u32(len as u32);
+    }
+
+    fn f32s(&mut self, values: &[f32]) {
+        self.len(values.len());
+        for value in values {
+            self.f32(*value);
+        }
+    }
+
+    fn str(&mut self, value: &str) {
+        self.len(value.len());
+        self.bytes.extend_from_slice(value.as_bytes());
+    }
+
+    fn enum_value<T: PartialEq>(&mut self, values: &[T], value: &T) {
+        let index = values.iter().position(|v| v == value);
+        self.u8(index.expect("enum value is listed in its table") as u8);
+    }
+
+    fn point(&mut self, point: &Point) {
+        self.f32(point.x);
+        self.f32(point.y);
+    }
+
+    fn rect(&mut self, rect: &Rect) {
+        self.f32(rect.left);
+        self.f32(rect.top);
+        self.f32(rect.right);
+        self.f32(rect.bottom);
+    }
+
+    fn color(&mut self, color: &Color) {
+        self.f32(color.r);
+        self.f32(color.g);
+        self.f32(color.b);
+        self.f32(color.a);
+    }
+
+    fn matrix4(&mut self, matrix: &Matrix4<f32>) {
+        for value in matrix.as_slice() {
+            self.f32(*value);
+        }
+    }
+
+    fn draws(&mut self, draws: &[Draw]) {
+        self.len(draws.len());
+        for draw in draws {
+            self.u32(draw.depth);
+            self.matrix4(&draw.transform);
+            self.command(&draw.command);
+        }
+    }
+
+    fn command(&mut self, command: &DrawCommand) {
+        match command {
+            DrawCommand::DrawPath(path, paint) => {
+                self.u8(0);
+                self.path(path);
+                self.paint(paint);
+            }
+            DrawCommand::ClipPath(path, op, anti_alias) => {
+                self.u8(1);
+                self.path(path);
+                self.enum_value(&CLIP_OPS, op);
+                self.bool(*anti_alias);
+            }
+            DrawCommand::DrawImage(image, rect, matrix, paint) => {
+                self.u8(2);
+                self.image(image);
+                self.rect(rect);
+                self.matrix4(matrix);
+                self.paint(paint);
+            }
+            DrawCommand::DrawText(blob, pos, paint) => {
+                self.u8(3);
+                self.text_blob(blob);
+                self.point(pos);
+                self.paint(paint);
+            }
+            DrawCommand::DrawLayer(layer) => {
+                self.u8(4);
+                self.option(layer.bounds.as_ref(), Self::rect);
+                self.paint(&layer.paint);
+                self.u32(layer.depth_count);
+                self.draws(&layer.draws);
+            }
+        }
+    }
+
+    fn option<T: ?Sized>(&mut self, value: Option<&T>, write: impl FnOnce(&mut Self, &T)) {
+        self.bool(value.is_some());
+        if let Some(value) = value {
+            write(self, value);
+        }
+    }
+
+    fn path(&mut self, path: &Path) {
+        self.enum_value(&FILL_TYPES, &path.fill_type);
+        self.len(path.verts.len());
+        for verb in &path.verts {
+            match verb {
+                PathVerb::MoveTo(p) => {
+                    self.u8(0);
+                    self.point(p);
+                }
+                PathVerb::LineTo(p) => {
+                    self.u8(1);
+                    self.point(p);
+                }
+                PathVerb::QuadTo(p1, p2) => {
+                    self.u8(2);
+                    self.point(p1);
+                    self.point(p2);
+                }
+                PathVerb::ConicTo(p1, p2, w) => {
+                    self.u8(3);
+                    self.point(p1);
+                    self.point(p2);
+                    self.f32(*w);
+                }
+                PathVerb::CubicTo(p1, p2, p3) => {
+                    self.u8(4);
+                    self.point(p1);
+                    self.point(p2);
+                    self.point(p3);
+                }
+                PathVerb::Close => self.u8(5),
+            }
+        }
+    }
+
+    fn paint(&mut self, paint: &Paint) {
+        self.color_type(&paint.color);
+        self.style(&paint.style);
+        self.enum_value(&BLEND_MODES, &paint.blend_mode);
+        self.option(paint.color_filter.as_ref(), Self::color_filter);
+        self.option(paint.mask_filter.as_ref(), |w, filter| match filter {
+            MaskFilter::Blur { style, sigma } => {
+                w.enum_value(&BLUR_STYLES, style);
+                w.f32(*sigma);
+            }
+        });
+        self.option(paint.image_filter.as_ref(), Self::image_filter);
+        self.bool(paint.dither);
+        self.bool(paint.anti_alias);
+        self.bool(paint.pixel_snap);
+    }
+
+    fn style(&mut self, style: &Style) {
+        let stroke = match style {
+            Style::Fill => {
+                self.u8(0);
+                return;
+            }
+            Style::Stroke(stroke) => {
+                self.u8(1);
+                stroke
+            }
+            Style::FillAndStroke(stroke) => {
+                self.u8(2);
+                stroke
+            }
+        };
+
+        self.f32(stroke.width);
+        self.f32(stroke.miter_limit);
+        self.enum_value(&STROKE_CAPS, &stroke.cap);
+        self.enum_value(&STROKE_JOINS, &stroke.join);
+    }
+
+    fn color_type(&mut self, color: &ColorType) {
+        match color {
+            ColorType::SolidColor(color) => {
+                self.u8(0);
+                self.color(color);
+            }
+            ColorType::LinearGradient(gradient) => {
+                self.u8(1);
+                self.matrix4(&gradient.matrix.matrix);
+                self.gradient_colors(&gradient.colors, &gradient.stops);
+                self.point(&gradient.p1);
+                self.point(&gradient.p2);
+                self.enum_value(&TILE_MODES, &gradient.tile_mode);
+                self.enum_value(&INTERPOLATIONS, &gradient.interpolation);
+            }
+            ColorType::RadialGradient(gradient) => {
+                self.u8(2);
+                self.matrix4(&gradient.matrix.matrix);
+                self.gradient_colors(&gradient.colors, &gradient.stops);
+                self.point(&gradient.center);
+                self.f32(gradient.radius);
+                self.enum_value(&TILE_MODES, &gradient.tile_mode);
+                self.enum_value(&INTERPOLATIONS, &gradient.interpolation);
+            }
+            ColorType::Image(image) => {
+                self.u8(3);
+                self.image(image);
+            }
+            ColorType::Blend(mode, src, dst) => {
+                self.u8(4);
+                self.enum_value(&BLEND_MODES, mode);
+                self.color_type(src);
+                self.color_type(dst);
+            }
+            ColorType::WithLocalMatrix(matrix, color) => {
+                self.u8(5);
+                self.matrix4(&matrix.matrix);
+                self.color_type(color);
+            }
+            ColorType::WithColorFilter(filter, color) => {
+                self.u8(6);
+                self.color_filter(filter);
+                self.color_type(color);
+            }
+            ColorType::RuntimeShader(uniforms) => {
+                self.u8(7);
+                self.runtime_shader(uniforms.shader());
+                self.f32s(uniforms.data());
+            }
+        }
+    }
+
+    fn gradient_colors(&mut self, colors: &[Color], stops: &[f32]) {
+        self.len(colors.len());
+        for color in colors {
+            self.color(color);
+        }
+        self.f32s(stops);
+    }
+
+    fn runtime_shader(&mut self, shader: &RuntimeShader) {
+        let (index, new) = intern(&mut self.shaders, shader, |a, b| a.id() == b.id());
+        self.u32(index);
+
+        if new {
+            self.str(shader.source());
+
+            let uniforms = shader.uniform_declarations();
+            self.len(uniforms.len());
+            for (name, ty) in uniforms {
+                self.str(name);
+                self.enum_value(&UNIFORM_TYPES, &ty);
+            }
+        }
+    }
+
+    fn color_filter(&mut self, filter: &ColorFilter) {
+        match filter {
+            ColorFilter::Matrix(matrix) => {
+                self.u8(0);
+                for value in matrix {
+                    self.f32(*value);
+                }
+            }
+            ColorFilter::Blend(color, mode) => {
+                self.u8(1);
+                self.color(color);
+                self.enum_value(&BLEND_MODES, mode);
+            }
+            ColorFilter::LumaToAlpha => self.u8(2),
+            ColorFilter::LinearToSrgb => self.u8(3),
+            ColorFilter::SrgbToLinear => self.u8(4),
+            ColorFilter::Compose(outer, inner) => {
+                self.u8(5);
+                self.color_filter(outer);
+                self.color_filter(inner);
+            }
+        }
+    }
+
+    fn image_filter(&mut self, filter: &ImageFilter) {
+        match filter {
+            ImageFilter::Blur {
+                sigma_x,
+                sigma_y,
+                input,
+            } => {
+                self.u8(0);
+                self.f32(*sigma_x);
+                self.f32(*sigma_y);
+                self.option(input.as_deref(), Self::image_filter);
+            }
+            ImageFilter::DropShadow {
+                dx,
+                dy,
+                sigma_x,
+                sigma_y,
+                color,
+                input,
+            }
+            | ImageFilter::DropShadowOnly {
+                dx,
+                dy,
+                sigma_x,
+                sigma_y,
+                color,
+                input,
+            } => {
+                let tag = if matches!(filter, ImageFilter::DropShadow { .. }) {
+                    1
+                } else {
+                    2
+                };
+                self.u8(tag);
+                self.f32(*dx);
+                self.f32(*dy);
+                self.f32(*sigma_x);
+                self.f32(*sigma_y);
+                self.color(color);
+                self.option(input.as_deref(), Self::image_filter);
+            }
+            ImageFilter::Offset { dx, dy, input } => {
+                self.u8(3);
+                self.f32(*dx);
+                self.f32(*dy);
+                self.option(input.as_deref(), Self::image_filter);
+            }
+            ImageFilter::ColorFilter { filter, input } => {
+                self.u8(4);
+                self.color_filter(filter);
+                self.option(input.as_deref(), Self::image_filter);
+            }
+            ImageFilter::Merge(inputs) => {
+                self.u8(5);
+                self.len(inputs.len());
+                for input in inputs {
+                    self.option(input.as_ref(), Self::image_filter);
+                }
+            }
+            ImageFilter::Compose(outer, inner) => {
+                self.u8(6);
+                self.image_filter(outer);
+                self.image_filter(inner);
+            }
+        }
+    }
+
+    fn image_info(&mut self, info: &ImageInfo) {
+        self.u32(info.width);
+        self.u32(info.height);
+        self.enum_value(&IMAGE_FORMATS, &info.format);
+        self.bool(info.premultiplied);
+    }
+
+    fn image(&mut self, image: &Image) {
+        match &image.source {
+            ImageSource::Bitmap(bitmap) => {
+                self.u8(0);
+
+                let (index, new) = intern(&mut self.bitmaps, bitmap, Rc::ptr_eq);
+                self.u32(index);
+
+                if new {
+                    self.image_info(&bitmap.info);
+                    self.u32(bitmap.bytes_per_row);
+                    self.len(bitmap.data.len());
+                    self.bytes.extend_from_slice(&bitmap.data);
+                }
+            }
+            ImageSource::Texture(texture, info) => {
+                self.u8(1);
+
+                let (index, new) = intern(&mut self.textures, texture, Rc::ptr_eq);
+                self.u32(index);
+
+                if new {
+                    self.image_info(info);
+                }
+            }
+        }
+    }
+
+    fn text_blob(&mut self, blob: &Rc<TextBlob>) {
+        let (index, new) = intern(&mut self.blobs, blob, Rc::ptr_eq);
+        self.u32(index);
+
+        if !new {
+            return;
+        }
+
+        self.f32(blob.width);
+        self.f32(blob.height);
+        self.f32(blob.ascent);
+        self.f32(blob.descent);
+        self.f32(blob.line_gap);
+
+        self.len(blob.runs.len());
+        for run in &blob.runs {
+            self.font(&run.font);
+            self.f32(run.px_size);
+
+            self.len(run.glyphs.len());
+            for glyph in &run.glyphs {
+                self.u16(glyph.id.0);
+                self.f32(glyph.scale.x);
+                self.f32(glyph.scale.y);
+                self.f32(glyph.position.x);
+                self.f32(glyph.position.y);
+            }
+        }
+    }
+
+    fn font(&mut self, font: &Rc<Font>) {
+        let (index, new) = intern(&mut self.fonts, font, Rc::ptr_eq);
+        self.u32(index);
+
+        if new {
+            let description = &font.description;
+            self.str(&description.name);
+            self.str(&description.family);
+            self.i32(description.style.weight);
+            self.bool(description.style.italic);
+            self.i32(description.style.stretch);
+        }
+    }
+}
+
+/// Reads the data written by `Writer`, every read checks the bounds of the data.
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+    resolver: &'a dyn FontResolver,
+    /// nesting of the value being read
+    nesting: u32,
+    bitmaps: Vec<Rc<Bitmap>>,
+    textures: Vec<Image>,
+    fonts: Vec<Rc<Font>>,
+    blobs: Vec<Rc<TextBlob>>,
+    shaders: Vec<RuntimeShader>,
+}
+
+impl<'a> Reader<'a> {
+    fn new(bytes: &'a [u8], resolver: &'a dyn FontResolver) -> Self {
+        Self {
+            bytes,
+            pos: 0,
+            resolver,
+            nesting: 0,
+            bitmaps: Vec::new(),
+            textures: Vec::new(),
+            fonts: Vec::new(),
+            blobs: Vec::new(),
+            shaders: Vec::new(),
+        }
+    }
+
+    fn take(&mut self, count: usize) -> Result<&'a [u8], DeserializeError> {
+        if self.bytes.len() - self.pos < count {
+            return Err(DeserializeError::Truncated);
+        }
+
+        let bytes = &self.bytes[self.pos..self.pos + count];
+        self.pos += count;
+
+        Ok(bytes)
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N], DeserializeError> {
+        Ok(self.take(N)?.try_into().expect("slice has N bytes"))
+    }
+
+    fn u8(&mut self) -> Result<u8, DeserializeError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn bool(&mut self) -> Result<bool, DeserializeError> {
+        match self.u8()? {
+            0 => Ok(false),
+            1 => Ok(true),
+            _ => Err(corrupt("invalid bool")),
+        }
+    }
+
+    fn u16(&mut self) -> Result<u16, DeserializeError> {
+        Ok(u16::from_le_bytes(self.array()?))
+    }
+
+    fn u32(&mut self) -> Result<u32, DeserializeError> {
+        Ok(u32::from_le_bytes(self.array()?))
+    }
+
+    fn i32(&mut self) -> Result<i32, DeserializeError> {
+        Ok(i32::from_le_bytes(self.array()?))
+    }
+
+    fn f32(&mut self) -> Result<f32, DeserializeError> {
+        Ok(f32::from_le_bytes(self.array()?))
+    }
+
+    /// Reads the length of a list whose elements take at least `element_size` bytes.
+    /// Lengths which can not fit in the remaining data are rejected before anything is allocated.
+    fn len(&mut self, element_size: usize) -> Result<usize, DeserializeError> {
+        let len = self.u32()? as usize;
+
+        if len.saturating_mul(element_size) > self.bytes.len() - self.pos {
+            return Err(DeserializeError::Truncated);
+        }
+
+        Ok(len)
+    }
+
+    fn f32s(&mut self) -> Result<Vec<f32>, DeserializeError> {
+        let len = self.len(4)?;
+        (0..len).map(|_| self.f32()).collect()
+    }
+
+    fn string(&mut self) -> Result<String, DeserializeError> {
+        let len = self.len(1)?;
+        let bytes = self.take(len)?;
+
+        String::from_utf8(bytes.to_vec()).map_err(|_| corrupt("invalid utf-8 string"))
+    }
+
+    fn enum_value<T: Copy>(&mut self, values: &[T], what: &str) -> Result<T, DeserializeError> {
+        let tag = self.u8()?;
+
+        values
+            .get(tag as usize)
+            .copied()
+            .ok_or_else(|| DeserializeError::Corrupt(format!("unknown {} {}", what, tag)))
+    }
+
+    fn tag_error(what: &str, tag: u8) -> DeserializeError {
+        DeserializeError::Corrupt(format!("unknown {} tag {}", what, tag))
+    }
+
+    /// Reads a value which may contain values of the same type.
+    fn nested<T>(
+        &mut self,
+        read: impl FnOnce(&mut Self) -> Result<T, DeserializeError>,
+    ) -> Result<T, DeserializeError> {
+        if self.nesting >= MAX_NESTING {
+            return Err(corrupt("values are nested too deep"));
+        }
+
+        self.nesting += 1;
+        let value = read(self);
+        self.nesting -= 1;
+
+        value
+    }
+
+    /// Reads the index of a shared resource, followed by its content the first time it appears.
+    fn shared<T: Clone>(
+        &mut self,
+        pool: fn(&mut Self) -> &mut Vec<T>,
+        read: impl FnOnce(&mut Self, u32) -> Result<T, DeserializeError>,
+    ) -> Result<T, DeserializeError> {
+        let index = self.u32()?;
+        let count = pool(self).len() as u32;
+
+        if index < count {
+            return Ok(pool(self)[index as usize].clone());
+        }
+
+        if index > count {
+            return Err(corrupt("reference to a resource not defined yet"));
+        }
+
+        let value = read(self, index)?;
+        pool(self).push(value.clone());
+
+        Ok(value)
+    }
+
+    fn option<T>(
+        &mut self,
+        read: impl FnOnce(&mut Self) -> Result<T, DeserializeError>,
+    ) -> Result<Option<T>, DeserializeError> {
+        if self.bool()? {
+            read(self).map(Some)
+        } else {
+            Ok(None)
+        }
+    }
+
+    fn point(&mut self) -> Result<Point, DeserializeError> {
+        Ok(Point::from(self.f32()?, self.f32()?))
+    }
+
+    fn rect(&mut self) -> Result<Rect, DeserializeError> {
+        Ok(Rect::from_ltrb(
+            self.f32()?,
+            self.f32()?,
+            self.f32()?,
+            self.f32()?,
+        ))
+    }
+
+    fn color(&mut self) -> Result<Color, DeserializeError> {
+        Ok(Color::from_rgba(
+            self.f32()?,
+            self.f32()?,
+            self.f32()?,
+            self.f32()?,
+        ))
+    }
+
+    fn matrix4(&mut self) -> Result<Matrix4<f32>, DeserializeError> {
+        let mut values = [0.0; 16];
+        for value in &mut values {
+            *value = self.f32()?;
+        }
+
+        Ok(Matrix4::from_column_slice(&values))
+    }
+
+    fn draws(&mut self) -> Result<Vec<Draw>, DeserializeError> {
+        // depth, transform and command tag
+        let len = self.len(69)?;
+
+        (0..len)
+            .map(|_| {
+                Ok(Draw {
+                    depth: self.u32()?,
+                    transform: self.matrix4()?,
+                    command: self.command()?,
+                })
+            })
+            .collect()
+    }
+
+    fn command(&mut self) -> Result<DrawCommand, DeserializeError> {
+        match self.u8()? {
+            0 => Ok(DrawCommand::DrawPath(self.path()?, self.paint()?)),
+            1 => Ok(DrawCommand::ClipPath(
+                self.path()?,
+                self.enum_value(&CLIP_OPS, "clip op")?,
+                self.bool()?,
+            )),
+            2 => Ok(DrawCommand::DrawImage(
+                self.image()?,
+                self.rect()?,
+                self.matrix4()?,
+                self.paint()?,
+            )),
+            3 => Ok(DrawCommand::DrawText(
+                self.text_blob()?,
+                self.point()?,
+                self.paint()?,
+            )),
+            4 => self.nested(|r| {
+                let layer = Layer {
+                    bounds: r.option(Self::rect)?,
+                    paint: r.paint()?,
+                    depth_count: r.u32()?,
+                    draws: r.draws()?,
+                };
+                check_depths(&layer.draws, layer.depth_count)?;
+
+                Ok(DrawCommand::DrawLayer(Box::new(layer)))
+            }),
+            tag => Err(Self::tag_error("draw command", tag)),
+        }
+    }
+
+    fn path(&mut self) -> Result<Path, DeserializeError> {
+        let mut path = Path::with_fill_type(self.enum_value(&FILL_TYPES, "fill type")?);
+
+        let len = self.len(1)?;
+        for _ in 0..len {
+            let verb = match self.u8()? {
+                0 => PathVerb::MoveTo(self.point()?),
+                1 => PathVerb::LineTo(self.point()?),
+                2 => PathVerb::QuadTo(self.point()?, self.point()?),
+                3 => PathVerb::ConicTo(self.point()?, self.point()?, self.f32()?),
+                4 => PathVerb::CubicTo(self.point()?, self.point()?, self.point()?),
+                5 => PathVerb::Close,
+                tag => return Err(Self::tag_error("path verb", tag)),
+            };
+
+            path = path.add_verb(verb);
+        }
+
+        Ok(path)
+    }
+
+    fn paint(&mut self) -> Result<Paint, DeserializeError> {
+        Ok(Paint {
+            color: self.color_type()?,
+            style: self.style()?,
+            blend_mode: self.enum_value(&BLEND_MODES, "blend mode")?,
+            color_filter: self.option(Self::color_filter)?,
+            mask_filter: self.option(|r| {
+                Ok(MaskFilter::Blur {
+                    style: r.enum_value(&BLUR_STYLES, "blur style")?,
+                    sigma: r.f32()?,
+                })
+            })?,
+            image_filter: self.option(Self::image_filter)?,
+            dither: self.bool()?,
+            anti_alias: self.bool()?,
+            pixel_snap: self.bool()?,
+        })
+    }
+
+    fn style(&mut self) -> Result<Style, DeserializeError> {
+        let tag = self.u8()?;
+        if tag == 0 {
+            return Ok(Style::Fill);
+        }
+
+        let stroke = Stroke {
+            width: self.f32()?,
+            miter_limit: self.f32()?,
+            cap: self.enum_value(&STROKE_CAPS, "stroke cap")?,
+            join: self.enum_value(&STROKE_JOINS, "stroke join")?,
+        };
+
+        match tag {
+            1 => Ok(Style::Stroke(stroke)),
+            2 => Ok(Style::FillAndStroke(stroke)),
+            tag => Err(Self::tag_error("style", tag)),
+        }
+    }
+
+    fn color_type(&mut self) -> Result<ColorType, DeserializeError> {
+        self.nested(|r| match r.u8()? {
+            0 => Ok(ColorType::SolidColor(r.color()?)),
+            1 => {
+                let matrix = r.matrix4()?;
+                let (colors, stops) = r.gradient_colors()?;
+
+                Ok(ColorType::LinearGradient(LinearGradient {
+                    matrix: Matrix { matrix },
+                    colors,
+                    stops,
+                    p1: r.point()?,
+                    p2: r.point()?,
+                    tile_mode: r.enum_value(&TILE_MODES, "tile mode")?,
+                    interpolation: r.enum_value(&INTERPOLATIONS, "interpolation")?,
+                }))
+            }
+            2 => {
+                let matrix = r.matrix4()?;
+                let (colors, stops) = r.gradient_colors()?;
+
+                Ok(ColorType::RadialGradient(RadialGradient {
+                    matrix: Matrix { matrix },
+                    colors,
+                    stops,
+                    center: r.point()?,
+                    radius: r.f32()?,
+                    tile_mode: r.enum_value(&TILE_MODES, "tile mode")?,
+                    interpolation: r.enum_value(&INTERPOLATIONS, "interpolation")?,
+                }))
+            }
+            3 => Ok(ColorType::Image(r.image()?)),
+            4 => Ok(ColorType::Blend(
+                r.enum_value(&BLEND_MODES, "blend mode")?,
+                Box::new(r.color_type()?),
+                Box::new(r.color_type()?),
+            )),
+            5 => Ok(ColorType::WithLocalMatrix(
+                Matrix {
+                    matrix: r.matrix4()?,
+                },
+                Box::new(r.color_type()?),
+            )),
+            6 => Ok(ColorType::WithColorFilter(
+                r.color_filter()?,
+                Box::new(r.color_type()?),
+            )),
+            7 => {
+                let shader = r.runtime_shader()?;
+
+                shader
+                    .make_uniforms_with_data(r.f32s()?)
+                    .map(ColorType::RuntimeShader)
+                    .ok_or_else(|| corrupt("uniform data does not match the runtime shader"))
+            }
+            tag => Err(Self::tag_error("color", tag)),
+        })
+    }
+
+    fn gradient_colors(&mut self) -> Result<(Vec<Color>, Vec<f32>), DeserializeError> {
+        let len = self.len(16)?;
+        let colors = (0..len)
+            .map(|_| self.color())
+            .collect::<Result<Vec<_>, DeserializeError>>()?;
+        let stops = self.f32s()?;
+
+        if !stops.is_empty() && stops.len() != colors.len() {
+            return Err(corrupt("gradient stops do not match its colors"));
+        }
+
+        Ok((colors, stops))
+    }
+
+    fn runtime_shader(&mut self) -> Result<RuntimeShader, DeserializeError> {
+        self.shared(
+            |r| &mut r.shaders,
+            |r, _| {
+                let source = r.string()?;
+
+                let len = r.len(5)?;
+                let uniforms = (0..len)
+                    .map(|_| Ok((r.string()?, r.enum_value(&UNIFORM_TYPES, "uniform type")?)))
+                    .collect::<Result<Vec<_>, DeserializeError>>()?;
+
+                let uniforms = uniforms
+                    .iter()
+                    .map(|(name, ty)| (name.as_str(), *ty))
+                    .collect::<Vec<_>>();
+
+                RuntimeShader::new(&source, &uniforms).map_err(DeserializeError::RuntimeShader)
+            },
+        )
+    }
+
+    fn color_filter(&mut self) -> Result<ColorFilter, DeserializeError> {
+        self.nested(|r| match r.u8()? {
+            0 => {
+                let mut matrix = [0.0; 20];
+                for value in &mut matrix {
+                    *value = r.f32()?;
+                }
+
+                Ok(ColorFilter::Matrix(matrix))
+            }
+            1 => Ok(ColorFilter::Blend(
+                r.color()?,
+                r.enum_value(&BLEND_MODES, "blend mode")?,
+            )),
+            2 => Ok(ColorFilter::LumaToAlpha),
+            3 => Ok(ColorFilter::LinearToSrgb),
+            4 => Ok(ColorFilter::SrgbToLinear),
+            5 => Ok(ColorFilter::Compose(
+                Box::new(r.color_filter()?),
+                Box::new(r.color_filter()?),
+            )),
+            tag => Err(Self::tag_error("color filter", tag)),
+        })
+    }
+
+    fn image_filter(&mut self) -> Result<ImageFilter, DeserializeError> {
+        let input = |r: &mut Self| -> Result<Option<Box<ImageFilter>>, DeserializeError> {
+            Ok(r.option(Self::image_filter)?.map(Box::new))
+        };
+
+        self.nested(|r| match r.u8()? {
+            0 => Ok(ImageFilter::Blur {
+                sigma_x: r.f32()?,
+                sigma_y: r.f32()?,
+                input: input(r)?,
+            }),
+            1 => Ok(ImageFilter::DropShadow {
+                dx: r.f32()?,
+                dy: r.f32()?,
+                sigma_x: r.f32()?,
+                sigma_y: r.f32()?,
+                color: r.color()?,
+                input: input(r)?,
+            }),
+            2 => Ok(ImageFilter::DropShadowOnly {
+                dx: r.f32()?,
+                dy: r.f32()?,
+                sigma_x: r.f32()?,
+                sigma_y: r.f32()?,
+                color: r.color()?,
+                input: input(r)?,
+            }),
+            3 => Ok(ImageFilter::Offset {
+                dx: r.f32()?,
+                dy: r.f32()?,
+                input: input(r)?,
+            }),
+            4 => Ok(ImageFilter::ColorFilter {
+                filter: r.color_filter()?,
+                input: input(r)?,
+            }),
+            5 => {
+                let len = r.len(1)?;
+                let inputs = (0..len)
+                    .map(|_| r.option(Self::image_filter))
+                    .collect::<Result<Vec<_>, DeserializeError>>()?;
+
+                Ok(ImageFilter::Merge(inputs))
+            }
+            6 => Ok(ImageFilter::Compose(
+                Box::new(r.image_filter()?),
+                Box::new(r.image_filter()?),
+            )),
+            tag => Err(Self::tag_error("image filter", tag)),
+        })
+    }
+
+    fn image_info(&mut self) -> Result<ImageInfo, DeserializeError> {
+        Ok(ImageInfo {
+            width: self.u32()?,
+            height: self.u32()?,
+            format: self.enum_value(&IMAGE_FORMATS, "image format")?,
+            premultiplied: self.bool()?,
+        })
+    }
+
+    fn image(&mut self) -> Result<Image, DeserializeError> {
+        match self.u8()? {
+            0 => {
+                let bitmap = self.shared(
+                    |r| &mut r.bitmaps,
+                    |r, _| {
+                        let info = r.image_info()?;
+                        let bytes_per_row = r.u32()?;
+                        let len = r.len(1)?;
+                        let data = r.take(len)?.to_vec();
+
+                        // the whole bitmap is uploaded to a texture when it is drawn
+                        let row = info.width as u64 * 4;
+                        if (bytes_per_row as u64) < row
+                            || (data.len() as u64) < bytes_per_row as u64 * info.height as u64
+                        {
+                            return Err(corrupt("bitmap data is smaller than its size"));
+                        }
+
+                        Ok(Rc::new(Bitmap::new(info, data, Some(bytes_per_row))))
+                    },
+                )?;
+
+                Ok(Image::from_bitmap(bitmap))
+            }
+            1 => self.shared(
+                |r| &mut r.textures,
+                |r, index| {
+                    let info = r.image_info()?;
+
+                    r.resolver
+                        .resolve_image(index, &info)
+                        .ok_or(DeserializeError::MissingImage(index))
+                },
+            ),
+            tag => Err(Self::tag_error("image", tag)),
+        }
+    }
+
+    fn text_blob(&mut self) -> Result<Rc<TextBlob>, DeserializeError> {
+        self.shared(
+            |r| &mut r.blobs,
+            |r, _| {
+                let width = r.f32()?;
+                let height = r.f32()?;
+                let ascent = r.f32()?;
+                let descent = r.f32()?;
+                let line_gap = r.f32()?;
+
+                // font index, size and glyph count
+                let len = r.len(12)?;
+                let runs = (0..len)
+                    .map(|_| r.text_run())
+                    .collect::<Result<Vec<_>, DeserializeError>>()?;
+
+                Ok(Rc::new(TextBlob {
+                    runs,
+                    width,
+                    height,
+                    ascent,
+                    descent,
+                    line_gap,
+                }))
+            },
+        )
+    }
+
+    fn text_run(&mut self) -> Result<TextRun, DeserializeError> {
+        let font = self.font()?;
+        let px_size = self.f32()?;
+
+        let len = self.len(18)?;
+        let glyphs = (0..len)
+            .map(|_| {
+                Ok(ab_glyph::Glyph {
+                    id: ab_glyph::GlyphId(self.u16()?),
+                    scale: ab_glyph::PxScale {
+                        x: self.f32()?,
+                        y: self.f32()?,
+                    },
+                    position: ab_glyph::point(self.f32()?, self.f32()?),
+                })
+            })
+            .collect::<Result<Vec<_>, DeserializeError>>()?;
+
+        Ok(TextRun {
+            glyphs,
+            px_size,
+            font,
+        })
+    }
+
+    fn font(&mut self) -> Result<Rc<Font>, DeserializeError> {
+        self.shared(
+            |r| &mut r.fonts,
+            |r, _| {
+                let description = FontDescription {
+                    name: r.string()?,
+                    family: r.string()?,
+                    style: FontStyle {
+                        weight: r.i32()?,
+                        italic: r.bool()?,
+                        stretch: r.i32()?,
+                    },
+                };
+
+                r.resolver
+                    .resolve_font(&description)
+                    .ok_or(DeserializeError::MissingFont(description))
+            },
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ab_glyph::FontArc;
+
+    use crate::{PictureRecorder, TextBlobBuilder};
+
+    use super::*;
+
+    fn test_font() -> Rc<Font> {
+        Rc::new(Font::new(
+            FontDescription {
+                name: "0xProtoNerdFont-Regular".to_string(),
+                family: "0xProtoNerdFont".to_string(),
+                style: FontStyle::normal(),
+            },
+            FontArc::try_from_slice(include_bytes!(
+                "../../examples/assets/0xProto/0xProtoNerdFont-Regular.ttf"
+            ))
+            .expect("Failed to load font"),
+        ))
+    }
+
+    fn test_picture(font: &Rc<Font>) -> Picture {
+        let bitmap = Rc::new(Bitmap::new(
+            ImageInfo {
+                width: 2,
+                height: 2,
+                format: ImageFormat::RGBA8888,
+                premultiplied: false,
+            },
+            vec![255; 16],
+            None,
+        ));
+        let image = Image::from_bitmap(bitmap);
+
+        let shader = RuntimeShader::new(
+            "fn main(coord: vec2<f32>) -> vec4<f32> { return uniforms.color; }",
+            &[("color", UniformType::Vec4)],
+        )
+        .unwrap();
+        let mut uniforms = shader.make_uniforms();
+        uniforms.set("color", &[1.0, 0.5, 0.0, 1.0]).unwrap();
+
+        let mut recorder = PictureRecorder::new();
+
+        let mut paint = Paint::new();
+        paint.color = ColorType::blend(
+            BlendMode::Modulate,
+            LinearGradient::new(Point::from(0.0, 0.0), Point::from(10.0, 0.0))
+                .with_colors(vec![Color::red(), Color::blue()])
+                .with_interpolation(GradientInterpolation::Oklch(HueInterpolation::Longer)),
+            ColorType::from(image.clone()).with_color_filter(ColorFilter::grayscale()),
+        );
+        paint.style = Style::Stroke(Stroke::new().with_width(3.0).with_cap(StrokeCap::Round));
+        paint.mask_filter = Some(MaskFilter::blur(BlurStyle::Outer, 2.0));
+        recorder.draw_path(
+            Path::new()
+                .move_to(1.0, 2.0)
+                .cubic_to(3.0, 4.0, 5.0, 6.0, 7.0, 8.0),
+            &paint,
+        );
+
+        recorder.clip_rect(&Rect::from_xywh(0.0, 0.0, 50.0, 50.0), ClipOp::Difference);
+
+        let mut layer_paint = Paint::new();
+        layer_paint.image_filter = Some(ImageFilter::merge(vec![
+            None,
+            Some(ImageFilter::drop_shadow(2.0, 2.0, 1.0, 1.0, Color::black(), None)),
+        ]));
+        recorder.save_layer(None, Some(&layer_paint));
+        recorder.draw_image(&image, &Rect::from_xywh(0.0, 0.0, 20.0, 20.0), None);
+
+        let mut shader_paint = Paint::new();
+        shader_paint.color = uniforms.into();
+        recorder.draw_rect(&Rect::from_xywh(5.0, 5.0, 10.0, 10.0), &shader_paint);
+        recorder.restore();
+
+        let blob = TextBlobBuilder::new(font.clone(), 12.0).build("mickey");
+        recorder.draw_text(blob.clone(), Point::from(0.0, 30.0), Color::green());
+        recorder.draw_text(blob, Point::from(0.0, 60.0), Color::green());
+
+        recorder.finish_record()
+    }
+
+    #[test]
+    fn round_trip() {
+        let font = test_font();
+        let bytes = test_picture(&font).serialize();
+
+        let resolver = |description: &FontDescription| {
+            (description == &font.description).then(|| font.clone())
+        };
+        let picture = Picture::deserialize(&bytes, &resolver).unwrap();
+
+        assert_eq!(picture.serialize(), bytes);
+        assert_eq!(picture.draws.len(), 5);
+
+        // shared resources are read once
+        match (&picture.draws[3].command, &picture.draws[4].command) {
+            (DrawCommand::DrawText(a, ..), DrawCommand::DrawText(b, ..)) => {
+                assert!(Rc::ptr_eq(a, b))
+            }
+            _ => panic!("expect two text draws"),
+        }
+    }
+
+    #[test]
+    fn reject_invalid_data() {
+        let font = test_font();
+        let bytes = test_picture(&font).serialize();
+        let resolver = |_: &FontDescription| Some(font.clone());
+
+        for len in 0..bytes.len() {
+            assert_eq!(
+                Picture::deserialize(&bytes[..len], &resolver).err(),
+                Some(DeserializeError::Truncated)
+            );
+        }
+
+        let mut data = bytes.clone();
+        data[0] = b'X';
+        assert_eq!(
+            Picture::deserialize(&data, &resolver).err(),
+            Some(DeserializeError::InvalidMagic)
+        );
+
+        let mut data = bytes.clone();
+        data[4..8].copy_from_slice(&(VERSION + 1).to_le_bytes());
+        assert_eq!(
+            Picture::deserialize(&data, &resolver).err(),
+            Some(DeserializeError::UnsupportedVersion(VERSION + 1))
+        );
+
+        // the command tag of the first draw follows the bounds, draw count, depth and transform
+        let mut data = bytes.clone();
+        data[8 + 16 + 4 + 4 + 64] = 9;
+        assert!(matches!(
+            Picture::deserialize(&data, &resolver),
+            Err(DeserializeError::Corrupt(_))
+        ));
+
+        // the depth of the first draw follows the bounds and draw count
+        let mut data = bytes.clone();
+        data[8 + 16 + 4..8 + 16 + 4 + 4].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert!(matches!(
+            Picture::deserialize(&data, &resolver),
+            Err(DeserializeError::Corrupt(_))
+        ));
+
+        let draw = |depth: u32, command: DrawCommand| Draw {
+            depth,
+            command,
+            transform: Matrix4::identity(),
+        };
+        let path = || DrawCommand::DrawPath(Path::new(), Paint::new());
+        let clip = || DrawCommand::ClipPath(Path::new(), ClipOp::Intersect, true);
+
+        assert!(check_depths(&[draw(3, clip()), draw(1, path()), draw(2, path())], 3).is_ok());
+        assert!(check_depths(&[draw(2, path()), draw(1, path())], 2).is_err());
+        assert!(check_depths(&[draw(1, path()), draw(2, path())], 3).is_err());
+        assert!(check_depths(&[draw(1, path()), draw(2, path())], 1).is_err());
+
+        let mut data = bytes.clone();
+        data.push(0);
+        assert!(matches!(
+            Picture::deserialize(&data, &resolver),
+            Err(DeserializeError::Corrupt(_))
+        ));
+
+        assert_eq!(
+            Picture::deserialize(&bytes, &|_: &FontDescription| None).err(),
+            Some(DeserializeError::MissingFont(font.description.clone()))
+        );
+    }
+}
@@ -1,12 +1,24 @@
 use nalgebra::{Matrix4, Vector3};
 
-use super::geometry::degree_to_radian;
+use super::{geometry::degree_to_radian, Rect};
 
 pub(crate) struct ClipState {
     pub(crate) clip_op: Vec<usize>,
+    /// Indices of the clips expanding the clip inside this state, including clips replayed from pictures.
+    pub(crate) expanding: Vec<usize>,
+    /// Conservative device space bounds of the clip. `None` means the clip is unbounded.
+    pub(crate) device_bounds: Option<Rect>,
 }
 
 impl ClipState {
+    fn new(device_bounds: Option<Rect>) -> Self {
+        Self {
+            clip_op: vec![],
+            expanding: vec![],
+            device_bounds,
+        }
+    }
+
     fn save_clip(&mut self, index: usize) {
         self.clip_op.push(index);
     }
@@ -21,10 +33,15 @@ impl State {
     pub(crate) fn new() -> Self {
         Self {
             matrix_stack: vec![Matrix4::identity()],
-            clip_stack: vec![ClipState { clip_op: vec![] }],
+            clip_stack: vec![ClipState::new(None)],
         }
     }
 
+    /// Returns the number of saved states. A new state has save count 0.
+    pub(crate) fn save_count(&self) -> usize {
+        self.matrix_stack.len() - 1
+    }
+
     pub(crate) fn current_transform(&self) -> Matrix4<f32> {
         return self
             .matrix_stack
@@ -37,27 +54,77 @@ impl State {
         let last_matrix = self.matrix_stack.last().unwrap();
 
         self.matrix_stack.push(last_matrix.clone());
+
+        let bounds = self.device_clip_bounds();
+        self.clip_stack.push(ClipState::new(bounds));
     }
 
+    /// Restores the last saved state and returns the clip state of the restored one.
+    /// Returns `None` and keeps current state if there is no saved state.
     pub(crate) fn restore(&mut self) -> Option<ClipState> {
-        self.matrix_stack.pop();
-
-        if self.matrix_stack.is_empty() {
-            self.matrix_stack.push(Matrix4::identity());
+        if self.save_count() == 0 {
+            return None;
         }
 
-        let clip_state = self.clip_stack.pop();
-        if self.clip_stack.is_empty() {
-            self.clip_stack.push(ClipState { clip_op: vec![] });
-        }
+        self.matrix_stack.pop();
 
-        return clip_state;
+        return self.clip_stack.pop();
     }
 
     pub(crate) fn save_clip(&mut self, index: usize) {
         self.clip_stack.last_mut().unwrap().save_clip(index);
     }
 
+    /// Saves the clip into the state of save count level, which may be below current state.
+    pub(crate) fn save_clip_at(&mut self, level: usize, index: usize) {
+        self.clip_stack[level].save_clip(index);
+    }
+
+    pub(crate) fn save_expanding_clip(&mut self, index: usize) {
+        self.clip_stack.last_mut().unwrap().expanding.push(index);
+    }
+
+    /// Returns the (save count, index) of clips saved in the states from save count level to current
+    /// state, in recording order.
+    pub(crate) fn clips_since(&self, level: usize) -> Vec<(usize, usize)> {
+        self.clip_stack
+            .iter()
+            .enumerate()
+            .skip(level)
+            .flat_map(|(level, clip)| clip.clip_op.iter().map(move |index| (level, *index)))
+            .collect()
+    }
+
+    /// Intersects the conservative clip bounds of current state with a device space rect.
+    pub(crate) fn intersect_clip_bounds(&mut self, bounds: &Rect) {
+        let clip = self.clip_stack.last_mut().unwrap();
+
+        clip.device_bounds = match clip.device_bounds {
+            Some(current) => {
+                Some(current.intersect(bounds).unwrap_or(Rect::from_ltrb(0.0, 0.0, 0.0, 0.0)))
+            }
+            None => Some(*bounds),
+        };
+    }
+
+    /// Replaces the conservative clip bounds of current state, such as the bounds of a layer which
+    /// content is cut by.
+    pub(crate) fn set_device_clip_bounds(&mut self, bounds: Option<Rect>) {
+        self.clip_stack.last_mut().unwrap().device_bounds = bounds;
+    }
+
+    pub(crate) fn device_clip_bounds(&self) -> Option<Rect> {
+        self.clip_stack.last().and_then(|c| c.device_bounds)
+    }
+
+    /// Returns true if device space bounds are empty or outside of the conservative clip bounds.
+    pub(crate) fn quick_reject(&self, bounds: &Rect) -> bool {
+        bounds.is_empty()
+            || self
+                .device_clip_bounds()
+                .is_some_and(|clip| bounds.intersect(&clip).is_none())
+    }
+
     pub(crate) fn pop_clip_stack(&mut self) -> Option<ClipState> {
         return self.clip_stack.pop();
     }
@@ -69,6 +136,25 @@ impl State {
             .push(current_matrix.unwrap() * Matrix4::new_translation(&Vector3::new(dx, dy, 0.0)));
     }
 
+    pub(crate) fn set_matrix(&mut self, matrix: &Matrix4<f32>) {
+        *self.matrix_stack.last_mut().expect("State stack is error") = *matrix;
+    }
+
+    pub(crate) fn skew(&mut self, sx: f32, sy: f32) {
+        let current_matrix = self.matrix_stack.pop();
+        let s: Matrix4<f32> = Matrix4::new(
+            1.0, sx, 0.0, 0.0, sy, 1.0, 0.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 0.0, 1.0,
+        );
+
+        self.matrix_stack.push(current_matrix.unwrap() * s);
+    }
+
+    pub(crate) fn concat(&mut self, matrix: &Matrix4<f32>) {
+        let current_matrix = self.matrix_stack.pop();
+
+        self.matrix_stack.push(current_matrix.unwrap() * matrix);
+    }
+
     pub(crate) fn rotate_at(&mut self, degree: f32, px: f32, py: f32) {
         let current_matrix = self.matrix_stack.pop();
         let rotate = Matrix4::new_rotation(Vector3::new(0.0, 0.0, degree_to_radian(degree)));
@@ -1,41 +1,37 @@
 use std::cell::{RefCell, RefMut};
 use std::collections::HashMap;
+use std::rc::Rc;
 
 use super::pipeline::Pipeline;
+use super::texture_pool::{TextureDesc, TexturePool};
+use crate::core::{BlendMode, ColorSpace};
 use crate::render::fragment::{
-    ColorPipelineGenerator, LINEAR_GRADIENT_PIPELINE_NAME, NON_COLOR_PIPELINE_NAME,
-    RADIAL_GRADIENT_PIPELINE_NAME, SOLID_PIPELINE_NAME, SOLID_TEXT_PIPELINE_NAME,
-    TEXTURE_PIPELINE_NAME,
+    ColorPipelineGenerator, FilterPipelineGenerator, BLUR_PIPELINE_NAME,
+    IMAGE_FILTER_PIPELINE_NAME, LAYER_PIPELINE_NAME, NON_COLOR_PIPELINE_NAME, SOLID_PIPELINE_NAME,
+    SOLID_TEXT_PIPELINE_NAME, TEXTURE_PIPELINE_NAME,
 };
 use crate::text::glyph_atlas::GlyphAtlasManager;
 
 pub(crate) trait PipelineGenerater {
-    fn gen_pipeline(
-        &self,
-        format: wgpu::TextureFormat,
-        sample_count: u32,
-        device: &wgpu::Device,
-    ) -> Pipeline;
+    fn gen_pipeline(&self, key: &PipelineKey, device: &wgpu::Device) -> Pipeline;
 }
 
 struct PipelineNode {
-    format: wgpu::TextureFormat,
-    sample_count: u32,
-    pipelines: HashMap<&'static str, Pipeline>,
+    key: PipelineKey,
+    pipelines: HashMap<String, Pipeline>,
 }
 
 impl PipelineNode {
-    pub(crate) fn new(format: wgpu::TextureFormat, sample_count: u32) -> Self {
+    pub(crate) fn new(key: PipelineKey) -> Self {
         PipelineNode {
-            format,
-            sample_count,
+            key,
             pipelines: HashMap::new(),
         }
     }
 
     pub(crate) fn load_pipeline(
         &mut self,
-        label: &'static str,
+        label: &str,
         generator: &Box<dyn PipelineGenerater>,
         device: &wgpu::Device,
     ) {
@@ -43,66 +39,129 @@ impl PipelineNode {
             return;
         }
 
-        self.pipelines.insert(
-            label,
-            generator.gen_pipeline(self.format, self.sample_count, device),
-        );
+        self.pipelines
+            .insert(label.to_string(), generator.gen_pipeline(&self.key, device));
     }
 
-    pub(crate) fn get_pipeline(&self, label: &'static str) -> Option<&Pipeline> {
+    pub(crate) fn get_pipeline(&self, label: &str) -> Option<&Pipeline> {
         self.pipelines.get(label)
     }
 }
 
+/// Describes the variant of pipelines. Each pipeline label is generated once per key.
 #[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
 pub(crate) struct PipelineKey {
-    format: wgpu::TextureFormat,
-    sample_count: u32,
+    pub(crate) format: wgpu::TextureFormat,
+    pub(crate) sample_count: u32,
+    pub(crate) blend_mode: BlendMode,
+    /// if true, fragment shader runs the color filter stage bind at group 2
+    pub(crate) color_filter: bool,
+    /// if true, fragment shader multiplies the coverage mask bind at the group after color filter
+    pub(crate) mask: bool,
+    /// if true, gradient and image shaders add ordered dither noise before output quantization
+    pub(crate) dither: bool,
+    /// color space of the target, color shaders convert their sRGB output into it
+    pub(crate) color_space: ColorSpace,
+}
+
+impl PipelineKey {
+    pub(crate) fn new(format: wgpu::TextureFormat, sample_count: u32) -> Self {
+        Self {
+            format,
+            sample_count,
+            blend_mode: BlendMode::SrcOver,
+            color_filter: false,
+            mask: false,
+            dither: false,
+            color_space: ColorSpace::Srgb,
+        }
+    }
+
+    pub(crate) fn with_blend_mode(mut self, blend_mode: BlendMode) -> Self {
+        self.blend_mode = blend_mode;
+        self
+    }
+
+    pub(crate) fn with_color_filter(mut self, color_filter: bool) -> Self {
+        self.color_filter = color_filter;
+        self
+    }
+
+    pub(crate) fn with_mask(mut self, mask: bool) -> Self {
+        self.mask = mask;
+        self
+    }
+
+    pub(crate) fn with_dither(mut self, dither: bool) -> Self {
+        self.dither = dither;
+        self
+    }
+
+    pub(crate) fn with_color_space(mut self, color_space: ColorSpace) -> Self {
+        self.color_space = color_space;
+        self
+    }
+
+    /// Returns the bind group slot of the mask stage.
+    pub(crate) fn mask_group(&self) -> u32 {
+        if self.color_filter {
+            3
+        } else {
+            2
+        }
+    }
 }
 
 /// GPU context for holding pipelines created by engine. Only one context is needed.
 pub struct GPUContext {
     pipelines: HashMap<PipelineKey, PipelineNode>,
 
-    generator: HashMap<&'static str, Box<dyn PipelineGenerater>>,
+    generator: HashMap<String, Box<dyn PipelineGenerater>>,
 
     linear_sampler: wgpu::Sampler,
 
     r8_atlas: RefCell<GlyphAtlasManager>,
+
+    texture_pool: RefCell<TexturePool>,
 }
 
 impl GPUContext {
     pub fn new(device: &wgpu::Device) -> Self {
-        let mut generator: HashMap<&'static str, Box<dyn PipelineGenerater>> = HashMap::new();
+        let mut generator: HashMap<String, Box<dyn PipelineGenerater>> = HashMap::new();
 
         generator.insert(
-            SOLID_PIPELINE_NAME,
-            ColorPipelineGenerator::solid_color_pipeline(device),
+            SOLID_PIPELINE_NAME.to_string(),
+            ColorPipelineGenerator::solid_color_pipeline(),
+        );
+
+        generator.insert(
+            TEXTURE_PIPELINE_NAME.to_string(),
+            ColorPipelineGenerator::image_pipeline(),
         );
 
         generator.insert(
-            LINEAR_GRADIENT_PIPELINE_NAME,
-            ColorPipelineGenerator::linear_gradient_pipeline(device),
+            NON_COLOR_PIPELINE_NAME.to_string(),
+            ColorPipelineGenerator::non_color_pipeline(),
         );
 
         generator.insert(
-            RADIAL_GRADIENT_PIPELINE_NAME,
-            ColorPipelineGenerator::radial_gradient_pipeline(device),
+            SOLID_TEXT_PIPELINE_NAME.to_string(),
+            ColorPipelineGenerator::solid_text_pipeline(),
         );
 
         generator.insert(
-            TEXTURE_PIPELINE_NAME,
-            ColorPipelineGenerator::image_pipeline(device),
+            LAYER_PIPELINE_NAME.to_string(),
+            ColorPipelineGenerator::layer_pipeline(),
         );
 
         generator.insert(
-            NON_COLOR_PIPELINE_NAME,
-            ColorPipelineGenerator::non_color_pipeline(device),
+            BLUR_PIPELINE_NAME.to_string(),
+            FilterPipelineGenerator::blur_pipeline(),
         );
 
         generator.insert(
-            SOLID_TEXT_PIPELINE_NAME,
-            ColorPipelineGenerator::solid_text_pipeline(device),
+            IMAGE_FILTER_PIPELINE_NAME.to_string(),
+            FilterPipelineGenerator::image_filter_pipeline(),
         );
 
         let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
@@ -125,16 +184,29 @@ impl GPUContext {
             generator,
             linear_sampler: sampler,
             r8_atlas: RefCell::new(GlyphAtlasManager::new(wgpu::TextureFormat::R8Unorm, device)),
+            texture_pool: RefCell::new(TexturePool::new()),
         }
     }
 
-    pub(crate) fn load_pipeline(
-        &mut self,
-        label: &'static str,
-        format: wgpu::TextureFormat,
-        anti_aliasing: bool,
-        device: &wgpu::Device,
-    ) {
+    /// Registers the generator of a pipeline built at runtime, such as shaders generated from a shader tree.
+    /// Generators are cached by label, `create` only runs the first time a label is registered.
+    ///
+    /// # Arguments
+    ///
+    /// * `label` unique name of the pipeline, same label must always describe the same shader
+    /// * `create` creates the generator of the pipeline
+    pub(crate) fn register_generator<F>(&mut self, label: &str, create: F)
+    where
+        F: FnOnce() -> Box<dyn PipelineGenerater>,
+    {
+        if self.generator.contains_key(label) {
+            return;
+        }
+
+        self.generator.insert(label.to_string(), create());
+    }
+
+    pub(crate) fn load_pipeline(&mut self, label: &str, key: PipelineKey, device: &wgpu::Device) {
         let pg = self.generator.get(label);
 
         if pg.is_none() {
@@ -145,25 +217,14 @@ impl GPUContext {
 
         let p = self
             .pipelines
-            .entry(PipelineKey {
-                format,
-                sample_count: if anti_aliasing { 4 } else { 1 },
-            })
-            .or_insert(PipelineNode::new(format, if anti_aliasing { 4 } else { 1 }));
+            .entry(key)
+            .or_insert(PipelineNode::new(key));
 
         p.load_pipeline(label, pg, device);
     }
 
-    pub(crate) fn get_pipeline(
-        &self,
-        label: &'static str,
-        format: wgpu::TextureFormat,
-        anti_alias: bool,
-    ) -> Option<&Pipeline> {
-        let node = self.pipelines.get(&PipelineKey {
-            format,
-            sample_count: if anti_alias { 4 } else { 1 },
-        });
+    pub(crate) fn get_pipeline(&self, label: &str, key: &PipelineKey) -> Option<&Pipeline> {
+        let node = self.pipelines.get(key);
 
         if node.is_none() {
             return None;
@@ -182,11 +243,25 @@ impl GPUContext {
         &self.linear_sampler
     }
 
+    /// Returns an offscreen texture matching `desc`. Textures are reused once the previous holder drops them.
+    pub(crate) fn acquire_texture(
+        &self,
+        label: &str,
+        desc: &TextureDesc,
+        device: &wgpu::Device,
+    ) -> Rc<wgpu::Texture> {
+        self.texture_pool.borrow_mut().acquire(label, desc, device)
+    }
+
     pub fn print_memory_usage(&self) {
         let total = self.r8_atlas.borrow().get_total_memory();
         let used = self.r8_atlas.borrow().get_used_memory();
 
         println!("Memory Usage: {}/{}", used / (1024), total / (1024));
+        println!(
+            "Offscreen Textures: {}",
+            self.texture_pool.borrow().get_total_memory() / 1024
+        );
     }
 }
 
@@ -195,9 +270,7 @@ mod tests {
     use super::*;
     use crate::{
         gpu::init_test_context,
-        render::fragment::{
-            LINEAR_GRADIENT_PIPELINE_NAME, NON_COLOR_PIPELINE_NAME, SOLID_PIPELINE_NAME,
-        },
+        render::fragment::{NON_COLOR_PIPELINE_NAME, SOLID_PIPELINE_NAME, TEXTURE_PIPELINE_NAME},
     };
 
     #[test]
@@ -206,47 +279,61 @@ mod tests {
 
         let mut ctx = GPUContext::new(&device);
 
-        ctx.load_pipeline(
-            SOLID_PIPELINE_NAME,
-            wgpu::TextureFormat::Rgba8Unorm,
-            false,
-            &device,
-        );
+        let key = PipelineKey::new(wgpu::TextureFormat::Rgba8Unorm, 1);
 
-        ctx.load_pipeline(
-            LINEAR_GRADIENT_PIPELINE_NAME,
-            wgpu::TextureFormat::Rgba8Unorm,
-            false,
-            &device,
-        );
+        ctx.load_pipeline(SOLID_PIPELINE_NAME, key, &device);
 
-        ctx.load_pipeline(
-            RADIAL_GRADIENT_PIPELINE_NAME,
-            wgpu::TextureFormat::Rgba8Unorm,
-            false,
-            &device,
-        );
+        ctx.register_generator("Registered", ColorPipelineGenerator::solid_color_pipeline);
+
+        ctx.load_pipeline("Registered", key, &device);
+
+        ctx.load_pipeline("Unknown", key, &device);
+
+        ctx.load_pipeline(NON_COLOR_PIPELINE_NAME, key, &device);
 
         ctx.load_pipeline(
-            NON_COLOR_PIPELINE_NAME,
-            wgpu::TextureFormat::Rgba8Unorm,
-            false,
+            SOLID_PIPELINE_NAME,
+            key.with_color_filter(true),
             &device,
         );
 
         assert!(ctx
-            .get_pipeline(SOLID_PIPELINE_NAME, wgpu::TextureFormat::Bgra8Unorm, false)
+            .get_pipeline(
+                SOLID_PIPELINE_NAME,
+                &PipelineKey::new(wgpu::TextureFormat::Bgra8Unorm, 1)
+            )
             .is_none());
+        assert!(ctx.get_pipeline(SOLID_PIPELINE_NAME, &key).is_some());
         assert!(ctx
-            .get_pipeline(SOLID_PIPELINE_NAME, wgpu::TextureFormat::Rgba8Unorm, false)
-            .is_some());
-
+            .get_pipeline(SOLID_PIPELINE_NAME, &key.with_blend_mode(BlendMode::Plus))
+            .is_none());
         assert!(ctx
-            .get_pipeline(
-                NON_COLOR_PIPELINE_NAME,
-                wgpu::TextureFormat::Rgba8Unorm,
-                false
-            )
+            .get_pipeline(SOLID_PIPELINE_NAME, &key.with_color_filter(true))
             .is_some());
+
+        assert!(ctx.get_pipeline(NON_COLOR_PIPELINE_NAME, &key).is_some());
+        assert!(ctx.get_pipeline("Registered", &key).is_some());
+        assert!(ctx.get_pipeline("Unknown", &key).is_none());
+    }
+
+    #[test]
+    fn dither_pipeline_variant() {
+        let (device, _queue) = init_test_context();
+
+        let mut ctx = GPUContext::new(&device);
+
+        let key = PipelineKey::new(wgpu::TextureFormat::Rgba8Unorm, 1);
+        let dithered = key.with_dither(true);
+
+        assert_ne!(key, dithered);
+
+        ctx.load_pipeline(TEXTURE_PIPELINE_NAME, dithered, &device);
+
+        assert!(ctx.get_pipeline(TEXTURE_PIPELINE_NAME, &dithered).is_some());
+        assert!(ctx.get_pipeline(TEXTURE_PIPELINE_NAME, &key).is_none());
+
+        ctx.load_pipeline(TEXTURE_PIPELINE_NAME, key, &device);
+
+        assert!(ctx.get_pipeline(TEXTURE_PIPELINE_NAME, &key).is_some());
     }
 }
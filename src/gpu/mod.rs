@@ -3,9 +3,10 @@ pub(crate) mod buffer;
 pub(crate) mod context;
 pub(crate) mod pipeline;
 pub(crate) mod surface;
+pub(crate) mod texture_pool;
 
 pub use context::GPUContext;
-pub use surface::GPUSurface;
+pub use surface::{AntiAlias, GPUSurface};
 
 /// Only for test
 #[cfg(test)]
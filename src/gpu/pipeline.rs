@@ -1,9 +1,80 @@
 use std::collections::HashMap;
 
+use crate::core::BlendMode;
+
+/// Maps a blend mode to the fixed function blend state. Colors are premultiplied.
+pub(crate) fn blend_state_for(mode: BlendMode) -> wgpu::BlendState {
+    let (src, dst) = match mode {
+        BlendMode::Clear => (wgpu::BlendFactor::Zero, wgpu::BlendFactor::Zero),
+        BlendMode::Src => (wgpu::BlendFactor::One, wgpu::BlendFactor::Zero),
+        BlendMode::Dst => (wgpu::BlendFactor::Zero, wgpu::BlendFactor::One),
+        BlendMode::SrcOver => (wgpu::BlendFactor::One, wgpu::BlendFactor::OneMinusSrcAlpha),
+        BlendMode::DstOver => (wgpu::BlendFactor::OneMinusDstAlpha, wgpu::BlendFactor::One),
+        BlendMode::SrcIn => (wgpu::BlendFactor::DstAlpha, wgpu::BlendFactor::Zero),
+        BlendMode::DstIn => (wgpu::BlendFactor::Zero, wgpu::BlendFactor::SrcAlpha),
+        BlendMode::SrcOut => (wgpu::BlendFactor::OneMinusDstAlpha, wgpu::BlendFactor::Zero),
+        BlendMode::DstOut => (wgpu::BlendFactor::Zero, wgpu::BlendFactor::OneMinusSrcAlpha),
+        BlendMode::SrcATop => (
+            wgpu::BlendFactor::DstAlpha,
+            wgpu::BlendFactor::OneMinusSrcAlpha,
+        ),
+        BlendMode::DstATop => (
+            wgpu::BlendFactor::OneMinusDstAlpha,
+            wgpu::BlendFactor::SrcAlpha,
+        ),
+        BlendMode::Xor => (
+            wgpu::BlendFactor::OneMinusDstAlpha,
+            wgpu::BlendFactor::OneMinusSrcAlpha,
+        ),
+        BlendMode::Plus => (wgpu::BlendFactor::One, wgpu::BlendFactor::One),
+        BlendMode::Modulate => {
+            return wgpu::BlendState {
+                color: wgpu::BlendComponent {
+                    src_factor: wgpu::BlendFactor::Zero,
+                    dst_factor: wgpu::BlendFactor::Src,
+                    operation: wgpu::BlendOperation::Add,
+                },
+                alpha: wgpu::BlendComponent {
+                    src_factor: wgpu::BlendFactor::Zero,
+                    dst_factor: wgpu::BlendFactor::SrcAlpha,
+                    operation: wgpu::BlendOperation::Add,
+                },
+            }
+        }
+        BlendMode::Screen => {
+            return wgpu::BlendState {
+                color: wgpu::BlendComponent {
+                    src_factor: wgpu::BlendFactor::One,
+                    dst_factor: wgpu::BlendFactor::OneMinusSrc,
+                    operation: wgpu::BlendOperation::Add,
+                },
+                alpha: wgpu::BlendComponent {
+                    src_factor: wgpu::BlendFactor::One,
+                    dst_factor: wgpu::BlendFactor::OneMinusSrcAlpha,
+                    operation: wgpu::BlendOperation::Add,
+                },
+            }
+        }
+    };
+
+    let component = wgpu::BlendComponent {
+        src_factor: src,
+        dst_factor: dst,
+        operation: wgpu::BlendOperation::Add,
+    };
+
+    wgpu::BlendState {
+        color: component,
+        alpha: component,
+    }
+}
+
 pub(crate) struct Pipeline {
     pub(crate) groups: Vec<wgpu::BindGroupLayout>,
     pub(crate) _layout: wgpu::PipelineLayout,
     pub(crate) pipelines: HashMap<wgpu::DepthStencilState, wgpu::RenderPipeline>,
+    /// pipeline used in render pass without depth stencil attachment
+    pub(crate) plain: Option<wgpu::RenderPipeline>,
 }
 
 impl Pipeline {
@@ -14,6 +85,10 @@ impl Pipeline {
         self.pipelines.get(state)
     }
 
+    pub(crate) fn get_plain_pipeline(&self) -> Option<&wgpu::RenderPipeline> {
+        self.plain.as_ref()
+    }
+
     pub(crate) fn get_group_layout(&self, slot: usize) -> Option<&wgpu::BindGroupLayout> {
         self.groups.get(slot)
     }
@@ -23,9 +98,11 @@ pub(crate) struct PipelineBuilder<'a> {
     format: wgpu::TextureFormat,
     sample_count: u32,
     color_writable: bool,
+    blend_mode: BlendMode,
     groups: Vec<Vec<wgpu::BindGroupLayoutEntry>>,
     buffers: Vec<wgpu::VertexBufferLayout<'a>>,
     states: Vec<wgpu::DepthStencilState>,
+    plain: bool,
 }
 
 impl<'a> PipelineBuilder<'a> {
@@ -34,9 +111,11 @@ impl<'a> PipelineBuilder<'a> {
             format: wgpu::TextureFormat::Bgra8Unorm,
             sample_count: 1,
             color_writable: true,
+            blend_mode: BlendMode::SrcOver,
             groups: vec![],
             buffers: vec![],
             states: vec![],
+            plain: false,
         }
     }
 
@@ -55,6 +134,11 @@ impl<'a> PipelineBuilder<'a> {
         self
     }
 
+    pub(crate) fn with_blend_mode(mut self, mode: BlendMode) -> Self {
+        self.blend_mode = mode;
+        self
+    }
+
     pub(crate) fn add_group(mut self, group: Vec<wgpu::BindGroupLayoutEntry>) -> Self {
         self.groups.push(group);
         self
@@ -70,22 +154,17 @@ impl<'a> PipelineBuilder<'a> {
         self
     }
 
+    /// Also build a pipeline without depth stencil state, used by passes like blur and downsample.
+    pub(crate) fn with_plain(mut self, plain: bool) -> Self {
+        self.plain = plain;
+        self
+    }
+
     pub(crate) fn build(&self, shader: &wgpu::ShaderModule, device: &wgpu::Device) -> Pipeline {
         let color_target = if self.color_writable {
             wgpu::ColorTargetState {
                 format: self.format,
-                blend: Some(wgpu::BlendState {
-                    color: wgpu::BlendComponent {
-                        src_factor: wgpu::BlendFactor::One,
-                        dst_factor: wgpu::BlendFactor::OneMinusSrcAlpha,
-                        operation: wgpu::BlendOperation::Add,
-                    },
-                    alpha: wgpu::BlendComponent {
-                        src_factor: wgpu::BlendFactor::One,
-                        dst_factor: wgpu::BlendFactor::OneMinusSrcAlpha,
-                        operation: wgpu::BlendOperation::Add,
-                    },
-                }),
+                blend: Some(blend_state_for(self.blend_mode)),
                 write_mask: wgpu::ColorWrites::ALL,
             }
         } else {
@@ -115,53 +194,59 @@ impl<'a> PipelineBuilder<'a> {
             push_constant_ranges: &[],
         });
 
+        let create_pipeline = |state: Option<wgpu::DepthStencilState>| {
+            device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: None,
+                layout: Some(&layout),
+                vertex: wgpu::VertexState {
+                    module: shader,
+                    entry_point: Some("vs_main"),
+                    compilation_options: Default::default(),
+                    buffers: self.buffers.as_slice(),
+                },
+                primitive: wgpu::PrimitiveState {
+                    topology: wgpu::PrimitiveTopology::TriangleList,
+                    strip_index_format: None,
+                    front_face: wgpu::FrontFace::Ccw,
+                    cull_mode: None,
+                    polygon_mode: wgpu::PolygonMode::Fill,
+                    unclipped_depth: false,
+                    conservative: false,
+                },
+                depth_stencil: state,
+                multisample: wgpu::MultisampleState {
+                    count: self.sample_count,
+                    mask: !0,
+                    alpha_to_coverage_enabled: false,
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: shader,
+                    entry_point: Some("fs_main"),
+                    compilation_options: Default::default(),
+                    targets: &[Some(color_target.clone())],
+                }),
+                multiview: None,
+                cache: None,
+            })
+        };
+
         let pipelins = self
             .states
             .iter()
-            .map(|s| {
-                (
-                    s.clone(),
-                    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-                        label: None,
-                        layout: Some(&layout),
-                        vertex: wgpu::VertexState {
-                            module: shader,
-                            entry_point: Some("vs_main"),
-                            compilation_options: Default::default(),
-                            buffers: self.buffers.as_slice(),
-                        },
-                        primitive: wgpu::PrimitiveState {
-                            topology: wgpu::PrimitiveTopology::TriangleList,
-                            strip_index_format: None,
-                            front_face: wgpu::FrontFace::Ccw,
-                            cull_mode: None,
-                            polygon_mode: wgpu::PolygonMode::Fill,
-                            unclipped_depth: false,
-                            conservative: false,
-                        },
-                        depth_stencil: Some(s.clone()),
-                        multisample: wgpu::MultisampleState {
-                            count: self.sample_count,
-                            mask: !0,
-                            alpha_to_coverage_enabled: false,
-                        },
-                        fragment: Some(wgpu::FragmentState {
-                            module: shader,
-                            entry_point: Some("fs_main"),
-                            compilation_options: Default::default(),
-                            targets: &[Some(color_target.clone())],
-                        }),
-                        multiview: None,
-                        cache: None,
-                    }),
-                )
-            })
+            .map(|s| (s.clone(), create_pipeline(Some(s.clone()))))
             .collect::<HashMap<_, _>>();
 
+        let plain = if self.plain {
+            Some(create_pipeline(None))
+        } else {
+            None
+        };
+
         Pipeline {
             groups: bind_groups,
             _layout: layout,
             pipelines: pipelins,
+            plain,
         }
     }
 }
@@ -178,7 +263,13 @@ mod tests {
         let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
             label: Some("test shader"),
             source: wgpu::ShaderSource::Wgsl(
-                include_str!("../render/shaders/solid_color.wgsl").into(),
+                concat!(
+                    include_str!("../render/shaders/no_color_filter.wgsl"),
+                    include_str!("../render/shaders/no_color_space.wgsl"),
+                    include_str!("../render/shaders/no_mask.wgsl"),
+                    include_str!("../render/shaders/solid_color.wgsl")
+                )
+                .into(),
             ),
         });
 
@@ -188,13 +279,20 @@ mod tests {
             .with_format(wgpu::TextureFormat::Bgra8Unorm)
             // buffer layout
             .add_buffer(wgpu::VertexBufferLayout {
-                array_stride: 8,
+                array_stride: 12,
                 step_mode: wgpu::VertexStepMode::Vertex,
-                attributes: &[wgpu::VertexAttribute {
-                    offset: 0,
-                    shader_location: 0,
-                    format: wgpu::VertexFormat::Float32x2,
-                }],
+                attributes: &[
+                    wgpu::VertexAttribute {
+                        offset: 0,
+                        shader_location: 0,
+                        format: wgpu::VertexFormat::Float32x2,
+                    },
+                    wgpu::VertexAttribute {
+                        offset: 8,
+                        shader_location: 1,
+                        format: wgpu::VertexFormat::Float32,
+                    },
+                ],
             })
             // group 0
             .add_group(vec![wgpu::BindGroupLayoutEntry {
@@ -296,54 +394,58 @@ mod tests {
 
         assert_eq!(pipeline.pipelines.len(), 3);
 
-        assert!(pipeline
-            .get_pipeline(&wgpu::DepthStencilState {
-                format: wgpu::TextureFormat::Depth24PlusStencil8,
-                depth_write_enabled: false,
-                depth_compare: wgpu::CompareFunction::Never,
-                stencil: wgpu::StencilState {
-                    front: wgpu::StencilFaceState {
-                        compare: wgpu::CompareFunction::Never,
-                        fail_op: wgpu::StencilOperation::Keep,
-                        depth_fail_op: wgpu::StencilOperation::Keep,
-                        pass_op: wgpu::StencilOperation::Keep,
-                    },
-                    back: wgpu::StencilFaceState {
-                        compare: wgpu::CompareFunction::Never,
-                        fail_op: wgpu::StencilOperation::Keep,
-                        depth_fail_op: wgpu::StencilOperation::Keep,
-                        pass_op: wgpu::StencilOperation::Keep,
-                    },
-                    read_mask: 0xff,
-                    write_mask: 0xff,
-                },
-                bias: Default::default(),
-            })
-            .is_some());
-
-        assert!(pipeline
-            .get_pipeline(&wgpu::DepthStencilState {
-                format: wgpu::TextureFormat::Depth24PlusStencil8,
-                depth_write_enabled: false,
-                depth_compare: wgpu::CompareFunction::Never,
-                stencil: wgpu::StencilState {
-                    front: wgpu::StencilFaceState {
-                        compare: wgpu::CompareFunction::NotEqual,
-                        fail_op: wgpu::StencilOperation::Keep,
-                        depth_fail_op: wgpu::StencilOperation::Keep,
-                        pass_op: wgpu::StencilOperation::Replace,
+        assert!(
+            pipeline
+                .get_pipeline(&wgpu::DepthStencilState {
+                    format: wgpu::TextureFormat::Depth24PlusStencil8,
+                    depth_write_enabled: false,
+                    depth_compare: wgpu::CompareFunction::Never,
+                    stencil: wgpu::StencilState {
+                        front: wgpu::StencilFaceState {
+                            compare: wgpu::CompareFunction::Never,
+                            fail_op: wgpu::StencilOperation::Keep,
+                            depth_fail_op: wgpu::StencilOperation::Keep,
+                            pass_op: wgpu::StencilOperation::Keep,
+                        },
+                        back: wgpu::StencilFaceState {
+                            compare: wgpu::CompareFunction::Never,
+                            fail_op: wgpu::StencilOperation::Keep,
+                            depth_fail_op: wgpu::StencilOperation::Keep,
+                            pass_op: wgpu::StencilOperation::Keep,
+                        },
+                        read_mask: 0xff,
+                        write_mask: 0xff,
                     },
-                    back: wgpu::StencilFaceState {
-                        compare: wgpu::CompareFunction::NotEqual,
-                        fail_op: wgpu::StencilOperation::Keep,
-                        depth_fail_op: wgpu::StencilOperation::Keep,
-                        pass_op: wgpu::StencilOperation::Replace,
+                    bias: Default::default(),
+                })
+                .is_some()
+        );
+
+        assert!(
+            pipeline
+                .get_pipeline(&wgpu::DepthStencilState {
+                    format: wgpu::TextureFormat::Depth24PlusStencil8,
+                    depth_write_enabled: false,
+                    depth_compare: wgpu::CompareFunction::Never,
+                    stencil: wgpu::StencilState {
+                        front: wgpu::StencilFaceState {
+                            compare: wgpu::CompareFunction::NotEqual,
+                            fail_op: wgpu::StencilOperation::Keep,
+                            depth_fail_op: wgpu::StencilOperation::Keep,
+                            pass_op: wgpu::StencilOperation::Replace,
+                        },
+                        back: wgpu::StencilFaceState {
+                            compare: wgpu::CompareFunction::NotEqual,
+                            fail_op: wgpu::StencilOperation::Keep,
+                            depth_fail_op: wgpu::StencilOperation::Keep,
+                            pass_op: wgpu::StencilOperation::Replace,
+                        },
+                        read_mask: 0xff,
+                        write_mask: 0xff,
                     },
-                    read_mask: 0xff,
-                    write_mask: 0xff,
-                },
-                bias: Default::default(),
-            })
-            .is_some());
+                    bias: Default::default(),
+                })
+                .is_some()
+        );
     }
 }
@@ -1,19 +1,50 @@
+use nalgebra::Matrix4;
+
 use crate::{
-    core::Picture,
+    core::{picture::gen_renders, ColorSpace, Picture, Rect},
     gpu::{buffer::StageBuffer, GPUContext},
-    render::{fragment::NON_COLOR_PIPELINE_NAME, CommandList, Renderer},
+    render::{fragment::NON_COLOR_PIPELINE_NAME, CommandList, RenderTarget, Renderer},
 };
 
+/// Defines how edges of geometry are anti-aliased by a surface.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AntiAlias {
+    /// Edges are not anti-aliased.
+    None,
+    /// Multisample anti-alias with the given sample count. The surface allocates a msaa texture and a
+    /// depth stencil with the sample count, which costs memory on large targets.
+    Msaa(u32),
+    /// Fills and strokes are drawn with feathered edge fringes which fade out over one pixel. It needs no
+    /// extra memory for the target. Clips are feathered with a coverage texture of the clip bounds, text and
+    /// draws with a mask filter keep hard clip edges.
+    Analytic,
+}
+
+impl AntiAlias {
+    /// Returns the sample count of the target and depth stencil.
+    pub fn sample_count(&self) -> u32 {
+        match self {
+            AntiAlias::Msaa(count) => (*count).max(1),
+            _ => 1,
+        }
+    }
+}
+
 /// A surface is a wrap around a wgpu::Texture. which can be used to render contents.
 pub struct GPUSurface<'a> {
     target: &'a wgpu::Texture,
-    anti_alias: bool,
+    anti_alias: AntiAlias,
+    color_space: ColorSpace,
+    linear_blending: bool,
+    text_gamma: [f32; 2],
     depth_stencil: wgpu::Texture,
     msaa_texture: Option<wgpu::Texture>,
     logical_width: f32,
     logical_height: f32,
 
     renders: Vec<Box<dyn Renderer>>,
+    /// number of depth values used by the replayed pictures
+    depth_count: u32,
 }
 
 impl<'a> GPUSurface<'a> {
@@ -24,17 +55,18 @@ impl<'a> GPUSurface<'a> {
     /// * `target` - The wgpu::Texture to be wrapped.
     /// * `logical_width` - The width of the surface in logical it can be different from actually texture size.
     /// * `logical_height` - The height of the surface in logical it can be different from actually texture size.
-    /// * `anti_alias` - Whether to use anti-alias we provide msaa with sample count 4.
+    /// * `anti_alias` - How edges are anti-aliased, `AntiAlias::Msaa(4)` matches the former `true`.
     /// * `device` - The wgpu::Device used to create other GPU resources.
     pub fn new(
         target: &'a wgpu::Texture,
         logical_width: f32,
         logical_height: f32,
-        anti_alias: bool,
+        anti_alias: AntiAlias,
         device: &wgpu::Device,
     ) -> Self {
         let width = target.width();
         let height = target.height();
+        let sample_count = anti_alias.sample_count();
 
         let depth_stencil = device.create_texture(&wgpu::TextureDescriptor {
             label: Some("depth stencil"),
@@ -44,28 +76,20 @@ impl<'a> GPUSurface<'a> {
                 depth_or_array_layers: 1,
             },
             mip_level_count: 1,
-            sample_count: if anti_alias { 4 } else { 1 },
+            sample_count,
             dimension: wgpu::TextureDimension::D2,
             usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
             format: wgpu::TextureFormat::Depth24PlusStencil8,
             view_formats: &[wgpu::TextureFormat::Depth24PlusStencil8],
         });
 
-        let msaa_texture = if anti_alias {
-            Some(device.create_texture(&wgpu::TextureDescriptor {
-                label: Some("msaa"),
-                size: wgpu::Extent3d {
-                    width,
-                    height,
-                    depth_or_array_layers: 1,
-                },
-                mip_level_count: 1,
-                sample_count: 4,
-                dimension: wgpu::TextureDimension::D2,
-                usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
-                format: target.format(),
-                view_formats: &[target.format()],
-            }))
+        let msaa_texture = if sample_count > 1 {
+            Some(create_msaa_texture(
+                target,
+                target.format(),
+                sample_count,
+                device,
+            ))
         } else {
             None
         };
@@ -73,26 +97,118 @@ impl<'a> GPUSurface<'a> {
         GPUSurface {
             target,
             anti_alias,
+            color_space: ColorSpace::Srgb,
+            linear_blending: false,
+            text_gamma: [2.2, 0.0],
             depth_stencil,
             msaa_texture,
             logical_width,
             logical_height,
             renders: Vec::new(),
+            depth_count: 0,
+        }
+    }
+
+    /// Declares the color space of the target texture. Colors and images are converted from sRGB into it
+    /// when drawing. Defaults to [`ColorSpace::Srgb`].
+    /// Must be called before replaying pictures.
+    ///
+    /// # Arguments
+    ///
+    /// * `color_space` - The color space of the values stored in the target texture.
+    pub fn with_color_space(mut self, color_space: ColorSpace) -> Self {
+        self.color_space = color_space;
+        self
+    }
+
+    /// Returns the color space of the target texture.
+    pub fn color_space(&self) -> ColorSpace {
+        self.color_space
+    }
+
+    /// Blends and resolves anti-aliased edges in linear light instead of in sRGB encoded values.
+    /// The target is rendered through a view with the sRGB variant of its format, so the target must list it
+    /// in `view_formats`, the msaa texture uses the sRGB format. Targets with an sRGB format always blend in linear
+    /// light, formats without sRGB variant ignore this option. The clear color passed to
+    /// [`GPUSurface::flush`] is in linear light when blending in linear light.
+    /// Must be called before replaying pictures.
+    ///
+    /// # Arguments
+    ///
+    /// * `linear_blending` - Whether to blend in linear light.
+    pub fn with_linear_blending(mut self, linear_blending: bool) -> Self {
+        self.linear_blending = linear_blending;
+        self
+    }
+
+    /// Sets how glyph coverage is adjusted when blending in linear light. Linear blending makes dark text
+    /// look thin and light text look bold, the coverage is shifted towards the result of blending in
+    /// sRGB. The default gamma 2.2 matches the weight of text blended in sRGB, 1.0 keeps the coverage.
+    /// Must be called before replaying pictures.
+    ///
+    /// # Arguments
+    ///
+    /// * `gamma` - The gamma applied to glyph coverage based on text luminance.
+    /// * `contrast` - Extra coverage added to partially covered pixels, between [0.0, 1.0].
+    pub fn with_text_gamma(mut self, gamma: f32, contrast: f32) -> Self {
+        self.text_gamma = [gamma.max(1.0), contrast.clamp(0.0, 1.0)];
+        self
+    }
+
+    /// Returns the format pipelines render into. The sRGB variant when blending in linear light.
+    fn view_format(&self) -> wgpu::TextureFormat {
+        if self.linear_blending {
+            self.target.format().add_srgb_suffix()
+        } else {
+            self.target.format()
         }
     }
 
     /// Replay a picture's draw commands to the surface.
     pub fn replay(&mut self, picture: &Picture) {
-        let depth_offset = self.renders.len() as u32;
-
-        for draw in &picture.draws {
-            self.renders.push(draw.gen_render(
-                self.logical_width,
-                self.logical_height,
-                self.target.format(),
-                self.anti_alias,
-                depth_offset,
-            ));
+        let depth_offset = self.depth_count;
+        let target = self.render_target();
+
+        self.renders
+            .extend(gen_renders(&picture.draws, &target, depth_offset));
+        self.depth_count += picture.depth_count();
+    }
+
+    /// Replay the draw commands of a picture which may be visible inside a rect. Draws whose bounds miss
+    /// the rect are skipped without being tessellated, which is useful when only a viewport of a large
+    /// picture is shown.
+    ///
+    /// # Arguments
+    ///
+    /// * `picture` - The picture to replay.
+    /// * `visible` - The visible rect in the logical coordinates of the surface.
+    pub fn replay_rect(&mut self, picture: &Picture, visible: Rect) {
+        let depth_offset = self.depth_count;
+        let target = self.render_target();
+
+        // skipped draws leave their depth unused, so draws keep their order with clips
+        let draws = picture
+            .rtree
+            .search(&visible)
+            .into_iter()
+            .map(|index| &picture.draws[index]);
+
+        self.renders
+            .extend(gen_renders(draws, &target, depth_offset));
+        self.depth_count += picture.depth_count();
+    }
+
+    fn render_target(&self) -> RenderTarget {
+        RenderTarget {
+            width: self.logical_width,
+            height: self.logical_height,
+            scale_x: self.target.width() as f32 / self.logical_width,
+            scale_y: self.target.height() as f32 / self.logical_height,
+            format: self.view_format(),
+            anti_alias: self.anti_alias,
+            color_space: self.color_space,
+            text_gamma: self.text_gamma,
+            base_transform: Matrix4::identity(),
         }
     }
 
@@ -111,6 +227,21 @@ impl<'a> GPUSurface<'a> {
         queue: &wgpu::Queue,
         clear_color: Option<wgpu::Color>,
     ) {
+        // msaa texture is created before linear blending is known, recreate it in the format of the view
+        let view_format = self.view_format();
+        if self
+            .msaa_texture
+            .as_ref()
+            .is_some_and(|msaa| msaa.format() != view_format)
+        {
+            self.msaa_texture = Some(create_msaa_texture(
+                self.target,
+                view_format,
+                self.anti_alias.sample_count(),
+                device,
+            ));
+        }
+
         let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
             label: Some("flush"),
         });
@@ -122,20 +253,14 @@ impl<'a> GPUSurface<'a> {
         // load non color pipeline before visit all renders.
         context.load_pipeline(
             NON_COLOR_PIPELINE_NAME,
-            self.target.format(),
-            self.anti_alias,
+            self.render_target().pipeline_key(),
             device,
         );
 
-        let total_depth = (self.renders.len() + 1) as f32;
+        let total_depth = (self.depth_count + 1) as f32;
 
         for render in &mut self.renders {
-            context.load_pipeline(
-                render.as_ref().pipeline_label(),
-                self.target.format(),
-                self.anti_alias,
-                device,
-            );
+            render.as_ref().load_pipelines(context, device);
 
             render
                 .as_mut()
@@ -144,6 +269,13 @@ impl<'a> GPUSurface<'a> {
 
         let gpu_buffer = stage_buffer.gen_gpu_buffer(device, queue);
 
+        // offscreen layers must be rendered before the onscreen pass samples them.
+        for render in &self.renders {
+            render
+                .as_ref()
+                .render_offscreen(&gpu_buffer, context, device, &mut encoder);
+        }
+
         let mut command_list = CommandList::new();
         for render in &mut self.renders {
             let commands = render.as_mut().render(&gpu_buffer, context, device);
@@ -151,17 +283,18 @@ impl<'a> GPUSurface<'a> {
         }
 
         {
-            let mut pass = self.begin_render_pass(
+            let mut pass = begin_render_pass(
+                "OnScreen render pass",
                 &target_view,
                 &depth_stencil_view,
-                &msaa_view.as_ref(),
+                msaa_view.as_ref(),
                 &mut encoder,
                 clear_color,
             );
 
             pass.set_stencil_reference(0);
 
-            command_list.run(&mut pass);
+            command_list.run(&mut pass, [self.target.width(), self.target.height()]);
         }
 
         queue.submit([encoder.finish()]);
@@ -174,9 +307,10 @@ impl<'a> GPUSurface<'a> {
         wgpu::TextureView,
         Option<wgpu::TextureView>,
     ) {
-        let target_view = self
-            .target
-            .create_view(&wgpu::TextureViewDescriptor::default());
+        let target_view = self.target.create_view(&wgpu::TextureViewDescriptor {
+            format: Some(self.view_format()),
+            ..Default::default()
+        });
 
         let depth_stencil_view = self
             .depth_stencil
@@ -191,71 +325,212 @@ impl<'a> GPUSurface<'a> {
 
         return (target_view, depth_stencil_view, msaa_view);
     }
+}
 
-    fn begin_render_pass(
-        &self,
-        target: &'a wgpu::TextureView,
-        depth_stencil: &'a wgpu::TextureView,
-        msaa: &Option<&'a wgpu::TextureView>,
-        encoder: &'a mut wgpu::CommandEncoder,
-        clear_color: Option<wgpu::Color>,
-    ) -> wgpu::RenderPass<'a> {
-        if self.anti_alias {
-            encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-                label: Some("OnScreen render pass"),
-                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                    view: msaa.unwrap(),
-                    resolve_target: Some(&target),
-                    ops: wgpu::Operations {
-                        load: match clear_color {
-                            Some(clear_color) => wgpu::LoadOp::Clear(clear_color),
-                            None => wgpu::LoadOp::Load,
-                        },
-                        store: wgpu::StoreOp::Store,
-                    },
-                })],
-                depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
-                    view: &depth_stencil,
-                    depth_ops: Some(wgpu::Operations {
-                        load: wgpu::LoadOp::Clear(0.0),
-                        store: wgpu::StoreOp::Discard,
-                    }),
-                    stencil_ops: Some(wgpu::Operations {
-                        load: wgpu::LoadOp::Clear(0),
-                        store: wgpu::StoreOp::Discard,
-                    }),
-                }),
-                timestamp_writes: None,
-                occlusion_query_set: None,
-            })
-        } else {
-            encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-                label: Some("OnScreen render pass"),
-                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                    view: &target,
-                    resolve_target: None,
-                    ops: wgpu::Operations {
-                        load: match clear_color {
-                            Some(clear_color) => wgpu::LoadOp::Clear(clear_color),
-                            None => wgpu::LoadOp::Load,
-                        },
-                        store: wgpu::StoreOp::Store,
-                    },
-                })],
-                depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
-                    view: &depth_stencil,
-                    depth_ops: Some(wgpu::Operations {
-                        load: wgpu::LoadOp::Clear(0.0),
-                        store: wgpu::StoreOp::Discard,
-                    }),
-                    stencil_ops: Some(wgpu::Operations {
-                        load: wgpu::LoadOp::Clear(0),
-                        store: wgpu::StoreOp::Discard,
-                    }),
-                }),
-                timestamp_writes: None,
-                occlusion_query_set: None,
-            })
-        }
+fn create_msaa_texture(
+    target: &wgpu::Texture,
+    format: wgpu::TextureFormat,
+    sample_count: u32,
+    device: &wgpu::Device,
+) -> wgpu::Texture {
+    device.create_texture(&wgpu::TextureDescriptor {
+        label: Some("msaa"),
+        size: wgpu::Extent3d {
+            width: target.width(),
+            height: target.height(),
+            depth_or_array_layers: 1,
+        },
+        mip_level_count: 1,
+        sample_count,
+        dimension: wgpu::TextureDimension::D2,
+        usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+        format,
+        view_formats: &[format],
+    })
+}
+
+/// Begins a render pass on target with a depth stencil attachment.
+/// If `msaa` is not `None`, the pass renders into it and resolves to target.
+pub(crate) fn begin_render_pass<'a>(
+    label: &str,
+    target: &wgpu::TextureView,
+    depth_stencil: &wgpu::TextureView,
+    msaa: Option<&wgpu::TextureView>,
+    encoder: &'a mut wgpu::CommandEncoder,
+    clear_color: Option<wgpu::Color>,
+) -> wgpu::RenderPass<'a> {
+    let (view, resolve_target) = match msaa {
+        Some(msaa) => (msaa, Some(target)),
+        None => (target, None),
+    };
+
+    encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+        label: Some(label),
+        color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+            view,
+            resolve_target,
+            ops: wgpu::Operations {
+                load: match clear_color {
+                    Some(clear_color) => wgpu::LoadOp::Clear(clear_color),
+                    None => wgpu::LoadOp::Load,
+                },
+                store: wgpu::StoreOp::Store,
+            },
+        })],
+        depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+            view: depth_stencil,
+            depth_ops: Some(wgpu::Operations {
+                load: wgpu::LoadOp::Clear(0.0),
+                store: wgpu::StoreOp::Discard,
+            }),
+            stencil_ops: Some(wgpu::Operations {
+                load: wgpu::LoadOp::Clear(0),
+                store: wgpu::StoreOp::Discard,
+            }),
+        }),
+        timestamp_writes: None,
+        occlusion_query_set: None,
+    })
+}
+
+/// Begins a render pass on target without depth stencil attachment. The target is cleared to transparent.
+/// Used by full target passes like blur and downsample.
+pub(crate) fn begin_filter_pass<'a>(
+    label: &str,
+    target: &wgpu::TextureView,
+    encoder: &'a mut wgpu::CommandEncoder,
+) -> wgpu::RenderPass<'a> {
+    encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+        label: Some(label),
+        color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+            view: target,
+            resolve_target: None,
+            ops: wgpu::Operations {
+                load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                store: wgpu::StoreOp::Store,
+            },
+        })],
+        depth_stencil_attachment: None,
+        timestamp_writes: None,
+        occlusion_query_set: None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        core::{ClipOp, Paint, PictureRecorder},
+        gpu::init_test_context,
+    };
+
+    #[test]
+    fn linear_blending_view_format() {
+        let (device, _queue) = init_test_context();
+
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("target"),
+            size: wgpu::Extent3d {
+                width: 16,
+                height: 16,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Rgba8Unorm,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            view_formats: &[],
+        });
+
+        let surface = GPUSurface::new(&texture, 16.0, 16.0, AntiAlias::None, &device);
+        assert_eq!(surface.view_format(), wgpu::TextureFormat::Rgba8Unorm);
+        assert_eq!(
+            surface.render_target().format,
+            wgpu::TextureFormat::Rgba8Unorm
+        );
+        assert_eq!(surface.render_target().text_gamma, [2.2, 0.0]);
+
+        let surface = surface
+            .with_linear_blending(true)
+            .with_text_gamma(1.8, 0.25);
+        assert_eq!(surface.view_format(), wgpu::TextureFormat::Rgba8UnormSrgb);
+        assert_eq!(
+            surface.render_target().format,
+            wgpu::TextureFormat::Rgba8UnormSrgb
+        );
+        assert_eq!(surface.render_target().text_gamma, [1.8, 0.25]);
+
+        let surface = surface
+            .with_linear_blending(false)
+            .with_text_gamma(0.5, 2.0);
+        assert_eq!(surface.view_format(), wgpu::TextureFormat::Rgba8Unorm);
+        assert_eq!(surface.render_target().text_gamma, [1.0, 1.0]);
+    }
+
+    #[test]
+    fn msaa_scissor_keeps_outside_pixels() {
+        let (device, queue) = init_test_context();
+        let mut context = GPUContext::new(&device);
+
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("target"),
+            size: wgpu::Extent3d {
+                width: 64,
+                height: 64,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Rgba8Unorm,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+            view_formats: &[],
+        });
+
+        // the last draw of the frame is inside a rect clip, which is replaced with scissor
+        let mut recorder = PictureRecorder::new();
+        recorder.clip_rect(&Rect::from_xywh(8.0, 8.0, 16.0, 16.0), ClipOp::Intersect);
+        recorder.draw_rect(&Rect::from_xywh(0.0, 0.0, 64.0, 64.0), &Paint::new());
+        let picture = recorder.finish_record();
+
+        let mut surface = GPUSurface::new(&texture, 64.0, 64.0, AntiAlias::Msaa(4), &device);
+        surface.replay(&picture);
+        surface.flush(&mut context, &device, &queue, Some(wgpu::Color::WHITE));
+
+        // 64 pixels of 4 bytes fill the 256 bytes row alignment
+        let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("read back"),
+            size: 256 * 64,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("read back"),
+        });
+        encoder.copy_texture_to_buffer(
+            texture.as_image_copy(),
+            wgpu::ImageCopyBuffer {
+                buffer: &buffer,
+                layout: wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(256),
+                    rows_per_image: None,
+                },
+            },
+            texture.size(),
+        );
+        queue.submit([encoder.finish()]);
+
+        buffer.slice(..).map_async(wgpu::MapMode::Read, |_| {});
+        device.poll(wgpu::Maintain::Wait);
+
+        let data = buffer.slice(..).get_mapped_range();
+        let pixel = |x: usize, y: usize| &data[y * 256 + x * 4..y * 256 + x * 4 + 4];
+
+        assert_eq!(pixel(16, 16), [0, 0, 0, 255]);
+        assert_eq!(pixel(40, 40), [255, 255, 255, 255]);
+        assert_eq!(pixel(2, 2), [255, 255, 255, 255]);
     }
 }
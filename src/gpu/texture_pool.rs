@@ -0,0 +1,131 @@
+use std::rc::Rc;
+
+/// Max number of idle textures kept by the pool.
+const MAX_IDLE_TEXTURES: usize = 32;
+
+/// Describes an offscreen texture requested from the pool.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct TextureDesc {
+    pub(crate) width: u32,
+    pub(crate) height: u32,
+    pub(crate) sample_count: u32,
+    pub(crate) format: wgpu::TextureFormat,
+    pub(crate) usage: wgpu::TextureUsages,
+}
+
+impl TextureDesc {
+    /// Describes a single sampled texture which can be rendered into and sampled from.
+    pub(crate) fn offscreen(width: u32, height: u32, format: wgpu::TextureFormat) -> Self {
+        Self {
+            width,
+            height,
+            sample_count: 1,
+            format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+        }
+    }
+
+    /// Describes a render attachment only texture, such as msaa or depth stencil.
+    pub(crate) fn attachment(
+        width: u32,
+        height: u32,
+        sample_count: u32,
+        format: wgpu::TextureFormat,
+    ) -> Self {
+        Self {
+            width,
+            height,
+            sample_count,
+            format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+        }
+    }
+
+    fn matches(&self, texture: &wgpu::Texture) -> bool {
+        texture.width() == self.width
+            && texture.height() == self.height
+            && texture.sample_count() == self.sample_count
+            && texture.format() == self.format
+            && texture.usage() == self.usage
+    }
+}
+
+/// Reuses offscreen textures between flushes.
+/// A texture is idle and can be handed out again once no renderer holds it.
+pub(crate) struct TexturePool {
+    textures: Vec<Rc<wgpu::Texture>>,
+}
+
+impl TexturePool {
+    pub(crate) fn new() -> Self {
+        Self {
+            textures: Vec::new(),
+        }
+    }
+
+    pub(crate) fn acquire(
+        &mut self,
+        label: &str,
+        desc: &TextureDesc,
+        device: &wgpu::Device,
+    ) -> Rc<wgpu::Texture> {
+        if let Some(texture) = self
+            .textures
+            .iter()
+            .find(|t| Rc::strong_count(t) == 1 && desc.matches(t))
+        {
+            return texture.clone();
+        }
+
+        self.purge_idle(MAX_IDLE_TEXTURES - 1);
+
+        let texture = Rc::new(device.create_texture(&wgpu::TextureDescriptor {
+            label: Some(label),
+            size: wgpu::Extent3d {
+                width: desc.width,
+                height: desc.height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: desc.sample_count,
+            dimension: wgpu::TextureDimension::D2,
+            usage: desc.usage,
+            format: desc.format,
+            view_formats: &[desc.format],
+        }));
+
+        self.textures.push(texture.clone());
+
+        texture
+    }
+
+    /// Drops the oldest idle textures until at most `count` idle textures are left.
+    pub(crate) fn purge_idle(&mut self, count: usize) {
+        let mut idle = self
+            .textures
+            .iter()
+            .filter(|t| Rc::strong_count(t) == 1)
+            .count();
+
+        self.textures.retain(|t| {
+            if idle > count && Rc::strong_count(t) == 1 {
+                idle -= 1;
+                return false;
+            }
+
+            true
+        });
+    }
+
+    /// Returns the bytes used by all textures in the pool.
+    pub(crate) fn get_total_memory(&self) -> usize {
+        self.textures
+            .iter()
+            .map(|t| {
+                let bytes = t.format().block_copy_size(None).unwrap_or(4);
+
+                (t.width() * t.height() * t.sample_count() * bytes) as usize
+            })
+            .sum()
+    }
+}
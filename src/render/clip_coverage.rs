@@ -0,0 +1,213 @@
+use std::{cell::RefCell, rc::Rc};
+
+use nalgebra::Matrix4;
+
+use crate::{
+    core::{BlurStyle, ColorSpace, Rect},
+    gpu::{
+        buffer::StageBuffer, surface::begin_render_pass, texture_pool::TextureDesc, GPUContext,
+    },
+};
+
+use super::{command::Command, fragment::MaskStage, CommandList, RenderTarget, Renderer};
+
+/// Coverage of the clip stack with feathered edges, stored in the red channel of an offscreen texture.
+/// It is shared by the clip which renders it and the draws inside the clip scope, which multiply their
+/// output with it.
+/// Aliased draws on a multisampled target share one as well, holding the coverage drawn without multisample.
+#[derive(Clone)]
+pub(crate) struct ClipCoverage {
+    /// bounds of the coverage in pixels of the target the scoped draws are replayed into
+    bounds: Rect,
+    /// created when the clip is prepared, before the scoped draws are prepared
+    texture: Rc<RefCell<Option<Rc<wgpu::Texture>>>>,
+}
+
+impl ClipCoverage {
+    /// Computes the coverage of a clip replayed into `target`.
+    /// Nested clips share the bounds of their parent, so the parent coverage can be sampled in place.
+    ///
+    /// # Arguments
+    ///
+    /// * `target` the target the clip is replayed into
+    /// * `bounds` device space bounds of the clip path, `None` means the clip may cover the whole target
+    /// * `parent` coverage of the clip the new clip is nested in
+    pub(crate) fn new(
+        target: &RenderTarget,
+        bounds: Option<&Rect>,
+        parent: Option<&ClipCoverage>,
+    ) -> Self {
+        let viewport = Rect::from_xywh(
+            0.0,
+            0.0,
+            (target.width * target.scale_x).round(),
+            (target.height * target.scale_y).round(),
+        );
+
+        let bounds = match (parent, bounds) {
+            (Some(parent), _) => Some(parent.bounds),
+            (None, Some(b)) => {
+                // the fringe reaches half a pixel outside of the path
+                let mut pixels = Rect::from_ltrb(
+                    b.left * target.scale_x,
+                    b.top * target.scale_y,
+                    b.right * target.scale_x,
+                    b.bottom * target.scale_y,
+                );
+                pixels.outset(1.0, 1.0);
+
+                pixels.round_out().intersect(&viewport)
+            }
+            (None, None) => Some(viewport),
+        };
+
+        Self {
+            bounds: bounds.unwrap_or(Rect::from_ltrb(0.0, 0.0, 0.0, 0.0)),
+            texture: Rc::new(RefCell::new(None)),
+        }
+    }
+
+    /// Returns the coverage as seen by draws inside the coverage target of a nested clip.
+    pub(crate) fn nested(&self) -> Self {
+        Self {
+            bounds: Rect::from_xywh(0.0, 0.0, self.bounds.width(), self.bounds.height()),
+            texture: self.texture.clone(),
+        }
+    }
+
+    /// Returns the target which the coverage of the clip should be drawn into.
+    pub(crate) fn coverage_target(&self, target: &RenderTarget) -> RenderTarget {
+        RenderTarget {
+            width: self.bounds.width() / target.scale_x,
+            height: self.bounds.height() / target.scale_y,
+            format: wgpu::TextureFormat::R8Unorm,
+            color_space: ColorSpace::Srgb,
+            base_transform: Matrix4::new_translation(&nalgebra::Vector3::new(
+                -self.bounds.left / target.scale_x,
+                -self.bounds.top / target.scale_y,
+                0.0,
+            )) * target.base_transform,
+            ..*target
+        }
+    }
+
+    /// Returns the mask stage multiplying the output of a scoped draw with the coverage.
+    pub(crate) fn mask_stage(&self) -> MaskStage {
+        MaskStage::new(self.bounds, BlurStyle::Normal)
+    }
+
+    pub(crate) fn texture(&self) -> Option<Rc<wgpu::Texture>> {
+        self.texture.borrow().clone()
+    }
+}
+
+/// Draws the coverage of a clip into the texture of a `ClipCoverage`.
+pub(crate) struct ClipCoverageRenderer {
+    coverage: ClipCoverage,
+    /// the coverage outside of the draws
+    clear_color: wgpu::Color,
+    /// draws replayed into the coverage target, in order
+    renders: Vec<Box<dyn Renderer>>,
+    depth_stencil: Option<Rc<wgpu::Texture>>,
+}
+
+impl ClipCoverageRenderer {
+    pub(crate) fn new(
+        coverage: ClipCoverage,
+        clear_color: wgpu::Color,
+        renders: Vec<Box<dyn Renderer>>,
+    ) -> Self {
+        Self {
+            coverage,
+            clear_color,
+            renders,
+            depth_stencil: None,
+        }
+    }
+}
+
+impl Renderer for ClipCoverageRenderer {
+    fn load_pipelines(&self, context: &mut GPUContext, device: &wgpu::Device) {
+        for render in &self.renders {
+            render.load_pipelines(context, device);
+        }
+    }
+
+    fn prepare(
+        &mut self,
+        _total_depth: f32,
+        buffer: &mut StageBuffer,
+        context: &GPUContext,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+    ) {
+        let width = self.coverage.bounds.width() as u32;
+        let height = self.coverage.bounds.height() as u32;
+
+        if width == 0 || height == 0 {
+            return;
+        }
+
+        *self.coverage.texture.borrow_mut() = Some(context.acquire_texture(
+            "clip coverage",
+            &TextureDesc::offscreen(width, height, wgpu::TextureFormat::R8Unorm),
+            device,
+        ));
+
+        self.depth_stencil = Some(context.acquire_texture(
+            "clip coverage depth stencil",
+            &TextureDesc::attachment(width, height, 1, wgpu::TextureFormat::Depth24PlusStencil8),
+            device,
+        ));
+
+        let total_depth = (self.renders.len() + 1) as f32;
+
+        for render in &mut self.renders {
+            render.prepare(total_depth, buffer, context, device, queue);
+        }
+    }
+
+    fn render_offscreen(
+        &self,
+        buffer: &wgpu::Buffer,
+        context: &GPUContext,
+        device: &wgpu::Device,
+        encoder: &mut wgpu::CommandEncoder,
+    ) {
+        let (texture, depth_stencil) = match (self.coverage.texture(), &self.depth_stencil) {
+            (Some(texture), Some(depth_stencil)) => (texture, depth_stencil),
+            _ => return,
+        };
+
+        let command_list: CommandList = self
+            .renders
+            .iter()
+            .flat_map(|render| render.render(buffer, context, device))
+            .collect();
+
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let depth_stencil_view = depth_stencil.create_view(&wgpu::TextureViewDescriptor::default());
+
+        let mut pass = begin_render_pass(
+            "Clip coverage pass",
+            &view,
+            &depth_stencil_view,
+            None,
+            encoder,
+            Some(self.clear_color),
+        );
+
+        pass.set_stencil_reference(0);
+
+        command_list.run(&mut pass, [texture.width(), texture.height()]);
+    }
+
+    fn render<'a>(
+        &self,
+        _buffer: &'a wgpu::Buffer,
+        _context: &'a GPUContext,
+        _device: &wgpu::Device,
+    ) -> Vec<Command<'a>> {
+        vec![]
+    }
+}
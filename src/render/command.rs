@@ -5,6 +5,8 @@ pub(crate) struct Command<'a> {
     draw_count: u32,
 
     groups: Vec<wgpu::BindGroup>,
+    /// [x, y, width, height] pixel rect of the target the command is restricted to, `None` means the whole target
+    scissor: Option<[u32; 4]>,
 }
 
 impl<'a> Command<'a> {
@@ -21,9 +23,16 @@ impl<'a> Command<'a> {
             index_buffer,
             draw_count,
             groups,
+            scissor: None,
         }
     }
 
+    /// Restricts the command to a pixel rect of the target, applied by `CommandList::run`.
+    pub(crate) fn with_scissor(mut self, scissor: Option<[u32; 4]>) -> Self {
+        self.scissor = scissor;
+        self
+    }
+
     pub(crate) fn run(&'a self, pass: &mut wgpu::RenderPass<'a>) {
         pass.set_pipeline(&self.pipeline);
 
@@ -53,10 +62,34 @@ impl<'a> CommandList<'a> {
         self.commands.extend(commands);
     }
 
-    pub(crate) fn run(&'a self, pass: &mut wgpu::RenderPass<'a>) {
+    /// Runs the commands in order. Commands with scissor are restricted to it, others cover the whole target.
+    /// The scissor is reset to the whole target after the last command.
+    ///
+    /// # Arguments
+    ///
+    /// * `pass` the render pass of the target
+    /// * `target_size` [width, height] of the target in pixels
+    pub(crate) fn run(&'a self, pass: &mut wgpu::RenderPass<'a>, target_size: [u32; 2]) {
+        let mut scissor = None;
+
         for command in &self.commands {
+            if command.scissor != scissor {
+                let [x, y, width, height] =
+                    command
+                        .scissor
+                        .unwrap_or([0, 0, target_size[0], target_size[1]]);
+
+                pass.set_scissor_rect(x, y, width, height);
+                scissor = command.scissor;
+            }
+
             command.run(pass);
         }
+
+        // some backends resolve multisampled targets inside the last scissor rect only
+        if scissor.is_some() {
+            pass.set_scissor_rect(0, 0, target_size[0], target_size[1]);
+        }
     }
 }
 
@@ -0,0 +1,177 @@
+use std::ops::Range;
+
+use crate::gpu::{
+    buffer::StageBuffer,
+    context::{PipelineGenerater, PipelineKey},
+    pipeline::{Pipeline, PipelineBuilder},
+    GPUContext,
+};
+
+use super::{color_filter_group_layout, sampler_entry, texture_entry, uniform_entry, ShaderSource};
+
+pub(crate) const BLUR_PIPELINE_NAME: &str = "Blur";
+pub(crate) const IMAGE_FILTER_PIPELINE_NAME: &str = "ImageFilter";
+
+/// Size in bytes of `FilterInfo` in image_filter.wgsl
+const FILTER_INFO_SIZE: usize = 16 * 4;
+
+/// Generates the pipelines of offscreen filter passes, which shade a full target quad from a source texture.
+pub(crate) struct FilterPipelineGenerator {
+    shader: ShaderSource,
+    groups: Vec<Vec<wgpu::BindGroupLayoutEntry>>,
+}
+
+impl FilterPipelineGenerator {
+    /// Pipeline of one separable blur or downsample pass of a coverage mask.
+    pub(crate) fn blur_pipeline() -> Box<dyn PipelineGenerater> {
+        Box::new(FilterPipelineGenerator {
+            shader: ShaderSource {
+                label: "Blur shader",
+                source: include_str!("../shaders/blur.wgsl").into(),
+            },
+            groups: vec![
+                // group 0: BlurInfo, source TextureView and Sampler
+                vec![uniform_entry(0, 16), texture_entry(1), sampler_entry(2)],
+            ],
+        })
+    }
+
+    /// Pipeline of one draw of an image filter pass, color filter is bind at group 2 if enabled by key.
+    pub(crate) fn image_filter_pipeline() -> Box<dyn PipelineGenerater> {
+        Box::new(FilterPipelineGenerator {
+            shader: ShaderSource {
+                label: "Image filter shader",
+                source: include_str!("../shaders/image_filter.wgsl").into(),
+            },
+            groups: vec![
+                // group 0: FilterInfo
+                vec![uniform_entry(0, FILTER_INFO_SIZE as u64)],
+                // group 1: source TextureView and Sampler
+                vec![texture_entry(0), sampler_entry(1)],
+            ],
+        })
+    }
+}
+
+impl PipelineGenerater for FilterPipelineGenerator {
+    fn gen_pipeline(&self, key: &PipelineKey, device: &wgpu::Device) -> Pipeline {
+        let mut builder = PipelineBuilder::new();
+
+        for group in &self.groups {
+            builder = builder.add_group(group.clone());
+        }
+
+        if key.color_filter {
+            builder = builder.add_group(color_filter_group_layout());
+        }
+
+        builder
+            .with_format(key.format)
+            .with_sample_count(key.sample_count)
+            .with_blend_mode(key.blend_mode)
+            .add_buffer(wgpu::VertexBufferLayout {
+                array_stride: 8,
+                step_mode: wgpu::VertexStepMode::Vertex,
+                attributes: &[wgpu::VertexAttribute {
+                    offset: 0,
+                    shader_location: 0,
+                    format: wgpu::VertexFormat::Float32x2,
+                }],
+            })
+            .with_plain(true)
+            .build(&self.shader.create_module(key, device), device)
+    }
+}
+
+/// Uniform data of a single blur pass.
+pub(crate) struct BlurPass {
+    /// [step x, step y, sigma, radius] see blur.wgsl
+    info: [f32; 4],
+    buffer_range: Range<wgpu::BufferAddress>,
+}
+
+impl BlurPass {
+    /// Creates a pass which samples the center of each 2x2 texels of a source twice the target size.
+    pub(crate) fn downsample() -> Self {
+        Self {
+            info: [0.0; 4],
+            buffer_range: 0..0,
+        }
+    }
+
+    /// Creates a Gaussian pass along one axis.
+    ///
+    /// # Arguments
+    ///
+    /// * `step` distance between two samples in uv of source texture
+    /// * `sigma` standard deviation in source pixels
+    pub(crate) fn gaussian(step: [f32; 2], sigma: f32) -> Self {
+        let radius = (sigma * 3.0).ceil().clamp(0.0, 64.0);
+
+        Self {
+            info: [step[0], step[1], sigma, radius],
+            buffer_range: 0..0,
+        }
+    }
+
+    pub(crate) fn prepare(&mut self, buffer: &mut StageBuffer) {
+        self.buffer_range = buffer.push_data_align(bytemuck::cast_slice(&self.info));
+    }
+
+    pub(crate) fn gen_bind_group(
+        &self,
+        device: &wgpu::Device,
+        buffer: &wgpu::Buffer,
+        pipeline: &Pipeline,
+        source: &wgpu::TextureView,
+        context: &GPUContext,
+    ) -> wgpu::BindGroup {
+        let layout = pipeline
+            .get_group_layout(0)
+            .expect("Blur pipeline not have group 0");
+
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Blur Group"),
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+                        buffer,
+                        offset: self.buffer_range.start,
+                        size: wgpu::BufferSize::new(
+                            self.buffer_range.end - self.buffer_range.start,
+                        ),
+                    }),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(source),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::Sampler(context.get_linear_sampler()),
+                },
+            ],
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn gaussian_kernel_radius() {
+        // the kernel reaches 3 sigma, rounded up to whole samples
+        assert_eq!(
+            BlurPass::gaussian([1.0, 0.0], 1.5).info,
+            [1.0, 0.0, 1.5, 5.0]
+        );
+        assert_eq!(BlurPass::gaussian([0.0, 0.5], 4.0).info[3], 12.0);
+
+        // zero sigma is a single sample copy, and the kernel is limited to 64 samples on each side
+        assert_eq!(BlurPass::gaussian([1.0, 0.0], 0.0).info[3], 0.0);
+        assert_eq!(BlurPass::gaussian([1.0, 0.0], 30.0).info[3], 64.0);
+    }
+}
@@ -10,6 +10,8 @@ use super::TransformGroup;
 pub(crate) struct ClipMaskFragment {
     transform: TransformGroup,
     identity: TransformGroup,
+    /// the transform of the path at depth 0, which any draw passes the depth test against
+    include: TransformGroup,
     pub(crate) bounds: Rect,
 }
 
@@ -26,6 +28,11 @@ impl ClipMaskFragment {
                 Matrix4::identity(),
                 Vector4::new(0.0, 0.0, 0.0, 0.0),
             ),
+            include: TransformGroup::new(
+                Matrix4::new_orthographic(0.0, vw, vh, 0.0, -1000.0, 1000.0),
+                transform,
+                Vector4::new(0.0, 0.0, 0.0, 0.0),
+            ),
             bounds: Rect::from_xywh(0.0, 0.0, vw, vh),
         }
     }
@@ -33,9 +40,16 @@ impl ClipMaskFragment {
     pub(crate) fn prepare(&mut self, depth: f32, op: ClipOp, buffer: &mut StageBuffer) {
         self.transform.prepare(depth, buffer);
 
-        if op == ClipOp::Intersect {
+        if matches!(
+            op,
+            ClipOp::Intersect | ClipOp::ReverseDifference | ClipOp::Replace
+        ) {
             self.identity.prepare(depth, buffer);
         }
+
+        if op.is_expanding() {
+            self.include.prepare(0.0, buffer);
+        }
     }
 
     pub(crate) fn gen_transform_group<'a>(
@@ -91,4 +105,29 @@ impl ClipMaskFragment {
             }],
         })
     }
+
+    pub(crate) fn gen_include_group<'a>(
+        &self,
+        device: &wgpu::Device,
+        buffer: &'a wgpu::Buffer,
+        pipeline: &'a Pipeline,
+    ) -> wgpu::BindGroup {
+        let group0_layout = pipeline
+            .get_group_layout(0)
+            .expect("Pipeline does not contains common transform slot");
+        let range = self.include.get_buffer_range();
+
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Clip Mask Include Group"),
+            layout: group0_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+                    buffer,
+                    offset: range.start,
+                    size: wgpu::BufferSize::new(range.end - range.start),
+                }),
+            }],
+        })
+    }
 }
@@ -0,0 +1,115 @@
+use std::ops::Range;
+
+use crate::{
+    core::{
+        color_filter::{ColorFilterStep, MAX_COLOR_FILTER_STEPS},
+        BlendMode, ColorFilter,
+    },
+    gpu::{buffer::StageBuffer, pipeline::Pipeline},
+};
+
+/// floats of one `ColorFilterStep` in shader: info, 4 rows and translation
+const STEP_SIZE: usize = 24;
+
+/// Size in bytes of `ColorFilterInfo` in color_filter.wgsl
+pub(crate) const COLOR_FILTER_INFO_SIZE: usize = (4 + STEP_SIZE * MAX_COLOR_FILTER_STEPS) * 4;
+
+/// Layout of bind group 2 used by pipelines with color filter stage.
+pub(crate) fn color_filter_group_layout() -> Vec<wgpu::BindGroupLayoutEntry> {
+    vec![wgpu::BindGroupLayoutEntry {
+        binding: 0,
+        visibility: wgpu::ShaderStages::FRAGMENT,
+        ty: wgpu::BindingType::Buffer {
+            ty: wgpu::BufferBindingType::Uniform,
+            has_dynamic_offset: false,
+            min_binding_size: wgpu::BufferSize::new(COLOR_FILTER_INFO_SIZE as wgpu::BufferAddress),
+        },
+        count: None,
+    }]
+}
+
+/// Uniform data of a color filter, bind at group 2 after the fragment groups.
+pub(crate) struct ColorFilterStage {
+    steps: Vec<ColorFilterStep>,
+    buffer_range: Range<wgpu::BufferAddress>,
+}
+
+impl ColorFilterStage {
+    pub(crate) fn new(filter: &ColorFilter) -> Self {
+        Self {
+            steps: filter.flatten(),
+            buffer_range: 0..0,
+        }
+    }
+
+    pub(crate) fn prepare(&mut self, buffer: &mut StageBuffer) {
+        let mut data = vec![0u32; COLOR_FILTER_INFO_SIZE / 4];
+
+        data[0] = self.steps.len() as u32;
+
+        for (i, step) in self.steps.iter().enumerate() {
+            let mut values = [0f32; STEP_SIZE];
+
+            match step {
+                ColorFilterStep::Matrix(m) => {
+                    values[0] = 0.0;
+                    for row in 0..4 {
+                        values[4 + row * 4..8 + row * 4].copy_from_slice(&m[row * 5..row * 5 + 4]);
+                        values[20 + row] = m[row * 5 + 4];
+                    }
+                }
+                ColorFilterStep::Blend(color, mode) => {
+                    let k = mode.coefficients();
+
+                    values[0] = 1.0;
+                    values[4..8].copy_from_slice(&[
+                        color.r * color.a,
+                        color.g * color.a,
+                        color.b * color.a,
+                        color.a,
+                    ]);
+                    values[8..12].copy_from_slice(&k[0..4]);
+                    values[12] = k[4];
+                    values[13] = if *mode == BlendMode::Plus { 1.0 } else { 0.0 };
+                }
+                ColorFilterStep::LinearToSrgb => values[0] = 2.0,
+                ColorFilterStep::SrgbToLinear => values[0] = 3.0,
+            }
+
+            let offset = 4 + i * STEP_SIZE;
+            for (j, v) in values.iter().enumerate() {
+                data[offset + j] = v.to_bits();
+            }
+        }
+
+        self.buffer_range = buffer.push_data_align(bytemuck::cast_slice(&data));
+    }
+
+    pub(crate) fn get_buffer_range(&self) -> Range<wgpu::BufferAddress> {
+        self.buffer_range.clone()
+    }
+
+    pub(crate) fn gen_bind_group(
+        &self,
+        device: &wgpu::Device,
+        buffer: &wgpu::Buffer,
+        pipeline: &Pipeline,
+    ) -> wgpu::BindGroup {
+        let layout = pipeline
+            .get_group_layout(2)
+            .expect("color filter group at slot 2 can not be get!");
+
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Color Filter Group"),
+            layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+                    buffer,
+                    offset: self.buffer_range.start,
+                    size: wgpu::BufferSize::new(self.buffer_range.end - self.buffer_range.start),
+                }),
+            }],
+        })
+    }
+}
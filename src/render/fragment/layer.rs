@@ -0,0 +1,145 @@
+use std::{ops::Range, rc::Rc};
+
+use nalgebra::{Matrix4, Vector4};
+
+use crate::{
+    core::Rect,
+    gpu::{buffer::StageBuffer, pipeline::Pipeline, GPUContext},
+    render::Fragment,
+};
+
+use super::{TransformGroup, LAYER_PIPELINE_NAME};
+
+/// Composites an offscreen layer texture back onto its parent target.
+pub(crate) struct LayerFragment {
+    transform: TransformGroup,
+    texture: Rc<wgpu::Texture>,
+    bounds: Rect,
+    alpha: f32,
+
+    info_range: Range<wgpu::BufferAddress>,
+}
+
+impl LayerFragment {
+    pub(crate) fn new(
+        vw: f32,
+        vh: f32,
+        texture: Rc<wgpu::Texture>,
+        bounds: Rect,
+        alpha: f32,
+    ) -> Self {
+        Self {
+            transform: TransformGroup::new(
+                Matrix4::new_orthographic(0.0, vw, vh, 0.0, -1000.0, 1000.0),
+                Matrix4::identity(),
+                Vector4::new(0.0, 0.0, 0.0, 0.0),
+            ),
+            texture,
+            bounds,
+            alpha,
+            info_range: 0..0,
+        }
+    }
+}
+
+impl Fragment for LayerFragment {
+    fn get_pipeline_name(&self) -> &str {
+        LAYER_PIPELINE_NAME
+    }
+
+    fn prepare(
+        &mut self,
+        depth: f32,
+        buffer: &mut StageBuffer,
+        _context: &GPUContext,
+        _device: &wgpu::Device,
+        _queue: &wgpu::Queue,
+    ) {
+        self.transform.prepare(depth, buffer);
+
+        let info: [f32; 8] = [
+            self.bounds.left,
+            self.bounds.top,
+            self.bounds.right,
+            self.bounds.bottom,
+            self.alpha,
+            0.0,
+            0.0,
+            0.0,
+        ];
+
+        self.info_range = buffer.push_data_align(bytemuck::cast_slice(&info));
+    }
+
+    fn gen_bind_groups<'a>(
+        &self,
+        device: &wgpu::Device,
+        buffer: &'a wgpu::Buffer,
+        pipeline: &'a Pipeline,
+        context: &'a GPUContext,
+    ) -> Vec<wgpu::BindGroup> {
+        let group1_layout = pipeline
+            .get_group_layout(1)
+            .expect("Layer pipeline not have group 1");
+
+        let layer_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Layer Group"),
+            layout: group1_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+                        buffer,
+                        offset: self.info_range.start,
+                        size: wgpu::BufferSize::new(self.info_range.end - self.info_range.start),
+                    }),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(
+                        &self
+                            .texture
+                            .create_view(&wgpu::TextureViewDescriptor::default()),
+                    ),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::Sampler(context.get_linear_sampler()),
+                },
+            ],
+        });
+
+        vec![
+            self.gen_common_bind_groups(device, buffer, pipeline, context),
+            layer_group,
+        ]
+    }
+
+    fn gen_common_bind_groups<'a>(
+        &self,
+        device: &wgpu::Device,
+        buffer: &'a wgpu::Buffer,
+        pipeline: &'a Pipeline,
+        _context: &'a GPUContext,
+    ) -> wgpu::BindGroup {
+        let group0_layout = pipeline
+            .get_group_layout(0)
+            .expect("common group at slot 0 can not be get!");
+
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Layer Common Group"),
+            layout: group0_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+                    buffer,
+                    offset: self.transform.get_buffer_range().start,
+                    size: wgpu::BufferSize::new(
+                        self.transform.get_buffer_range().end
+                            - self.transform.get_buffer_range().start,
+                    ),
+                }),
+            }],
+        })
+    }
+}
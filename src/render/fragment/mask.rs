@@ -0,0 +1,149 @@
+use std::{ops::Range, rc::Rc};
+
+use crate::{
+    core::{BlurStyle, Rect},
+    gpu::{buffer::StageBuffer, pipeline::Pipeline, GPUContext},
+};
+
+/// Size in bytes of `MaskInfo` in mask.wgsl
+const MASK_INFO_SIZE: usize = 8 * 4;
+
+/// Layout of the mask bind group used by pipelines with mask stage.
+pub(crate) fn mask_group_layout() -> Vec<wgpu::BindGroupLayoutEntry> {
+    let texture = |binding: u32| wgpu::BindGroupLayoutEntry {
+        binding,
+        visibility: wgpu::ShaderStages::FRAGMENT,
+        ty: wgpu::BindingType::Texture {
+            sample_type: wgpu::TextureSampleType::Float { filterable: true },
+            view_dimension: wgpu::TextureViewDimension::D2,
+            multisampled: false,
+        },
+        count: None,
+    };
+
+    vec![
+        // binding 0: MaskInfo
+        wgpu::BindGroupLayoutEntry {
+            binding: 0,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Uniform,
+                has_dynamic_offset: false,
+                min_binding_size: wgpu::BufferSize::new(MASK_INFO_SIZE as wgpu::BufferAddress),
+            },
+            count: None,
+        },
+        // binding 1: blurred coverage
+        texture(1),
+        // binding 2: original coverage
+        texture(2),
+        // binding 3: Sampler
+        wgpu::BindGroupLayoutEntry {
+            binding: 3,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+            count: None,
+        },
+    ]
+}
+
+/// Coverage mask multiplied to the fragment output, bind at the group after color filter.
+pub(crate) struct MaskStage {
+    /// bounds of the mask in target pixels
+    bounds: Rect,
+    style: BlurStyle,
+    blurred: Option<Rc<wgpu::Texture>>,
+    coverage: Option<Rc<wgpu::Texture>>,
+
+    buffer_range: Range<wgpu::BufferAddress>,
+}
+
+impl MaskStage {
+    pub(crate) fn new(bounds: Rect, style: BlurStyle) -> Self {
+        Self {
+            bounds,
+            style,
+            blurred: None,
+            coverage: None,
+            buffer_range: 0..0,
+        }
+    }
+
+    /// Sets the mask textures. They are created after the stage, when the owner renderer is prepared.
+    pub(crate) fn set_textures(&mut self, blurred: Rc<wgpu::Texture>, coverage: Rc<wgpu::Texture>) {
+        self.blurred = Some(blurred);
+        self.coverage = Some(coverage);
+    }
+
+    pub(crate) fn prepare(&mut self, buffer: &mut StageBuffer) {
+        let style = match self.style {
+            BlurStyle::Normal => 0.0,
+            BlurStyle::Solid => 1.0,
+            BlurStyle::Outer => 2.0,
+            BlurStyle::Inner => 3.0,
+        };
+
+        let info: [f32; 8] = [
+            self.bounds.left,
+            self.bounds.top,
+            self.bounds.width(),
+            self.bounds.height(),
+            style,
+            0.0,
+            0.0,
+            0.0,
+        ];
+
+        self.buffer_range = buffer.push_data_align(bytemuck::cast_slice(&info));
+    }
+
+    pub(crate) fn gen_bind_group(
+        &self,
+        device: &wgpu::Device,
+        buffer: &wgpu::Buffer,
+        pipeline: &Pipeline,
+        group: u32,
+        context: &GPUContext,
+    ) -> Option<wgpu::BindGroup> {
+        let (blurred, coverage) = match (&self.blurred, &self.coverage) {
+            (Some(blurred), Some(coverage)) => (blurred, coverage),
+            _ => return None,
+        };
+
+        let layout = pipeline
+            .get_group_layout(group as usize)
+            .expect("mask group can not be get!");
+
+        let blurred_view = blurred.create_view(&wgpu::TextureViewDescriptor::default());
+        let coverage_view = coverage.create_view(&wgpu::TextureViewDescriptor::default());
+
+        Some(device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Mask Group"),
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+                        buffer,
+                        offset: self.buffer_range.start,
+                        size: wgpu::BufferSize::new(
+                            self.buffer_range.end - self.buffer_range.start,
+                        ),
+                    }),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(&blurred_view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::TextureView(&coverage_view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: wgpu::BindingResource::Sampler(context.get_linear_sampler()),
+                },
+            ],
+        }))
+    }
+}
@@ -1,29 +1,41 @@
-use std::ops::Range;
+use std::{borrow::Cow, ops::Range};
 
 use nalgebra::{Matrix4, Vector4};
 
+use crate::core::ColorSpace;
 use crate::gpu::{
     buffer::StageBuffer,
-    context::PipelineGenerater,
+    context::{PipelineGenerater, PipelineKey},
     pipeline::{Pipeline, PipelineBuilder},
 };
 
+pub(crate) mod blur;
 pub(crate) mod clip_mask;
+pub(crate) mod color_filter;
 pub(crate) mod gradient;
+pub(crate) mod layer;
+pub(crate) mod mask;
+pub(crate) mod shader;
 pub(crate) mod solid_color;
 pub(crate) mod texture;
 
+pub(crate) use blur::{
+    BlurPass, FilterPipelineGenerator, BLUR_PIPELINE_NAME, IMAGE_FILTER_PIPELINE_NAME,
+};
 pub(crate) use clip_mask::ClipMaskFragment;
-pub(crate) use gradient::{GradientColorInfo, LinearGradientFragment, RadialGradientFragment};
+pub(crate) use color_filter::{color_filter_group_layout, ColorFilterStage};
+pub(crate) use layer::LayerFragment;
+pub(crate) use gradient::GradientColorInfo;
+pub(crate) use mask::{mask_group_layout, MaskStage};
+pub(crate) use shader::ShaderFragment;
 pub(crate) use solid_color::SolidColorFragment;
 pub(crate) use texture::TextureFragment;
 
 pub(crate) const SOLID_PIPELINE_NAME: &str = "SolidColor";
 pub(crate) const NON_COLOR_PIPELINE_NAME: &str = "NonColor";
-pub(crate) const LINEAR_GRADIENT_PIPELINE_NAME: &str = "LinearGradient";
-pub(crate) const RADIAL_GRADIENT_PIPELINE_NAME: &str = "RadialGradient";
 pub(crate) const TEXTURE_PIPELINE_NAME: &str = "TextureColor";
 pub(crate) const SOLID_TEXT_PIPELINE_NAME: &str = "SolidText";
+pub(crate) const LAYER_PIPELINE_NAME: &str = "Layer";
 
 pub(crate) fn state_for_convex_polygon() -> wgpu::DepthStencilState {
     wgpu::DepthStencilState {
@@ -125,6 +137,15 @@ pub(crate) fn state_for_no_overlap() -> wgpu::DepthStencilState {
     }
 }
 
+/// Returns the state with depth write enabled. Later geometry of the same draw fails the depth test
+/// where this geometry is drawn, so fill and stroke blend their overlap once.
+pub(crate) fn with_depth_write(state: wgpu::DepthStencilState) -> wgpu::DepthStencilState {
+    wgpu::DepthStencilState {
+        depth_write_enabled: true,
+        ..state
+    }
+}
+
 pub(crate) fn state_for_stencil_mask() -> wgpu::DepthStencilState {
     wgpu::DepthStencilState {
         format: wgpu::TextureFormat::Depth24PlusStencil8,
@@ -150,6 +171,14 @@ pub(crate) fn state_for_stencil_mask() -> wgpu::DepthStencilState {
     }
 }
 
+/// Stencil mask of clips which can grow the clip, also written where the pixel is already clipped out.
+pub(crate) fn state_for_stencil_mask_always() -> wgpu::DepthStencilState {
+    wgpu::DepthStencilState {
+        depth_compare: wgpu::CompareFunction::Always,
+        ..state_for_stencil_mask()
+    }
+}
+
 pub(crate) fn state_for_clip_intersect() -> wgpu::DepthStencilState {
     wgpu::DepthStencilState {
         format: wgpu::TextureFormat::Depth24PlusStencil8,
@@ -250,25 +279,362 @@ pub(crate) fn state_for_clip_even_odd_difference() -> wgpu::DepthStencilState {
     }
 }
 
+/// Returns the state of a pass of clips which can grow the clip, such as `ClipOp::Union`.
+/// The stencil is reset to 0 where the pass is drawn.
+fn clip_state(
+    depth_compare: wgpu::CompareFunction,
+    compare: wgpu::CompareFunction,
+    fail_op: wgpu::StencilOperation,
+    depth_fail_op: wgpu::StencilOperation,
+    read_mask: u32,
+) -> wgpu::DepthStencilState {
+    let face = wgpu::StencilFaceState {
+        compare,
+        fail_op,
+        depth_fail_op,
+        pass_op: wgpu::StencilOperation::Replace,
+    };
+
+    wgpu::DepthStencilState {
+        format: wgpu::TextureFormat::Depth24PlusStencil8,
+        depth_write_enabled: true,
+        depth_compare,
+        stencil: wgpu::StencilState {
+            front: face,
+            back: face,
+            read_mask,
+            write_mask: 0xff,
+        },
+        bias: Default::default(),
+    }
+}
+
+/// State of a union clip, drawn with depth 0 inside of the stencil so any following draw passes there.
+pub(crate) fn state_for_clip_union() -> wgpu::DepthStencilState {
+    clip_state(
+        wgpu::CompareFunction::Always,
+        wgpu::CompareFunction::NotEqual,
+        wgpu::StencilOperation::Replace,
+        wgpu::StencilOperation::Keep,
+        0xff,
+    )
+}
+
+/// State of an even-odd union clip, drawn with depth 0 inside of the stencil so any following draw
+/// passes there.
+pub(crate) fn state_for_clip_even_odd_union() -> wgpu::DepthStencilState {
+    clip_state(
+        wgpu::CompareFunction::Always,
+        wgpu::CompareFunction::NotEqual,
+        wgpu::StencilOperation::Replace,
+        wgpu::StencilOperation::Keep,
+        0x01,
+    )
+}
+
+/// State of a xor clip, clips out the inside of the stencil which was inside the clip.
+/// The stencil is kept where the clip was already clipped out, a following union pass lets it through.
+pub(crate) fn state_for_clip_xor() -> wgpu::DepthStencilState {
+    clip_state(
+        wgpu::CompareFunction::Greater,
+        wgpu::CompareFunction::NotEqual,
+        wgpu::StencilOperation::Keep,
+        wgpu::StencilOperation::Keep,
+        0xff,
+    )
+}
+
+/// State of an even-odd xor clip, clips out the inside of the stencil which was inside the clip.
+/// The stencil is kept where the clip was already clipped out, a following union pass lets it through.
+pub(crate) fn state_for_clip_even_odd_xor() -> wgpu::DepthStencilState {
+    clip_state(
+        wgpu::CompareFunction::Greater,
+        wgpu::CompareFunction::NotEqual,
+        wgpu::StencilOperation::Keep,
+        wgpu::StencilOperation::Keep,
+        0x01,
+    )
+}
+
+/// State of a replace or reverse difference clip, clips out the outside of the stencil.
+/// The stencil inside is kept for the following passes.
+pub(crate) fn state_for_clip_replace() -> wgpu::DepthStencilState {
+    clip_state(
+        wgpu::CompareFunction::Greater,
+        wgpu::CompareFunction::Equal,
+        wgpu::StencilOperation::Keep,
+        wgpu::StencilOperation::Keep,
+        0xff,
+    )
+}
+
+/// State of an even-odd replace or reverse difference clip, clips out the outside of the stencil.
+/// The stencil inside is kept for the following passes.
+pub(crate) fn state_for_clip_even_odd_replace() -> wgpu::DepthStencilState {
+    clip_state(
+        wgpu::CompareFunction::Greater,
+        wgpu::CompareFunction::Equal,
+        wgpu::StencilOperation::Keep,
+        wgpu::StencilOperation::Keep,
+        0x01,
+    )
+}
+
+/// Returns the state testing the stencil with reference 0 without writing depth.
+/// Fringes are drawn with it, so they blend only where the stencil of their geometry allows.
+fn fringe_state(
+    compare: wgpu::CompareFunction,
+    pass_op: wgpu::StencilOperation,
+    read_mask: u32,
+) -> wgpu::DepthStencilState {
+    let face = wgpu::StencilFaceState {
+        compare,
+        fail_op: wgpu::StencilOperation::Keep,
+        depth_fail_op: wgpu::StencilOperation::Keep,
+        pass_op,
+    };
+
+    wgpu::DepthStencilState {
+        format: wgpu::TextureFormat::Depth24PlusStencil8,
+        depth_write_enabled: false,
+        depth_compare: wgpu::CompareFunction::Greater,
+        stencil: wgpu::StencilState {
+            front: face,
+            back: face,
+            read_mask,
+            write_mask: 0xff,
+        },
+        bias: Default::default(),
+    }
+}
+
+/// State of the fringe of a winding fill, drawn outside of the stencil before the cover pass.
+pub(crate) fn state_for_complex_fringe() -> wgpu::DepthStencilState {
+    fringe_state(
+        wgpu::CompareFunction::Equal,
+        wgpu::StencilOperation::Keep,
+        0xff,
+    )
+}
+
+/// State of the fringe of an even-odd fill, drawn outside of the stencil before the cover pass.
+pub(crate) fn state_for_complex_even_odd_fringe() -> wgpu::DepthStencilState {
+    fringe_state(
+        wgpu::CompareFunction::Equal,
+        wgpu::StencilOperation::Keep,
+        0x01,
+    )
+}
+
+/// State of the fringe of an intersect clip, marks the fringe outside of the stencil as inside the clip.
+pub(crate) fn state_for_clip_fringe_intersect() -> wgpu::DepthStencilState {
+    fringe_state(
+        wgpu::CompareFunction::Equal,
+        wgpu::StencilOperation::IncrementClamp,
+        0xff,
+    )
+}
+
+/// State of the fringe of an even-odd intersect clip, marks the fringe outside of the stencil as inside the clip.
+pub(crate) fn state_for_clip_fringe_even_odd_intersect() -> wgpu::DepthStencilState {
+    fringe_state(
+        wgpu::CompareFunction::Equal,
+        wgpu::StencilOperation::IncrementClamp,
+        0x01,
+    )
+}
+
+/// State of the fringe of a difference clip, removes the fringe inside of the stencil from the clipped out area.
+pub(crate) fn state_for_clip_fringe_difference() -> wgpu::DepthStencilState {
+    fringe_state(
+        wgpu::CompareFunction::NotEqual,
+        wgpu::StencilOperation::Zero,
+        0xff,
+    )
+}
+
+/// State of the fringe of an even-odd difference clip, removes the fringe inside of the stencil from the
+/// clipped out area.
+pub(crate) fn state_for_clip_fringe_even_odd_difference() -> wgpu::DepthStencilState {
+    fringe_state(
+        wgpu::CompareFunction::NotEqual,
+        wgpu::StencilOperation::Zero,
+        0x01,
+    )
+}
+
+/// State of the fringe of clips which can grow the clip, drawn with depth 0 after the clip mask.
+/// The fringe is let through whatever the clip before is, the feathered coverage decides.
+pub(crate) fn state_for_clip_fringe_include() -> wgpu::DepthStencilState {
+    let face = wgpu::StencilFaceState {
+        compare: wgpu::CompareFunction::Always,
+        fail_op: wgpu::StencilOperation::Keep,
+        depth_fail_op: wgpu::StencilOperation::Keep,
+        pass_op: wgpu::StencilOperation::Keep,
+    };
+
+    wgpu::DepthStencilState {
+        format: wgpu::TextureFormat::Depth24PlusStencil8,
+        depth_write_enabled: true,
+        depth_compare: wgpu::CompareFunction::Always,
+        stencil: wgpu::StencilState {
+            front: face,
+            back: face,
+            read_mask: 0xff,
+            write_mask: 0xff,
+        },
+        bias: Default::default(),
+    }
+}
+
+fn is_float_format(format: wgpu::TextureFormat) -> bool {
+    matches!(
+        format,
+        wgpu::TextureFormat::R16Float
+            | wgpu::TextureFormat::Rg16Float
+            | wgpu::TextureFormat::Rgba16Float
+            | wgpu::TextureFormat::R32Float
+            | wgpu::TextureFormat::Rg32Float
+            | wgpu::TextureFormat::Rgba32Float
+    )
+}
+
+/// Returns one quantization step of `format`, the amplitude of dither noise.
+/// Float formats do not band visibly, so they return zero and no noise is added.
+fn dither_scale(format: wgpu::TextureFormat) -> f32 {
+    if is_float_format(format) {
+        return 0.0;
+    }
+
+    match format {
+        wgpu::TextureFormat::Rgb10a2Unorm => 1.0 / 1023.0,
+        _ => 1.0 / 255.0,
+    }
+}
+
+fn uniform_entry(binding: u32, size: u64) -> wgpu::BindGroupLayoutEntry {
+    wgpu::BindGroupLayoutEntry {
+        binding,
+        visibility: wgpu::ShaderStages::FRAGMENT,
+        ty: wgpu::BindingType::Buffer {
+            ty: wgpu::BufferBindingType::Uniform,
+            has_dynamic_offset: false,
+            min_binding_size: wgpu::BufferSize::new(size),
+        },
+        count: None,
+    }
+}
+
+fn texture_entry(binding: u32) -> wgpu::BindGroupLayoutEntry {
+    wgpu::BindGroupLayoutEntry {
+        binding,
+        visibility: wgpu::ShaderStages::FRAGMENT,
+        ty: wgpu::BindingType::Texture {
+            sample_type: wgpu::TextureSampleType::Float { filterable: true },
+            view_dimension: wgpu::TextureViewDimension::D2,
+            multisampled: false,
+        },
+        count: None,
+    }
+}
+
+fn sampler_entry(binding: u32) -> wgpu::BindGroupLayoutEntry {
+    wgpu::BindGroupLayoutEntry {
+        binding,
+        visibility: wgpu::ShaderStages::FRAGMENT,
+        ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+        count: None,
+    }
+}
+
+/// WGSL source of a pipeline. Shader modules are created per pipeline variant.
+struct ShaderSource {
+    label: &'static str,
+    source: Cow<'static, str>,
+}
+
+impl ShaderSource {
+    /// Creates the shader module with the color filter, color space, dither and mask stages selected by the
+    /// pipeline key. Every fragment entry passes its premultiplied output through `apply_color_filter` and then
+    /// `apply_mask`, entries producing sRGB colors call `apply_color_space` in between and gradient and image
+    /// entries call `apply_dither` after it. Layer entries skip `apply_color_space` since their content is
+    /// already in the target color space. Targets with an sRGB format blend in linear light, so
+    /// `apply_color_space` returns linear values for them.
+    fn create_module(&self, key: &PipelineKey, device: &wgpu::Device) -> wgpu::ShaderModule {
+        let color_filter_lib = include_str!("../shaders/color_filter_lib.wgsl");
+        let color_space_lib = include_str!("../shaders/color_space_lib.wgsl");
+        let color_filter = if key.color_filter {
+            include_str!("../shaders/color_filter.wgsl")
+        } else {
+            include_str!("../shaders/no_color_filter.wgsl")
+        };
+
+        let mask = if key.mask {
+            include_str!("../shaders/mask.wgsl")
+                .replace("MASK_GROUP", &key.mask_group().to_string())
+        } else {
+            include_str!("../shaders/no_mask.wgsl").to_string()
+        };
+
+        // unorm targets clamp sRGB values by themselves, sRGB formats expect linear values
+        let linear_output = key.format.is_srgb();
+        let color_space = match key.color_space {
+            ColorSpace::Srgb if !is_float_format(key.format) && !linear_output => {
+                include_str!("../shaders/no_color_space.wgsl").to_string()
+            }
+            color_space => include_str!("../shaders/color_space.wgsl")
+                .replace("COLOR_SPACE_VALUE", &format!("{}u", color_space as u32))
+                .replace("LINEAR_OUTPUT_VALUE", &linear_output.to_string()),
+        };
+
+        let scale = dither_scale(key.format);
+        let dither = if key.dither && scale > 0.0 {
+            include_str!("../shaders/dither.wgsl")
+                .replace("DITHER_SCALE_VALUE", &format!("{:?}", scale))
+                .replace("DITHER_ENCODED_VALUE", &linear_output.to_string())
+        } else {
+            include_str!("../shaders/no_dither.wgsl").to_string()
+        };
+
+        device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some(self.label),
+            source: wgpu::ShaderSource::Wgsl(
+                format!(
+                    "{}\n{}\n{}\n{}\n{}\n{}\n{}",
+                    color_filter_lib,
+                    color_filter,
+                    color_space_lib,
+                    color_space,
+                    dither,
+                    mask,
+                    self.source
+                )
+                .into(),
+            ),
+        })
+    }
+}
+
 pub(crate) struct ColorPipelineGenerator {
     color_writable: bool,
-    shader: wgpu::ShaderModule,
+    shader: ShaderSource,
     states: Vec<wgpu::DepthStencilState>,
     groups: Vec<Vec<wgpu::BindGroupLayoutEntry>>,
 }
 
 struct TextPipelineGenerator {
-    shader: wgpu::ShaderModule,
+    shader: ShaderSource,
     states: Vec<wgpu::DepthStencilState>,
     groups: Vec<Vec<wgpu::BindGroupLayoutEntry>>,
 }
 
 impl ColorPipelineGenerator {
-    pub(crate) fn solid_color_pipeline(device: &wgpu::Device) -> Box<dyn PipelineGenerater> {
-        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
-            label: Some("Solid Color shader"),
-            source: wgpu::ShaderSource::Wgsl(include_str!("../shaders/solid_color.wgsl").into()),
-        });
+    pub(crate) fn solid_color_pipeline() -> Box<dyn PipelineGenerater> {
+        let shader = ShaderSource {
+            label: "Solid Color shader",
+            source: include_str!("../shaders/solid_color.wgsl").into(),
+        };
+
         Box::new(ColorPipelineGenerator {
             color_writable: true,
             shader,
@@ -281,6 +647,12 @@ impl ColorPipelineGenerator {
                 state_for_complex_even_odd(),
                 // for stroke no-overlap fill
                 state_for_no_overlap(),
+                // for fill of fill and stroke, convex fill uses no-overlap
+                with_depth_write(state_for_complex_winding()),
+                with_depth_write(state_for_complex_even_odd()),
+                // for anti-alias fringe of Stencil and Cover fill
+                state_for_complex_fringe(),
+                state_for_complex_even_odd_fringe(),
             ],
             groups: vec![
                 // group 0
@@ -312,13 +684,20 @@ impl ColorPipelineGenerator {
         })
     }
 
-    pub(crate) fn linear_gradient_pipeline(device: &wgpu::Device) -> Box<dyn PipelineGenerater> {
-        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
-            label: Some("Linear Gradient shader"),
-            source: wgpu::ShaderSource::Wgsl(
-                include_str!("../shaders/linear_gradient.wgsl").into(),
-            ),
-        });
+    /// Creates the generator of a pipeline shading with a shader tree.
+    ///
+    /// # Arguments
+    ///
+    /// * `source` WGSL generated from the shape of the tree
+    /// * `entries` layout of group 1, holds the uniforms and textures of all nodes
+    pub(crate) fn shader_pipeline(
+        source: String,
+        entries: Vec<wgpu::BindGroupLayoutEntry>,
+    ) -> Box<dyn PipelineGenerater> {
+        let shader = ShaderSource {
+            label: "Shader tree shader",
+            source: source.into(),
+        };
 
         Box::new(ColorPipelineGenerator {
             color_writable: true,
@@ -332,6 +711,12 @@ impl ColorPipelineGenerator {
                 state_for_complex_even_odd(),
                 // for stroke no-overlap fill
                 state_for_no_overlap(),
+                // for fill of fill and stroke, convex fill uses no-overlap
+                with_depth_write(state_for_complex_winding()),
+                with_depth_write(state_for_complex_even_odd()),
+                // for anti-alias fringe of Stencil and Cover fill
+                state_for_complex_fringe(),
+                state_for_complex_even_odd_fringe(),
             ],
             groups: vec![
                 // group 0
@@ -348,54 +733,16 @@ impl ColorPipelineGenerator {
                     count: None,
                 }],
                 // group 1
-                vec![
-                    wgpu::BindGroupLayoutEntry {
-                        binding: 0,
-                        visibility: wgpu::ShaderStages::VERTEX,
-                        ty: wgpu::BindingType::Buffer {
-                            ty: wgpu::BufferBindingType::Uniform,
-                            has_dynamic_offset: false,
-                            min_binding_size: wgpu::BufferSize::new(
-                                std::mem::size_of::<Matrix4<f32>>() as wgpu::BufferAddress,
-                            ),
-                        },
-                        count: None,
-                    },
-                    wgpu::BindGroupLayoutEntry {
-                        binding: 1,
-                        visibility: wgpu::ShaderStages::FRAGMENT,
-                        ty: wgpu::BindingType::Buffer {
-                            ty: wgpu::BufferBindingType::Uniform,
-                            has_dynamic_offset: false,
-                            min_binding_size: wgpu::BufferSize::new(std::mem::size_of::<
-                                GradientColorInfo,
-                            >()
-                                as wgpu::BufferAddress),
-                        },
-                        count: None,
-                    },
-                    wgpu::BindGroupLayoutEntry {
-                        binding: 2,
-                        visibility: wgpu::ShaderStages::FRAGMENT,
-                        ty: wgpu::BindingType::Buffer {
-                            ty: wgpu::BufferBindingType::Uniform,
-                            has_dynamic_offset: false,
-                            min_binding_size: wgpu::BufferSize::new(4 * 4),
-                        },
-                        count: None,
-                    },
-                ],
+                entries,
             ],
         })
     }
 
-    pub(crate) fn radial_gradient_pipeline(device: &wgpu::Device) -> Box<dyn PipelineGenerater> {
-        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
-            label: Some("Linear Gradient shader"),
-            source: wgpu::ShaderSource::Wgsl(
-                include_str!("../shaders/radial_gradient.wgsl").into(),
-            ),
-        });
+    pub(crate) fn image_pipeline() -> Box<dyn PipelineGenerater> {
+        let shader = ShaderSource {
+            label: "Image shader",
+            source: include_str!("../shaders/image.wgsl").into(),
+        };
 
         Box::new(ColorPipelineGenerator {
             color_writable: true,
@@ -409,6 +756,9 @@ impl ColorPipelineGenerator {
                 state_for_complex_even_odd(),
                 // for stroke no-overlap fill
                 state_for_no_overlap(),
+                // for anti-alias fringe of Stencil and Cover fill
+                state_for_complex_fringe(),
+                state_for_complex_even_odd_fringe(),
             ],
             groups: vec![
                 // group 0
@@ -419,13 +769,15 @@ impl ColorPipelineGenerator {
                         ty: wgpu::BufferBindingType::Uniform,
                         has_dynamic_offset: false,
                         min_binding_size: wgpu::BufferSize::new(
-                            (std::mem::size_of::<Matrix4<f32>>() * 2 + 16) as wgpu::BufferAddress,
+                            (std::mem::size_of::<nalgebra::Matrix4<f32>>() * 2 + 16)
+                                as wgpu::BufferAddress,
                         ),
                     },
                     count: None,
                 }],
                 // group 1
                 vec![
+                    // binding 0: ImageTransform
                     wgpu::BindGroupLayoutEntry {
                         binding: 0,
                         visibility: wgpu::ShaderStages::VERTEX,
@@ -433,47 +785,53 @@ impl ColorPipelineGenerator {
                             ty: wgpu::BufferBindingType::Uniform,
                             has_dynamic_offset: false,
                             min_binding_size: wgpu::BufferSize::new(
-                                std::mem::size_of::<Matrix4<f32>>() as wgpu::BufferAddress,
+                                (std::mem::size_of::<nalgebra::Matrix4<f32>>() + 16)
+                                    as wgpu::BufferAddress,
                             ),
                         },
                         count: None,
                     },
+                    // binding 1: ImageBounds
                     wgpu::BindGroupLayoutEntry {
                         binding: 1,
                         visibility: wgpu::ShaderStages::FRAGMENT,
                         ty: wgpu::BindingType::Buffer {
                             ty: wgpu::BufferBindingType::Uniform,
                             has_dynamic_offset: false,
-                            min_binding_size: wgpu::BufferSize::new(std::mem::size_of::<
-                                GradientColorInfo,
-                            >()
-                                as wgpu::BufferAddress),
+                            min_binding_size: wgpu::BufferSize::new(16 as wgpu::BufferAddress),
                         },
                         count: None,
                     },
+                    // binding 2: TextureView
                     wgpu::BindGroupLayoutEntry {
                         binding: 2,
                         visibility: wgpu::ShaderStages::FRAGMENT,
-                        ty: wgpu::BindingType::Buffer {
-                            ty: wgpu::BufferBindingType::Uniform,
-                            has_dynamic_offset: false,
-                            min_binding_size: wgpu::BufferSize::new(4 * 4),
+                        ty: wgpu::BindingType::Texture {
+                            sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                            view_dimension: wgpu::TextureViewDimension::D2,
+                            multisampled: false,
                         },
                         count: None,
                     },
+                    // binding 3: Sampler
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 3,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                        count: None,
+                    },
                 ],
             ],
         })
     }
 
-    pub(crate) fn image_pipeline(device: &wgpu::Device) -> Box<dyn PipelineGenerater> {
-        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
-            label: Some("Image shader"),
-            source: wgpu::ShaderSource::Wgsl(include_str!("../shaders/image.wgsl").into()),
-        });
+    pub(crate) fn solid_text_pipeline() -> Box<dyn PipelineGenerater> {
+        let shader = ShaderSource {
+            label: "Solid Text shader",
+            source: include_str!("../shaders/solid_text.wgsl").into(),
+        };
 
-        Box::new(ColorPipelineGenerator {
-            color_writable: true,
+        Box::new(TextPipelineGenerator {
             shader,
             states: vec![
                 // for Convex Polygon no stencil test
@@ -502,34 +860,20 @@ impl ColorPipelineGenerator {
                 }],
                 // group 1
                 vec![
-                    // binding 0: ImageTransform
+                    // binding 0: TextInfo
                     wgpu::BindGroupLayoutEntry {
                         binding: 0,
-                        visibility: wgpu::ShaderStages::VERTEX,
-                        ty: wgpu::BindingType::Buffer {
-                            ty: wgpu::BufferBindingType::Uniform,
-                            has_dynamic_offset: false,
-                            min_binding_size: wgpu::BufferSize::new(
-                                (std::mem::size_of::<nalgebra::Matrix4<f32>>() + 16)
-                                    as wgpu::BufferAddress,
-                            ),
-                        },
-                        count: None,
-                    },
-                    // binding 1: ImageBounds
-                    wgpu::BindGroupLayoutEntry {
-                        binding: 1,
                         visibility: wgpu::ShaderStages::FRAGMENT,
                         ty: wgpu::BindingType::Buffer {
                             ty: wgpu::BufferBindingType::Uniform,
                             has_dynamic_offset: false,
-                            min_binding_size: wgpu::BufferSize::new(16 as wgpu::BufferAddress),
+                            min_binding_size: wgpu::BufferSize::new(32),
                         },
                         count: None,
                     },
-                    // binding 2: TextureView
+                    // binding 1: TextureView
                     wgpu::BindGroupLayoutEntry {
-                        binding: 2,
+                        binding: 1,
                         visibility: wgpu::ShaderStages::FRAGMENT,
                         ty: wgpu::BindingType::Texture {
                             sample_type: wgpu::TextureSampleType::Float { filterable: true },
@@ -538,9 +882,9 @@ impl ColorPipelineGenerator {
                         },
                         count: None,
                     },
-                    // binding 3: Sampler
+                    // binding 2: Sampler
                     wgpu::BindGroupLayoutEntry {
-                        binding: 3,
+                        binding: 2,
                         visibility: wgpu::ShaderStages::FRAGMENT,
                         ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
                         count: None,
@@ -550,23 +894,18 @@ impl ColorPipelineGenerator {
         })
     }
 
-    pub(crate) fn solid_text_pipeline(device: &wgpu::Device) -> Box<dyn PipelineGenerater> {
-        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
-            label: Some("Solid Text shader"),
-            source: wgpu::ShaderSource::Wgsl(include_str!("../shaders/solid_text.wgsl").into()),
-        });
+    pub(crate) fn layer_pipeline() -> Box<dyn PipelineGenerater> {
+        let shader = ShaderSource {
+            label: "Layer shader",
+            source: include_str!("../shaders/layer.wgsl").into(),
+        };
 
-        Box::new(TextPipelineGenerator {
+        Box::new(ColorPipelineGenerator {
+            color_writable: true,
             shader,
             states: vec![
-                // for Convex Polygon no stencil test
+                // layer bounds is always a convex rect
                 state_for_convex_polygon(),
-                // for Stencil and Cover winding fill
-                state_for_complex_winding(),
-                // for Stencil and Cover even-odd fill
-                state_for_complex_even_odd(),
-                // for stroke no-overlap fill
-                state_for_no_overlap(),
             ],
             groups: vec![
                 // group 0
@@ -585,14 +924,14 @@ impl ColorPipelineGenerator {
                 }],
                 // group 1
                 vec![
-                    // binding 0: Color
+                    // binding 0: LayerInfo
                     wgpu::BindGroupLayoutEntry {
                         binding: 0,
-                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
                         ty: wgpu::BindingType::Buffer {
                             ty: wgpu::BufferBindingType::Uniform,
                             has_dynamic_offset: false,
-                            min_binding_size: wgpu::BufferSize::new(16),
+                            min_binding_size: wgpu::BufferSize::new(32),
                         },
                         count: None,
                     },
@@ -619,11 +958,11 @@ impl ColorPipelineGenerator {
         })
     }
 
-    pub(crate) fn non_color_pipeline(device: &wgpu::Device) -> Box<dyn PipelineGenerater> {
-        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
-            label: Some("Non Color shader"),
-            source: wgpu::ShaderSource::Wgsl(include_str!("../shaders/non_color.wgsl").into()),
-        });
+    pub(crate) fn non_color_pipeline() -> Box<dyn PipelineGenerater> {
+        let shader = ShaderSource {
+            label: "Non Color shader",
+            source: include_str!("../shaders/non_color.wgsl").into(),
+        };
 
         Box::new(ColorPipelineGenerator {
             color_writable: false,
@@ -631,6 +970,7 @@ impl ColorPipelineGenerator {
             states: vec![
                 // for Complex Polygon stencil mask
                 state_for_stencil_mask(),
+                state_for_stencil_mask_always(),
                 // for intersect clip mask
                 state_for_clip_intersect(),
                 // for even-odd intersect clip mask
@@ -639,6 +979,19 @@ impl ColorPipelineGenerator {
                 state_for_clip_difference(),
                 // for even-odd difference clip mask
                 state_for_clip_even_odd_difference(),
+                // for clip masks which can grow the clip
+                state_for_clip_union(),
+                state_for_clip_even_odd_union(),
+                state_for_clip_xor(),
+                state_for_clip_even_odd_xor(),
+                state_for_clip_replace(),
+                state_for_clip_even_odd_replace(),
+                // for anti-alias fringe of clip masks
+                state_for_clip_fringe_intersect(),
+                state_for_clip_fringe_even_odd_intersect(),
+                state_for_clip_fringe_difference(),
+                state_for_clip_fringe_even_odd_difference(),
+                state_for_clip_fringe_include(),
             ],
             groups: vec![
                 // group 0
@@ -661,52 +1014,68 @@ impl ColorPipelineGenerator {
 }
 
 impl PipelineGenerater for ColorPipelineGenerator {
-    fn gen_pipeline(
-        &self,
-        format: wgpu::TextureFormat,
-        sample_count: u32,
-        device: &wgpu::Device,
-    ) -> Pipeline {
+    fn gen_pipeline(&self, key: &PipelineKey, device: &wgpu::Device) -> Pipeline {
         let mut builder = PipelineBuilder::new();
 
         for group in &self.groups {
             builder = builder.add_group(group.clone());
         }
 
+        if key.color_filter {
+            builder = builder.add_group(color_filter_group_layout());
+        }
+
+        if key.mask {
+            builder = builder.add_group(mask_group_layout());
+        }
+
         return builder
-            .with_format(format)
-            .with_sample_count(sample_count)
+            .with_format(key.format)
+            .with_sample_count(key.sample_count)
+            .with_blend_mode(key.blend_mode)
             .with_color_writable(self.color_writable)
+            // position and anti-alias coverage of raster vertices
             .add_buffer(wgpu::VertexBufferLayout {
-                array_stride: 8,
+                array_stride: 12,
                 step_mode: wgpu::VertexStepMode::Vertex,
-                attributes: &[wgpu::VertexAttribute {
-                    offset: 0,
-                    shader_location: 0,
-                    format: wgpu::VertexFormat::Float32x2,
-                }],
+                attributes: &[
+                    wgpu::VertexAttribute {
+                        offset: 0,
+                        shader_location: 0,
+                        format: wgpu::VertexFormat::Float32x2,
+                    },
+                    wgpu::VertexAttribute {
+                        offset: 8,
+                        shader_location: 1,
+                        format: wgpu::VertexFormat::Float32,
+                    },
+                ],
             })
             .with_states(self.states.clone())
-            .build(&self.shader, device);
+            .build(&self.shader.create_module(key, device), device);
     }
 }
 
 impl PipelineGenerater for TextPipelineGenerator {
-    fn gen_pipeline(
-        &self,
-        format: wgpu::TextureFormat,
-        sample_count: u32,
-        device: &wgpu::Device,
-    ) -> Pipeline {
+    fn gen_pipeline(&self, key: &PipelineKey, device: &wgpu::Device) -> Pipeline {
         let mut builder = PipelineBuilder::new();
 
         for group in &self.groups {
             builder = builder.add_group(group.clone());
         }
 
+        if key.color_filter {
+            builder = builder.add_group(color_filter_group_layout());
+        }
+
+        if key.mask {
+            builder = builder.add_group(mask_group_layout());
+        }
+
         return builder
-            .with_format(format)
-            .with_sample_count(sample_count)
+            .with_format(key.format)
+            .with_sample_count(key.sample_count)
+            .with_blend_mode(key.blend_mode)
             .with_color_writable(true)
             .add_buffer(wgpu::VertexBufferLayout {
                 array_stride: 16,
@@ -725,7 +1094,7 @@ impl PipelineGenerater for TextPipelineGenerator {
                 ],
             })
             .with_states(self.states.clone())
-            .build(&self.shader, device);
+            .build(&self.shader.create_module(key, device), device);
     }
 }
 
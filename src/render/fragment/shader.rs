@@ -0,0 +1,580 @@
+use std::ops::Range;
+
+use nalgebra::{Matrix4, Vector4};
+
+use crate::{
+    core::{
+        image::ImageFormat, BlendMode, Color, ColorFilter, ColorType, GradientInterpolation,
+        RuntimeUniforms, TileMode,
+    },
+    gpu::{buffer::StageBuffer, pipeline::Pipeline, GPUContext},
+    render::Fragment,
+    Matrix,
+};
+
+use super::{
+    color_filter::COLOR_FILTER_INFO_SIZE,
+    sampler_entry,
+    texture::{texture_provider, TextureProvider},
+    texture_entry, uniform_entry, ColorFilterStage, ColorPipelineGenerator, GradientColorInfo,
+    TransformGroup,
+};
+
+/// Size in bytes of `GradientInfo` in gradient.wgsl
+const GRADIENT_INFO_SIZE: u64 = 20 * 4;
+
+/// Returns the matrix mapping local coordinates into the space of a node.
+/// Nodes with a non-invertible matrix are shaded in local space.
+fn inverse_matrix(matrix: &Matrix) -> Matrix4<f32> {
+    matrix.try_invert().unwrap_or_else(Matrix::new).matrix
+}
+
+struct GradientNode {
+    matrix: Matrix4<f32>,
+    params: [f32; 4],
+    colors: GradientColorInfo,
+}
+
+impl GradientNode {
+    /// Returns `None` if the gradient has less than two colors or the stops do not match the colors.
+    fn new(
+        colors: &[Color],
+        stops: &[f32],
+        tile_mode: TileMode,
+        interpolation: GradientInterpolation,
+        matrix: &Matrix,
+        params: [f32; 4],
+    ) -> Option<Self> {
+        if colors.len() < 2 || (!stops.is_empty() && stops.len() != colors.len()) {
+            return None;
+        }
+
+        Some(Self {
+            matrix: inverse_matrix(matrix),
+            params,
+            colors: GradientColorInfo::new(
+                colors,
+                if stops.is_empty() { None } else { Some(stops) },
+                tile_mode,
+                interpolation,
+            ),
+        })
+    }
+}
+
+enum NodeKind {
+    Solid(Color),
+    LinearGradient(GradientNode),
+    RadialGradient(GradientNode),
+    Image(Box<dyn TextureProvider>),
+    LocalMatrix(Matrix4<f32>),
+    ColorFilter(ColorFilterStage),
+    Blend(BlendMode),
+    Runtime(RuntimeUniforms),
+}
+
+impl NodeKind {
+    /// Name of the node inside the shape of a tree.
+    /// Runtime shaders are named by their program, the generated WGSL embeds their source.
+    fn name(&self) -> String {
+        match self {
+            NodeKind::Solid(_) => "Solid".to_string(),
+            NodeKind::LinearGradient(_) => "LinearGradient".to_string(),
+            NodeKind::RadialGradient(_) => "RadialGradient".to_string(),
+            NodeKind::Image(_) => "Image".to_string(),
+            NodeKind::LocalMatrix(_) => "LocalMatrix".to_string(),
+            NodeKind::ColorFilter(_) => "ColorFilter".to_string(),
+            NodeKind::Blend(_) => "Blend".to_string(),
+            NodeKind::Runtime(uniforms) => format!("Runtime{}", uniforms.shader().id()),
+        }
+    }
+
+    /// WGSL of the node. `NODE_ID`, `BINDING_n` and `CHILD_n` are replaced when the shader is generated.
+    fn template(&self) -> &'static str {
+        match self {
+            NodeKind::Solid(_) => include_str!("../shaders/shader_solid.wgsl"),
+            NodeKind::LinearGradient(_) => concat!(
+                include_str!("../shaders/shader_gradient.wgsl"),
+                include_str!("../shaders/shader_linear_gradient.wgsl")
+            ),
+            NodeKind::RadialGradient(_) => concat!(
+                include_str!("../shaders/shader_gradient.wgsl"),
+                include_str!("../shaders/shader_radial_gradient.wgsl")
+            ),
+            NodeKind::Image(_) => include_str!("../shaders/shader_image.wgsl"),
+            NodeKind::LocalMatrix(_) => include_str!("../shaders/shader_local_matrix.wgsl"),
+            NodeKind::ColorFilter(_) => include_str!("../shaders/shader_color_filter.wgsl"),
+            NodeKind::Blend(_) => include_str!("../shaders/shader_blend.wgsl"),
+            NodeKind::Runtime(_) => include_str!("../shaders/shader_runtime.wgsl"),
+        }
+    }
+
+    /// Layout entries of the node inside group 1, uniforms always come before textures.
+    fn layout_entries(&self, binding: u32) -> Vec<wgpu::BindGroupLayoutEntry> {
+        match self {
+            NodeKind::Solid(_) => vec![uniform_entry(binding, 16)],
+            NodeKind::LinearGradient(_) | NodeKind::RadialGradient(_) => vec![
+                uniform_entry(binding, GRADIENT_INFO_SIZE),
+                uniform_entry(binding + 1, std::mem::size_of::<GradientColorInfo>() as u64),
+            ],
+            NodeKind::Image(_) => vec![
+                uniform_entry(binding, 16),
+                texture_entry(binding + 1),
+                sampler_entry(binding + 2),
+            ],
+            NodeKind::LocalMatrix(_) => vec![uniform_entry(
+                binding,
+                std::mem::size_of::<Matrix4<f32>>() as u64,
+            )],
+            NodeKind::ColorFilter(_) => {
+                vec![uniform_entry(binding, COLOR_FILTER_INFO_SIZE as u64)]
+            }
+            NodeKind::Blend(_) => vec![uniform_entry(binding, 32)],
+            NodeKind::Runtime(uniforms) => match uniforms.shader().uniform_size() {
+                0 => vec![],
+                size => vec![uniform_entry(binding, size)],
+            },
+        }
+    }
+}
+
+/// One node of the flattened shader tree.
+struct ShaderNode {
+    kind: NodeKind,
+    children: Vec<usize>,
+    /// first binding of the node inside group 1
+    binding: u32,
+    /// ranges of the uniforms of the node, in binding order
+    ranges: Vec<Range<wgpu::BufferAddress>>,
+}
+
+/// Shades the geometry with a composed `ColorType`.
+///
+/// The tree is flattened in pre-order, every node becomes a `shader_N` function in the generated WGSL
+/// and its uniforms and textures are bind in group 1. The WGSL only depends on the shape of the tree,
+/// so pipelines are cached in `GPUContext` by the shape and shared by trees with different uniforms.
+pub(crate) struct ShaderFragment {
+    transform: TransformGroup,
+    nodes: Vec<ShaderNode>,
+    /// pipeline name, describes the shape of the tree
+    name: String,
+}
+
+impl ShaderFragment {
+    pub(crate) fn new(color: &ColorType, vw: f32, vh: f32, transform: Matrix4<f32>) -> Self {
+        let mut nodes = Vec::new();
+
+        Self::push_node(color, &mut nodes, &mut 0);
+
+        let name = format!("Shader:{}", Self::shape(&nodes, 0));
+
+        Self {
+            transform: TransformGroup::new(
+                Matrix4::new_orthographic(0.0, vw, vh, 0.0, -1000.0, 1000.0),
+                transform,
+                Vector4::new(0.0, 0.0, 0.0, 0.0),
+            ),
+            nodes,
+            name,
+        }
+    }
+
+    /// Appends the nodes of `color` in pre-order and returns the index of its root node.
+    /// Invalid gradients are replaced by black color.
+    ///
+    /// # Arguments
+    ///
+    /// * `color` the sub tree to flatten
+    /// * `nodes` the flattened nodes
+    /// * `binding` next free binding in group 1
+    fn push_node(color: &ColorType, nodes: &mut Vec<ShaderNode>, binding: &mut u32) -> usize {
+        let (kind, children): (NodeKind, Vec<&ColorType>) = match color {
+            ColorType::SolidColor(color) => (NodeKind::Solid(*color), vec![]),
+            ColorType::LinearGradient(gradient) => (
+                GradientNode::new(
+                    &gradient.colors,
+                    &gradient.stops,
+                    gradient.tile_mode,
+                    gradient.interpolation,
+                    &gradient.matrix,
+                    [gradient.p1.x, gradient.p1.y, gradient.p2.x, gradient.p2.y],
+                )
+                .map_or(NodeKind::Solid(Color::black()), NodeKind::LinearGradient),
+                vec![],
+            ),
+            ColorType::RadialGradient(gradient) => (
+                GradientNode::new(
+                    &gradient.colors,
+                    &gradient.stops,
+                    gradient.tile_mode,
+                    gradient.interpolation,
+                    &gradient.matrix,
+                    [gradient.center.x, gradient.center.y, gradient.radius, 0.0],
+                )
+                .map_or(NodeKind::Solid(Color::black()), NodeKind::RadialGradient),
+                vec![],
+            ),
+            ColorType::Image(image) => (NodeKind::Image(texture_provider(image)), vec![]),
+            ColorType::Blend(mode, src, dst) => (NodeKind::Blend(*mode), vec![src, dst]),
+            ColorType::WithLocalMatrix(matrix, color) => {
+                (NodeKind::LocalMatrix(inverse_matrix(matrix)), vec![color])
+            }
+            ColorType::WithColorFilter(filter, color) => {
+                return Self::push_filter_node(&filter.split(), color, nodes, binding);
+            }
+            ColorType::RuntimeShader(uniforms) => (NodeKind::Runtime(uniforms.clone()), vec![]),
+        };
+
+        let index = Self::push_kind(kind, nodes, binding);
+
+        nodes[index].children = children
+            .into_iter()
+            .map(|child| Self::push_node(child, nodes, binding))
+            .collect();
+
+        index
+    }
+
+    /// Appends the nodes of color filters applied to `color` and returns the index of the root node.
+    /// The filters are split by `ColorFilter::split` and nested, so the last one is the root.
+    ///
+    /// # Arguments
+    ///
+    /// * `filters` the filters in execution order
+    /// * `color` the sub tree the filters are applied to
+    /// * `nodes` the flattened nodes
+    /// * `binding` next free binding in group 1
+    fn push_filter_node(
+        filters: &[ColorFilter],
+        color: &ColorType,
+        nodes: &mut Vec<ShaderNode>,
+        binding: &mut u32,
+    ) -> usize {
+        let Some((filter, inner)) = filters.split_last() else {
+            return Self::push_node(color, nodes, binding);
+        };
+
+        let index = Self::push_kind(
+            NodeKind::ColorFilter(ColorFilterStage::new(filter)),
+            nodes,
+            binding,
+        );
+
+        nodes[index].children = vec![Self::push_filter_node(inner, color, nodes, binding)];
+
+        index
+    }
+
+    /// Appends a node without children and returns its index.
+    fn push_kind(kind: NodeKind, nodes: &mut Vec<ShaderNode>, binding: &mut u32) -> usize {
+        let index = nodes.len();
+        let count = kind.layout_entries(0).len() as u32;
+
+        nodes.push(ShaderNode {
+            kind,
+            children: vec![],
+            binding: *binding,
+            ranges: vec![],
+        });
+
+        *binding += count;
+
+        index
+    }
+
+    /// Describes the sub tree at `index`, for example `Blend(LinearGradient,Image)`.
+    fn shape(nodes: &[ShaderNode], index: usize) -> String {
+        let node = &nodes[index];
+
+        if node.children.is_empty() {
+            return node.kind.name();
+        }
+
+        let children: Vec<String> = node
+            .children
+            .iter()
+            .map(|child| Self::shape(nodes, *child))
+            .collect();
+
+        format!("{}({})", node.kind.name(), children.join(","))
+    }
+
+    /// Generates the WGSL of the tree, the fragment entry shades with `shader_0`.
+    fn gen_source(&self) -> String {
+        let mut source = include_str!("../shaders/shader.wgsl").to_string();
+
+        if self.nodes.iter().any(|node| {
+            matches!(
+                node.kind,
+                NodeKind::LinearGradient(_) | NodeKind::RadialGradient(_)
+            )
+        }) {
+            source.push('\n');
+            source.push_str(include_str!("../shaders/gradient.wgsl"));
+        }
+
+        for (index, node) in self.nodes.iter().enumerate() {
+            let mut code = node.kind.template().replace("NODE_ID", &index.to_string());
+
+            for i in 0..node.kind.layout_entries(0).len() {
+                code = code.replace(
+                    &format!("BINDING_{}", i),
+                    &(node.binding + i as u32).to_string(),
+                );
+            }
+
+            for (i, child) in node.children.iter().enumerate() {
+                code = code.replace(&format!("CHILD_{}", i), &child.to_string());
+            }
+
+            if let NodeKind::Runtime(uniforms) = &node.kind {
+                code.push_str(&uniforms.shader().gen_source(
+                    &format!("runtime_{}_", index),
+                    1,
+                    node.binding,
+                ));
+            }
+
+            source.push('\n');
+            source.push_str(&code);
+        }
+
+        source
+    }
+
+    fn gen_layout(&self) -> Vec<wgpu::BindGroupLayoutEntry> {
+        self.nodes
+            .iter()
+            .flat_map(|node| node.kind.layout_entries(node.binding))
+            .collect()
+    }
+}
+
+impl Fragment for ShaderFragment {
+    fn get_pipeline_name(&self) -> &str {
+        &self.name
+    }
+
+    fn register_pipeline(&self, context: &mut GPUContext) {
+        context.register_generator(&self.name, || {
+            ColorPipelineGenerator::shader_pipeline(self.gen_source(), self.gen_layout())
+        });
+    }
+
+    fn prepare(
+        &mut self,
+        depth: f32,
+        buffer: &mut StageBuffer,
+        _context: &GPUContext,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+    ) {
+        self.transform.prepare(depth, buffer);
+
+        for node in &mut self.nodes {
+            node.ranges = match &mut node.kind {
+                NodeKind::Solid(color) => {
+                    let premul = [
+                        color.r * color.a,
+                        color.g * color.a,
+                        color.b * color.a,
+                        color.a,
+                    ];
+
+                    vec![buffer.push_data_align(bytemuck::cast_slice(&premul))]
+                }
+                NodeKind::LinearGradient(gradient) | NodeKind::RadialGradient(gradient) => {
+                    let mut info = smallvec::SmallVec::<[f32; 20]>::new();
+
+                    info.extend_from_slice(gradient.matrix.as_slice());
+                    info.extend_from_slice(&gradient.params);
+
+                    vec![
+                        buffer.push_data_align(bytemuck::cast_slice(info.as_slice())),
+                        buffer.push_data_align(bytemuck::cast_slice(&[gradient.colors])),
+                    ]
+                }
+                NodeKind::Image(texture) => {
+                    texture.prepare(device, queue);
+
+                    let info = [
+                        texture.get_width() as f32,
+                        texture.get_height() as f32,
+                        if texture.is_premutied() { 1.0 } else { 0.0 },
+                        if texture.get_format() == ImageFormat::BGRA8888 {
+                            1.0
+                        } else {
+                            0.0
+                        },
+                    ];
+
+                    vec![buffer.push_data_align(bytemuck::cast_slice(&info))]
+                }
+                NodeKind::LocalMatrix(matrix) => {
+                    vec![buffer.push_data_align(bytemuck::cast_slice(matrix.as_slice()))]
+                }
+                NodeKind::ColorFilter(stage) => {
+                    stage.prepare(buffer);
+
+                    vec![stage.get_buffer_range()]
+                }
+                NodeKind::Blend(mode) => {
+                    let k = mode.coefficients();
+                    let clamp = if *mode == BlendMode::Plus { 1.0 } else { 0.0 };
+                    let info = [k[0], k[1], k[2], k[3], k[4], clamp, 0.0, 0.0];
+
+                    vec![buffer.push_data_align(bytemuck::cast_slice(&info))]
+                }
+                NodeKind::Runtime(uniforms) => {
+                    if uniforms.data().is_empty() {
+                        vec![]
+                    } else {
+                        vec![buffer.push_data_align(bytemuck::cast_slice(uniforms.data()))]
+                    }
+                }
+            };
+        }
+    }
+
+    fn gen_bind_groups<'a>(
+        &self,
+        device: &wgpu::Device,
+        buffer: &'a wgpu::Buffer,
+        pipeline: &'a Pipeline,
+        context: &'a GPUContext,
+    ) -> Vec<wgpu::BindGroup> {
+        let group1_layout = pipeline
+            .get_group_layout(1)
+            .expect("Shader pipeline not have group 1");
+
+        let views: Vec<Option<wgpu::TextureView>> = self
+            .nodes
+            .iter()
+            .map(|node| match &node.kind {
+                NodeKind::Image(texture) => Some(
+                    texture
+                        .get_texture()
+                        .expect("Texture not prepared")
+                        .create_view(&wgpu::TextureViewDescriptor::default()),
+                ),
+                _ => None,
+            })
+            .collect();
+
+        let mut entries = Vec::new();
+
+        for (node, view) in self.nodes.iter().zip(views.iter()) {
+            for (i, range) in node.ranges.iter().enumerate() {
+                entries.push(wgpu::BindGroupEntry {
+                    binding: node.binding + i as u32,
+                    resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+                        buffer,
+                        offset: range.start,
+                        size: wgpu::BufferSize::new(range.end - range.start),
+                    }),
+                });
+            }
+
+            if let Some(view) = view {
+                let binding = node.binding + node.ranges.len() as u32;
+
+                entries.push(wgpu::BindGroupEntry {
+                    binding,
+                    resource: wgpu::BindingResource::TextureView(view),
+                });
+                entries.push(wgpu::BindGroupEntry {
+                    binding: binding + 1,
+                    resource: wgpu::BindingResource::Sampler(context.get_linear_sampler()),
+                });
+            }
+        }
+
+        let shader_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Shader Group"),
+            layout: group1_layout,
+            entries: &entries,
+        });
+
+        vec![
+            self.gen_common_bind_groups(device, buffer, pipeline, context),
+            shader_group,
+        ]
+    }
+
+    fn gen_common_bind_groups<'a>(
+        &self,
+        device: &wgpu::Device,
+        buffer: &'a wgpu::Buffer,
+        pipeline: &'a Pipeline,
+        _context: &'a GPUContext,
+    ) -> wgpu::BindGroup {
+        let group0_layout = pipeline
+            .get_group_layout(0)
+            .expect("common group at slot 0 can not be get!");
+
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Shader Common Group"),
+            layout: group0_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+                    buffer,
+                    offset: self.transform.get_buffer_range().start,
+                    size: wgpu::BufferSize::new(
+                        self.transform.get_buffer_range().end
+                            - self.transform.get_buffer_range().start,
+                    ),
+                }),
+            }],
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::{LinearGradient, Point};
+
+    #[test]
+    fn shape_and_bindings() {
+        let gradient = LinearGradient::new(Point::from(0.0, 0.0), Point::from(1.0, 0.0))
+            .with_colors(vec![Color::black(), Color::white()]);
+
+        let color = ColorType::blend(
+            BlendMode::Modulate,
+            gradient,
+            ColorType::SolidColor(Color::white()).with_color_filter(ColorFilter::grayscale()),
+        );
+
+        let fragment = ShaderFragment::new(&color, 100.0, 100.0, Matrix4::identity());
+
+        assert_eq!(
+            fragment.get_pipeline_name(),
+            "Shader:Blend(LinearGradient,ColorFilter(Solid))"
+        );
+
+        let bindings: Vec<u32> = fragment.nodes.iter().map(|node| node.binding).collect();
+        assert_eq!(bindings, vec![0, 1, 3, 4]);
+        assert_eq!(fragment.nodes[0].children, vec![1, 2]);
+        assert_eq!(fragment.gen_layout().len(), 5);
+
+        // invalid gradients keep a valid tree shaded with black
+        let invalid = LinearGradient::new(Point::from(0.0, 0.0), Point::from(1.0, 0.0));
+        let fragment = ShaderFragment::new(&invalid.into(), 100.0, 100.0, Matrix4::identity());
+
+        assert_eq!(fragment.get_pipeline_name(), "Shader:Solid");
+
+        // filters longer than one stage are split into nested stages
+        let blend = ColorFilter::Blend(Color::white(), BlendMode::Plus);
+        let filter = (0..10).fold(blend.clone(), |inner, _| {
+            ColorFilter::compose(blend.clone(), inner)
+        });
+        let color = ColorType::SolidColor(Color::black()).with_color_filter(filter);
+        let fragment = ShaderFragment::new(&color, 100.0, 100.0, Matrix4::identity());
+
+        assert_eq!(
+            fragment.get_pipeline_name(),
+            "Shader:ColorFilter(ColorFilter(Solid))"
+        );
+    }
+}
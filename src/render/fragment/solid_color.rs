@@ -31,7 +31,7 @@ impl SolidColorFragment {
 }
 
 impl Fragment for SolidColorFragment {
-    fn get_pipeline_name(&self) -> &'static str {
+    fn get_pipeline_name(&self) -> &str {
         SOLID_PIPELINE_NAME
     }
 
@@ -4,8 +4,8 @@ use nalgebra::{Matrix4, Vector4};
 
 use crate::{
     core::{
-        image::{Bitmap, ImageFormat},
-        ImageInfo,
+        image::{Bitmap, ImageFormat, ImageSource},
+        Image, ImageInfo,
     },
     gpu::{buffer::StageBuffer, pipeline::Pipeline, GPUContext},
     render::Fragment,
@@ -13,7 +13,7 @@ use crate::{
 
 use super::{TransformGroup, TEXTURE_PIPELINE_NAME};
 
-trait TextureProvider {
+pub(crate) trait TextureProvider {
     fn prepare(&mut self, device: &wgpu::Device, queue: &wgpu::Queue);
 
     fn get_texture(&self) -> Option<Rc<wgpu::Texture>>;
@@ -145,6 +145,16 @@ impl TextureProvider for DirectTextureProvider {
     }
 }
 
+/// Returns the provider which uploads or wraps the texture of image.
+pub(crate) fn texture_provider(image: &Image) -> Box<dyn TextureProvider> {
+    match &image.source {
+        ImageSource::Bitmap(bitmap) => Box::new(BitmapTextureProvider::new(bitmap.clone())),
+        ImageSource::Texture(texture, info) => {
+            Box::new(DirectTextureProvider::new(texture.clone(), info.clone()))
+        }
+    }
+}
+
 pub(crate) struct TextureFragment {
     transform: TransformGroup,
     texture: Box<dyn TextureProvider>,
@@ -198,7 +208,7 @@ impl TextureFragment {
 }
 
 impl Fragment for TextureFragment {
-    fn get_pipeline_name(&self) -> &'static str {
+    fn get_pipeline_name(&self) -> &str {
         TEXTURE_PIPELINE_NAME
     }
 
@@ -4,14 +4,14 @@ use ab_glyph::ScaleFont;
 use nalgebra::{Matrix4, Vector4};
 
 use crate::{
-    core::{Color, Point},
-    gpu::{buffer::StageBuffer, pipeline::Pipeline},
+    core::{Color, ColorFilter, Point},
+    gpu::{buffer::StageBuffer, context::PipelineKey, pipeline::Pipeline, GPUContext},
     text::TextBlob,
 };
 
 use super::{
     command::Command,
-    fragment::{TransformGroup, SOLID_TEXT_PIPELINE_NAME},
+    fragment::{ColorFilterStage, TransformGroup, SOLID_TEXT_PIPELINE_NAME},
     Renderer,
 };
 
@@ -66,6 +66,7 @@ impl GlyphRunDrawable {
         buffer: &'a wgpu::Buffer,
         pipeline: &'a Pipeline,
         context: &'a crate::gpu::GPUContext,
+        color_filter: Option<&ColorFilterStage>,
         device: &wgpu::Device,
     ) -> Command<'a> {
         let group0_layout = pipeline
@@ -76,7 +77,7 @@ impl GlyphRunDrawable {
             .get_group_layout(1)
             .expect("text color group at slot 1 can not be get!");
 
-        let groups = vec![
+        let mut groups = vec![
             device.create_bind_group(&wgpu::BindGroupDescriptor {
                 label: Some("Common Group"),
                 layout: &group0_layout,
@@ -122,6 +123,10 @@ impl GlyphRunDrawable {
             }),
         ];
 
+        if let Some(color_filter) = color_filter {
+            groups.push(color_filter.gen_bind_group(device, buffer, pipeline));
+        }
+
         let state = wgpu::DepthStencilState {
             format: wgpu::TextureFormat::Depth24PlusStencil8,
             depth_write_enabled: false,
@@ -158,21 +163,22 @@ impl GlyphRunDrawable {
 }
 
 pub(crate) struct TextBlobRender {
-    format: wgpu::TextureFormat,
-    anti_alias: bool,
+    pipeline_key: PipelineKey,
     blob: Rc<TextBlob>,
     color: Color,
     pos: Point,
     depth: f32,
     transform: TransformGroup,
+    color_filter: Option<ColorFilterStage>,
+    /// [gamma, contrast] of glyph coverage
+    text_gamma: [f32; 2],
 
     drawables: Vec<GlyphRunDrawable>,
 }
 
 impl TextBlobRender {
     pub(crate) fn new(
-        format: wgpu::TextureFormat,
-        anti_alias: bool,
+        pipeline_key: PipelineKey,
         blob: Rc<TextBlob>,
         color: Color,
         pos: Point,
@@ -182,8 +188,7 @@ impl TextBlobRender {
         transform: Matrix4<f32>,
     ) -> Self {
         Self {
-            format,
-            anti_alias,
+            pipeline_key,
             blob,
             color,
             pos,
@@ -193,14 +198,31 @@ impl TextBlobRender {
                 transform,
                 Vector4::new(0.0, 0.0, 0.0, 0.0),
             ),
+            color_filter: None,
+            text_gamma: [1.0, 0.0],
             drawables: Vec::new(),
         }
     }
+
+    /// Runs the color filter on text color. Pass `None` to disable the stage.
+    pub(crate) fn with_color_filter(mut self, color_filter: Option<&ColorFilter>) -> Self {
+        self.color_filter = color_filter.map(ColorFilterStage::new);
+        self.pipeline_key = self
+            .pipeline_key
+            .with_color_filter(self.color_filter.is_some());
+        self
+    }
+
+    /// Adjusts glyph coverage for targets blending in linear light. `[1.0, 0.0]` keeps the coverage.
+    pub(crate) fn with_text_gamma(mut self, text_gamma: [f32; 2]) -> Self {
+        self.text_gamma = text_gamma;
+        self
+    }
 }
 
 impl Renderer for TextBlobRender {
-    fn pipeline_label(&self) -> &'static str {
-        SOLID_TEXT_PIPELINE_NAME
+    fn load_pipelines(&self, context: &mut GPUContext, device: &wgpu::Device) {
+        context.load_pipeline(SOLID_TEXT_PIPELINE_NAME, self.pipeline_key, device);
     }
 
     fn prepare(
@@ -213,7 +235,22 @@ impl Renderer for TextBlobRender {
     ) {
         self.transform.prepare(self.depth / total_depth, buffer);
 
-        let color_range = buffer.push_data_align(bytemuck::cast_slice(&[self.color]));
+        let text_info = [
+            self.color.r,
+            self.color.g,
+            self.color.b,
+            self.color.a,
+            self.text_gamma[0],
+            self.text_gamma[1],
+            0.0,
+            0.0,
+        ];
+
+        let color_range = buffer.push_data_align(bytemuck::cast_slice(&text_info));
+
+        if let Some(color_filter) = &mut self.color_filter {
+            color_filter.prepare(buffer);
+        }
 
         let mut drawable: Option<GlyphRunDrawable> = None;
 
@@ -317,7 +354,7 @@ impl Renderer for TextBlobRender {
         context: &'a crate::gpu::GPUContext,
         device: &wgpu::Device,
     ) -> Vec<Command<'a>> {
-        let pipeline = context.get_pipeline(self.pipeline_label(), self.format, self.anti_alias);
+        let pipeline = context.get_pipeline(SOLID_TEXT_PIPELINE_NAME, &self.pipeline_key);
 
         if self.drawables.is_empty() || pipeline.is_none() {
             return vec![];
@@ -328,7 +365,13 @@ impl Renderer for TextBlobRender {
         let mut commands = Vec::new();
 
         for drawable in &self.drawables {
-            commands.push(drawable.gen_command(buffer, &pipeline, context, device));
+            commands.push(drawable.gen_command(
+                buffer,
+                &pipeline,
+                context,
+                self.color_filter.as_ref(),
+                device,
+            ));
         }
 
         return commands;
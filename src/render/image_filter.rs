@@ -0,0 +1,564 @@
+use std::{ops::Range, rc::Rc};
+
+use crate::{
+    core::{image_filter::blur_extent, BlendMode, Color, ImageFilter, Rect},
+    gpu::{
+        buffer::StageBuffer, context::PipelineKey, surface::begin_filter_pass,
+        texture_pool::TextureDesc, GPUContext,
+    },
+};
+
+use super::{
+    command::Command,
+    fragment::{ColorFilterStage, IMAGE_FILTER_PIPELINE_NAME},
+};
+
+/// Max number of samples on each side of a blur pass. Larger blurs sample with a wider step.
+const MAX_BLUR_RADIUS: f32 = 64.0;
+
+/// An intermediate image of the filter graph.
+#[derive(Clone)]
+pub(crate) struct FilterImage {
+    pub(crate) texture: Rc<wgpu::Texture>,
+    /// bounds of the texture in pixels of the layer target
+    pub(crate) bounds: Rect,
+}
+
+enum FilterMode {
+    /// copies the source moved by (dx, dy)
+    Copy(f32, f32),
+    /// blurs the source along one axis
+    Blur {
+        step: [f32; 2],
+        sigma: f32,
+        radius: f32,
+    },
+    /// fills the alpha of the source moved by (dx, dy) with a color
+    Shadow(f32, f32, Color),
+}
+
+/// One quad drawn into the target of a pass, sampling a single source image.
+struct FilterDraw {
+    source: FilterImage,
+    mode: FilterMode,
+    color_filter: Option<ColorFilterStage>,
+
+    buffer_range: Range<wgpu::BufferAddress>,
+}
+
+impl FilterDraw {
+    fn new(source: &FilterImage, mode: FilterMode) -> Self {
+        Self {
+            source: source.clone(),
+            mode,
+            color_filter: None,
+            buffer_range: 0..0,
+        }
+    }
+
+    fn copy(source: &FilterImage) -> Self {
+        Self::new(source, FilterMode::Copy(0.0, 0.0))
+    }
+
+    /// Creates a Gaussian blur along one axis.
+    ///
+    /// # Arguments
+    ///
+    /// * `source` the image to blur
+    /// * `axis` unit vector of the blur direction
+    /// * `sigma` standard deviation in pixels
+    fn blur(source: &FilterImage, axis: [f32; 2], sigma: f32) -> Self {
+        let extent = blur_extent(sigma).ceil();
+        let spacing = (extent / MAX_BLUR_RADIUS).max(1.0);
+
+        Self::new(
+            source,
+            FilterMode::Blur {
+                step: [axis[0] * spacing, axis[1] * spacing],
+                sigma: sigma / spacing,
+                radius: (extent / spacing).ceil(),
+            },
+        )
+    }
+
+    fn key(&self, format: wgpu::TextureFormat) -> PipelineKey {
+        PipelineKey::new(format, 1)
+            .with_blend_mode(BlendMode::SrcOver)
+            .with_color_filter(self.color_filter.is_some())
+    }
+
+    fn prepare(&mut self, target: &Rect, buffer: &mut StageBuffer) {
+        let (mode, radius, params, color) = match self.mode {
+            FilterMode::Copy(dx, dy) => (0.0, 0.0, [dx, dy, 0.0, 0.0], Color::transparent()),
+            FilterMode::Blur {
+                step,
+                sigma,
+                radius,
+            } => (
+                1.0,
+                radius,
+                [step[0], step[1], sigma, 0.0],
+                Color::transparent(),
+            ),
+            FilterMode::Shadow(dx, dy, color) => (2.0, 0.0, [dx, dy, 0.0, 0.0], color),
+        };
+
+        let source = &self.source.bounds;
+
+        let info: [f32; 16] = [
+            target.left,
+            target.top,
+            source.left,
+            source.top,
+            source.width(),
+            source.height(),
+            mode,
+            radius,
+            params[0],
+            params[1],
+            params[2],
+            params[3],
+            color.r * color.a,
+            color.g * color.a,
+            color.b * color.a,
+            color.a,
+        ];
+
+        self.buffer_range = buffer.push_data_align(bytemuck::cast_slice(&info));
+
+        if let Some(color_filter) = &mut self.color_filter {
+            color_filter.prepare(buffer);
+        }
+    }
+}
+
+/// Renders all draws into one intermediate image.
+struct FilterPass {
+    target: FilterImage,
+    draws: Vec<FilterDraw>,
+}
+
+/// The passes which evaluate an `ImageFilter` on the content of a layer.
+/// Passes are recorded in dependency order, each one only reads images written by earlier passes.
+pub(crate) struct FilterGraph {
+    format: wgpu::TextureFormat,
+    passes: Vec<FilterPass>,
+
+    vertex_range: Range<wgpu::BufferAddress>,
+    index_range: Range<wgpu::BufferAddress>,
+}
+
+impl FilterGraph {
+    /// Builds the passes of `filter` and returns the graph with the filtered image.
+    /// The image is `None` if the output is empty inside `clip`.
+    ///
+    /// # Arguments
+    ///
+    /// * `filter` the filter with distances and sigmas in pixels
+    /// * `source` the layer content
+    /// * `clip` region of the output which is visible, in pixels
+    /// * `format` format of the intermediate textures
+    pub(crate) fn new(
+        filter: &ImageFilter,
+        source: FilterImage,
+        clip: &Rect,
+        format: wgpu::TextureFormat,
+        context: &GPUContext,
+        device: &wgpu::Device,
+    ) -> (Self, Option<FilterImage>) {
+        let mut graph = Self {
+            format,
+            passes: Vec::new(),
+            vertex_range: 0..0,
+            index_range: 0..0,
+        };
+
+        // nothing is evaluated if the output can not reach the visible region
+        if filter.map_bounds(&source.bounds).intersect(clip).is_none() {
+            return (graph, None);
+        }
+
+        let image = graph.eval(filter, &source, clip, context, device);
+
+        (graph, image)
+    }
+
+    /// Loads the pipelines used by filter passes into `context`.
+    pub(crate) fn load_pipelines(
+        format: wgpu::TextureFormat,
+        context: &mut GPUContext,
+        device: &wgpu::Device,
+    ) {
+        for color_filter in [false, true] {
+            context.load_pipeline(
+                IMAGE_FILTER_PIPELINE_NAME,
+                PipelineKey::new(format, 1)
+                    .with_blend_mode(BlendMode::SrcOver)
+                    .with_color_filter(color_filter),
+                device,
+            );
+        }
+    }
+
+    fn eval_input(
+        &mut self,
+        input: &Option<Box<ImageFilter>>,
+        source: &FilterImage,
+        needed: &Rect,
+        context: &GPUContext,
+        device: &wgpu::Device,
+    ) -> Option<FilterImage> {
+        match input {
+            Some(input) => self.eval(input, source, needed, context, device),
+            None => Some(source.clone()),
+        }
+    }
+
+    /// Evaluates `filter` and returns the part of its output inside `needed`.
+    fn eval(
+        &mut self,
+        filter: &ImageFilter,
+        source: &FilterImage,
+        needed: &Rect,
+        context: &GPUContext,
+        device: &wgpu::Device,
+    ) -> Option<FilterImage> {
+        match filter {
+            ImageFilter::Blur {
+                sigma_x,
+                sigma_y,
+                input,
+            } => {
+                let mut input_needed = *needed;
+                input_needed.outset(blur_extent(*sigma_x), blur_extent(*sigma_y));
+
+                let image = self.eval_input(input, source, &input_needed, context, device)?;
+
+                self.blur(&image, *sigma_x, *sigma_y, needed, context, device)
+            }
+            ImageFilter::DropShadow {
+                dx,
+                dy,
+                sigma_x,
+                sigma_y,
+                color,
+                input,
+            }
+            | ImageFilter::DropShadowOnly {
+                dx,
+                dy,
+                sigma_x,
+                sigma_y,
+                color,
+                input,
+            } => {
+                let shadow_only = matches!(filter, ImageFilter::DropShadowOnly { .. });
+
+                let mut shadow_needed = *needed;
+                shadow_needed.outset(blur_extent(*sigma_x), blur_extent(*sigma_y));
+                shadow_needed.offset(-dx, -dy);
+
+                let input_needed = if shadow_only {
+                    shadow_needed
+                } else {
+                    shadow_needed.union(needed)
+                };
+
+                let image = self.eval_input(input, source, &input_needed, context, device)?;
+
+                let mut shadow_bounds = image.bounds;
+                shadow_bounds.offset(*dx, *dy);
+                let mut blurred_needed = *needed;
+                blurred_needed.outset(blur_extent(*sigma_x), blur_extent(*sigma_y));
+
+                let shadow = self.push_pass(
+                    &shadow_bounds,
+                    &blurred_needed,
+                    vec![FilterDraw::new(
+                        &image,
+                        FilterMode::Shadow(*dx, *dy, *color),
+                    )],
+                    context,
+                    device,
+                );
+
+                let shadow = match shadow {
+                    Some(shadow) => self.blur(&shadow, *sigma_x, *sigma_y, needed, context, device),
+                    None => None,
+                };
+
+                if shadow_only {
+                    return shadow;
+                }
+
+                match shadow {
+                    Some(shadow) => {
+                        let bounds = shadow.bounds.union(&image.bounds);
+
+                        self.push_pass(
+                            &bounds,
+                            needed,
+                            vec![FilterDraw::copy(&shadow), FilterDraw::copy(&image)],
+                            context,
+                            device,
+                        )
+                    }
+                    None => Some(image),
+                }
+            }
+            ImageFilter::Offset { dx, dy, input } => {
+                let mut input_needed = *needed;
+                input_needed.offset(-dx, -dy);
+
+                let image = self.eval_input(input, source, &input_needed, context, device)?;
+
+                if dx.fract() == 0.0 && dy.fract() == 0.0 {
+                    // whole pixel offsets only move the image
+                    let mut bounds = image.bounds;
+                    bounds.offset(*dx, *dy);
+
+                    return Some(FilterImage {
+                        texture: image.texture,
+                        bounds,
+                    });
+                }
+
+                let mut bounds = image.bounds;
+                bounds.offset(*dx, *dy);
+
+                self.push_pass(
+                    &bounds,
+                    needed,
+                    vec![FilterDraw::new(&image, FilterMode::Copy(*dx, *dy))],
+                    context,
+                    device,
+                )
+            }
+            ImageFilter::ColorFilter { filter, input } => {
+                let mut image = self.eval_input(input, source, needed, context, device)?;
+
+                // filters longer than one stage are applied by a pass for each part
+                for part in filter.split() {
+                    let mut draw = FilterDraw::copy(&image);
+                    draw.color_filter = Some(ColorFilterStage::new(&part));
+
+                    image = self.push_pass(&image.bounds, needed, vec![draw], context, device)?;
+                }
+
+                Some(image)
+            }
+            ImageFilter::Merge(inputs) => {
+                let images: Vec<FilterImage> = inputs
+                    .iter()
+                    .filter_map(|input| match input {
+                        Some(input) => self.eval(input, source, needed, context, device),
+                        None => Some(source.clone()),
+                    })
+                    .collect();
+
+                let bounds = images
+                    .iter()
+                    .fold(Rect::from_ltrb(0.0, 0.0, 0.0, 0.0), |acc, image| {
+                        acc.union(&image.bounds)
+                    });
+
+                self.push_pass(
+                    &bounds,
+                    needed,
+                    images.iter().map(FilterDraw::copy).collect(),
+                    context,
+                    device,
+                )
+            }
+            ImageFilter::Compose(outer, inner) => {
+                let inner_needed = outer.reverse_bounds(needed);
+
+                let image = self.eval(inner, source, &inner_needed, context, device)?;
+
+                self.eval(outer, &image, needed, context, device)
+            }
+        }
+    }
+
+    /// Blurs `image` with a horizontal pass followed by a vertical pass. Axes with zero sigma are skipped.
+    fn blur(
+        &mut self,
+        image: &FilterImage,
+        sigma_x: f32,
+        sigma_y: f32,
+        needed: &Rect,
+        context: &GPUContext,
+        device: &wgpu::Device,
+    ) -> Option<FilterImage> {
+        let (extent_x, extent_y) = (blur_extent(sigma_x), blur_extent(sigma_y));
+
+        let mut image = image.clone();
+
+        if extent_x > 0.0 {
+            let mut bounds = image.bounds;
+            bounds.outset(extent_x, 0.0);
+
+            // the vertical pass still reads rows outside of needed
+            let mut pass_needed = *needed;
+            pass_needed.outset(0.0, extent_y);
+
+            image = self.push_pass(
+                &bounds,
+                &pass_needed,
+                vec![FilterDraw::blur(&image, [1.0, 0.0], sigma_x)],
+                context,
+                device,
+            )?;
+        }
+
+        if extent_y > 0.0 {
+            let mut bounds = image.bounds;
+            bounds.outset(0.0, extent_y);
+
+            image = self.push_pass(
+                &bounds,
+                needed,
+                vec![FilterDraw::blur(&image, [0.0, 1.0], sigma_y)],
+                context,
+                device,
+            )?;
+        }
+
+        Some(image)
+    }
+
+    /// Records a pass rendering `draws` into a new image covering `bounds` inside `needed`.
+    fn push_pass(
+        &mut self,
+        bounds: &Rect,
+        needed: &Rect,
+        draws: Vec<FilterDraw>,
+        context: &GPUContext,
+        device: &wgpu::Device,
+    ) -> Option<FilterImage> {
+        let bounds = bounds.intersect(needed)?.round_out();
+
+        let texture = context.acquire_texture(
+            "image filter",
+            &TextureDesc::offscreen(bounds.width() as u32, bounds.height() as u32, self.format),
+            device,
+        );
+
+        let target = FilterImage { texture, bounds };
+
+        self.passes.push(FilterPass {
+            target: target.clone(),
+            draws,
+        });
+
+        Some(target)
+    }
+
+    pub(crate) fn prepare(&mut self, buffer: &mut StageBuffer) {
+        if self.passes.is_empty() {
+            return;
+        }
+
+        let quad: [f32; 8] = [0.0, 0.0, 1.0, 0.0, 1.0, 1.0, 0.0, 1.0];
+        let indices: [u32; 6] = [0, 1, 2, 0, 2, 3];
+
+        self.vertex_range = buffer.push_data(bytemuck::cast_slice(&quad));
+        self.index_range = buffer.push_data(bytemuck::cast_slice(&indices));
+
+        for pass in &mut self.passes {
+            for draw in &mut pass.draws {
+                draw.prepare(&pass.target.bounds, buffer);
+            }
+        }
+    }
+
+    pub(crate) fn render(
+        &self,
+        buffer: &wgpu::Buffer,
+        context: &GPUContext,
+        device: &wgpu::Device,
+        encoder: &mut wgpu::CommandEncoder,
+    ) {
+        for pass in &self.passes {
+            let mut commands = Vec::new();
+
+            for draw in &pass.draws {
+                let pipeline = match context
+                    .get_pipeline(IMAGE_FILTER_PIPELINE_NAME, &draw.key(self.format))
+                {
+                    Some(pipeline) => pipeline,
+                    None => continue,
+                };
+
+                let raw_pipeline = pipeline
+                    .get_plain_pipeline()
+                    .expect("Can not get image filter pipeline");
+
+                let source_view = draw
+                    .source
+                    .texture
+                    .create_view(&wgpu::TextureViewDescriptor::default());
+
+                let mut groups = vec![
+                    device.create_bind_group(&wgpu::BindGroupDescriptor {
+                        label: Some("Filter Info Group"),
+                        layout: pipeline
+                            .get_group_layout(0)
+                            .expect("Image filter pipeline not have group 0"),
+                        entries: &[wgpu::BindGroupEntry {
+                            binding: 0,
+                            resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+                                buffer,
+                                offset: draw.buffer_range.start,
+                                size: wgpu::BufferSize::new(
+                                    draw.buffer_range.end - draw.buffer_range.start,
+                                ),
+                            }),
+                        }],
+                    }),
+                    device.create_bind_group(&wgpu::BindGroupDescriptor {
+                        label: Some("Filter Source Group"),
+                        layout: pipeline
+                            .get_group_layout(1)
+                            .expect("Image filter pipeline not have group 1"),
+                        entries: &[
+                            wgpu::BindGroupEntry {
+                                binding: 0,
+                                resource: wgpu::BindingResource::TextureView(&source_view),
+                            },
+                            wgpu::BindGroupEntry {
+                                binding: 1,
+                                resource: wgpu::BindingResource::Sampler(
+                                    context.get_linear_sampler(),
+                                ),
+                            },
+                        ],
+                    }),
+                ];
+
+                if let Some(color_filter) = &draw.color_filter {
+                    groups.push(color_filter.gen_bind_group(device, buffer, pipeline));
+                }
+
+                commands.push(Command::new(
+                    raw_pipeline,
+                    buffer.slice(self.vertex_range.clone()),
+                    buffer.slice(self.index_range.clone()),
+                    6,
+                    groups,
+                ));
+            }
+
+            let target_view = pass
+                .target
+                .texture
+                .create_view(&wgpu::TextureViewDescriptor::default());
+
+            let mut render_pass = begin_filter_pass("Image filter pass", &target_view, encoder);
+
+            for command in &commands {
+                command.run(&mut render_pass);
+            }
+        }
+    }
+}
@@ -0,0 +1,339 @@
+use std::rc::Rc;
+
+use nalgebra::Matrix4;
+
+use crate::{
+    core::{ImageFilter, Paint, Path, Rect},
+    gpu::{
+        buffer::StageBuffer, surface::begin_render_pass, texture_pool::TextureDesc, GPUContext,
+    },
+};
+
+use super::{
+    clip_coverage::ClipCoverage,
+    command::Command,
+    fragment::{LayerFragment, LAYER_PIPELINE_NAME},
+    image_filter::{FilterGraph, FilterImage},
+    raster::PathFill,
+    CommandList, PathRenderer, RenderTarget, Renderer,
+};
+
+/// Renders a group of draws into an offscreen texture and composites the result onto the parent target.
+pub(crate) struct LayerRenderer {
+    target: RenderTarget,
+    /// bounds of the layer in parent target logical space, aligned to physical pixels
+    bounds: Rect,
+    paint: Paint,
+    depth: f32,
+    /// total depth used by draws inside the layer
+    depth_count: u32,
+    renders: Vec<Box<dyn Renderer>>,
+
+    texture: Option<Rc<wgpu::Texture>>,
+    msaa_texture: Option<Rc<wgpu::Texture>>,
+    depth_stencil: Option<Rc<wgpu::Texture>>,
+    /// evaluates the image filter of paint on the layer texture
+    filter_graph: Option<FilterGraph>,
+    /// feathered coverage of the clip the layer is inside, applied when compositing
+    clip: Option<ClipCoverage>,
+    composite: Option<PathRenderer>,
+}
+
+impl LayerRenderer {
+    /// Computes the pixel aligned layer bounds inside the parent target.
+    ///
+    /// # Arguments
+    ///
+    /// * `target` the parent target
+    /// * `bounds` device space bounds of the layer, `None` means the whole target
+    /// * `filter` image filter of the layer in device space, content outside the viewport
+    ///   which the filter moves or blurs into it is kept
+    pub(crate) fn layer_bounds(
+        target: &RenderTarget,
+        bounds: Option<&Rect>,
+        filter: Option<&ImageFilter>,
+    ) -> Rect {
+        let mut viewport = Rect::from_xywh(0.0, 0.0, target.width, target.height);
+
+        if let Some(filter) = filter {
+            viewport = filter.reverse_bounds(&viewport);
+        }
+
+        let bounds = match bounds {
+            Some(bounds) => crate::core::Matrix {
+                matrix: target.base_transform,
+            }
+            .map_rect(bounds)
+            .intersect(&viewport),
+            None => Some(viewport),
+        };
+
+        match bounds {
+            Some(b) => {
+                let pixels = Rect::from_ltrb(
+                    b.left * target.scale_x,
+                    b.top * target.scale_y,
+                    b.right * target.scale_x,
+                    b.bottom * target.scale_y,
+                )
+                .round_out();
+
+                Rect::from_ltrb(
+                    pixels.left / target.scale_x,
+                    pixels.top / target.scale_y,
+                    pixels.right / target.scale_x,
+                    pixels.bottom / target.scale_y,
+                )
+            }
+            None => Rect::from_ltrb(0.0, 0.0, 0.0, 0.0),
+        }
+    }
+
+    /// Returns the target which draws inside a layer with `bounds` should be replayed into.
+    pub(crate) fn child_target(target: &RenderTarget, bounds: &Rect) -> RenderTarget {
+        RenderTarget {
+            width: bounds.width(),
+            height: bounds.height(),
+            base_transform: Matrix4::new_translation(&nalgebra::Vector3::new(
+                -bounds.left,
+                -bounds.top,
+                0.0,
+            )) * target.base_transform,
+            ..*target
+        }
+    }
+
+    pub(crate) fn new(
+        target: RenderTarget,
+        bounds: Rect,
+        paint: &Paint,
+        depth: f32,
+        depth_count: u32,
+        renders: Vec<Box<dyn Renderer>>,
+    ) -> Self {
+        Self {
+            target,
+            bounds,
+            paint: paint.clone(),
+            depth,
+            depth_count,
+            renders,
+            texture: None,
+            msaa_texture: None,
+            depth_stencil: None,
+            filter_graph: None,
+            clip: None,
+            composite: None,
+        }
+    }
+
+    /// Composites the layer through the feathered coverage of the clip it is inside.
+    /// Pass `None` if the layer is only clipped by the hard clip.
+    pub(crate) fn with_clip(mut self, clip: Option<&ClipCoverage>) -> Self {
+        self.clip = clip.cloned();
+        self
+    }
+}
+
+impl Renderer for LayerRenderer {
+    fn load_pipelines(&self, context: &mut GPUContext, device: &wgpu::Device) {
+        context.load_pipeline(
+            LAYER_PIPELINE_NAME,
+            self.target
+                .pipeline_key()
+                .with_blend_mode(self.paint.blend_mode)
+                .with_color_filter(self.paint.color_filter.is_some())
+                .with_mask(self.clip.is_some()),
+            device,
+        );
+
+        if self.paint.image_filter.is_some() {
+            FilterGraph::load_pipelines(self.target.format, context, device);
+        }
+
+        for render in &self.renders {
+            render.load_pipelines(context, device);
+        }
+    }
+
+    fn prepare(
+        &mut self,
+        total_depth: f32,
+        buffer: &mut StageBuffer,
+        context: &GPUContext,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+    ) {
+        let width = (self.bounds.width() * self.target.scale_x).round() as u32;
+        let height = (self.bounds.height() * self.target.scale_y).round() as u32;
+
+        if width == 0 || height == 0 {
+            return;
+        }
+
+        let sample_count = self.target.anti_alias.sample_count();
+
+        let texture = context.acquire_texture(
+            "layer",
+            &TextureDesc::offscreen(width, height, self.target.format),
+            device,
+        );
+
+        if sample_count > 1 {
+            self.msaa_texture = Some(context.acquire_texture(
+                "layer msaa",
+                &TextureDesc::attachment(width, height, sample_count, self.target.format),
+                device,
+            ));
+        }
+
+        self.depth_stencil = Some(context.acquire_texture(
+            "layer depth stencil",
+            &TextureDesc::attachment(
+                width,
+                height,
+                sample_count,
+                wgpu::TextureFormat::Depth24PlusStencil8,
+            ),
+            device,
+        ));
+
+        let layer_depth = (self.depth_count + 1) as f32;
+        for render in &mut self.renders {
+            render.prepare(layer_depth, buffer, context, device, queue);
+        }
+
+        let (scale_x, scale_y) = (self.target.scale_x, self.target.scale_y);
+
+        let mut image = FilterImage {
+            texture: texture.clone(),
+            bounds: Rect::from_ltrb(
+                (self.bounds.left * scale_x).round(),
+                (self.bounds.top * scale_y).round(),
+                (self.bounds.right * scale_x).round(),
+                (self.bounds.bottom * scale_y).round(),
+            ),
+        };
+
+        if let Some(filter) = &self.paint.image_filter {
+            let viewport = Rect::from_ltrb(
+                0.0,
+                0.0,
+                (self.target.width * scale_x).round(),
+                (self.target.height * scale_y).round(),
+            );
+
+            let (mut graph, filtered) = FilterGraph::new(
+                &filter.transformed(&Matrix4::new_nonuniform_scaling(&nalgebra::Vector3::new(
+                    scale_x, scale_y, 1.0,
+                ))),
+                image,
+                &viewport,
+                self.target.format,
+                context,
+                device,
+            );
+
+            image = match filtered {
+                Some(filtered) => filtered,
+                None => return,
+            };
+
+            graph.prepare(buffer);
+            self.filter_graph = Some(graph);
+        }
+
+        let bounds = Rect::from_ltrb(
+            image.bounds.left / scale_x,
+            image.bounds.top / scale_y,
+            image.bounds.right / scale_x,
+            image.bounds.bottom / scale_y,
+        );
+
+        let mut composite = PathRenderer::new(
+            self.target
+                .pipeline_key()
+                .with_blend_mode(self.paint.blend_mode),
+            Box::new(PathFill::new(
+                Path::new().add_rect(&bounds),
+                Matrix4::identity(),
+            )),
+            Box::new(LayerFragment::new(
+                self.target.width,
+                self.target.height,
+                image.texture,
+                bounds,
+                self.paint.alpha(),
+            )),
+            self.depth,
+        )
+        .with_color_filter(self.paint.color_filter.as_ref())
+        .with_clip(self.clip.as_ref());
+
+        composite.prepare(total_depth, buffer, context, device, queue);
+
+        self.texture = Some(texture);
+        self.composite = Some(composite);
+    }
+
+    fn render_offscreen(
+        &self,
+        buffer: &wgpu::Buffer,
+        context: &GPUContext,
+        device: &wgpu::Device,
+        encoder: &mut wgpu::CommandEncoder,
+    ) {
+        let (texture, depth_stencil) = match (&self.texture, &self.depth_stencil) {
+            (Some(texture), Some(depth_stencil)) => (texture, depth_stencil),
+            _ => return,
+        };
+
+        // nested layers must be finished before this layer samples them
+        for render in &self.renders {
+            render.render_offscreen(buffer, context, device, encoder);
+        }
+
+        let mut command_list = CommandList::new();
+        for render in &self.renders {
+            command_list.add_command_list(render.render(buffer, context, device));
+        }
+
+        let target_view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let depth_stencil_view = depth_stencil.create_view(&wgpu::TextureViewDescriptor::default());
+        let msaa_view = self
+            .msaa_texture
+            .as_ref()
+            .map(|t| t.create_view(&wgpu::TextureViewDescriptor::default()));
+
+        {
+            let mut pass = begin_render_pass(
+                "Layer render pass",
+                &target_view,
+                &depth_stencil_view,
+                msaa_view.as_ref(),
+                encoder,
+                Some(wgpu::Color::TRANSPARENT),
+            );
+
+            pass.set_stencil_reference(0);
+
+            command_list.run(&mut pass, [texture.width(), texture.height()]);
+        }
+
+        if let Some(graph) = &self.filter_graph {
+            graph.render(buffer, context, device, encoder);
+        }
+    }
+
+    fn render<'a>(
+        &self,
+        buffer: &'a wgpu::Buffer,
+        context: &'a GPUContext,
+        device: &wgpu::Device,
+    ) -> Vec<Command<'a>> {
+        match &self.composite {
+            Some(composite) => composite.render(buffer, context, device),
+            None => vec![],
+        }
+    }
+}
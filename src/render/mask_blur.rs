@@ -0,0 +1,491 @@
+use std::rc::Rc;
+
+use nalgebra::Matrix4;
+
+use crate::{
+    core::{BlendMode, BlurStyle, ColorSpace, Matrix, Path, Point, Rect},
+    gpu::{
+        buffer::StageBuffer,
+        context::PipelineKey,
+        surface::{begin_filter_pass, begin_render_pass},
+        texture_pool::TextureDesc,
+        AntiAlias, GPUContext,
+    },
+};
+
+use super::{
+    command::Command,
+    fragment::{BlurPass, MaskStage, BLUR_PIPELINE_NAME},
+    CommandList, PathRenderer, RenderTarget, Renderer,
+};
+
+/// Max factor the coverage mask is downsampled by before blur.
+const MAX_DOWNSAMPLE: u32 = 16;
+
+/// Max sigma in pixels a blur pass runs at before the mask is downsampled again.
+const MAX_PASS_SIGMA: f32 = 4.0;
+
+/// Geometry of the offscreen coverage mask of a blurred draw.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct BlurMask {
+    /// bounds of the mask in target logical space, aligned to physical pixels
+    bounds: Rect,
+    /// standard deviation of the blur in target pixels
+    sigma: f32,
+    /// the blurred mask is `scale` times smaller than the coverage mask
+    scale: u32,
+    style: BlurStyle,
+    /// whether the coverage is drawn with the anti-alias of the target
+    anti_alias: bool,
+}
+
+impl BlurMask {
+    /// Computes the mask which holds the blurred coverage of a geometry.
+    ///
+    /// # Arguments
+    ///
+    /// * `target` the target the draw is replayed into
+    /// * `bounds` bounds of the geometry in local space
+    /// * `transform` transform from local space to target
+    /// * `style` how the blurred coverage is combined with the original coverage
+    /// * `sigma` standard deviation of the blur in local space
+    pub(crate) fn new(
+        target: &RenderTarget,
+        bounds: &Rect,
+        transform: &Matrix4<f32>,
+        style: BlurStyle,
+        sigma: f32,
+    ) -> Self {
+        let det = transform.m11 * transform.m22 - transform.m12 * transform.m21;
+        let sigma = sigma.max(0.0) * det.abs().sqrt();
+        let sigma_px = sigma * (target.scale_x + target.scale_y) * 0.5;
+
+        let mut scale = 1;
+        while sigma_px / scale as f32 > MAX_PASS_SIGMA && scale < MAX_DOWNSAMPLE {
+            scale *= 2;
+        }
+
+        // pixels farther than 3 sigma outside the viewport do not affect the visible result
+        let mut device_bounds = Matrix { matrix: *transform }.map_rect(bounds);
+        device_bounds.outset(sigma * 3.0, sigma * 3.0);
+
+        let mut viewport = Rect::from_xywh(0.0, 0.0, target.width, target.height);
+        viewport.outset(sigma * 3.0, sigma * 3.0);
+
+        let bounds = match device_bounds.intersect(&viewport) {
+            Some(b) => {
+                let pixels = Rect::from_ltrb(
+                    b.left * target.scale_x,
+                    b.top * target.scale_y,
+                    b.right * target.scale_x,
+                    b.bottom * target.scale_y,
+                )
+                .round_out();
+
+                // each downsample pass halves the size exactly
+                let s = scale as f32;
+                let width = (pixels.width() / s).ceil() * s;
+                let height = (pixels.height() / s).ceil() * s;
+
+                Rect::from_ltrb(
+                    pixels.left / target.scale_x,
+                    pixels.top / target.scale_y,
+                    (pixels.left + width) / target.scale_x,
+                    (pixels.top + height) / target.scale_y,
+                )
+            }
+            None => Rect::from_ltrb(0.0, 0.0, 0.0, 0.0),
+        };
+
+        Self {
+            bounds,
+            sigma: sigma_px,
+            scale,
+            style,
+            anti_alias: true,
+        }
+    }
+
+    /// Sets whether the coverage is drawn with the anti-alias of the target.
+    /// Without it the coverage is drawn without multisample, so draws stay aliased on multisampled targets.
+    pub(crate) fn with_anti_alias(mut self, anti_alias: bool) -> Self {
+        self.anti_alias = anti_alias;
+        self
+    }
+
+    /// Returns the target which the coverage of the geometry should be drawn into.
+    pub(crate) fn coverage_target(&self, target: &RenderTarget) -> RenderTarget {
+        RenderTarget {
+            width: self.bounds.width(),
+            height: self.bounds.height(),
+            format: wgpu::TextureFormat::R8Unorm,
+            color_space: ColorSpace::Srgb,
+            anti_alias: if self.anti_alias {
+                target.anti_alias
+            } else {
+                AntiAlias::None
+            },
+            base_transform: Matrix4::new_translation(&nalgebra::Vector3::new(
+                -self.bounds.left,
+                -self.bounds.top,
+                0.0,
+            )) * target.base_transform,
+            ..*target
+        }
+    }
+
+    /// Returns the quad covering the mask in local space of a draw with `transform`.
+    /// The quad is empty if the transform is not invertible.
+    pub(crate) fn local_quad(&self, transform: &Matrix4<f32>) -> Path {
+        let inverse = match (Matrix { matrix: *transform }).try_invert() {
+            Some(inverse) => inverse,
+            None => return Path::new(),
+        };
+
+        let b = &self.bounds;
+        let points = [
+            Point::from(b.left, b.top),
+            Point::from(b.right, b.top),
+            Point::from(b.right, b.bottom),
+            Point::from(b.left, b.bottom),
+        ]
+        .map(|p| inverse.map_point(&p));
+
+        Path::new()
+            .move_to_point(points[0])
+            .line_to_point(points[1])
+            .line_to_point(points[2])
+            .line_to_point(points[3])
+            .close()
+    }
+
+    /// Returns the bounds of the mask in pixels of target.
+    pub(crate) fn pixel_bounds(&self, target: &RenderTarget) -> Rect {
+        Rect::from_ltrb(
+            (self.bounds.left * target.scale_x).round(),
+            (self.bounds.top * target.scale_y).round(),
+            (self.bounds.right * target.scale_x).round(),
+            (self.bounds.bottom * target.scale_y).round(),
+        )
+    }
+}
+
+/// Draws the coverage of a geometry into an offscreen mask, blurs it and shades the paint through the blurred mask.
+pub(crate) struct MaskBlurRenderer {
+    target: RenderTarget,
+    mask: BlurMask,
+    /// draws the geometry with white color into the coverage target
+    coverage: Box<dyn Renderer>,
+    /// shades the mask bounds with the paint
+    composite: PathRenderer,
+
+    vertex_range: std::ops::Range<wgpu::BufferAddress>,
+    index_range: std::ops::Range<wgpu::BufferAddress>,
+    passes: Vec<BlurPass>,
+    /// the coverage mask followed by the output of each pass
+    textures: Vec<Rc<wgpu::Texture>>,
+    msaa_texture: Option<Rc<wgpu::Texture>>,
+    depth_stencil: Option<Rc<wgpu::Texture>>,
+}
+
+impl MaskBlurRenderer {
+    pub(crate) fn new(
+        target: RenderTarget,
+        mask: BlurMask,
+        coverage: Box<dyn Renderer>,
+        composite: PathRenderer,
+    ) -> Self {
+        let composite = composite.with_mask(MaskStage::new(mask.pixel_bounds(&target), mask.style));
+
+        Self {
+            target,
+            mask,
+            coverage,
+            composite,
+            vertex_range: 0..0,
+            index_range: 0..0,
+            passes: Vec::new(),
+            textures: Vec::new(),
+            msaa_texture: None,
+            depth_stencil: None,
+        }
+    }
+
+    fn blur_key() -> PipelineKey {
+        PipelineKey::new(wgpu::TextureFormat::R8Unorm, 1).with_blend_mode(BlendMode::Src)
+    }
+
+    /// Pushes the downsample passes and the two gaussian passes with their output textures.
+    fn push_blur_passes(
+        &mut self,
+        width: u32,
+        height: u32,
+        context: &GPUContext,
+        device: &wgpu::Device,
+    ) {
+        let (mut w, mut h) = (width, height);
+        let mut level = 1;
+        while level < self.mask.scale {
+            w /= 2;
+            h /= 2;
+            level *= 2;
+
+            self.passes.push(BlurPass::downsample());
+            self.textures.push(create_mask_texture(
+                "mask downsample",
+                w,
+                h,
+                context,
+                device,
+            ));
+        }
+
+        let sigma = self.mask.sigma / self.mask.scale as f32;
+
+        self.passes
+            .push(BlurPass::gaussian([1.0 / w as f32, 0.0], sigma));
+        self.textures.push(create_mask_texture(
+            "mask blur horizontal",
+            w,
+            h,
+            context,
+            device,
+        ));
+
+        self.passes
+            .push(BlurPass::gaussian([0.0, 1.0 / h as f32], sigma));
+        self.textures.push(create_mask_texture(
+            "mask blur vertical",
+            w,
+            h,
+            context,
+            device,
+        ));
+    }
+}
+
+fn create_mask_texture(
+    label: &str,
+    width: u32,
+    height: u32,
+    context: &GPUContext,
+    device: &wgpu::Device,
+) -> Rc<wgpu::Texture> {
+    context.acquire_texture(
+        label,
+        &TextureDesc::offscreen(width, height, wgpu::TextureFormat::R8Unorm),
+        device,
+    )
+}
+
+impl Renderer for MaskBlurRenderer {
+    fn load_pipelines(&self, context: &mut GPUContext, device: &wgpu::Device) {
+        self.coverage.load_pipelines(context, device);
+        self.composite.load_pipelines(context, device);
+
+        context.load_pipeline(BLUR_PIPELINE_NAME, Self::blur_key(), device);
+    }
+
+    fn prepare(
+        &mut self,
+        total_depth: f32,
+        buffer: &mut StageBuffer,
+        context: &GPUContext,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+    ) {
+        let pixels = self.mask.pixel_bounds(&self.target);
+        let width = pixels.width() as u32;
+        let height = pixels.height() as u32;
+
+        if width == 0 || height == 0 {
+            return;
+        }
+
+        let sample_count = self
+            .mask
+            .coverage_target(&self.target)
+            .anti_alias
+            .sample_count();
+
+        let coverage = create_mask_texture("mask coverage", width, height, context, device);
+
+        if sample_count > 1 {
+            self.msaa_texture = Some(context.acquire_texture(
+                "mask coverage msaa",
+                &TextureDesc::attachment(width, height, sample_count, wgpu::TextureFormat::R8Unorm),
+                device,
+            ));
+        }
+
+        self.depth_stencil = Some(context.acquire_texture(
+            "mask depth stencil",
+            &TextureDesc::attachment(
+                width,
+                height,
+                sample_count,
+                wgpu::TextureFormat::Depth24PlusStencil8,
+            ),
+            device,
+        ));
+
+        // coverage is the only draw inside the mask target
+        self.coverage.prepare(2.0, buffer, context, device, queue);
+
+        let quad: [f32; 8] = [0.0, 0.0, 1.0, 0.0, 1.0, 1.0, 0.0, 1.0];
+        let indices: [u32; 6] = [0, 1, 2, 0, 2, 3];
+
+        self.vertex_range = buffer.push_data(bytemuck::cast_slice(&quad));
+        self.index_range = buffer.push_data(bytemuck::cast_slice(&indices));
+
+        self.textures = vec![coverage];
+        self.passes.clear();
+
+        // a mask without blur shades through the coverage directly
+        if self.mask.sigma > 0.0 {
+            self.push_blur_passes(width, height, context, device);
+        }
+
+        for pass in &mut self.passes {
+            pass.prepare(buffer);
+        }
+
+        self.composite.set_mask_textures(
+            self.textures.last().unwrap().clone(),
+            self.textures[0].clone(),
+        );
+        self.composite
+            .prepare(total_depth, buffer, context, device, queue);
+    }
+
+    fn render_offscreen(
+        &self,
+        buffer: &wgpu::Buffer,
+        context: &GPUContext,
+        device: &wgpu::Device,
+        encoder: &mut wgpu::CommandEncoder,
+    ) {
+        let depth_stencil = match &self.depth_stencil {
+            Some(depth_stencil) => depth_stencil,
+            None => return,
+        };
+
+        let command_list: CommandList = self
+            .coverage
+            .render(buffer, context, device)
+            .into_iter()
+            .collect();
+
+        {
+            let coverage_view =
+                self.textures[0].create_view(&wgpu::TextureViewDescriptor::default());
+            let depth_stencil_view =
+                depth_stencil.create_view(&wgpu::TextureViewDescriptor::default());
+            let msaa_view = self
+                .msaa_texture
+                .as_ref()
+                .map(|t| t.create_view(&wgpu::TextureViewDescriptor::default()));
+
+            let mut pass = begin_render_pass(
+                "Mask coverage pass",
+                &coverage_view,
+                &depth_stencil_view,
+                msaa_view.as_ref(),
+                encoder,
+                Some(wgpu::Color::TRANSPARENT),
+            );
+
+            pass.set_stencil_reference(0);
+
+            command_list.run(
+                &mut pass,
+                [self.textures[0].width(), self.textures[0].height()],
+            );
+        }
+
+        let pipeline = match context.get_pipeline(BLUR_PIPELINE_NAME, &Self::blur_key()) {
+            Some(pipeline) => pipeline,
+            None => return,
+        };
+
+        let raw_pipeline = pipeline
+            .get_plain_pipeline()
+            .expect("Can not get blur pipeline");
+
+        for (i, blur) in self.passes.iter().enumerate() {
+            let source = self.textures[i].create_view(&wgpu::TextureViewDescriptor::default());
+            let target = self.textures[i + 1].create_view(&wgpu::TextureViewDescriptor::default());
+
+            let command = Command::new(
+                raw_pipeline,
+                buffer.slice(self.vertex_range.clone()),
+                buffer.slice(self.index_range.clone()),
+                6,
+                vec![blur.gen_bind_group(device, buffer, pipeline, &source, context)],
+            );
+
+            let mut pass = begin_filter_pass("Mask blur pass", &target, encoder);
+
+            command.run(&mut pass);
+        }
+    }
+
+    fn render<'a>(
+        &self,
+        buffer: &'a wgpu::Buffer,
+        context: &'a GPUContext,
+        device: &wgpu::Device,
+    ) -> Vec<Command<'a>> {
+        self.composite.render(buffer, context, device)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn target(scale: f32) -> RenderTarget {
+        RenderTarget {
+            width: 100.0,
+            height: 100.0,
+            scale_x: scale,
+            scale_y: scale,
+            format: wgpu::TextureFormat::Rgba8Unorm,
+            anti_alias: AntiAlias::Msaa(4),
+            color_space: ColorSpace::Srgb,
+            text_gamma: [2.2, 0.0],
+            base_transform: Matrix4::identity(),
+        }
+    }
+
+    #[test]
+    fn blur_mask_bounds_and_scale() {
+        let identity = Matrix4::identity();
+
+        // sigma 2 in a 2x target is 4 pixels, which is blurred without downsample
+        let bounds = Rect::from_xywh(10.0, 10.0, 20.0, 20.0);
+        let mask = BlurMask::new(&target(2.0), &bounds, &identity, BlurStyle::Normal, 2.0);
+
+        assert_eq!(mask.scale, 1);
+        assert_eq!(mask.sigma, 4.0);
+        assert_eq!(mask.bounds, Rect::from_ltrb(4.0, 4.0, 36.0, 36.0));
+
+        // the transform scales sigma to 10 pixels, the size is rounded up to the downsample factor
+        let scale = Matrix4::new_nonuniform_scaling(&nalgebra::Vector3::new(2.0, 2.0, 1.0));
+        let bounds = Rect::from_xywh(10.0, 10.0, 21.0, 21.0);
+        let mask = BlurMask::new(&target(1.0), &bounds, &scale, BlurStyle::Normal, 5.0);
+
+        assert_eq!(mask.scale, 4);
+        assert_eq!(mask.sigma, 10.0);
+        assert_eq!(mask.bounds, Rect::from_ltrb(-10.0, -10.0, 94.0, 94.0));
+
+        let mask = BlurMask::new(&target(1.0), &bounds, &identity, BlurStyle::Normal, 100.0);
+        assert_eq!(mask.scale, MAX_DOWNSAMPLE);
+
+        // blur outside of the viewport by more than 3 sigma is not visible
+        let bounds = Rect::from_xywh(200.0, 0.0, 10.0, 10.0);
+        let mask = BlurMask::new(&target(1.0), &bounds, &identity, BlurStyle::Normal, 1.0);
+
+        assert!(mask.bounds.is_empty());
+    }
+}
@@ -1,30 +1,97 @@
+pub(crate) mod clip_coverage;
 pub(crate) mod command;
 pub(crate) mod fragment;
 pub(crate) mod glyph_render;
+pub(crate) mod image_filter;
+pub(crate) mod layer;
+pub(crate) mod mask_blur;
 pub(crate) mod raster;
 
-use std::ops::Range;
+use std::{ops::Range, rc::Rc};
 
 pub(crate) use command::CommandList;
+use nalgebra::Matrix4;
 
 use crate::{
-    core::{picture::ClipOp, PathFillType, Point},
-    gpu::{buffer::StageBuffer, pipeline::Pipeline, GPUContext},
+    core::{picture::ClipOp, BlendMode, ColorFilter, ColorSpace, PathFillType, Point},
+    gpu::{
+        buffer::StageBuffer, context::PipelineKey, pipeline::Pipeline, AntiAlias, GPUContext,
+    },
 };
 
 use self::{
     command::Command,
     fragment::{
         state_for_clip_difference, state_for_clip_even_odd_difference,
-        state_for_clip_even_odd_intersect, state_for_clip_intersect, state_for_complex_even_odd,
-        state_for_complex_winding, state_for_convex_polygon, state_for_no_overlap,
-        state_for_stencil_mask, ClipMaskFragment, NON_COLOR_PIPELINE_NAME,
+        state_for_clip_even_odd_intersect, state_for_clip_even_odd_replace,
+        state_for_clip_even_odd_union, state_for_clip_even_odd_xor,
+        state_for_clip_fringe_difference, state_for_clip_fringe_even_odd_difference,
+        state_for_clip_fringe_even_odd_intersect, state_for_clip_fringe_include,
+        state_for_clip_fringe_intersect, state_for_clip_intersect, state_for_clip_replace,
+        state_for_clip_union, state_for_clip_xor, state_for_complex_even_odd,
+        state_for_complex_even_odd_fringe, state_for_complex_fringe, state_for_complex_winding,
+        state_for_convex_polygon, state_for_no_overlap, state_for_stencil_mask,
+        state_for_stencil_mask_always, with_depth_write,
+        ClipMaskFragment, ColorFilterStage, MaskStage, NON_COLOR_PIPELINE_NAME,
     },
-    raster::PathFill,
+    clip_coverage::{ClipCoverage, ClipCoverageRenderer},
+    raster::{PathFill, Vertex},
 };
 
+/// Describes the target which draws are replayed into.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct RenderTarget {
+    /// logical width of the target
+    pub(crate) width: f32,
+    /// logical height of the target
+    pub(crate) height: f32,
+    /// physical pixels per logical unit at x-axis
+    pub(crate) scale_x: f32,
+    /// physical pixels per logical unit at y-axis
+    pub(crate) scale_y: f32,
+    pub(crate) format: wgpu::TextureFormat,
+    pub(crate) anti_alias: AntiAlias,
+    /// color space of the values stored in the target
+    pub(crate) color_space: ColorSpace,
+    /// [gamma, contrast] of glyph coverage, only used when the target blends in linear light
+    pub(crate) text_gamma: [f32; 2],
+    /// transform from picture device space to this target. Only offscreen layers have non-identity value.
+    pub(crate) base_transform: Matrix4<f32>,
+}
+
+impl RenderTarget {
+    /// Returns the pipeline key of plain draws into this target.
+    pub(crate) fn pipeline_key(&self) -> PipelineKey {
+        PipelineKey::new(self.format, self.anti_alias.sample_count())
+            .with_color_space(self.color_space)
+    }
+
+    /// Returns the pixel scale passed to rasters if edges of the draw are anti-aliased with fringes.
+    ///
+    /// # Arguments
+    ///
+    /// * `anti_alias` whether the draw is anti-aliased
+    pub(crate) fn fringe(&self, anti_alias: bool) -> Option<[f32; 2]> {
+        if anti_alias && self.anti_alias == AntiAlias::Analytic {
+            Some([self.scale_x, self.scale_y])
+        } else {
+            None
+        }
+    }
+
+    /// Returns the [gamma, contrast] of glyph coverage. Targets with sRGB format blend in linear light
+    /// and need the adjustment, others keep the coverage.
+    pub(crate) fn text_gamma(&self) -> [f32; 2] {
+        if self.format.is_srgb() {
+            self.text_gamma
+        } else {
+            [1.0, 0.0]
+        }
+    }
+}
+
 pub(crate) trait Renderer {
-    fn pipeline_label(&self) -> &'static str;
+    fn load_pipelines(&self, context: &mut GPUContext, device: &wgpu::Device);
 
     fn prepare(
         &mut self,
@@ -35,6 +102,17 @@ pub(crate) trait Renderer {
         queue: &wgpu::Queue,
     );
 
+    /// Encode render passes which must be finished before the target render pass begins.
+    /// Such as drawing the content of an offscreen layer.
+    fn render_offscreen(
+        &self,
+        _buffer: &wgpu::Buffer,
+        _context: &GPUContext,
+        _device: &wgpu::Device,
+        _encoder: &mut wgpu::CommandEncoder,
+    ) {
+    }
+
     fn render<'a>(
         &self,
         buffer: &'a wgpu::Buffer,
@@ -43,28 +121,66 @@ pub(crate) trait Renderer {
     ) -> Vec<Command<'a>>;
 }
 
-#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Default)]
 pub(crate) enum VertexMode {
+    #[default]
     Convex,
     Complex,
     EvenOddFill,
     NonOverlap,
 }
 
+/// Geometry generated by a raster, the ranges point into the stage buffer.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Mesh {
+    pub(crate) vertex_range: Range<wgpu::BufferAddress>,
+    pub(crate) index_range: Range<wgpu::BufferAddress>,
+    pub(crate) mode: VertexMode,
+    pub(crate) draw_count: u32,
+    /// indices of the anti-alias fringe, they share the vertices of the geometry
+    pub(crate) fringe_range: Range<wgpu::BufferAddress>,
+    pub(crate) fringe_count: u32,
+}
+
+impl Mesh {
+    pub(crate) fn is_empty(&self) -> bool {
+        self.draw_count == 0 && self.fringe_count == 0
+    }
+
+    /// Returns (vertex range, index range, draw count) of the geometry.
+    fn geometry(&self) -> (Range<wgpu::BufferAddress>, Range<wgpu::BufferAddress>, u32) {
+        (
+            self.vertex_range.clone(),
+            self.index_range.clone(),
+            self.draw_count,
+        )
+    }
+
+    /// Returns (vertex range, index range, draw count) of the fringe.
+    fn fringe(&self) -> (Range<wgpu::BufferAddress>, Range<wgpu::BufferAddress>, u32) {
+        (
+            self.vertex_range.clone(),
+            self.fringe_range.clone(),
+            self.fringe_count,
+        )
+    }
+
+    /// Returns true if the geometry is filled with stencil and cover.
+    fn needs_stencil(&self) -> bool {
+        self.mode == VertexMode::Complex || self.mode == VertexMode::EvenOddFill
+    }
+}
+
 pub(crate) trait Raster {
-    fn rasterize(
-        &self,
-        buffer: &mut StageBuffer,
-    ) -> (
-        Range<wgpu::BufferAddress>,
-        Range<wgpu::BufferAddress>,
-        VertexMode,
-        u32,
-    );
+    fn rasterize(&self, buffer: &mut StageBuffer) -> Mesh;
 }
 
 pub(crate) trait Fragment {
-    fn get_pipeline_name(&self) -> &'static str;
+    fn get_pipeline_name(&self) -> &str;
+
+    /// Registers the generator of the pipeline if it is built at runtime.
+    /// Built-in pipelines are registered when the context is created.
+    fn register_pipeline(&self, _context: &mut GPUContext) {}
 
     fn prepare(
         &mut self,
@@ -93,36 +209,90 @@ pub(crate) trait Fragment {
 }
 
 pub(crate) struct PathRenderer {
-    format: wgpu::TextureFormat,
-    anti_alias: bool,
+    pipeline_key: PipelineKey,
     raster: Box<dyn Raster>,
     fragment: Box<dyn Fragment>,
+    color_filter: Option<ColorFilterStage>,
+    mask: Option<MaskStage>,
+    /// feathered coverage of the clip the draw is inside, multiplied through the mask stage
+    clip: Option<ClipCoverage>,
+    /// stroke of fill and stroke, drawn after the fill without blending the overlap twice
+    outline: Option<Box<dyn Raster>>,
     depth: f32,
-    vertex_range: Range<wgpu::BufferAddress>,
-    index_range: Range<wgpu::BufferAddress>,
-    vertex_mode: VertexMode,
-    draw_count: u32,
+    mesh: Mesh,
+    outline_mesh: Mesh,
 }
 
 impl PathRenderer {
     pub(crate) fn new(
-        format: wgpu::TextureFormat,
-        anti_alias: bool,
+        pipeline_key: PipelineKey,
         raster: Box<dyn Raster>,
         fragment: Box<dyn Fragment>,
         depth: f32,
     ) -> Self {
         Self {
-            format,
-            anti_alias,
+            pipeline_key,
             raster,
             fragment,
+            color_filter: None,
+            mask: None,
+            clip: None,
+            outline: None,
             depth,
-            vertex_range: 0..0,
-            index_range: 0..0,
-            vertex_mode: VertexMode::Convex,
-            draw_count: 0,
+            mesh: Mesh::default(),
+            outline_mesh: Mesh::default(),
+        }
+    }
+
+    /// Draws the outline geometry with the same fragment after the geometry of raster.
+    /// The coverage of both is unioned, the overlap is blended once. Pass `None` to draw raster only.
+    pub(crate) fn with_outline(mut self, outline: Option<Box<dyn Raster>>) -> Self {
+        self.outline = outline;
+        self
+    }
+
+    /// Runs the color filter after fragment shading. Pass `None` to disable the stage.
+    pub(crate) fn with_color_filter(mut self, color_filter: Option<&ColorFilter>) -> Self {
+        self.color_filter = color_filter.map(ColorFilterStage::new);
+        self.pipeline_key = self
+            .pipeline_key
+            .with_color_filter(self.color_filter.is_some());
+        self
+    }
+
+    /// Multiplies the fragment output with the coverage mask after color filter.
+    pub(crate) fn with_mask(mut self, mask: MaskStage) -> Self {
+        self.mask = Some(mask);
+        self.pipeline_key = self.pipeline_key.with_mask(true);
+        self
+    }
+
+    /// Multiplies the fragment output with the feathered coverage of the clip the draw is inside.
+    /// Pass `None` if the draw is only clipped by the hard clip.
+    pub(crate) fn with_clip(mut self, clip: Option<&ClipCoverage>) -> Self {
+        if let Some(clip) = clip {
+            self = self.with_mask(clip.mask_stage());
+            self.clip = Some(clip.clone());
         }
+        self
+    }
+
+    pub(crate) fn set_mask_textures(
+        &mut self,
+        blurred: Rc<wgpu::Texture>,
+        coverage: Rc<wgpu::Texture>,
+    ) {
+        if let Some(mask) = &mut self.mask {
+            mask.set_textures(blurred, coverage);
+        }
+    }
+
+    fn stencil_key(&self) -> PipelineKey {
+        self.pipeline_key
+            .with_blend_mode(BlendMode::SrcOver)
+            .with_color_filter(false)
+            .with_mask(false)
+            .with_dither(false)
     }
 
     fn gen_stencil_command<'a>(
@@ -132,7 +302,7 @@ impl PathRenderer {
         device: &wgpu::Device,
     ) -> Command<'a> {
         let pipeline = context
-            .get_pipeline(NON_COLOR_PIPELINE_NAME, self.format, self.anti_alias)
+            .get_pipeline(NON_COLOR_PIPELINE_NAME, &self.stencil_key())
             .expect("Can not get non color pipeline");
 
         let common_group = self
@@ -147,29 +317,82 @@ impl PathRenderer {
 
         Command::new(
             raw_pipeline,
-            buffer.slice(self.vertex_range.clone()),
-            buffer.slice(self.index_range.clone()),
-            self.draw_count,
+            buffer.slice(self.mesh.vertex_range.clone()),
+            buffer.slice(self.mesh.index_range.clone()),
+            self.mesh.draw_count,
             vec![common_group],
         )
     }
 
+    /// Generates the command shading `geometry` with the fragment, the geometry is
+    /// (vertex range, index range, draw count).
+    fn gen_color_command<'a>(
+        &self,
+        buffer: &'a wgpu::Buffer,
+        context: &'a GPUContext,
+        device: &wgpu::Device,
+        pipeline: &'a Pipeline,
+        state: &wgpu::DepthStencilState,
+        geometry: (Range<wgpu::BufferAddress>, Range<wgpu::BufferAddress>, u32),
+    ) -> Option<Command<'a>> {
+        let raw_pipeline = pipeline.get_pipeline(state)?;
+
+        let mut bind_groups = self
+            .fragment
+            .gen_bind_groups(device, buffer, pipeline, context);
+
+        if let Some(color_filter) = &self.color_filter {
+            bind_groups.push(color_filter.gen_bind_group(device, buffer, pipeline));
+        }
+
+        if let Some(mask) = &self.mask {
+            bind_groups.push(mask.gen_bind_group(
+                device,
+                buffer,
+                pipeline,
+                self.pipeline_key.mask_group(),
+                context,
+            )?);
+        }
+
+        let (vertex_range, index_range, draw_count) = geometry;
+
+        Some(Command::new(
+            raw_pipeline,
+            buffer.slice(vertex_range),
+            buffer.slice(index_range),
+            draw_count,
+            bind_groups,
+        ))
+    }
+
     fn gen_stencil_state(&self) -> wgpu::DepthStencilState {
-        if self.vertex_mode == VertexMode::Convex {
+        if self.mesh.mode == VertexMode::Convex {
             state_for_convex_polygon()
-        } else if self.vertex_mode == VertexMode::EvenOddFill {
+        } else if self.mesh.mode == VertexMode::EvenOddFill {
             state_for_complex_even_odd()
-        } else if self.vertex_mode == VertexMode::Complex {
+        } else if self.mesh.mode == VertexMode::Complex {
             state_for_complex_winding()
         } else {
             state_for_no_overlap()
         }
     }
+
+    /// Returns the state of the fringe of stencil and cover fills, it is drawn outside of the stencil.
+    fn gen_fringe_state(&self) -> wgpu::DepthStencilState {
+        if self.mesh.mode == VertexMode::EvenOddFill {
+            state_for_complex_even_odd_fringe()
+        } else {
+            state_for_complex_fringe()
+        }
+    }
 }
 
 impl Renderer for PathRenderer {
-    fn pipeline_label(&self) -> &'static str {
-        self.fragment.get_pipeline_name()
+    fn load_pipelines(&self, context: &mut GPUContext, device: &wgpu::Device) {
+        context.load_pipeline(NON_COLOR_PIPELINE_NAME, self.stencil_key(), device);
+        self.fragment.register_pipeline(context);
+        context.load_pipeline(self.fragment.get_pipeline_name(), self.pipeline_key, device);
     }
 
     fn prepare(
@@ -180,15 +403,27 @@ impl Renderer for PathRenderer {
         device: &wgpu::Device,
         queue: &wgpu::Queue,
     ) {
-        (
-            self.vertex_range,
-            self.index_range,
-            self.vertex_mode,
-            self.draw_count,
-        ) = self.raster.rasterize(buffer);
+        self.mesh = self.raster.rasterize(buffer);
+
+        if let Some(outline) = &self.outline {
+            self.outline_mesh = outline.rasterize(buffer);
+        }
 
         self.fragment
             .prepare(self.depth / total_depth, buffer, context, device, queue);
+
+        if let Some(color_filter) = &mut self.color_filter {
+            color_filter.prepare(buffer);
+        }
+
+        // the clip is prepared before the draws inside it, its texture is created by now
+        if let Some(texture) = self.clip.as_ref().and_then(|clip| clip.texture()) {
+            self.set_mask_textures(texture.clone(), texture);
+        }
+
+        if let Some(mask) = &mut self.mask {
+            mask.prepare(buffer);
+        }
     }
 
     fn render<'a>(
@@ -197,46 +432,61 @@ impl Renderer for PathRenderer {
         context: &'a GPUContext,
         device: &wgpu::Device,
     ) -> Vec<Command<'a>> {
-        if self.vertex_range.is_empty() || self.index_range.is_empty() {
+        if self.mesh.is_empty() && self.outline_mesh.is_empty() {
             return vec![];
         }
-        let pipeline = context.get_pipeline(
-            self.fragment.get_pipeline_name(),
-            self.format,
-            self.anti_alias,
-        );
+        let pipeline = context.get_pipeline(self.fragment.get_pipeline_name(), &self.pipeline_key);
         if pipeline.is_none() {
             return vec![];
         }
 
+        let pipeline = pipeline.unwrap();
+
         let mut commands: Vec<Command<'a>> = Vec::new();
-        if self.vertex_mode != VertexMode::Convex && self.vertex_mode != VertexMode::NonOverlap {
-            commands.push(self.gen_stencil_command(buffer, context, device));
+        // (geometry, state) of each color draw
+        let mut draws = Vec::new();
+
+        let mut state = self.gen_stencil_state();
+        if self.outline.is_some() {
+            state = with_depth_write(state);
         }
 
-        let pipeline = pipeline.unwrap();
+        // the outline straddles the edges of the fill, so the fill needs no fringe
+        let fill_fringe = self.mesh.fringe_count > 0 && self.outline.is_none();
 
-        let bind_groups = self
-            .fragment
-            .gen_bind_groups(device, buffer, pipeline, context);
+        if self.mesh.draw_count > 0 && self.mesh.needs_stencil() {
+            commands.push(self.gen_stencil_command(buffer, context, device));
+
+            // drawn outside of the stencil, before the cover pass clears it
+            if fill_fringe {
+                draws.push((self.mesh.fringe(), self.gen_fringe_state()));
+            }
 
-        let state = self.gen_stencil_state();
+            draws.push((self.mesh.geometry(), state));
+        } else {
+            if self.mesh.draw_count > 0 {
+                draws.push((self.mesh.geometry(), state.clone()));
+            }
 
-        let raw_pipeline = pipeline.get_pipeline(&state);
+            if fill_fringe {
+                draws.push((self.mesh.fringe(), state));
+            }
+        }
 
-        if raw_pipeline.is_none() {
-            return vec![];
+        if self.outline_mesh.draw_count > 0 {
+            draws.push((self.outline_mesh.geometry(), state_for_no_overlap()));
         }
 
-        let raw_pipeline = raw_pipeline.unwrap();
+        if self.outline_mesh.fringe_count > 0 {
+            draws.push((self.outline_mesh.fringe(), state_for_no_overlap()));
+        }
 
-        commands.push(Command::new(
-            raw_pipeline,
-            buffer.slice(self.vertex_range.clone()),
-            buffer.slice(self.index_range.clone()),
-            self.draw_count,
-            bind_groups,
-        ));
+        for (geometry, state) in draws {
+            match self.gen_color_command(buffer, context, device, pipeline, &state, geometry) {
+                Some(command) => commands.push(command),
+                None => return vec![],
+            }
+        }
 
         return commands;
     }
@@ -244,16 +494,15 @@ impl Renderer for PathRenderer {
 
 pub(crate) struct PathCliper {
     format: wgpu::TextureFormat,
-    anti_alias: bool,
+    sample_count: u32,
     pub(crate) raster: PathFill,
     pub(crate) fragment: ClipMaskFragment,
     pub(crate) op: ClipOp,
     pub(crate) depth: f32,
+    /// draws the feathered coverage of the clip, the hard clip is extended by the fringe to let it through
+    coverage: Option<ClipCoverageRenderer>,
 
-    vertex_range: Range<wgpu::BufferAddress>,
-    index_range: Range<wgpu::BufferAddress>,
-    vertex_mode: VertexMode,
-    draw_count: u32,
+    mesh: Mesh,
 
     bounds_vertex_range: Range<wgpu::BufferAddress>,
     bounds_index_range: Range<wgpu::BufferAddress>,
@@ -262,7 +511,7 @@ pub(crate) struct PathCliper {
 impl PathCliper {
     pub(crate) fn new(
         format: wgpu::TextureFormat,
-        anti_alias: bool,
+        sample_count: u32,
         raster: PathFill,
         fragment: ClipMaskFragment,
         op: ClipOp,
@@ -270,68 +519,110 @@ impl PathCliper {
     ) -> Self {
         Self {
             format,
-            anti_alias,
+            sample_count,
             raster,
             fragment,
             op,
             depth,
-            vertex_range: 0..0,
-            index_range: 0..0,
-            vertex_mode: VertexMode::Convex,
-            draw_count: 0,
+            coverage: None,
+            mesh: Mesh::default(),
             bounds_vertex_range: 0..0,
             bounds_index_range: 0..0,
         }
     }
 
-    fn raster_bounds(&self) -> ([Point; 4], [u32; 6]) {
+    /// Draws the coverage of the clip before the target pass. Pass `None` to clip with hard edges only.
+    pub(crate) fn with_coverage(mut self, coverage: Option<ClipCoverageRenderer>) -> Self {
+        self.coverage = coverage;
+        self
+    }
+
+    fn raster_bounds(&self) -> ([Vertex; 4], [u32; 6]) {
         let left = self.fragment.bounds.left;
         let right = self.fragment.bounds.right;
         let top = self.fragment.bounds.top;
         let bottom = self.fragment.bounds.bottom;
 
         let points = [
-            Point::from(left, top),
-            Point::from(right, top),
-            Point::from(right, bottom),
-            Point::from(left, bottom),
+            Vertex::new(Point::from(left, top), 1.0),
+            Vertex::new(Point::from(right, top), 1.0),
+            Vertex::new(Point::from(right, bottom), 1.0),
+            Vertex::new(Point::from(left, bottom), 1.0),
         ];
 
         let indices: [u32; 6] = [0, 1, 2, 0, 2, 3];
 
         (points, indices)
     }
+
+    fn pipeline_key(&self) -> PipelineKey {
+        PipelineKey::new(self.format, self.sample_count)
+    }
+
+    /// Returns true if the outside of the path is clipped out by covering the bounds of target.
+    fn covers_bounds(&self) -> bool {
+        matches!(
+            self.op,
+            ClipOp::Intersect | ClipOp::ReverseDifference | ClipOp::Replace
+        )
+    }
+}
+
+/// Transform group a pass of `PathCliper` is drawn with.
+#[derive(Clone, Copy)]
+enum CliperGroup {
+    /// the path at the depth of the clip
+    Transform,
+    /// the bounds of target at the depth of the clip
+    Identity,
+    /// the path at depth 0, which lets the area through for any following draw
+    Include,
 }
 
 impl Renderer for PathCliper {
-    fn pipeline_label(&self) -> &'static str {
-        NON_COLOR_PIPELINE_NAME
+    fn load_pipelines(&self, context: &mut GPUContext, device: &wgpu::Device) {
+        context.load_pipeline(NON_COLOR_PIPELINE_NAME, self.pipeline_key(), device);
+
+        if let Some(coverage) = &self.coverage {
+            coverage.load_pipelines(context, device);
+        }
     }
 
     fn prepare(
         &mut self,
         total_depth: f32,
         buffer: &mut StageBuffer,
-        _context: &GPUContext,
-        _device: &wgpu::Device,
-        _queue: &wgpu::Queue,
+        context: &GPUContext,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
     ) {
         self.fragment
             .prepare(self.depth / total_depth, self.op, buffer);
 
-        (
-            self.vertex_range,
-            self.index_range,
-            self.vertex_mode,
-            self.draw_count,
-        ) = self.raster.rasterize(buffer);
+        self.mesh = self.raster.rasterize(buffer);
 
-        if self.op == ClipOp::Intersect {
+        if self.covers_bounds() {
             let (points, indices) = self.raster_bounds();
 
             self.bounds_vertex_range = buffer.push_data(bytemuck::cast_slice(&points));
             self.bounds_index_range = buffer.push_data(bytemuck::cast_slice(&indices));
         }
+
+        if let Some(coverage) = &mut self.coverage {
+            coverage.prepare(total_depth, buffer, context, device, queue);
+        }
+    }
+
+    fn render_offscreen(
+        &self,
+        buffer: &wgpu::Buffer,
+        context: &GPUContext,
+        device: &wgpu::Device,
+        encoder: &mut wgpu::CommandEncoder,
+    ) {
+        if let Some(coverage) = &self.coverage {
+            coverage.render_offscreen(buffer, context, device, encoder);
+        }
     }
 
     fn render<'a>(
@@ -340,77 +631,219 @@ impl Renderer for PathCliper {
         context: &'a GPUContext,
         device: &wgpu::Device,
     ) -> Vec<Command<'a>> {
-        if self.vertex_range.is_empty() || self.index_range.is_empty() {
+        if self.mesh.is_empty() {
             return vec![];
         }
 
         let pipeline = context
-            .get_pipeline(NON_COLOR_PIPELINE_NAME, self.format, self.anti_alias)
+            .get_pipeline(NON_COLOR_PIPELINE_NAME, &self.pipeline_key())
             .expect("Can not get non color pipeline");
 
-        let mut commands: Vec<Command<'a>> = Vec::new();
+        let winding = self.raster.path.fill_type == PathFillType::Winding;
+        let has_fill = self.mesh.draw_count > 0;
+        let has_fringe = self.mesh.fringe_count > 0;
 
-        // step 1: draw stencil mask
-        {
-            let common_group = self.fragment.gen_transform_group(device, buffer, pipeline);
+        let bounds = (
+            self.bounds_vertex_range.clone(),
+            self.bounds_index_range.clone(),
+            6,
+        );
 
-            let state = state_for_stencil_mask();
+        // (geometry, state, transform group) of each pass
+        let mut passes = Vec::new();
 
-            let raw_pipeline = pipeline
-                .get_pipeline(&state)
-                .expect("Can not get stencil mask pipeline");
+        // step 1: draw stencil mask, clips which can grow the clip need it outside of the clip too
+        if has_fill {
+            let state = if self.op.is_expanding() {
+                state_for_stencil_mask_always()
+            } else {
+                state_for_stencil_mask()
+            };
 
-            commands.push(Command::new(
-                raw_pipeline,
-                buffer.slice(self.vertex_range.clone()),
-                buffer.slice(self.index_range.clone()),
-                self.draw_count,
-                vec![common_group],
-            ));
+            passes.push((self.mesh.geometry(), state, CliperGroup::Transform));
         }
 
-        // step 2: draw clip mask
-        {
-            let state = if self.op == ClipOp::Intersect {
-                if self.raster.path.fill_type == PathFillType::Winding {
+        // step 2: move the fringe to the side of the clip which lets the feathered coverage through
+        if has_fringe {
+            let state = match (self.op, winding) {
+                (ClipOp::Intersect, true) => Some(state_for_clip_fringe_intersect()),
+                (ClipOp::Intersect, false) => Some(state_for_clip_fringe_even_odd_intersect()),
+                (ClipOp::Difference, true) => Some(state_for_clip_fringe_difference()),
+                (ClipOp::Difference, false) => Some(state_for_clip_fringe_even_odd_difference()),
+                // the fringe of clips which can grow the clip is let through in step 4
+                _ => None,
+            };
+
+            if let Some(state) = state {
+                passes.push((self.mesh.fringe(), state, CliperGroup::Transform));
+            }
+        }
+
+        // step 3: draw clip mask
+        let (union, xor, replace) = if winding {
+            (
+                state_for_clip_union(),
+                state_for_clip_xor(),
+                state_for_clip_replace(),
+            )
+        } else {
+            (
+                state_for_clip_even_odd_union(),
+                state_for_clip_even_odd_xor(),
+                state_for_clip_even_odd_replace(),
+            )
+        };
+
+        match self.op {
+            ClipOp::Intersect => {
+                let state = if winding {
                     state_for_clip_intersect()
                 } else {
                     state_for_clip_even_odd_intersect()
+                };
+
+                passes.push((bounds, state, CliperGroup::Identity));
+            }
+            ClipOp::Difference => {
+                if has_fill {
+                    let state = if winding {
+                        state_for_clip_difference()
+                    } else {
+                        state_for_clip_even_odd_difference()
+                    };
+
+                    passes.push((self.mesh.geometry(), state, CliperGroup::Transform));
                 }
-            } else {
-                if self.raster.path.fill_type == PathFillType::Winding {
-                    state_for_clip_difference()
-                } else {
-                    state_for_clip_even_odd_difference()
+            }
+            // the inside is let through
+            ClipOp::Union => {
+                if has_fill {
+                    passes.push((self.mesh.geometry(), union, CliperGroup::Include));
                 }
-            };
+            }
+            // the inside is clipped out where it was inside the clip, and let through where it was not
+            ClipOp::Xor => {
+                if has_fill {
+                    passes.push((self.mesh.geometry(), xor, CliperGroup::Transform));
+                    passes.push((self.mesh.geometry(), union, CliperGroup::Include));
+                }
+            }
+            // the outside is clipped out, then the inside is flipped as xor
+            ClipOp::ReverseDifference => {
+                passes.push((bounds, replace, CliperGroup::Identity));
 
-            let raw_pipeline = pipeline
-                .get_pipeline(&state)
-                .expect("Can not get clip mask pipeline");
+                if has_fill {
+                    passes.push((self.mesh.geometry(), xor, CliperGroup::Transform));
+                    passes.push((self.mesh.geometry(), union, CliperGroup::Include));
+                }
+            }
+            // the outside is clipped out and the inside is let through
+            ClipOp::Replace => {
+                passes.push((bounds, replace, CliperGroup::Identity));
 
-            if self.op == ClipOp::Intersect {
-                let identity_group = self.fragment.gen_identity_group(device, buffer, pipeline);
+                if has_fill {
+                    passes.push((self.mesh.geometry(), union, CliperGroup::Include));
+                }
+            }
+        }
 
-                commands.push(Command::new(
-                    raw_pipeline,
-                    buffer.slice(self.bounds_vertex_range.clone()),
-                    buffer.slice(self.bounds_index_range.clone()),
-                    6,
-                    vec![identity_group],
-                ));
-            } else {
-                let common_group = self.fragment.gen_transform_group(device, buffer, pipeline);
+        // step 4: let the fringe of clips which can grow the clip through, the coverage decides
+        if has_fringe && self.op.is_expanding() {
+            passes.push((
+                self.mesh.fringe(),
+                state_for_clip_fringe_include(),
+                CliperGroup::Include,
+            ));
+        }
 
-                commands.push(Command::new(
+        passes
+            .into_iter()
+            .map(|((vertex_range, index_range, draw_count), state, group)| {
+                let group = match group {
+                    CliperGroup::Transform => {
+                        self.fragment.gen_transform_group(device, buffer, pipeline)
+                    }
+                    CliperGroup::Identity => {
+                        self.fragment.gen_identity_group(device, buffer, pipeline)
+                    }
+                    CliperGroup::Include => {
+                        self.fragment.gen_include_group(device, buffer, pipeline)
+                    }
+                };
+
+                let raw_pipeline = pipeline
+                    .get_pipeline(&state)
+                    .expect("Can not get clip mask pipeline");
+
+                Command::new(
                     raw_pipeline,
-                    buffer.slice(self.vertex_range.clone()),
-                    buffer.slice(self.index_range.clone()),
-                    self.draw_count,
-                    vec![common_group],
-                ));
-            }
+                    buffer.slice(vertex_range),
+                    buffer.slice(index_range),
+                    draw_count,
+                    vec![group],
+                )
+            })
+            .collect()
+    }
+}
+
+/// Restricts the commands of a renderer to a pixel rect of the target.
+/// Draws inside the scope of rect clips replaced with scissor are wrapped with it.
+pub(crate) struct ScissorRenderer {
+    renderer: Box<dyn Renderer>,
+    /// [x, y, width, height] pixel rect of the target
+    scissor: [u32; 4],
+}
+
+impl ScissorRenderer {
+    pub(crate) fn new(renderer: Box<dyn Renderer>, scissor: [u32; 4]) -> Self {
+        Self { renderer, scissor }
+    }
+}
+
+impl Renderer for ScissorRenderer {
+    fn load_pipelines(&self, context: &mut GPUContext, device: &wgpu::Device) {
+        self.renderer.load_pipelines(context, device);
+    }
+
+    fn prepare(
+        &mut self,
+        total_depth: f32,
+        buffer: &mut StageBuffer,
+        context: &GPUContext,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+    ) {
+        self.renderer
+            .prepare(total_depth, buffer, context, device, queue);
+    }
+
+    fn render_offscreen(
+        &self,
+        buffer: &wgpu::Buffer,
+        context: &GPUContext,
+        device: &wgpu::Device,
+        encoder: &mut wgpu::CommandEncoder,
+    ) {
+        self.renderer
+            .render_offscreen(buffer, context, device, encoder);
+    }
+
+    fn render<'a>(
+        &self,
+        buffer: &'a wgpu::Buffer,
+        context: &'a GPUContext,
+        device: &wgpu::Device,
+    ) -> Vec<Command<'a>> {
+        // nothing is visible inside an empty scissor
+        if self.scissor[2] == 0 || self.scissor[3] == 0 {
+            return vec![];
         }
-        return commands;
+
+        self.renderer
+            .render(buffer, context, device)
+            .into_iter()
+            .map(|command| command.with_scissor(Some(self.scissor)))
+            .collect()
     }
 }
@@ -1,11 +1,134 @@
-use super::{Raster, VertexMode};
-use crate::core::{
-    geometry::{circle_interpolation, cross_product, distance},
-    paint::{StrokeCap, StrokeJoin},
-    path::{Contour, Path, PathFillType, PolylineBuilder},
-    Point,
+use std::ops::Range;
+
+use super::{Mesh, Raster, VertexMode};
+use crate::{
+    core::{
+        geometry::{circle_interpolation, cross_product, distance},
+        paint::{StrokeCap, StrokeJoin},
+        path::{Contour, Path, PathFillType, Polyline, PolylineBuilder},
+        Point,
+    },
+    gpu::buffer::StageBuffer,
 };
-use nalgebra::{Matrix4, Vector2};
+use bytemuck::{Pod, Zeroable};
+use nalgebra::{Matrix2, Matrix4, Vector2};
+
+/// Max length of the fringe offset at sharp corners, in half pixels.
+const MAX_FRINGE_MITER: f64 = 4.0;
+
+/// Vertex of path geometry. The fragment output is multiplied by the coverage clamped to [0, 1].
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Pod, Zeroable)]
+pub(crate) struct Vertex {
+    pub(crate) position: Point,
+    pub(crate) coverage: f32,
+}
+
+impl Vertex {
+    pub(crate) fn new(position: Point, coverage: f32) -> Self {
+        Self { position, coverage }
+    }
+
+    fn from_highp(p: &Vector2<f64>, coverage: f32) -> Self {
+        Self::new(Point::from_highp(p.x, p.y), coverage)
+    }
+}
+
+/// Pushes the geometry into buffer. Fringe indices share the vertices of the geometry.
+fn push_mesh(
+    buffer: &mut StageBuffer,
+    vertices: &[Vertex],
+    indices: &[u32],
+    mode: VertexMode,
+    fringe: &[u32],
+) -> Mesh {
+    let vertex_range = buffer.push_data(bytemuck::cast_slice(vertices));
+    let index_range = buffer.push_data(bytemuck::cast_slice(indices));
+    let fringe_range = buffer.push_data(bytemuck::cast_slice(fringe));
+
+    Mesh {
+        vertex_range,
+        index_range,
+        mode,
+        draw_count: indices.len() as u32,
+        fringe_range,
+        fringe_count: fringe.len() as u32,
+    }
+}
+
+/// Returns the linear transform from local space to target pixels.
+///
+/// # Arguments
+///
+/// * `matrix` transform from local space to target logical space
+/// * `scale` physical pixels per logical unit of the target
+fn pixel_matrix(matrix: &Matrix4<f32>, scale: [f32; 2]) -> Matrix2<f64> {
+    Matrix2::new(
+        (matrix.m11 * scale[0]) as f64,
+        (matrix.m12 * scale[0]) as f64,
+        (matrix.m21 * scale[1]) as f64,
+        (matrix.m22 * scale[1]) as f64,
+    )
+}
+
+/// Returns the local length of half a target pixel along `dir`, zero if the transform collapses `dir`.
+fn half_pixel(pixel: &Matrix2<f64>, dir: &Vector2<f64>) -> f64 {
+    let len = (pixel * dir).norm();
+
+    if len > f64::EPSILON { 0.5 / len } else { 0.0 }
+}
+
+/// Returns the outward normal of edge `a` to `b`, zero if the edge is degenerated.
+/// `ccw` is true if filled area is on the left side of edges in a y-up space.
+fn edge_normal(a: &Point, b: &Point, ccw: bool) -> Vector2<f64> {
+    let d = Vector2::new((b.x - a.x) as f64, (b.y - a.y) as f64);
+    let len = d.norm();
+
+    if len <= f64::EPSILON {
+        return Vector2::zeros();
+    }
+
+    let n = Vector2::new(d.y, -d.x) / len;
+
+    if ccw { n } else { -n }
+}
+
+/// Returns the offsets which move the vertices of a closed polygon half a pixel outward.
+/// Corners are mitered, sharp corners are limited by `MAX_FRINGE_MITER`.
+fn fringe_offsets(polygon: &[Point], ccw: bool, pixel: &Matrix2<f64>) -> Vec<Vector2<f64>> {
+    let n = polygon.len();
+
+    (0..n)
+        .map(|i| {
+            let prev = &polygon[(i + n - 1) % n];
+            let curr = &polygon[i];
+            let next = &polygon[(i + 1) % n];
+
+            let n0 = edge_normal(prev, curr, ccw);
+            let n1 = edge_normal(curr, next, ccw);
+
+            let dm = if n0 == Vector2::zeros() {
+                n1
+            } else if n1 == Vector2::zeros() {
+                n0
+            } else {
+                let dm = (n0 + n1) * 0.5;
+                let dmr2 = dm.norm_squared();
+
+                if dmr2 > 1e-6 {
+                    dm * (1.0 / dmr2).min(MAX_FRINGE_MITER)
+                } else {
+                    n0
+                }
+            };
+
+            match dm.try_normalize(f64::EPSILON) {
+                Some(dir) => dm * half_pixel(pixel, &dir),
+                None => Vector2::zeros(),
+            }
+        })
+        .collect()
+}
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 enum Orientation {
@@ -38,21 +161,41 @@ impl Orientation {
 pub(crate) struct PathFill {
     pub(crate) path: Path,
     pub(crate) matrix: Matrix4<f32>,
+    /// physical pixels per logical unit of the target, set if edges are anti-aliased with fringes
+    fringe: Option<[f32; 2]>,
 }
 
 impl PathFill {
     pub(crate) fn new(path: Path, matrix: Matrix4<f32>) -> Self {
-        Self { path, matrix }
+        Self {
+            path,
+            matrix,
+            fringe: None,
+        }
     }
 
-    fn do_raster(&self) -> (Vec<Point>, Vec<u32>, VertexMode) {
+    /// Generates a fringe of one pixel width straddling the edges, its coverage ramps from 1 inside to 0 outside.
+    /// Pass `None` to generate the geometry only.
+    ///
+    /// # Arguments
+    ///
+    /// * `fringe` physical pixels per logical unit of the target
+    pub(crate) fn with_fringe(mut self, fringe: Option<[f32; 2]>) -> Self {
+        self.fringe = fringe;
+        self
+    }
+
+    /// Triangulates contours into fans. Returns the points, indices, mode and the point range of each fan.
+    fn do_raster(
+        &self,
+        polyline: &Polyline,
+    ) -> (Vec<Point>, Vec<u32>, VertexMode, Vec<Range<usize>>) {
         let mut points: Vec<Point> = Vec::new();
         let mut indices: Vec<u32> = Vec::new();
+        let mut fans: Vec<Range<usize>> = Vec::new();
         let mut front_count = 0;
         let mut back_count = 0;
 
-        let polyline = PolylineBuilder::from(&self.path, &self.matrix).build();
-
         for contour in &polyline.contours {
             if contour.points.len() < 3 {
                 // can not fill contour with less than 3 points
@@ -90,6 +233,8 @@ impl PathFill {
                 prev_pt = curr_pt;
                 prev_index = curr_index;
             }
+
+            fans.push(first_index as usize..points.len());
         }
 
         let mode = if self.path.fill_type == PathFillType::EvenOdd {
@@ -100,44 +245,170 @@ impl PathFill {
             VertexMode::Complex
         };
 
-        return (points, indices, mode);
+        return (points, indices, mode, fans);
     }
 }
 
 impl Raster for PathFill {
-    fn rasterize(
-        &self,
-        buffer: &mut crate::gpu::buffer::StageBuffer,
-    ) -> (
-        std::ops::Range<wgpu::BufferAddress>,
-        std::ops::Range<wgpu::BufferAddress>,
-        super::VertexMode,
-        u32,
-    ) {
-        let (points, indices, mode) = self.do_raster();
+    fn rasterize(&self, buffer: &mut StageBuffer) -> Mesh {
+        let polyline = PolylineBuilder::from(&self.path, &self.matrix).build();
+
+        let (points, indices, mode, fans) = self.do_raster(&polyline);
+
+        let mut vertices: Vec<Vertex> = points.iter().map(|p| Vertex::new(*p, 1.0)).collect();
+        let mut fringe: Vec<u32> = Vec::new();
+
+        if let Some(scale) = self.fringe {
+            let pixel = pixel_matrix(&self.matrix, scale);
+
+            // outer contours and holes usually have opposite direction, the sign of the total area tells
+            // which side of edges is filled
+            let area: f64 = fans
+                .iter()
+                .map(|fan| {
+                    let polygon = &points[fan.clone()];
+                    (0..polygon.len())
+                        .map(|i| {
+                            let a = &polygon[i];
+                            let b = &polygon[(i + 1) % polygon.len()];
+                            a.x as f64 * b.y as f64 - b.x as f64 * a.y as f64
+                        })
+                        .sum::<f64>()
+                })
+                .sum();
+
+            for fan in &fans {
+                let offsets = fringe_offsets(&points[fan.clone()], area >= 0.0, &pixel);
+
+                let first = vertices.len() as u32;
+                for (i, offset) in offsets.iter().enumerate() {
+                    let p = &points[fan.start + i];
+                    let p = Vector2::new(p.x as f64, p.y as f64);
+
+                    if mode == VertexMode::Convex {
+                        // convex fill is inset by half a pixel, the fringe ramps over both sides of the edges
+                        vertices[fan.start + i] = Vertex::from_highp(&(p - offset), 1.0);
+                    } else {
+                        // stencil covers the inner half of the fringe, only the outer half is drawn
+                        vertices.push(Vertex::from_highp(&(p - offset), 1.0));
+                    }
+                    vertices.push(Vertex::from_highp(&(p + offset), 0.0));
+                }
+
+                let n = offsets.len() as u32;
+                let (inner, outer, stride) = if mode == VertexMode::Convex {
+                    (fan.start as u32, first, 1)
+                } else {
+                    (first, first + 1, 2)
+                };
+
+                for i in 0..n {
+                    let j = (i + 1) % n;
+
+                    fringe.extend_from_slice(&[
+                        inner + i * stride,
+                        outer + i * stride,
+                        outer + j * stride,
+                        inner + i * stride,
+                        outer + j * stride,
+                        inner + j * stride,
+                    ]);
+                }
+            }
+        }
+
+        push_mesh(buffer, &vertices, &indices, mode, &fringe)
+    }
+}
 
-        let vertex_range = buffer.push_data(bytemuck::cast_slice(&points));
-        let index_range = buffer.push_data(bytemuck::cast_slice(&indices));
+/// Half width of stroke geometry and the coverage of its vertices.
+/// Coverage is interpolated linearly from the center line to the outer edges.
+#[derive(Debug, Clone, Copy)]
+struct StrokeExtent {
+    /// half width of the geometry
+    radius: f64,
+    /// how far square caps extend beyond the end points
+    cap: f64,
+    /// coverage at the center line
+    center: f32,
+    /// coverage at the outer edges
+    edge: f32,
+}
 
-        (vertex_range, index_range, mode, indices.len() as u32)
+impl StrokeExtent {
+    fn solid(radius: f64) -> Self {
+        Self {
+            radius,
+            cap: radius,
+            center: 1.0,
+            edge: 1.0,
+        }
     }
 }
 
+/// Pushes the quad `a c d b` where `a c` and `b d` are outer edges. The quad is split along the center line
+/// if the coverage ramps from center to edges.
+fn push_quad(
+    a: &Vector2<f64>,
+    b: &Vector2<f64>,
+    c: &Vector2<f64>,
+    d: &Vector2<f64>,
+    extent: &StrokeExtent,
+    points: &mut Vec<Vertex>,
+    indices: &mut Vec<u32>,
+) {
+    let a_index = points.len() as u32;
+    points.push(Vertex::from_highp(a, extent.edge));
+
+    let b_index = points.len() as u32;
+    points.push(Vertex::from_highp(b, extent.edge));
+
+    let c_index = points.len() as u32;
+    points.push(Vertex::from_highp(c, extent.edge));
+
+    let d_index = points.len() as u32;
+    points.push(Vertex::from_highp(d, extent.edge));
+
+    // a --------- c
+    // |           |
+    // |           |
+    // b-----------d
+    if extent.center == extent.edge {
+        indices.extend_from_slice(&[a_index, b_index, c_index, b_index, d_index, c_index]);
+        return;
+    }
+
+    // a --------- c
+    // m --------- n
+    // b-----------d
+    let m_index = points.len() as u32;
+    points.push(Vertex::from_highp(&((a + b) * 0.5), extent.center));
+
+    let n_index = points.len() as u32;
+    points.push(Vertex::from_highp(&((c + d) * 0.5), extent.center));
+
+    indices.extend_from_slice(&[
+        a_index, m_index, c_index, m_index, n_index, c_index, m_index, b_index, n_index, b_index,
+        d_index, n_index,
+    ]);
+}
+
 fn handle_bevel_join(
     prev_join: &Point,
     next_join: &Point,
     center: &Point,
-    points: &mut Vec<Point>,
+    extent: &StrokeExtent,
+    points: &mut Vec<Vertex>,
     indices: &mut Vec<u32>,
 ) {
     let center_index = points.len() as u32;
-    points.push(center.clone());
+    points.push(Vertex::new(center.clone(), extent.center));
 
     let prev_index = points.len() as u32;
-    points.push(prev_join.clone());
+    points.push(Vertex::new(prev_join.clone(), extent.edge));
 
     let next_index = points.len() as u32;
-    points.push(next_join.clone());
+    points.push(Vertex::new(next_join.clone(), extent.edge));
 
     indices.push(prev_index);
     indices.push(center_index);
@@ -148,13 +419,13 @@ fn handle_miter_join(
     prev_join: &Point,
     next_join: &Point,
     center: &Point,
-    stroke_radius: f32,
+    extent: &StrokeExtent,
     limit: f32,
-    points: &mut Vec<Point>,
+    points: &mut Vec<Vertex>,
     indices: &mut Vec<u32>,
 ) -> bool {
     let limit = limit as f64;
-    let stroke_radius = stroke_radius as f64;
+    let stroke_radius = extent.radius;
     let prev_join = Vector2::new(prev_join.x as f64, prev_join.y as f64);
     let next_join = Vector2::new(next_join.x as f64, next_join.y as f64);
     let center = Vector2::new(center.x as f64, center.y as f64);
@@ -175,16 +446,16 @@ fn handle_miter_join(
     let join = center + pe;
 
     let center_index = points.len() as u32;
-    points.push(Point::from_highp(center.x, center.y));
+    points.push(Vertex::from_highp(&center, extent.center));
 
     let join_index = points.len() as u32;
-    points.push(Point::from_highp(join.x, join.y));
+    points.push(Vertex::from_highp(&join, extent.edge));
 
     let prev_index = points.len() as u32;
-    points.push(Point::from_highp(prev_join.x, prev_join.y));
+    points.push(Vertex::from_highp(&prev_join, extent.edge));
 
     let next_index = points.len() as u32;
-    points.push(Point::from_highp(next_join.x, next_join.y));
+    points.push(Vertex::from_highp(&next_join, extent.edge));
 
     indices.push(join_index);
     indices.push(prev_index);
@@ -201,8 +472,8 @@ fn gen_round_mesh(
     prev_join: &Vector2<f64>,
     next_join: &Vector2<f64>,
     center: &Vector2<f64>,
-    stroke_radius: f64,
-    points: &mut Vec<Point>,
+    extent: &StrokeExtent,
+    points: &mut Vec<Vertex>,
     indices: &mut Vec<u32>,
 ) {
     let start = (prev_join - center).normalize();
@@ -211,16 +482,16 @@ fn gen_round_mesh(
     let result = circle_interpolation(&start, &end, 8);
 
     let center_index = points.len() as u32;
-    points.push(Point::from_highp(center.x, center.y));
+    points.push(Vertex::from_highp(center, extent.center));
 
     let mut prev_index = points.len() as u32;
-    points.push(Point::from_highp(prev_join.x, prev_join.y));
+    points.push(Vertex::from_highp(prev_join, extent.edge));
 
     for d in &result {
         let curr_index = points.len() as u32;
-        let p = d * stroke_radius + center;
+        let p = d * extent.radius + center;
 
-        points.push(Point::from_highp(p.x, p.y));
+        points.push(Vertex::from_highp(&p, extent.edge));
 
         indices.push(prev_index);
         indices.push(center_index);
@@ -237,6 +508,8 @@ pub(crate) struct PathStroke {
     miter_limit: f32,
     cap: StrokeCap,
     join: StrokeJoin,
+    /// physical pixels per logical unit of the target, set if edges are anti-aliased with fringes
+    fringe: Option<[f32; 2]>,
 }
 
 impl PathStroke {
@@ -255,15 +528,65 @@ impl PathStroke {
             miter_limit,
             cap,
             join,
+            fringe: None,
         }
     }
 
-    pub(crate) fn stroke_contour(
+    /// Generates a fringe of one pixel width along the edges, its coverage ramps from 1 inside to 0 outside.
+    /// Strokes thinner than one pixel are widened to one pixel and their coverage is scaled by the width.
+    /// Pass `None` to generate the geometry only.
+    ///
+    /// # Arguments
+    ///
+    /// * `fringe` physical pixels per logical unit of the target
+    pub(crate) fn with_fringe(mut self, fringe: Option<[f32; 2]>) -> Self {
+        self.fringe = fringe;
+        self
+    }
+
+    /// Returns the extent of the solid geometry and the extent of the fringe drawn after it.
+    fn extents(&self) -> (Option<StrokeExtent>, Option<StrokeExtent>) {
+        let radius = self.stroke_width as f64 * 0.5;
+
+        let scale = match self.fringe {
+            Some(scale) => scale,
+            None => return (Some(StrokeExtent::solid(radius)), None),
+        };
+
+        let pixel = pixel_matrix(&self.matrix, scale);
+        let det = pixel.determinant().abs();
+        if det <= f64::EPSILON {
+            return (None, None);
+        }
+
+        // half a pixel in local space
+        let half = 0.5 / det.sqrt();
+        let alpha = (radius / half).min(1.0) as f32;
+        let radius = radius.max(half);
+
+        let solid = if radius - half > f64::EPSILON {
+            Some(StrokeExtent::solid(radius - half))
+        } else {
+            None
+        };
+
+        let fringe = StrokeExtent {
+            radius: radius + half,
+            cap: radius,
+            center: ((radius + half) / (half * 2.0)) as f32 * alpha,
+            edge: 0.0,
+        };
+
+        (solid, Some(fringe))
+    }
+
+    fn stroke_contour(
         &self,
         contour: &Contour,
-        mut points: Vec<Point>,
+        extent: &StrokeExtent,
+        mut points: Vec<Vertex>,
         mut indices: Vec<u32>,
-    ) -> (Vec<Point>, Vec<u32>) {
+    ) -> (Vec<Vertex>, Vec<u32>) {
         for i in 0..contour.points.len() {
             if !contour.closed && i == contour.points.len() - 1 {
                 break;
@@ -287,31 +610,9 @@ impl PathStroke {
                 continue;
             }
 
-            let (a, b, c, d) = self.expend_line(p1, p2);
-
-            let a_index = points.len() as u32;
-            points.push(a.clone());
-
-            let b_index = points.len() as u32;
-            points.push(b.clone());
-
-            let c_index = points.len() as u32;
-            points.push(c.clone());
-
-            let d_index = points.len() as u32;
-            points.push(d.clone());
+            let (a, b, c, d) = self.expend_line(p1, p2, extent.radius);
 
-            // a --------- c
-            // |           |
-            // |           |
-            // b-----------d
-            indices.push(a_index);
-            indices.push(b_index);
-            indices.push(c_index);
-
-            indices.push(b_index);
-            indices.push(d_index);
-            indices.push(c_index);
+            push_quad(&a, &b, &c, &d, extent, &mut points, &mut indices);
 
             if !contour.closed && i == 0 {
                 continue;
@@ -331,7 +632,8 @@ impl PathStroke {
                 continue;
             }
 
-            let (prev_join, next_join) = self.get_join_points(p0, p1, p2, orientation, cross);
+            let (prev_join, next_join) =
+                self.get_join_points(p0, p1, p2, orientation, cross, extent.radius);
 
             match self.join {
                 StrokeJoin::Miter => {
@@ -339,7 +641,7 @@ impl PathStroke {
                         &prev_join,
                         &next_join,
                         p1,
-                        self.stroke_width * 0.5,
+                        extent,
                         self.miter_limit,
                         &mut points,
                         &mut indices,
@@ -347,7 +649,14 @@ impl PathStroke {
                         continue;
                     }
 
-                    handle_bevel_join(&prev_join, &next_join, p1, &mut points, &mut indices);
+                    handle_bevel_join(
+                        &prev_join,
+                        &next_join,
+                        p1,
+                        extent,
+                        &mut points,
+                        &mut indices,
+                    );
                 }
                 StrokeJoin::Round => {
                     let p0 = Vector2::new(p0.x as f64, p0.y as f64);
@@ -358,48 +667,43 @@ impl PathStroke {
                     let pp2 = (p1 - p2).normalize();
 
                     let out_dir = ((pp1 + pp2) * 0.5).normalize();
-                    let stroke_radius = self.stroke_width as f64 * 0.5;
 
-                    let out_p = p1 + out_dir * stroke_radius;
+                    let out_p = p1 + out_dir * extent.radius;
 
                     let prev_join = Vector2::new(prev_join.x as f64, prev_join.y as f64);
                     let next_join = Vector2::new(next_join.x as f64, next_join.y as f64);
 
-                    gen_round_mesh(
-                        &prev_join,
-                        &out_p,
-                        &p1,
-                        stroke_radius,
-                        &mut points,
-                        &mut indices,
-                    );
+                    gen_round_mesh(&prev_join, &out_p, &p1, extent, &mut points, &mut indices);
 
-                    gen_round_mesh(
-                        &out_p,
+                    gen_round_mesh(&out_p, &next_join, &p1, extent, &mut points, &mut indices);
+                }
+                StrokeJoin::Bevel => {
+                    handle_bevel_join(
+                        &prev_join,
                         &next_join,
-                        &p1,
-                        stroke_radius,
+                        p1,
+                        extent,
                         &mut points,
                         &mut indices,
                     );
                 }
-                StrokeJoin::Bevel => {
-                    handle_bevel_join(&prev_join, &next_join, p1, &mut points, &mut indices);
-                }
             }
         }
 
         if contour.closed {
         } else {
-            self.handle_cap(contour, &mut points, &mut indices);
+            self.handle_cap(contour, extent, &mut points, &mut indices);
         }
 
         return (points, indices);
     }
 
-    fn expend_line(&self, p1: &Point, p2: &Point) -> (Point, Point, Point, Point) {
-        let stroke_radius = (self.stroke_width as f64) * 0.5;
-
+    fn expend_line(
+        &self,
+        p1: &Point,
+        p2: &Point,
+        stroke_radius: f64,
+    ) -> (Vector2<f64>, Vector2<f64>, Vector2<f64>, Vector2<f64>) {
         let p1 = Vector2::new(p1.x as f64, p1.y as f64);
         let p2 = Vector2::new(p2.x as f64, p2.y as f64);
 
@@ -411,12 +715,7 @@ impl PathStroke {
         let c = p2 + normal * stroke_radius;
         let d = p2 - normal * stroke_radius;
 
-        return (
-            Point::from_highp(a.x, a.y),
-            Point::from_highp(b.x, b.y),
-            Point::from_highp(c.x, c.y),
-            Point::from_highp(d.x, d.y),
-        );
+        return (a, b, c, d);
     }
 
     fn get_join_points(
@@ -426,6 +725,7 @@ impl PathStroke {
         p2: &Point,
         orientation: Orientation,
         cross: f32,
+        stroke_radius: f64,
     ) -> (Point, Point) {
         let p0 = Vector2::new(p0.x as f64, p0.y as f64);
         let p1 = Vector2::new(p1.x as f64, p1.y as f64);
@@ -437,8 +737,6 @@ impl PathStroke {
         let prev_normal = Vector2::new(-prev_dir.y, prev_dir.x);
         let next_normal = Vector2::new(-next_dir.y, next_dir.x);
 
-        let stroke_radius = self.stroke_width as f64 * 0.5;
-
         if orientation == Orientation::CW || (orientation == Orientation::LINEAR && cross < 0.0) {
             let prev_join_pt = p1 - prev_normal * stroke_radius;
             let next_join_pt = p1 - next_normal * stroke_radius;
@@ -458,154 +756,118 @@ impl PathStroke {
         }
     }
 
-    fn handle_cap(&self, contour: &Contour, points: &mut Vec<Point>, indices: &mut Vec<u32>) {
-        match self.cap {
-            StrokeCap::Butt => {}
-            StrokeCap::Round => {
-                let start = Vector2::new(contour.points[0].x as f64, contour.points[0].y as f64);
-                let next = Vector2::new(contour.points[1].x as f64, contour.points[1].y as f64);
-
-                let out = (start - next).normalize();
-                let normal = Vector2::new(-out.y, out.x);
-
-                let stroke_radius = self.stroke_width as f64 * 0.5;
-                let out_p = start + out * stroke_radius;
+    fn handle_cap(
+        &self,
+        contour: &Contour,
+        extent: &StrokeExtent,
+        points: &mut Vec<Vertex>,
+        indices: &mut Vec<u32>,
+    ) {
+        if self.cap == StrokeCap::Butt || contour.points.len() < 2 {
+            return;
+        }
 
-                let p0 = start + normal * stroke_radius;
-                let p1 = start - normal * stroke_radius;
+        let last = contour.points.len() - 1;
 
-                gen_round_mesh(&p0, &out_p, &start, stroke_radius, points, indices);
-                gen_round_mesh(&out_p, &p1, &start, stroke_radius, points, indices);
+        // each end point with the point next to it
+        let ends = [(0, 1), (last, last - 1)];
 
-                let start = Vector2::new(
-                    contour.points[contour.points.len() - 1].x as f64,
-                    contour.points[contour.points.len() - 1].y as f64,
-                );
+        for (end, next) in ends {
+            let start = Vector2::new(contour.points[end].x as f64, contour.points[end].y as f64);
+            let next = Vector2::new(contour.points[next].x as f64, contour.points[next].y as f64);
 
-                let next = Vector2::new(
-                    contour.points[contour.points.len() - 2].x as f64,
-                    contour.points[contour.points.len() - 2].y as f64,
-                );
+            let out = (start - next).normalize();
+            let normal = Vector2::new(-out.y, out.x);
 
-                let out = (start - next).normalize();
-                let normal = Vector2::new(-out.y, out.x);
+            let p0 = start + normal * extent.radius;
+            let p1 = start - normal * extent.radius;
 
-                let stroke_radius = self.stroke_width as f64 * 0.5;
-                let out_p = start + out * stroke_radius;
+            match self.cap {
+                StrokeCap::Butt => {}
+                StrokeCap::Round => {
+                    let out_p = start + out * extent.radius;
 
-                let p0 = start + normal * stroke_radius;
-                let p1 = start - normal * stroke_radius;
+                    gen_round_mesh(&p0, &out_p, &start, extent, points, indices);
+                    gen_round_mesh(&out_p, &p1, &start, extent, points, indices);
+                }
+                StrokeCap::Square => {
+                    let p2 = p0 + out * extent.cap;
+                    let p3 = p1 + out * extent.cap;
 
-                gen_round_mesh(&p0, &out_p, &start, stroke_radius, points, indices);
-                gen_round_mesh(&out_p, &p1, &start, stroke_radius, points, indices);
+                    push_quad(&p0, &p1, &p2, &p3, extent, points, indices);
+                }
             }
-            StrokeCap::Square => {
-                let start = Vector2::new(contour.points[0].x as f64, contour.points[0].y as f64);
-                let next = Vector2::new(contour.points[1].x as f64, contour.points[1].y as f64);
-
-                let out = (start - next).normalize();
-                let normal = Vector2::new(-out.y, out.x);
-
-                let stroke_radius = self.stroke_width as f64 * 0.5;
-
-                let p0 = start + normal * stroke_radius;
-                let p1 = start - normal * stroke_radius;
-
-                let p2 = p0 + out * stroke_radius;
-                let p3 = p1 + out * stroke_radius;
-
-                let a = points.len() as u32;
-                points.push(Point::from_highp(p0.x, p0.y));
-
-                let b = points.len() as u32;
-                points.push(Point::from_highp(p1.x, p1.y));
-
-                let c = points.len() as u32;
-                points.push(Point::from_highp(p2.x, p2.y));
-
-                let d = points.len() as u32;
-                points.push(Point::from_highp(p3.x, p3.y));
-
-                indices.push(a);
-                indices.push(b);
-                indices.push(c);
-
-                indices.push(b);
-                indices.push(d);
-                indices.push(c);
-
-                let start = Vector2::new(
-                    contour.points[contour.points.len() - 1].x as f64,
-                    contour.points[contour.points.len() - 1].y as f64,
-                );
-
-                let next = Vector2::new(
-                    contour.points[contour.points.len() - 2].x as f64,
-                    contour.points[contour.points.len() - 2].y as f64,
-                );
-
-                let out = (start - next).normalize();
-                let normal = Vector2::new(-out.y, out.x);
-
-                let stroke_radius = self.stroke_width as f64 * 0.5;
+        }
+    }
 
-                let p0 = start + normal * stroke_radius;
-                let p1 = start - normal * stroke_radius;
+    /// Strokes all contours of the polyline with extent.
+    fn stroke_polyline(
+        &self,
+        polyline: &Polyline,
+        extent: &StrokeExtent,
+    ) -> (Vec<Vertex>, Vec<u32>) {
+        let mut points: Vec<Vertex> = Vec::new();
+        let mut indices: Vec<u32> = Vec::new();
 
-                let p2 = p0 + out * stroke_radius;
-                let p3 = p1 + out * stroke_radius;
+        for contour in &polyline.contours {
+            (points, indices) = self.stroke_contour(contour, extent, points, indices);
+        }
 
-                let a = points.len() as u32;
-                points.push(Point::from_highp(p0.x, p0.y));
+        (points, indices)
+    }
+}
 
-                let b = points.len() as u32;
-                points.push(Point::from_highp(p1.x, p1.y));
+impl Raster for PathStroke {
+    fn rasterize(&self, buffer: &mut StageBuffer) -> Mesh {
+        let polyline = PolylineBuilder::from(&self.path, &self.matrix).build();
 
-                let c = points.len() as u32;
-                points.push(Point::from_highp(p2.x, p2.y));
+        let (solid, fringe) = self.extents();
 
-                let d = points.len() as u32;
-                points.push(Point::from_highp(p3.x, p3.y));
+        let (mut points, indices) = match &solid {
+            Some(extent) => self.stroke_polyline(&polyline, extent),
+            None => (Vec::new(), Vec::new()),
+        };
 
-                indices.push(a);
-                indices.push(b);
-                indices.push(c);
+        // the fringe is drawn after the solid geometry and only covers pixels outside of it
+        let fringe = match &fringe {
+            Some(extent) => {
+                let (fringe_points, fringe_indices) = self.stroke_polyline(&polyline, extent);
+                let offset = points.len() as u32;
 
-                indices.push(b);
-                indices.push(d);
-                indices.push(c);
+                points.extend(fringe_points);
+                fringe_indices.iter().map(|i| i + offset).collect()
             }
-        }
+            None => Vec::new(),
+        };
+
+        push_mesh(buffer, &points, &indices, VertexMode::NonOverlap, &fringe)
     }
 }
 
-impl Raster for PathStroke {
-    fn rasterize(
-        &self,
-        buffer: &mut crate::gpu::buffer::StageBuffer,
-    ) -> (
-        std::ops::Range<wgpu::BufferAddress>,
-        std::ops::Range<wgpu::BufferAddress>,
-        VertexMode,
-        u32,
-    ) {
-        let polyline = PolylineBuilder::from(&self.path, &self.matrix).build();
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{core::Rect, gpu::init_test_context};
 
-        let mut points: Vec<Point> = Vec::new();
-        let mut indices: Vec<u32> = Vec::new();
+    #[test]
+    fn fill_fringe() {
+        let (device, _queue) = init_test_context();
+        let mut buffer = StageBuffer::new(&device);
 
-        for contour in &polyline.contours {
-            (points, indices) = self.stroke_contour(contour, points, indices);
-        }
+        let path = Path::new().add_rect(&Rect::from_xywh(10.0, 10.0, 20.0, 20.0));
+
+        let hard = PathFill::new(path.clone(), Matrix4::identity()).rasterize(&mut buffer);
+
+        assert_eq!(hard.mode, VertexMode::Convex);
+        assert_eq!(hard.draw_count, 6);
+        assert_eq!(hard.fringe_count, 0);
 
-        let vertex_range = buffer.push_data(bytemuck::cast_slice(&points));
-        let index_range = buffer.push_data(bytemuck::cast_slice(&indices));
+        let smooth = PathFill::new(path, Matrix4::identity())
+            .with_fringe(Some([1.0, 1.0]))
+            .rasterize(&mut buffer);
 
-        return (
-            vertex_range,
-            index_range,
-            VertexMode::NonOverlap,
-            indices.len() as u32,
-        );
+        // two triangles along each edge
+        assert_eq!(smooth.draw_count, 6);
+        assert_eq!(smooth.fringe_count, 4 * 6);
     }
 }
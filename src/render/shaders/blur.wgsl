@@ -0,0 +1,57 @@
+struct VertexInput {
+    // uv of the full target quad
+    @location(0) position: vec2<f32>,
+};
+
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+};
+
+struct BlurInfo {
+    // [step x, step y, sigma, radius]
+    // step is the distance between two samples in uv, radius 0 means a single sample copy
+    info: vec4<f32>,
+};
+
+@group(0) @binding(0)
+var<uniform> blurInfo: BlurInfo;
+
+@group(0) @binding(1)
+var source: texture_2d<f32>;
+
+@group(0) @binding(2)
+var sourceSampler: sampler;
+
+@vertex
+fn vs_main(vertex: VertexInput) -> VertexOutput {
+    var out: VertexOutput;
+
+    out.uv = vertex.position;
+    out.position = vec4<f32>(vertex.position.x * 2.0 - 1.0, 1.0 - vertex.position.y * 2.0, 0.0, 1.0);
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    var radius = i32(blurInfo.info[3]);
+
+    if radius == 0 {
+        // bilinear sample at the center of 2x2 source texels
+        return textureSampleLevel(source, sourceSampler, in.uv, 0.0);
+    }
+
+    var sigma = blurInfo.info[2];
+    var sum = vec4<f32>(0.0);
+    var total = 0.0;
+
+    for (var i: i32 = -radius; i <= radius; i++) {
+        var x = f32(i);
+        var weight = exp(-x * x / (2.0 * sigma * sigma));
+
+        sum += textureSampleLevel(source, sourceSampler, in.uv + blurInfo.info.xy * x, 0.0) * weight;
+        total += weight;
+    }
+
+    return sum / total;
+}
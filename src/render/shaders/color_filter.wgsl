@@ -0,0 +1,12 @@
+@group(2) @binding(0)
+var<uniform> colorFilter: ColorFilterInfo;
+
+fn apply_color_filter(color: vec4<f32>) -> vec4<f32> {
+    var ret = color;
+
+    for (var i: u32 = 0; i < colorFilter.counts[0]; i++) {
+        ret = color_filter_step(ret, colorFilter.steps[i]);
+    }
+
+    return ret;
+}
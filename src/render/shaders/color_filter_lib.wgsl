@@ -0,0 +1,93 @@
+struct ColorFilterStep {
+    // [kind, dummy, dummy, dummy]
+    // kind: 0 matrix, 1 blend, 2 linear to sRGB, 3 sRGB to linear
+    info: vec4<f32>,
+    // matrix rows for matrix step
+    // blend step: [premultiplied color, [k0, k1, k2, k3], [k4, clamp, dummy, dummy], dummy]
+    m0: vec4<f32>,
+    m1: vec4<f32>,
+    m2: vec4<f32>,
+    m3: vec4<f32>,
+    // translation column for matrix step
+    t: vec4<f32>,
+};
+
+struct ColorFilterInfo {
+    // [step count, dummy, dummy, dummy]
+    counts: vec4<u32>,
+    steps: array<ColorFilterStep, 8>,
+};
+
+fn color_filter_unpremul(color: vec4<f32>) -> vec4<f32> {
+    if color.a <= 0.0 {
+        return vec4<f32>(0.0);
+    }
+
+    return vec4<f32>(color.rgb / color.a, color.a);
+}
+
+fn color_filter_linear_to_srgb(c: f32) -> f32 {
+    if c <= 0.0031308 {
+        return c * 12.92;
+    }
+
+    return 1.055 * pow(c, 1.0 / 2.4) - 0.055;
+}
+
+fn color_filter_srgb_to_linear(c: f32) -> f32 {
+    if c <= 0.04045 {
+        return c / 12.92;
+    }
+
+    return pow((c + 0.055) / 1.055, 2.4);
+}
+
+// runs one step of a color filter on the premultiplied color
+fn color_filter_step(color: vec4<f32>, step: ColorFilterStep) -> vec4<f32> {
+    var ret = color;
+    var kind = u32(step.info[0]);
+
+    if kind == 1 {
+        // blend with color, the color is source and current color is destination
+        var s = step.m0;
+        var k = step.m1;
+        var f_s = k[0] + k[1] * ret.a;
+        var f_d = k[2] + k[3] * s.a;
+
+        ret = s * f_s + ret * f_d + s * ret * step.m2[0];
+
+        if step.m2[1] > 0.0 {
+            ret = min(ret, vec4<f32>(1.0));
+        }
+    } else {
+        var c = color_filter_unpremul(ret);
+
+        if kind == 0 {
+            c = vec4<f32>(
+                dot(step.m0, c) + step.t[0],
+                dot(step.m1, c) + step.t[1],
+                dot(step.m2, c) + step.t[2],
+                dot(step.m3, c) + step.t[3],
+            );
+            c = clamp(c, vec4<f32>(0.0), vec4<f32>(1.0));
+        } else if kind == 2 {
+            c = vec4<f32>(
+                color_filter_linear_to_srgb(c.r),
+                color_filter_linear_to_srgb(c.g),
+                color_filter_linear_to_srgb(c.b),
+                c.a,
+            );
+        } else {
+            c = vec4<f32>(
+                color_filter_srgb_to_linear(c.r),
+                color_filter_srgb_to_linear(c.g),
+                color_filter_srgb_to_linear(c.b),
+                c.a,
+            );
+        }
+
+        ret = vec4<f32>(c.rgb * c.a, c.a);
+    }
+
+    return ret;
+}
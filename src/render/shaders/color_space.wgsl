@@ -0,0 +1,38 @@
+// 0: sRGB, 1: linear sRGB, 2: Display P3, 3: extended sRGB
+const COLOR_SPACE: u32 = COLOR_SPACE_VALUE;
+
+// true if the target view is an sRGB format, blending happens in linear light and the hardware encodes
+const LINEAR_OUTPUT: bool = LINEAR_OUTPUT_VALUE;
+
+// converts the premultiplied sRGB color into the color space of the target
+fn apply_color_space(color: vec4<f32>) -> vec4<f32> {
+    var c = color;
+
+    if COLOR_SPACE == 0u {
+        c = vec4<f32>(clamp(c.rgb, vec3<f32>(0.0), vec3<f32>(c.a)), c.a);
+    }
+
+    if (COLOR_SPACE == 0u || COLOR_SPACE == 3u) && !LINEAR_OUTPUT {
+        return c;
+    }
+
+    if c.a <= 0.0 {
+        return c;
+    }
+
+    var rgb = color_space_to_linear(c.rgb / c.a);
+
+    if COLOR_SPACE == 2u {
+        rgb = vec3<f32>(
+            dot(rgb, vec3<f32>(0.8224621, 0.177538, 0.0)),
+            dot(rgb, vec3<f32>(0.0331941, 0.9668058, 0.0)),
+            dot(rgb, vec3<f32>(0.0170827, 0.0723974, 0.9105199)),
+        );
+
+        if !LINEAR_OUTPUT {
+            rgb = color_space_to_srgb(rgb);
+        }
+    }
+
+    return vec4<f32>(rgb * c.a, c.a);
+}
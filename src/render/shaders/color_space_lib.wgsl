@@ -0,0 +1,14 @@
+// sRGB transfer functions, mirrored for negative values of extended range
+fn color_space_to_linear(c: vec3<f32>) -> vec3<f32> {
+    var x = abs(c);
+    var linear = select(pow((x + 0.055) / 1.055, vec3<f32>(2.4)), x / 12.92, x <= vec3<f32>(0.04045));
+
+    return sign(c) * linear;
+}
+
+fn color_space_to_srgb(c: vec3<f32>) -> vec3<f32> {
+    var x = abs(c);
+    var srgb = select(1.055 * pow(x, vec3<f32>(1.0 / 2.4)) - 0.055, x * 12.92, x <= vec3<f32>(0.0031308));
+
+    return sign(c) * srgb;
+}
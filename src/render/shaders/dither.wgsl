@@ -0,0 +1,29 @@
+// one quantization step of the target format
+const DITHER_SCALE: f32 = DITHER_SCALE_VALUE;
+
+// true if the target quantizes after sRGB encoding, noise is added to the encoded value
+const DITHER_ENCODED: bool = DITHER_ENCODED_VALUE;
+
+// ordered dither with 8x8 Bayer matrix, noise is centered at zero
+fn apply_dither(color: vec4<f32>, frag_coord: vec4<f32>) -> vec4<f32> {
+    var p = vec2<u32>(frag_coord.xy) % vec2<u32>(8u);
+    var xy = p.x ^ p.y;
+    var index = ((xy & 1u) << 5u) | ((p.y & 1u) << 4u)
+        | ((xy & 2u) << 2u) | ((p.y & 2u) << 1u)
+        | ((xy & 4u) >> 1u) | ((p.y & 4u) >> 2u);
+
+    var noise = ((f32(index) + 0.5) / 64.0 - 0.5) * DITHER_SCALE;
+
+    if DITHER_ENCODED {
+        if color.a <= 0.0 {
+            return color;
+        }
+
+        var srgb = clamp(color_space_to_srgb(color.rgb / color.a) + vec3<f32>(noise), vec3<f32>(0.0), vec3<f32>(1.0));
+
+        return vec4<f32>(color_space_to_linear(srgb) * color.a, color.a);
+    }
+
+    // color is premultiplied, keep it valid
+    return vec4<f32>(clamp(color.rgb + vec3<f32>(noise), vec3<f32>(0.0), vec3<f32>(color.a)), color.a);
+}
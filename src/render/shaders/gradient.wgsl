@@ -0,0 +1,105 @@
+const MAX_COUNT: u32 = 16;
+const STOP_COUNT = MAX_COUNT / 4;
+
+const TILE_MODE_CLAMP: u32 = 0;
+const TILE_MODE_REPEAT: u32 = 1;
+const TILE_MODE_MIRROR: u32 = 2;
+const TILE_MODE_DECAL: u32 = 3;
+
+const INTERPOLATION_UNPREMUL_SRGB: u32 = 0;
+const INTERPOLATION_PREMUL_SRGB: u32 = 1;
+const INTERPOLATION_LINEAR_SRGB: u32 = 2;
+const INTERPOLATION_OKLAB: u32 = 3;
+const INTERPOLATION_OKLCH: u32 = 4;
+
+struct ColorInfo {
+    // [color_count, stops_count, tile_mode, interpolation]
+    counts: vec4<u32>,
+    // colors converted into the interpolation space
+    colors: array<vec4<f32>, MAX_COUNT>,
+    stops: array<vec4<f32>, STOP_COUNT>,
+};
+
+struct GradientInfo {
+    // maps local coordinates into gradient space
+    matrix: mat4x4<f32>,
+    // linear: [p1.x, p1.y, p2.x, p2.y]
+    // radial: [center.x, center.y, radius, dummy]
+    params: vec4<f32>,
+};
+
+fn remap_t(t: f32, mode: u32) -> f32 {
+    if mode == TILE_MODE_CLAMP {
+        return clamp(t, 0.0, 1.0);
+    } else if mode == TILE_MODE_REPEAT {
+        return fract(t);
+    } else if mode == TILE_MODE_MIRROR {
+        var t1 = t - 1.0;
+        var t2 = t1 - 2.0 * floor(t1 * 0.5) - 1.0;
+        return abs(t2);
+    }
+
+    return t;
+}
+
+fn gradient_step(edge0: f32, edge1: f32, x: f32) -> f32 {
+    return clamp((x - edge0) / (edge1 - edge0), 0.0, 1.0);
+}
+
+fn gradient_linear_to_srgb(c: f32) -> f32 {
+    if c <= 0.0031308 {
+        return c * 12.92;
+    }
+
+    return 1.055 * pow(c, 1.0 / 2.4) - 0.055;
+}
+
+fn gradient_oklab_to_linear(lab: vec3<f32>) -> vec3<f32> {
+    var l = lab.x + 0.3963377774 * lab.y + 0.2158037573 * lab.z;
+    var m = lab.x - 0.1055613458 * lab.y - 0.0638541728 * lab.z;
+    var s = lab.x - 0.0894841775 * lab.y - 1.2914855480 * lab.z;
+
+    l = l * l * l;
+    m = m * m * m;
+    s = s * s * s;
+
+    return vec3<f32>(
+        4.0767416621 * l - 3.3077115913 * m + 0.2309699292 * s,
+        -1.2684380046 * l + 2.6097574011 * m - 0.3413193965 * s,
+        -0.0041960863 * l - 0.7034186147 * m + 1.7076147010 * s,
+    );
+}
+
+// converts an interpolated color back to premultiplied sRGB
+fn gradient_to_srgb(color: vec4<f32>, mode: u32) -> vec4<f32> {
+    if mode == INTERPOLATION_UNPREMUL_SRGB {
+        return vec4<f32>(color.rgb * color.a, color.a);
+    } else if mode == INTERPOLATION_PREMUL_SRGB {
+        return color;
+    }
+
+    if color.a <= 0.0 {
+        return vec4<f32>(0.0);
+    }
+
+    var linear = color.rgb / color.a;
+
+    if mode == INTERPOLATION_OKLAB {
+        linear = gradient_oklab_to_linear(linear);
+    } else if mode == INTERPOLATION_OKLCH {
+        // hue is not premultiplied
+        var chroma = color.y / color.a;
+        linear = gradient_oklab_to_linear(vec3<f32>(linear.x, chroma * cos(color.z), chroma * sin(color.z)));
+    }
+
+    // values above 1.0 are kept for extended color spaces
+    linear = max(linear, vec3<f32>(0.0));
+
+    var srgb = vec3<f32>(
+        gradient_linear_to_srgb(linear.r),
+        gradient_linear_to_srgb(linear.g),
+        gradient_linear_to_srgb(linear.b),
+    );
+
+    return vec4<f32>(srgb * color.a, color.a);
+}
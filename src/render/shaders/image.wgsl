@@ -1,10 +1,13 @@
 struct VertexInput {
     @location(0) position: vec2<f32>,
+    // anti-alias coverage, 0 at the outer edge of fringes
+    @location(1) coverage: f32,
 };
 
 struct VertexOutput {
     @builtin(position) position: vec4<f32>,
     @location(0) vUV: vec2<f32>,
+    @location(1) coverage: f32,
 };
 
 struct UserMatrix {
@@ -48,6 +51,7 @@ fn vs_main(vertex: VertexInput) -> VertexOutput {
 
     out.vUV = vec2<f32>(vUV.x / uvTransform.bounds.x, vUV.y / uvTransform.bounds.y);
     out.position = vec4<f32>(pos.x / pos.w, pos.y / pos.w, transform.info[0], 1.0);
+    out.coverage = vertex.coverage;
     return out;
 }
 
@@ -59,9 +63,11 @@ fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
         color = vec4<f32>(color.b, color.g, color.r, color.a);
     }
 
-    if imageInfo.info[0] == 1 {
-        return color;
-    } else {
-        return vec4<f32>(color.rgb * color.a, color.a);
+    if imageInfo.info[0] != 1 {
+        color = vec4<f32>(color.rgb * color.a, color.a);
     }
+
+    var out = apply_mask(apply_dither(apply_color_space(apply_color_filter(color)), in.position), in.position);
+
+    return out * clamp(in.coverage, 0.0, 1.0);
 }
\ No newline at end of file
@@ -0,0 +1,80 @@
+struct VertexInput {
+    // uv of the full target quad
+    @location(0) position: vec2<f32>,
+};
+
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+};
+
+struct FilterInfo {
+    // [target left, target top, source left, source top] in pixels of the layer target
+    origin: vec4<f32>,
+    // [source width, source height, mode, radius]
+    // mode: 0 copy, 1 blur, 2 shadow
+    info: vec4<f32>,
+    // copy and shadow: [dx, dy, dummy, dummy] offset of the output in pixels
+    // blur: [step x, step y, sigma, dummy] step is the distance between two samples in pixels
+    params: vec4<f32>,
+    // premultiplied shadow color
+    color: vec4<f32>,
+};
+
+@group(0) @binding(0)
+var<uniform> filterInfo: FilterInfo;
+
+@group(1) @binding(0)
+var source: texture_2d<f32>;
+
+@group(1) @binding(1)
+var sourceSampler: sampler;
+
+@vertex
+fn vs_main(vertex: VertexInput) -> VertexOutput {
+    var out: VertexOutput;
+
+    out.position = vec4<f32>(vertex.position.x * 2.0 - 1.0, 1.0 - vertex.position.y * 2.0, 0.0, 1.0);
+    return out;
+}
+
+// samples the source at p in pixels of the layer target, transparent outside of the source
+fn sample_source(p: vec2<f32>) -> vec4<f32> {
+    var size = filterInfo.info.xy;
+    var local = p - filterInfo.origin.zw;
+
+    if local.x < 0.0 || local.y < 0.0 || local.x > size.x || local.y > size.y {
+        return vec4<f32>(0.0);
+    }
+
+    return textureSampleLevel(source, sourceSampler, local / size, 0.0);
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    var p = in.position.xy + filterInfo.origin.xy;
+    var mode = i32(filterInfo.info[2]);
+
+    var color = vec4<f32>(0.0);
+
+    if mode == 1 {
+        var radius = i32(filterInfo.info[3]);
+        var sigma = filterInfo.params[2];
+        var total = 0.0;
+
+        for (var i: i32 = -radius; i <= radius; i++) {
+            var x = f32(i);
+            var weight = exp(-x * x / (2.0 * sigma * sigma));
+
+            color += sample_source(p + filterInfo.params.xy * x) * weight;
+            total += weight;
+        }
+
+        color = color / total;
+    } else if mode == 2 {
+        color = filterInfo.color * sample_source(p - filterInfo.params.xy).a;
+    } else {
+        color = sample_source(p - filterInfo.params.xy);
+    }
+
+    return apply_color_filter(color);
+}
@@ -0,0 +1,53 @@
+struct VertexInput {
+    @location(0) position: vec2<f32>,
+};
+
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) vUV: vec2<f32>,
+};
+
+struct UserMatrix {
+    mvp: mat4x4<f32>,
+    transform: mat4x4<f32>,
+    info: vec4<f32>,
+};
+
+struct LayerInfo {
+    // [left, top, right, bottom]
+    bounds: vec4<f32>,
+    // [alpha, dummy, dummy, dummy]
+    info: vec4<f32>,
+};
+
+@group(0) @binding(0)
+var<uniform> transform: UserMatrix;
+
+@group(1) @binding(0)
+var<uniform> layerInfo: LayerInfo;
+
+@group(1) @binding(1)
+var layer: texture_2d<f32>;
+
+@group(1) @binding(2)
+var layerSampler: sampler;
+
+@vertex
+fn vs_main(vertex: VertexInput) -> VertexOutput {
+    var out: VertexOutput;
+    var pos: vec4<f32> = transform.mvp * transform.transform * vec4<f32>(vertex.position, 0.0, 1.0);
+
+    var size = layerInfo.bounds.zw - layerInfo.bounds.xy;
+
+    out.vUV = (vertex.position - layerInfo.bounds.xy) / size;
+    out.position = vec4<f32>(pos.x / pos.w, pos.y / pos.w, transform.info[0], 1.0);
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    // layer content is already premultiplied
+    var color = textureSample(layer, layerSampler, in.vUV) * layerInfo.info[0];
+
+    return apply_mask(apply_color_filter(color), in.position);
+}
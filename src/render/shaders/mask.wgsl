@@ -0,0 +1,39 @@
+struct MaskInfo {
+    // [left, top, width, height] of the mask in target pixels
+    bounds: vec4<f32>,
+    // [style, dummy, dummy, dummy]
+    // style: 0 normal, 1 solid, 2 outer, 3 inner
+    info: vec4<f32>,
+};
+
+@group(MASK_GROUP) @binding(0)
+var<uniform> maskInfo: MaskInfo;
+
+@group(MASK_GROUP) @binding(1)
+var maskBlurred: texture_2d<f32>;
+
+@group(MASK_GROUP) @binding(2)
+var maskCoverage: texture_2d<f32>;
+
+@group(MASK_GROUP) @binding(3)
+var maskSampler: sampler;
+
+fn apply_mask(color: vec4<f32>, frag_coord: vec4<f32>) -> vec4<f32> {
+    var uv = (frag_coord.xy - maskInfo.bounds.xy) / maskInfo.bounds.zw;
+
+    var blurred = textureSampleLevel(maskBlurred, maskSampler, uv, 0.0).r;
+    var coverage = textureSampleLevel(maskCoverage, maskSampler, uv, 0.0).r;
+
+    var style = u32(maskInfo.info[0]);
+
+    var alpha = blurred;
+    if style == 1 {
+        alpha = max(blurred, coverage);
+    } else if style == 2 {
+        alpha = blurred * (1.0 - coverage);
+    } else if style == 3 {
+        alpha = blurred * coverage;
+    }
+
+    return color * alpha;
+}
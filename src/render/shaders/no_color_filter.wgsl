@@ -0,0 +1,3 @@
+fn apply_color_filter(color: vec4<f32>) -> vec4<f32> {
+    return color;
+}
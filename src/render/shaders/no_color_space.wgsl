@@ -0,0 +1,3 @@
+fn apply_color_space(color: vec4<f32>) -> vec4<f32> {
+    return color;
+}
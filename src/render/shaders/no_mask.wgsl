@@ -0,0 +1,3 @@
+fn apply_mask(color: vec4<f32>, frag_coord: vec4<f32>) -> vec4<f32> {
+    return color;
+}
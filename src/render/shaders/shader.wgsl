@@ -0,0 +1,41 @@
+struct VertexInput {
+    @location(0) position: vec2<f32>,
+    // anti-alias coverage, 0 at the outer edge of fringes
+    @location(1) coverage: f32,
+};
+
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    // local position of the geometry
+    @location(0) vPos: vec2<f32>,
+    @location(1) coverage: f32,
+};
+
+struct UserMatrix {
+    mvp: mat4x4<f32>,
+    transform: mat4x4<f32>,
+    info: vec4<f32>,
+};
+
+@group(0) @binding(0)
+var<uniform> transform: UserMatrix;
+
+@vertex
+fn vs_main(vertex: VertexInput) -> VertexOutput {
+    var out: VertexOutput;
+    var pos: vec4<f32> = transform.mvp * transform.transform * vec4<f32>(vertex.position, 0.0, 1.0);
+
+    out.position = vec4<f32>(pos.x / pos.w, pos.y / pos.w, transform.info[0], 1.0);
+    out.vPos = vertex.position;
+    out.coverage = vertex.coverage;
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    var color = shader_0(in.vPos);
+
+    var out = apply_mask(apply_dither(apply_color_space(apply_color_filter(color)), in.position), in.position);
+
+    return out * clamp(in.coverage, 0.0, 1.0);
+}
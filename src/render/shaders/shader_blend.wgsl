@@ -0,0 +1,18 @@
+// [[k0, k1, k2, k3], [k4, clamp, dummy, dummy]]
+@group(1) @binding(BINDING_0)
+var<uniform> blend_NODE_ID: array<vec4<f32>, 2>;
+
+// blends the first child as source with the second child as destination
+fn shader_NODE_ID(p: vec2<f32>) -> vec4<f32> {
+    var s = shader_CHILD_0(p);
+    var d = shader_CHILD_1(p);
+    var k = blend_NODE_ID[0];
+
+    var ret = s * (k[0] + k[1] * d.a) + d * (k[2] + k[3] * s.a) + s * d * blend_NODE_ID[1][0];
+
+    if blend_NODE_ID[1][1] > 0.0 {
+        ret = min(ret, vec4<f32>(1.0));
+    }
+
+    return ret;
+}
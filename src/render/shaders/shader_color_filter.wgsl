@@ -0,0 +1,12 @@
+@group(1) @binding(BINDING_0)
+var<uniform> colorFilter_NODE_ID: ColorFilterInfo;
+
+fn shader_NODE_ID(p: vec2<f32>) -> vec4<f32> {
+    var ret = shader_CHILD_0(p);
+
+    for (var i: u32 = 0; i < colorFilter_NODE_ID.counts[0]; i++) {
+        ret = color_filter_step(ret, colorFilter_NODE_ID.steps[i]);
+    }
+
+    return ret;
+}
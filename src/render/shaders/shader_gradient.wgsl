@@ -0,0 +1,59 @@
+@group(1) @binding(BINDING_0)
+var<uniform> gradient_NODE_ID: GradientInfo;
+
+@group(1) @binding(BINDING_1)
+var<uniform> colorInfo_NODE_ID: ColorInfo;
+
+fn gradient_stop_NODE_ID(index: u32) -> f32 {
+    var relIndex = index;
+    if relIndex >= MAX_COUNT {
+        relIndex = MAX_COUNT - 1;
+    }
+
+    var i: u32 = relIndex / 4;
+    var j: u32 = relIndex % 4;
+
+    return colorInfo_NODE_ID.stops[i][j];
+}
+
+// returns the premultiplied sRGB color at t
+fn gradient_color_NODE_ID(t: f32) -> vec4<f32> {
+    var ret = vec4<f32>(0.0);
+
+    var color_count = colorInfo_NODE_ID.counts[0];
+    var count: u32 = colorInfo_NODE_ID.counts[1];
+
+    var max_t = 1.0;
+    if count > 0 { max_t = gradient_stop_NODE_ID(count - 1); }
+
+    if t <= 0 {
+        ret = colorInfo_NODE_ID.colors[0];
+    } else if t >= max_t {
+        ret = colorInfo_NODE_ID.colors[color_count - 1];
+    } else {
+        for (var i: u32 = 0; i < color_count - 1; i++) {
+            var stopi = f32(i) / f32(color_count - 1);
+
+            if count > 0 { stopi = gradient_stop_NODE_ID(i); }
+
+            var stopi1 = f32(i + 1) / f32(color_count - 1);
+
+            if count > 0 { stopi1 = gradient_stop_NODE_ID(i + 1); }
+
+            if t >= stopi && t < stopi1 {
+                ret = colorInfo_NODE_ID.colors[i] * (1.0 - gradient_step(stopi, stopi1, t));
+                ret += colorInfo_NODE_ID.colors[i + 1] * gradient_step(stopi, stopi1, t);
+                break;
+            }
+        }
+    }
+
+    return gradient_to_srgb(ret, colorInfo_NODE_ID.counts[3]);
+}
+
+// maps the local position into gradient space
+fn gradient_pos_NODE_ID(p: vec2<f32>) -> vec2<f32> {
+    var pos = gradient_NODE_ID.matrix * vec4<f32>(p, 0.0, 1.0);
+
+    return pos.xy / pos.w;
+}
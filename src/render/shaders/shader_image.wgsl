@@ -0,0 +1,24 @@
+// [width, height, premultiplied, bgra]
+@group(1) @binding(BINDING_0)
+var<uniform> imageInfo_NODE_ID: vec4<f32>;
+
+@group(1) @binding(BINDING_1)
+var image_NODE_ID: texture_2d<f32>;
+
+@group(1) @binding(BINDING_2)
+var imageSampler_NODE_ID: sampler;
+
+// the image is placed at the local origin with its pixel size
+fn shader_NODE_ID(p: vec2<f32>) -> vec4<f32> {
+    var color = textureSampleLevel(image_NODE_ID, imageSampler_NODE_ID, p / imageInfo_NODE_ID.xy, 0.0);
+
+    if imageInfo_NODE_ID[3] > 0.0 {
+        color = vec4<f32>(color.b, color.g, color.r, color.a);
+    }
+
+    if imageInfo_NODE_ID[2] > 0.0 {
+        return color;
+    }
+
+    return vec4<f32>(color.rgb * color.a, color.a);
+}
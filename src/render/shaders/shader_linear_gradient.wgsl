@@ -0,0 +1,13 @@
+fn shader_NODE_ID(p: vec2<f32>) -> vec4<f32> {
+    var pos = gradient_pos_NODE_ID(p);
+    var st: vec2<f32> = gradient_NODE_ID.params.xy;
+    var ed: vec2<f32> = gradient_NODE_ID.params.zw;
+
+    var ba = ed - st;
+
+    var t = abs(dot(pos - st, ba) / dot(ba, ba));
+
+    t = remap_t(t, colorInfo_NODE_ID.counts[2]);
+
+    return gradient_color_NODE_ID(t);
+}
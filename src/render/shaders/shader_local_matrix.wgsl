@@ -0,0 +1,9 @@
+// maps the local position into the space of the child
+@group(1) @binding(BINDING_0)
+var<uniform> localMatrix_NODE_ID: mat4x4<f32>;
+
+fn shader_NODE_ID(p: vec2<f32>) -> vec4<f32> {
+    var pos = localMatrix_NODE_ID * vec4<f32>(p, 0.0, 1.0);
+
+    return shader_CHILD_0(pos.xy / pos.w);
+}
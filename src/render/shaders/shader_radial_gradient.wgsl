@@ -0,0 +1,9 @@
+fn shader_NODE_ID(p: vec2<f32>) -> vec4<f32> {
+    var pos = gradient_pos_NODE_ID(p);
+
+    var t = distance(pos, gradient_NODE_ID.params.xy) / gradient_NODE_ID.params[2];
+
+    t = remap_t(t, colorInfo_NODE_ID.counts[2]);
+
+    return gradient_color_NODE_ID(t);
+}
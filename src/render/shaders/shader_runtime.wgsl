@@ -0,0 +1,7 @@
+// main of the runtime shader returns an unpremultiplied color, values above 1.0 are kept for extended color spaces
+fn shader_NODE_ID(p: vec2<f32>) -> vec4<f32> {
+    var color = runtime_NODE_ID_main(p);
+    var alpha = clamp(color.a, 0.0, 1.0);
+
+    return vec4<f32>(max(color.rgb, vec3<f32>(0.0)) * alpha, alpha);
+}
@@ -0,0 +1,7 @@
+// premultiplied color
+@group(1) @binding(BINDING_0)
+var<uniform> solid_NODE_ID: vec4<f32>;
+
+fn shader_NODE_ID(p: vec2<f32>) -> vec4<f32> {
+    return solid_NODE_ID;
+}
@@ -1,9 +1,12 @@
 struct VertexInput {
     @location(0) position: vec2<f32>,
+    // anti-alias coverage, 0 at the outer edge of fringes
+    @location(1) coverage: f32,
 };
 
 struct VertexOutput {
     @builtin(position) position: vec4<f32>,
+    @location(0) coverage: f32,
 };
 
 struct UserMatrix {
@@ -24,10 +27,13 @@ fn vs_main(vertex: VertexInput) -> VertexOutput {
     var pos: vec4<f32> = transform.mvp * transform.transform * vec4<f32>(vertex.position, 0.0, 1.0);
 
     out.position = vec4<f32>(pos.x / pos.w, pos.y / pos.w, transform.info[0], 1.0);
+    out.coverage = vertex.coverage;
     return out;
 }
 
 @fragment
 fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
-    return vec4<f32>(color.rgb * color.a, color.a);
+    var out = apply_mask(apply_color_space(apply_color_filter(vec4<f32>(color.rgb * color.a, color.a))), in.position);
+
+    return out * clamp(in.coverage, 0.0, 1.0);
 }
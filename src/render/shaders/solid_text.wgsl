@@ -17,8 +17,14 @@ struct UserMatrix {
 @group(0) @binding(0)
 var<uniform> transform: UserMatrix;
 
+struct TextInfo {
+    color: vec4<f32>,
+    // [gamma, contrast, dummy, dummy]
+    gamma: vec4<f32>,
+};
+
 @group(1) @binding(0)
-var<uniform> color: vec4<f32>;
+var<uniform> text: TextInfo;
 
 @group(1) @binding(1)
 var image: texture_2d<f32>;
@@ -37,9 +43,29 @@ fn vs_main(vertex: VertexInput) -> VertexOutput {
     return out;
 }
 
+// Blending in linear light thins dark text and bolds light text. Shifts the coverage towards the
+// result of blending in gamma space, based on the luminance of the text color.
+fn text_coverage(coverage: f32, color: vec4<f32>) -> f32 {
+    if color.a <= 0.0 {
+        return coverage;
+    }
+
+    var luma = clamp(dot(color.rgb / color.a, vec3<f32>(0.2126, 0.7152, 0.0722)), 0.0, 1.0);
+
+    var dark = 1.0 - pow(1.0 - coverage, text.gamma.x);
+    var light = pow(coverage, text.gamma.x);
+    var c = mix(dark, light, luma);
+
+    // contrast raises partially covered pixels
+    return clamp(c + text.gamma.y * c * (1.0 - c), 0.0, 1.0);
+}
+
 @fragment
 fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
     var alpha: f32 = textureSample(image, imageSampler, in.uv).r;
 
-    return vec4<f32>(color.rgb * color.a * alpha, color.a * alpha);
+    var color = text.color;
+    var filtered = apply_color_space(apply_color_filter(vec4<f32>(color.rgb * color.a, color.a)));
+
+    return apply_mask(filtered * text_coverage(alpha, filtered), in.position);
 }
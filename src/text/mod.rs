@@ -7,7 +7,7 @@ pub(crate) mod glyph_atlas;
 
 pub use font::{Font, FontDescription, FontStyle};
 
-use crate::core::{Bitmap, ImageFormat, ImageInfo};
+use crate::core::{Bitmap, ImageFormat, ImageInfo, Point, Rect};
 
 pub struct TextRun {
     pub(crate) glyphs: Vec<Glyph>,
@@ -102,6 +102,16 @@ impl TextBlob {
         }
     }
 
+    /// Returns the bounds of this blob when drawn with baseline at pos.
+    pub(crate) fn bounds(&self, pos: &Point) -> Rect {
+        Rect::from_ltrb(
+            pos.x,
+            pos.y - self.ascent,
+            pos.x + self.width,
+            pos.y - self.descent,
+        )
+    }
+
     /// Raster this blob to bitmap. Only for debug.
     pub fn raster_to_image(&self) -> Bitmap {
         let width = self.width.ceil() as u32;